@@ -105,9 +105,11 @@ fn bench_search_funnel(c: &mut Criterion) {
             all_ids.push(id);
         }
 
-        let mut config = Config::default();
-        config.stage1_candidates = 1000;
-        config.stage2_candidates = 1000;
+        let config = Config {
+            stage1_candidates: 1000,
+            stage2_candidates: 1000,
+            ..Config::default()
+        };
         let funnel = SearchFunnel::new_sqlite(&db, &config);
 
 
@@ -1,8 +1,10 @@
-use crate::config::Config;
+use crate::config::{ChunkingStrategy, Config, PdfExtraction};
 use crate::engine::funnel::SearchFunnel;
-use crate::storage::sqlite::SqliteDatabase;
-use crate::model::{get_unified_model, check_llm_connectivity, check_embedding_connectivity};
+use crate::storage::sqlite::{ConflictResolution, ConflictStatus, SqliteDatabase};
+use crate::storage::embedding_cache::{EmbeddingCache, EMBEDDING_CACHE_FILE};
+use crate::model::{get_unified_model, check_llm_connectivity, check_embedding_connectivity, EmbeddingBatcher};
 use crate::engine::vectors::{encode_bq, slice_vector};
+use crate::model::candle::registry::{ArchConfig, MatchSource, ModelRegistry};
 use anyhow::Result;
 use clap::{Parser, Subcommand};
 use colored::Colorize;
@@ -13,6 +15,8 @@ use tabled::{
 };
 use uuid::Uuid;
 use crate::engine::ingestion::IngestionPipeline;
+use crate::engine::sources::DirectoryIngest;
+use crate::model::UnifiedModel;
 use std::sync::Arc;
 
 #[derive(Parser)]
@@ -96,6 +100,36 @@ pub enum Commands {
         /// Enable semantic chunking (requires LLM)
         #[arg(short = 'S', long)]
         semantic: bool,
+        /// Chunking strategy: none, tokens, markdown, code, similarity or llm
+        /// (default: by file type, then config)
+        #[arg(short, long)]
+        chunking: Option<ChunkingStrategy>,
+        /// PDF text extraction: auto, text or llm (default: config)
+        #[arg(long)]
+        pdf: Option<PdfExtraction>,
+    },
+    /// Index a directory tree, re-ingesting only files that changed
+    Ingest {
+        /// Directory to index
+        dir: PathBuf,
+        /// Only index paths matching this glob, relative to the directory (repeatable)
+        #[arg(short, long)]
+        include: Vec<String>,
+        /// Skip paths matching this glob (repeatable)
+        #[arg(short, long)]
+        exclude: Vec<String>,
+        /// Also index files ignored by .gitignore and .ignore
+        #[arg(long)]
+        no_gitignore: bool,
+        /// Namespace to ingest into
+        #[arg(short, long)]
+        namespace: Option<String>,
+        /// Chunking strategy for every file (default: by file type, then config)
+        #[arg(short, long)]
+        chunking: Option<ChunkingStrategy>,
+        /// PDF text extraction: auto, text or llm (default: config)
+        #[arg(long)]
+        pdf: Option<PdfExtraction>,
     },
     /// Explore an entity's neighborhood in the knowledge graph
     Explore {
@@ -105,10 +139,55 @@ pub enum Commands {
         #[arg(short, long)]
         namespace: Option<String>,
     },
+    /// Inspect and resolve contradictions detected during extraction
+    Conflicts {
+        #[command(subcommand)]
+        action: ConflictCommands,
+    },
+    /// Inspect the model registry (embedded models.yaml plus user overrides)
+    Models {
+        #[command(subcommand)]
+        action: ModelCommands,
+    },
     /// Run diagnostic tests (insert, search)
     Test,
 }
 
+#[derive(Subcommand)]
+pub enum ModelCommands {
+    /// List known architectures, models and name patterns
+    List,
+    /// Show what a model name resolves to
+    Resolve {
+        /// Model name, e.g. numind/NuExtract-2.0-2B
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ConflictCommands {
+    /// List detected conflicts
+    List {
+        /// Status to show
+        #[arg(short = 'S', long, value_enum, default_value = "open")]
+        status: ConflictStatus,
+        /// Namespace to list from (all namespaces if omitted)
+        #[arg(short, long)]
+        namespace: Option<String>,
+        /// Max number of conflicts to show
+        #[arg(short, long, default_value = "20")]
+        limit: usize,
+    },
+    /// Resolve a conflict; the losing fact is marked as superseded
+    Resolve {
+        /// Conflict UUID
+        id: String,
+        /// Which side wins
+        #[arg(value_enum)]
+        resolution: ConflictResolution,
+    },
+}
+
 #[derive(Tabled)]
 struct MemoryRow {
     #[tabled(rename = "ID")]
@@ -151,6 +230,32 @@ struct CommunityRow {
     summary: String,
 }
 
+#[derive(Tabled)]
+struct ConflictRow {
+    #[tabled(rename = "ID")]
+    id: String,
+    #[tabled(rename = "Entity")]
+    entity: String,
+    #[tabled(rename = "Existing")]
+    existing: String,
+    #[tabled(rename = "Claim")]
+    claim: String,
+    #[tabled(rename = "Status")]
+    status: String,
+}
+
+#[derive(Tabled)]
+struct ModelRow {
+    #[tabled(rename = "Model / Pattern")]
+    key: String,
+    #[tabled(rename = "Match")]
+    kind: String,
+    #[tabled(rename = "Arch")]
+    arch: String,
+    #[tabled(rename = "Template")]
+    template: String,
+}
+
 #[derive(Tabled)]
 struct StatsRow {
     #[tabled(rename = "Metric")]
@@ -200,9 +305,26 @@ pub fn run(cli: Cli) -> Result<()> {
             })
         },
         Commands::Inspect { id } => run_inspect(&config.storage_path, &id),
-        Commands::Memorize { path, text, namespace, semantic } => {
+        Commands::Memorize { path, text, namespace, semantic, chunking, pdf } => {
+            let mut config = config;
+            if let Some(pdf) = pdf {
+                config.pdf.extraction = pdf;
+            }
             tokio::runtime::Runtime::new()?.block_on(async {
-                run_memorize(&config, path, text, namespace.as_deref().unwrap_or("default"), semantic).await
+                run_memorize(&config, path, text, namespace.as_deref().unwrap_or("default"), semantic, chunking).await
+            })
+        },
+        Commands::Ingest { dir, include, exclude, no_gitignore, namespace, chunking, pdf } => {
+            let mut config = config;
+            if let Some(pdf) = pdf {
+                config.pdf.extraction = pdf;
+            }
+            let ingest = DirectoryIngest::new(dir)
+                .with_include(include)
+                .with_exclude(exclude)
+                .with_gitignore(!no_gitignore);
+            tokio::runtime::Runtime::new()?.block_on(async {
+                run_ingest(&config, ingest, namespace.as_deref().unwrap_or("default"), chunking).await
             })
         },
         Commands::Explore { entity_name, namespace } => {
@@ -210,6 +332,12 @@ pub fn run(cli: Cli) -> Result<()> {
                 run_explore(&config, &entity_name, namespace.as_deref().unwrap_or("default")).await
             })
         },
+        Commands::Conflicts { action } => {
+            tokio::runtime::Runtime::new()?.block_on(async {
+                run_conflicts(&config, action).await
+            })
+        },
+        Commands::Models { action } => run_models(&config, action),
         Commands::Test => {
             tokio::runtime::Runtime::new()?.block_on(async {
                 run_test(&config).await
@@ -218,7 +346,14 @@ pub fn run(cli: Cli) -> Result<()> {
     }
 }
 
-async fn run_memorize(config: &Config, path: Option<PathBuf>, text: Option<String>, namespace: &str, semantic: bool) -> Result<()> {
+async fn run_memorize(
+    config: &Config,
+    path: Option<PathBuf>,
+    text: Option<String>,
+    namespace: &str,
+    semantic: bool,
+    chunking: Option<ChunkingStrategy>,
+) -> Result<()> {
     if path.is_none() && text.is_none() {
         anyhow::bail!("Either --path or --text must be provided");
     }
@@ -229,13 +364,7 @@ async fn run_memorize(config: &Config, path: Option<PathBuf>, text: Option<Strin
     let db_path = config.storage_path.join("local-memory.db");
     let db = Arc::new(SqliteDatabase::open(&db_path, model.dimension())?);
 
-    let pipeline = IngestionPipeline::new(
-        model.clone(),
-        db.clone(),
-        Some(model.clone()),
-        semantic,
-        None
-    );
+    let pipeline = cli_pipeline(config, &model, db.clone(), semantic, chunking);
 
     let id = if let Some(path) = path {
         println!("{} {:?}", "Ingesting document:".cyan().bold(), path);
@@ -253,6 +382,67 @@ async fn run_memorize(config: &Config, path: Option<PathBuf>, text: Option<Strin
     Ok(())
 }
 
+fn cli_pipeline(
+    config: &Config,
+    model: &Arc<dyn UnifiedModel>,
+    db: Arc<SqliteDatabase>,
+    semantic: bool,
+    chunking: Option<ChunkingStrategy>,
+) -> IngestionPipeline {
+    IngestionPipeline::new(
+        model.clone(),
+        db,
+        Some(model.clone()),
+        semantic,
+        None
+    )
+    .with_chunking(config.chunking.clone())
+    .with_pdf(config.pdf.clone())
+    .with_images(config.image.clone())
+    .with_strategy(chunking)
+    .with_ontologies(config.ontology.clone())
+    .with_max_retries(config.extraction_retries)
+    .with_batcher(Arc::new(EmbeddingBatcher::new(model.clone()).with_batch_size(config.embedding_batch_size)))
+    .with_token_counter(model.token_counter())
+}
+
+async fn run_ingest(config: &Config, ingest: DirectoryIngest, namespace: &str, chunking: Option<ChunkingStrategy>) -> Result<()> {
+    let root = ingest.root()?;
+    let model = get_unified_model(config).await?;
+    model.prepare().await?;
+
+    let db_path = config.storage_path.join("local-memory.db");
+    let db = Arc::new(SqliteDatabase::open(&db_path, model.dimension())?);
+    let pipeline = cli_pipeline(config, &model, db.clone(), config.semantic_chunking, chunking);
+
+    println!("{} {}", "Indexing directory:".cyan().bold(), root.display());
+    let report = ingest.run(&pipeline, &db, namespace).await?;
+
+    for path in &report.added {
+        println!("  {} {}", "+".green(), path);
+    }
+    for path in &report.updated {
+        println!("  {} {}", "~".yellow(), path);
+    }
+    for path in &report.deleted {
+        println!("  {} {}", "-".red(), path);
+    }
+    for (path, error) in &report.failed {
+        println!("  {} {}: {}", "!".red().bold(), path, error);
+    }
+    println!(
+        "  {} {} added, {} updated, {} unchanged, {} deleted, {} skipped (unsupported format), {} failed",
+        "•".blue(),
+        report.added.len(), report.updated.len(), report.unchanged, report.deleted.len(), report.skipped, report.failed.len()
+    );
+    println!("  {} Namespace: {}", "•".blue(), namespace);
+
+    if !report.failed.is_empty() {
+        anyhow::bail!("{} file(s) failed to ingest", report.failed.len());
+    }
+    Ok(())
+}
+
 async fn run_explore(config: &Config, entity_name: &str, namespace: &str) -> Result<()> {
     let db_path = config.storage_path.join("local-memory.db");
     let model = get_unified_model(config).await?;
@@ -285,8 +475,7 @@ async fn run_recall(config: &Config, query: &str, top_k: usize, namespace: &str)
     println!("{} \"{}\" in namespace: {}", "Recalling for:".cyan().bold(), query, namespace.yellow());
     println!();
 
-    let query_vector = model.embed_one(query).await.map_err(|e| anyhow::anyhow!("Embedding failed: {}", e))?;
-    let results = funnel.hybrid_search_with_namespace(&query_vector, top_k, namespace)?;
+    let results = funnel.recall(model.as_ref(), query, top_k, namespace).await?;
 
     if results.is_empty() {
         println!("{}", "No memories found.".yellow());
@@ -351,12 +540,30 @@ async fn run_stats(config: &Config) -> Result<()> {
     let model = get_unified_model(config).await?;
     let db = SqliteDatabase::open(&db_path, model.dimension())?;
 
-    let stats = vec![
+    let mut stats = vec![
         StatsRow { metric: "Storage Path".to_string(), value: config.storage_path.display().to_string() },
         StatsRow { metric: "Database File".to_string(), value: db_path.display().to_string() },
-        StatsRow { metric: "Total Entities (Latest)".to_string(), value: db.count_entities()?.to_string().green().to_string() }
+        StatsRow { metric: "Total Entities (Latest)".to_string(), value: db.count_entities()?.to_string().green().to_string() },
+        StatsRow { metric: "Open Conflicts".to_string(), value: db.count_conflicts(ConflictStatus::Open)?.to_string() },
+        StatsRow { metric: "Failed Extractions".to_string(), value: db.count_failed_extractions()?.to_string() }
     ];
 
+    let cache_path = config.storage_path.join(EMBEDDING_CACHE_FILE);
+    if cache_path.exists() {
+        for cache in EmbeddingCache::open(&cache_path)?.stats()? {
+            stats.push(StatsRow {
+                metric: format!("Embedding Cache ({})", cache.model),
+                value: format!(
+                    "{} vectors, {}/{} hits ({:.1}%)",
+                    cache.entries,
+                    cache.hits,
+                    cache.hits + cache.misses,
+                    cache.hit_rate() * 100.0
+                ),
+            });
+        }
+    }
+
     println!("{}", Table::new(stats).with(Modify::new(Rows::new(1..)).with(Alignment::right())));
     Ok(())
 }
@@ -415,6 +622,85 @@ async fn run_list_communities(config: &Config, limit: usize) -> Result<()> {
     Ok(())
 }
 
+async fn run_conflicts(config: &Config, action: ConflictCommands) -> Result<()> {
+    let db_path = config.storage_path.join("local-memory.db");
+    if !db_path.exists() {
+        println!("{}", "Database file not found. Run 'lmcli init' first.".yellow());
+        return Ok(());
+    }
+    let model = get_unified_model(config).await?;
+    let db = SqliteDatabase::open(&db_path, model.dimension())?;
+
+    match action {
+        ConflictCommands::List { status, namespace, limit } => {
+            let conflicts = db.list_conflicts(Some(status), namespace.as_deref(), limit)?;
+            if conflicts.is_empty() {
+                println!("{}", format!("No {} conflicts found.", status).yellow());
+                return Ok(());
+            }
+            let rows: Vec<ConflictRow> = conflicts.into_iter().map(|c| ConflictRow {
+                id: c.id.to_string(),
+                entity: c.entity_name,
+                existing: c.existing,
+                claim: c.claim,
+                status: c.resolution.map_or(c.status.as_str(), |r| r.as_str()).to_string(),
+            }).collect();
+            println!("{}", Table::new(rows));
+        }
+        ConflictCommands::Resolve { id, resolution } => {
+            let id = Uuid::parse_str(&id)?;
+            let conflict = db.resolve_conflict(id, resolution)?;
+            println!("  {} Conflict {} {} ({})", "✓".green(), conflict.id, conflict.status, resolution.as_str());
+        }
+    }
+    Ok(())
+}
+
+fn run_models(config: &Config, action: ModelCommands) -> Result<()> {
+    let registry = ModelRegistry::for_config(config)?;
+    match action {
+        ModelCommands::List => {
+            let exact = registry.models().into_iter().map(|e| (e, "exact"));
+            let patterns = registry.patterns().into_iter().map(|e| (e, "contains"));
+            let rows: Vec<ModelRow> = exact.chain(patterns).map(|(e, kind)| ModelRow {
+                key: e.key,
+                kind: kind.to_string(),
+                arch: e.arch,
+                template: e.prompt_template.unwrap_or_else(|| "-".to_string()),
+            }).collect();
+            println!("{}", Table::new(rows));
+        }
+        ModelCommands::Resolve { name } => {
+            let resolved = registry.resolve(&name)?;
+            let matched = match &resolved.matched_by {
+                MatchSource::Exact => "exact entry".to_string(),
+                MatchSource::Pattern(p) => format!("pattern '{}'", p),
+            };
+            println!("{} {}", "Model:".bold(), name);
+            println!("  Matched by:   {}", matched);
+            println!("  Architecture: {} (backend {})", resolved.arch_name, resolved.arch.as_str());
+            match &resolved.config {
+                ArchConfig::Embedding(e) => {
+                    println!("  Role:         embedding");
+                    println!("  Query prefix: {:?}", e.query_prefix);
+                    println!("  Doc prefix:   {:?}", e.document_prefix);
+                }
+                ArchConfig::Generation(g) => {
+                    let mut templates: Vec<&str> = g.prompt_templates.keys().map(String::as_str).collect();
+                    templates.sort_unstable();
+                    println!("  Role:         generation");
+                    println!("  Template:     {} (available: {})", g.default_template, templates.join(", "));
+                    println!("  Max tokens:   {}", g.max_new_tokens);
+                    println!("  EOS tokens:   {}", g.eos_tokens.join(", "));
+                    println!("  Chat:         {}", if g.chat_template.is_some() { "yes" } else { "no" });
+                    println!("  Constrained:  {}", if g.constrained_decoding { "yes" } else { "no" });
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 async fn run_history(_config: &Config, _title: &str, _namespace: &str) -> Result<()> {
     println!("{}", "History command not yet fully implemented in storage layer".yellow());
     Ok(())
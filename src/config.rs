@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::path::PathBuf;
@@ -56,6 +57,12 @@ pub struct ModelConfig {
     pub base_url: Option<String>,
     /// Optional API key (used for OpenAI-compatible providers)
     pub api_key: Option<String>,
+    /// Prefix for search queries; defaults to the registry entry matching `name`
+    pub query_prefix: Option<String>,
+    /// Prefix for ingested documents; defaults to the registry entry matching `name`
+    pub document_prefix: Option<String>,
+    /// Input limit in tokens; defaults to the model's (e.g. 512 for BERT)
+    pub max_tokens: Option<usize>,
 }
 
 fn default_auto_download() -> bool { true }
@@ -70,6 +77,9 @@ impl Default for ModelConfig {
             dimension: 768,
             base_url: None,
             api_key: None,
+            query_prefix: None,
+            document_prefix: None,
+            max_tokens: None,
         }
     }
 }
@@ -97,6 +107,12 @@ pub struct ExtractorConfig {
     pub api_key: Option<String>,
     /// Optional base URL for the API
     pub base_url: Option<String>,
+    /// Precision of safetensors weights for local (`huggingface`) models
+    #[serde(default)]
+    pub dtype: ModelDType,
+    /// Load a quantized GGUF file instead of safetensors weights
+    #[serde(default)]
+    pub gguf: Option<GgufConfig>,
 }
 
 impl Default for ExtractorConfig {
@@ -107,6 +123,374 @@ impl Default for ExtractorConfig {
             auto_download: true,
             api_key: None,
             base_url: None,
+            dtype: ModelDType::default(),
+            gguf: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ModelDType {
+    #[default]
+    F32,
+    F16,
+    Bf16,
+}
+
+/// A quantized GGUF checkpoint. The tokenizer still comes from the model's
+/// own repository (`name`), since GGUF repositories usually lack one.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct GgufConfig {
+    /// File name within the repository, e.g. `qwen2.5-1.5b-instruct-q4_k_m.gguf`
+    pub file: String,
+    /// Repository holding the file (default: the model's own repository)
+    #[serde(default)]
+    pub repo: Option<String>,
+}
+
+/// An allowed entity type or predicate, written either as a bare string or
+/// as an object with a description and aliases.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(from = "RawOntologyTerm")]
+pub struct OntologyTerm {
+    /// Canonical spelling stored in the graph
+    pub name: String,
+    /// Optional explanation injected into the extraction prompt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Alternative spellings mapped onto `name`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawOntologyTerm {
+    Name(String),
+    Full {
+        name: String,
+        #[serde(default)]
+        description: Option<String>,
+        #[serde(default)]
+        aliases: Vec<String>,
+    },
+}
+
+impl From<RawOntologyTerm> for OntologyTerm {
+    fn from(raw: RawOntologyTerm) -> Self {
+        match raw {
+            RawOntologyTerm::Name(name) => Self { name, description: None, aliases: Vec::new() },
+            RawOntologyTerm::Full { name, description, aliases } => Self { name, description, aliases },
+        }
+    }
+}
+
+/// What to do with an extracted type or predicate that is not in the ontology.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum UnknownTermPolicy {
+    /// Replace the value with the configured fallback
+    #[default]
+    Map,
+    /// Drop the entity or relationship
+    Reject,
+}
+
+/// Schema constraining what the LLM extractor may write into a namespace.
+///
+/// Empty `entity_types` / `predicates` lists leave that side unconstrained.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct OntologyConfig {
+    #[serde(default)]
+    pub entity_types: Vec<OntologyTerm>,
+    #[serde(default)]
+    pub predicates: Vec<OntologyTerm>,
+    /// Entity type used for unknown types (and for relationship endpoints the LLM did not type)
+    #[serde(default = "default_fallback_entity_type")]
+    pub fallback_entity_type: String,
+    /// Predicate used for unknown predicates
+    #[serde(default = "default_fallback_predicate")]
+    pub fallback_predicate: String,
+    #[serde(default)]
+    pub on_unknown: UnknownTermPolicy,
+}
+
+fn default_fallback_entity_type() -> String { "Concept".to_string() }
+fn default_fallback_predicate() -> String { "RELATED_TO".to_string() }
+
+impl Default for OntologyConfig {
+    fn default() -> Self {
+        Self {
+            entity_types: Vec::new(),
+            predicates: Vec::new(),
+            fallback_entity_type: default_fallback_entity_type(),
+            fallback_predicate: default_fallback_predicate(),
+            on_unknown: UnknownTermPolicy::Map,
+        }
+    }
+}
+
+impl OntologyConfig {
+    /// Reject ontologies whose names or aliases collide after normalisation.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for (kind, terms) in [("entity type", &self.entity_types), ("predicate", &self.predicates)] {
+            let mut seen = HashMap::new();
+            for term in terms {
+                if term.name.trim().is_empty() {
+                    anyhow::bail!("Empty {} name in ontology", kind);
+                }
+                for spelling in std::iter::once(&term.name).chain(term.aliases.iter()) {
+                    let key = normalize_term(spelling);
+                    if let Some(other) = seen.insert(key, term.name.clone())
+                        && other != term.name {
+                            anyhow::bail!("Ontology {} '{}' is ambiguous between '{}' and '{}'", kind, spelling, other, term.name);
+                        }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Case- and punctuation-insensitive key used to compare ontology terms
+/// (`works at`, `WORKS_AT` and `worksAt` all compare equal).
+pub fn normalize_term(term: &str) -> String {
+    term.chars().filter(|c| c.is_alphanumeric()).flat_map(|c| c.to_lowercase()).collect()
+}
+
+/// How ingested text is split into chunks before embedding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default, schemars::JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkingStrategy {
+    /// One chunk per document (long ones are embedded in windows)
+    #[default]
+    None,
+    /// Fixed-size token windows with overlap
+    Tokens,
+    /// One chunk per Markdown section, split at headings
+    Markdown,
+    /// Top-level functions, impls and classes of source code
+    Code,
+    /// Breaks where the embeddings of consecutive sentences diverge
+    Similarity,
+    /// The LLM re-emits the text with separators (slow, needs an extractor)
+    Llm,
+}
+
+impl ChunkingStrategy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChunkingStrategy::None => "none",
+            ChunkingStrategy::Tokens => "tokens",
+            ChunkingStrategy::Markdown => "markdown",
+            ChunkingStrategy::Code => "code",
+            ChunkingStrategy::Similarity => "similarity",
+            ChunkingStrategy::Llm => "llm",
+        }
+    }
+}
+
+impl std::str::FromStr for ChunkingStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "none" => Ok(ChunkingStrategy::None),
+            "tokens" => Ok(ChunkingStrategy::Tokens),
+            "markdown" => Ok(ChunkingStrategy::Markdown),
+            "code" => Ok(ChunkingStrategy::Code),
+            "similarity" => Ok(ChunkingStrategy::Similarity),
+            "llm" | "semantic" => Ok(ChunkingStrategy::Llm),
+            other => Err(anyhow::anyhow!(
+                "Unknown chunking strategy '{}' (expected none, tokens, markdown, code, similarity or llm)",
+                other
+            )),
+        }
+    }
+}
+
+/// Chunking rules for ingestion.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ChunkingConfig {
+    /// Strategy for text and files without a more specific rule
+    #[serde(default)]
+    pub strategy: ChunkingStrategy,
+    /// Strategy per file extension (without the dot), over the built-in
+    /// rules: Markdown, PDF, HTML and notebook files by heading, source
+    /// files by definition
+    #[serde(default)]
+    pub by_extension: HashMap<String, ChunkingStrategy>,
+    /// Target chunk size in tokens (capped by the embedder's limit)
+    #[serde(default = "default_chunk_tokens")]
+    pub chunk_tokens: usize,
+    /// Tokens shared by consecutive token windows
+    #[serde(default = "default_chunk_overlap")]
+    pub overlap_tokens: usize,
+    /// Similarity chunking breaks where the distance between consecutive
+    /// sentences is above this percentile of all distances in the text
+    #[serde(default = "default_breakpoint_percentile")]
+    pub breakpoint_percentile: f32,
+}
+
+fn default_chunk_tokens() -> usize { 256 }
+fn default_chunk_overlap() -> usize { 32 }
+fn default_breakpoint_percentile() -> f32 { 90.0 }
+
+const MARKDOWN_EXTENSIONS: &[&str] = &["md", "markdown", "mdx", "pdf", "html", "htm", "xhtml", "ipynb"];
+const CODE_EXTENSIONS: &[&str] = &[
+    "rs", "py", "js", "jsx", "ts", "tsx", "go", "java", "kt", "c", "h", "cc", "cpp", "hpp", "cs",
+    "rb", "php", "swift", "scala",
+];
+
+impl Default for ChunkingConfig {
+    fn default() -> Self {
+        Self {
+            strategy: ChunkingStrategy::None,
+            by_extension: HashMap::new(),
+            chunk_tokens: default_chunk_tokens(),
+            overlap_tokens: default_chunk_overlap(),
+            breakpoint_percentile: default_breakpoint_percentile(),
+        }
+    }
+}
+
+impl ChunkingConfig {
+    /// The strategy for a file with `extension`, or for plain text when `None`.
+    pub fn strategy_for(&self, extension: Option<&str>) -> ChunkingStrategy {
+        let Some(ext) = extension.map(str::to_lowercase) else { return self.strategy };
+        if let Some(strategy) = self.by_extension.get(&ext) {
+            return *strategy;
+        }
+        if MARKDOWN_EXTENSIONS.contains(&ext.as_str()) {
+            ChunkingStrategy::Markdown
+        } else if CODE_EXTENSIONS.contains(&ext.as_str()) {
+            ChunkingStrategy::Code
+        } else {
+            self.strategy
+        }
+    }
+}
+
+/// How text is taken out of PDF files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PdfExtraction {
+    /// Text layer, with the LLM converter for pages that have none (scans)
+    #[default]
+    Auto,
+    /// Text layer only; pages without one are left out
+    Text,
+    /// Every page through the LLM converter (slow, keeps tables and layout)
+    Llm,
+}
+
+impl std::str::FromStr for PdfExtraction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "auto" => Ok(PdfExtraction::Auto),
+            "text" => Ok(PdfExtraction::Text),
+            "llm" => Ok(PdfExtraction::Llm),
+            other => Err(anyhow::anyhow!("Unknown PDF extraction '{}' (expected auto, text or llm)", other)),
+        }
+    }
+}
+
+/// PDF ingestion.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PdfConfig {
+    #[serde(default)]
+    pub extraction: PdfExtraction,
+    /// Pages with fewer non-blank characters in their text layer count as scanned
+    #[serde(default = "default_min_page_chars")]
+    pub min_page_chars: usize,
+}
+
+fn default_min_page_chars() -> usize { 20 }
+
+impl Default for PdfConfig {
+    fn default() -> Self {
+        Self { extraction: PdfExtraction::Auto, min_page_chars: default_min_page_chars() }
+    }
+}
+
+/// Image ingestion: screenshots and diagrams are stored as the text a
+/// vision-capable LLM and/or a local OCR command produce for them.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ImageConfig {
+    /// Describe images with the LLM extractor, if `vision` is set
+    #[serde(default = "default_true")]
+    pub caption: bool,
+    /// The LLM extractor accepts images. Off by default: a text-only model
+    /// would caption an image it never saw.
+    #[serde(default)]
+    pub vision: bool,
+    /// OCR command and arguments, `{path}` standing for the image file,
+    /// e.g. `["tesseract", "{path}", "stdout"]`; its stdout is the text
+    #[serde(default)]
+    pub ocr_command: Vec<String>,
+    /// Longest side, in pixels, of the copy sent to the LLM
+    #[serde(default = "default_max_image_side")]
+    pub max_side: u32,
+}
+
+fn default_max_image_side() -> u32 { 1568 }
+
+impl Default for ImageConfig {
+    fn default() -> Self {
+        Self { caption: true, vision: false, ocr_command: Vec::new(), max_side: default_max_image_side() }
+    }
+}
+
+/// Project files re-ingested when they change (requires `enable_observers`).
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct WatchConfig {
+    /// Directories to watch recursively, relative to the server's working directory
+    #[serde(default)]
+    pub paths: Vec<PathBuf>,
+    /// Namespace the watched files are ingested into
+    #[serde(default = "default_watch_namespace")]
+    pub namespace: String,
+    /// Only watch files matching these globs, relative to the watched directory
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Ignore files matching these globs
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Honour `.gitignore` and `.ignore` files
+    #[serde(default = "default_true")]
+    pub gitignore: bool,
+    /// Quiet period after the last change to a file before it is re-ingested
+    #[serde(default = "default_debounce_ms")]
+    pub debounce_ms: u64,
+    /// Poll instead of using native file events (e.g. on network filesystems).
+    /// Polling is also the fallback when native events are unavailable.
+    #[serde(default)]
+    pub poll: bool,
+    /// Interval between polls
+    #[serde(default = "default_poll_interval_seconds")]
+    pub poll_interval_seconds: u64,
+}
+
+fn default_watch_namespace() -> String { "docs".to_string() }
+fn default_true() -> bool { true }
+fn default_debounce_ms() -> u64 { 1000 }
+fn default_poll_interval_seconds() -> u64 { 5 }
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            paths: Vec::new(),
+            namespace: default_watch_namespace(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            gitignore: true,
+            debounce_ms: default_debounce_ms(),
+            poll: false,
+            poll_interval_seconds: default_poll_interval_seconds(),
         }
     }
 }
@@ -128,14 +512,30 @@ pub struct Config {
     #[serde(default)]
     pub llm_extractor: Option<ExtractorConfig>,
 
-    /// Enable semantic chunking during ingestion
+    /// Enable semantic chunking during ingestion (same as `chunking.strategy: llm`)
     #[serde(default)]
     pub semantic_chunking: bool,
 
+    /// Chunking strategies for ingestion
+    #[serde(default)]
+    pub chunking: ChunkingConfig,
+
+    /// PDF text extraction
+    #[serde(default)]
+    pub pdf: PdfConfig,
+
+    /// Image captioning and OCR
+    #[serde(default)]
+    pub image: ImageConfig,
+
     /// Enable background observers (Git, Shell)
     #[serde(default)]
     pub enable_observers: bool,
 
+    /// Project directories re-ingested on change by the file observer
+    #[serde(default)]
+    pub watch: WatchConfig,
+
     /// Optional path to OpenCode database (defaults to ~/.local/share/opencode/opencode.db)
     #[serde(default)]
     pub opencode_db_path: Option<PathBuf>,
@@ -144,6 +544,11 @@ pub struct Config {
     #[serde(default = "default_idle_timeout")]
     pub idle_timeout_seconds: u64,
 
+    /// Drop HTTP sessions after N seconds without a request or an open
+    /// event stream (0 to keep them until DELETE)
+    #[serde(default = "default_session_timeout")]
+    pub session_timeout_seconds: u64,
+
     /// Number of candidates to retrieve in Stage 1 (Binary Quantization)
     #[serde(default = "default_stage1_candidates")]
     pub stage1_candidates: usize,
@@ -151,9 +556,47 @@ pub struct Config {
     /// Number of candidates to retrieve in Stage 2 (Matryoshka Slicing)
     #[serde(default = "default_stage2_candidates")]
     pub stage2_candidates: usize,
+
+    /// Per-namespace entity/predicate ontologies. The `"*"` entry applies to
+    /// namespaces without their own.
+    #[serde(default)]
+    pub ontology: HashMap<String, OntologyConfig>,
+
+    /// Re-prompts allowed when an LLM reply cannot be parsed as JSON
+    #[serde(default = "default_extraction_retries")]
+    pub extraction_retries: usize,
+
+    /// Background workers extracting knowledge graphs for `memorize` jobs
+    #[serde(default = "default_extraction_workers")]
+    pub extraction_workers: usize,
+
+    /// MCP requests handled at the same time; later ones wait for a slot
+    #[serde(default = "default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+
+    /// User model registry merged over the embedded `models.yaml`
+    /// (default: `~/.local-memory/models.yaml` if it exists)
+    #[serde(default)]
+    pub models_file: Option<PathBuf>,
+    /// Never download model files; fail fast if any are missing
+    /// (also enabled by `HF_HUB_OFFLINE=1`)
+    #[serde(default)]
+    pub offline: bool,
+    /// Cache embeddings in `storage_path/embedding_cache.db` (default: true)
+    #[serde(default = "default_embedding_cache")]
+    pub embedding_cache: bool,
+    /// Most texts sent to the embedding model in one batch (default: 32)
+    #[serde(default = "default_embedding_batch_size")]
+    pub embedding_batch_size: usize,
 }
 
 fn default_idle_timeout() -> u64 { 3600 }
+fn default_session_timeout() -> u64 { 1800 }
+fn default_extraction_retries() -> usize { 2 }
+fn default_extraction_workers() -> usize { 2 }
+fn default_max_concurrent_requests() -> usize { 8 }
+fn default_embedding_cache() -> bool { true }
+fn default_embedding_batch_size() -> usize { crate::model::batching::DEFAULT_BATCH_SIZE }
 fn default_stage1_candidates() -> usize { 100 }
 fn default_stage2_candidates() -> usize { 20 }
 
@@ -177,13 +620,28 @@ impl Default for Config {
                 auto_download: true,
                 api_key: None,
                 base_url: None,
+                dtype: ModelDType::default(),
+                gguf: None,
             }),
             semantic_chunking: false,
+            chunking: ChunkingConfig::default(),
+            pdf: PdfConfig::default(),
+            image: ImageConfig::default(),
             enable_observers: false,
+            watch: WatchConfig::default(),
             opencode_db_path: None,
             idle_timeout_seconds: default_idle_timeout(),
+            session_timeout_seconds: default_session_timeout(),
             stage1_candidates: default_stage1_candidates(),
             stage2_candidates: default_stage2_candidates(),
+            ontology: HashMap::new(),
+            extraction_retries: default_extraction_retries(),
+            extraction_workers: default_extraction_workers(),
+            max_concurrent_requests: default_max_concurrent_requests(),
+            models_file: None,
+            offline: false,
+            embedding_cache: default_embedding_cache(),
+            embedding_batch_size: default_embedding_batch_size(),
         }
     }
 }
@@ -208,8 +666,15 @@ impl Config {
             match fs::read_to_string(&config_path) {
                 Ok(content) => {
                     match serde_json::from_str::<Config>(&content) {
-                        Ok(config) => {
+                        Ok(mut config) => {
                             eprintln!("DEBUG: Config loaded successfully from {}", config_path.display());
+                            config.ontology.retain(|namespace, ontology| match ontology.validate() {
+                                Ok(()) => true,
+                                Err(e) => {
+                                    eprintln!("  ! Warning: Ignoring ontology for namespace '{}': {}", namespace, e);
+                                    false
+                                }
+                            });
                             if let Some(ext) = &config.llm_extractor {
                                 eprintln!("DEBUG: Loaded extractor: {:?} ({})", ext.provider, ext.name);
                             }
@@ -0,0 +1,418 @@
+//! Strategies for splitting ingested text into chunks.
+//!
+//! Every chunker except [`LlmChunker`] returns verbatim slices of its input,
+//! so each chunk carries the byte range it came from.
+
+use crate::model::tokens::TokenCounter;
+use crate::model::EmbeddingBatcher;
+use anyhow::Result;
+use async_trait::async_trait;
+use edgequake_llm::LLMProvider;
+use serde_json::{json, Value};
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Explicit separator; text containing it is always split there.
+pub const CHUNK_MARKER: &str = "---CHUNK---";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub text: String,
+    /// Byte range in the source text, when the chunk is a slice of it
+    pub range: Option<Range<usize>>,
+    /// Heading path (Markdown) or definition (code) the chunk belongs to
+    pub section: Option<String>,
+}
+
+impl Chunk {
+    fn slice(source: &str, range: Range<usize>, section: Option<String>) -> Self {
+        Self { text: source[range.clone()].to_string(), range: Some(range), section }
+    }
+
+    /// The `chunk` entry of the chunk's document metadata.
+    pub fn metadata(&self, index: usize, count: usize, strategy: &str) -> Value {
+        json!({
+            "index": index,
+            "count": count,
+            "strategy": strategy,
+            "start": self.range.as_ref().map(|r| r.start),
+            "end": self.range.as_ref().map(|r| r.end),
+            "section": self.section,
+        })
+    }
+}
+
+#[async_trait]
+pub trait Chunker: Send + Sync {
+    async fn chunk(&self, text: &str) -> Result<Vec<Chunk>>;
+}
+
+/// Splits at [`CHUNK_MARKER`].
+pub struct MarkerChunker;
+
+#[async_trait]
+impl Chunker for MarkerChunker {
+    async fn chunk(&self, text: &str) -> Result<Vec<Chunk>> {
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        for end in text.match_indices(CHUNK_MARKER).map(|(i, _)| i).chain([text.len()]) {
+            if let Some(range) = trimmed(text, start..end) {
+                chunks.push(Chunk::slice(text, range, None));
+            }
+            start = end + CHUNK_MARKER.len();
+        }
+        Ok(chunks)
+    }
+}
+
+/// Fixed-size token windows, each sharing `overlap` tokens with the previous one.
+pub struct TokenChunker {
+    tokens: Arc<dyn TokenCounter>,
+    size: usize,
+    overlap: usize,
+}
+
+impl TokenChunker {
+    pub fn new(tokens: Arc<dyn TokenCounter>, size: usize, overlap: usize) -> Self {
+        let size = size.max(1);
+        Self { tokens, size, overlap: overlap.min(size - 1) }
+    }
+
+    /// Windows over `source[range]`, all labelled with `section`.
+    fn split(&self, source: &str, range: Range<usize>, section: Option<String>) -> Vec<Chunk> {
+        let spans = self.tokens.token_spans(&source[range.clone()]);
+        if spans.len() <= self.size {
+            return trimmed(source, range).map(|r| Chunk::slice(source, r, section)).into_iter().collect();
+        }
+        let mut chunks = Vec::new();
+        let mut first = 0;
+        loop {
+            let last = (first + self.size).min(spans.len());
+            let window = range.start + spans[first].start..range.start + spans[last - 1].end;
+            chunks.push(Chunk::slice(source, window, section.clone()));
+            if last == spans.len() {
+                break;
+            }
+            first += self.size - self.overlap;
+        }
+        chunks
+    }
+
+    fn count(&self, text: &str) -> usize {
+        self.tokens.token_spans(text).len()
+    }
+}
+
+#[async_trait]
+impl Chunker for TokenChunker {
+    async fn chunk(&self, text: &str) -> Result<Vec<Chunk>> {
+        Ok(self.split(text, 0..text.len(), None))
+    }
+}
+
+/// One chunk per Markdown section. Headings inside fenced code blocks are
+/// ignored, heading-only sections join the next one, and sections over the
+/// size limit are split into token windows.
+pub struct MarkdownChunker {
+    windows: TokenChunker,
+}
+
+impl MarkdownChunker {
+    pub fn new(tokens: Arc<dyn TokenCounter>, size: usize, overlap: usize) -> Self {
+        Self { windows: TokenChunker::new(tokens, size, overlap) }
+    }
+}
+
+#[async_trait]
+impl Chunker for MarkdownChunker {
+    async fn chunk(&self, text: &str) -> Result<Vec<Chunk>> {
+        // (start offset, heading path) of each section.
+        let mut sections: Vec<(usize, Option<String>)> = vec![(0, None)];
+        let mut path: Vec<(usize, String)> = Vec::new();
+        let mut fence: Option<&str> = None;
+        let mut body_since_heading = true;
+        for (offset, line) in lines(text) {
+            let trimmed_line = line.trim_start();
+            if let Some(open) = fence {
+                if trimmed_line.starts_with(open) {
+                    fence = None;
+                }
+                body_since_heading = true;
+                continue;
+            }
+            if let Some(marker) = ["```", "~~~"].into_iter().find(|m| trimmed_line.starts_with(m)) {
+                fence = Some(marker);
+                body_since_heading = true;
+                continue;
+            }
+            let Some((level, title)) = heading(line) else {
+                body_since_heading |= !line.trim().is_empty();
+                continue;
+            };
+            path.retain(|(l, _)| *l < level);
+            path.push((level, title.to_string()));
+            let label = Some(path.iter().map(|(_, t)| t.as_str()).collect::<Vec<_>>().join(" > "));
+            if body_since_heading {
+                sections.push((offset, label));
+            } else if let Some(last) = sections.last_mut() {
+                // The previous heading had no body: it joins this section.
+                last.1 = label;
+            }
+            body_since_heading = false;
+        }
+
+        let mut chunks = Vec::new();
+        for (i, (start, section)) in sections.iter().enumerate() {
+            let end = sections.get(i + 1).map_or(text.len(), |(next, _)| *next);
+            chunks.extend(self.windows.split(text, *start..end, section.clone()));
+        }
+        Ok(chunks)
+    }
+}
+
+/// Splits source code at top-level definitions (functions, impls, classes,
+/// ...), keeping the doc comments and attributes above each with it. Small
+/// neighbouring definitions are merged up to the size limit; larger ones are
+/// split into token windows.
+pub struct CodeChunker {
+    windows: TokenChunker,
+}
+
+impl CodeChunker {
+    pub fn new(tokens: Arc<dyn TokenCounter>, size: usize, overlap: usize) -> Self {
+        Self { windows: TokenChunker::new(tokens, size, overlap) }
+    }
+}
+
+const DEFINITION_KEYWORDS: &[&str] = &[
+    "fn ", "impl ", "impl<", "struct ", "enum ", "trait ", "mod ", "type ", "macro_rules!", "union ",
+    "def ", "class ", "func ", "function ", "function*", "interface ",
+];
+const DEFINITION_MODIFIERS: &[&str] = &[
+    "pub(crate) ", "pub(super) ", "pub ", "export default ", "export ", "async ", "unsafe ",
+    "extern \"C\" ", "const ", "static ", "abstract ", "public ", "private ", "protected ", "final ",
+];
+const PREAMBLE_PREFIXES: &[&str] = &["///", "//!", "//", "#[", "#!", "@", "/*", "*", "#"];
+
+/// The definition a top-level line starts, if any.
+fn definition(line: &str) -> Option<&str> {
+    if line.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let mut rest = line;
+    while let Some(stripped) = DEFINITION_MODIFIERS.iter().find_map(|m| rest.strip_prefix(m)) {
+        rest = stripped;
+    }
+    DEFINITION_KEYWORDS.iter().any(|k| rest.starts_with(k)).then(|| line.trim_end())
+}
+
+#[async_trait]
+impl Chunker for CodeChunker {
+    async fn chunk(&self, text: &str) -> Result<Vec<Chunk>> {
+        // (start offset, definition line) of each block.
+        let mut blocks: Vec<(usize, Option<String>)> = vec![(0, None)];
+        let mut preamble: Option<usize> = None;
+        for (offset, line) in lines(text) {
+            let trimmed_line = line.trim_start();
+            if let Some(def) = definition(line) {
+                let start = preamble.take().unwrap_or(offset);
+                let label: String = def.trim_end_matches('{').trim_end().chars().take(80).collect();
+                blocks.push((start, Some(label)));
+            } else if PREAMBLE_PREFIXES.iter().any(|p| trimmed_line.starts_with(p))
+                && (trimmed_line.len() == line.len() || trimmed_line.starts_with('*'))
+            {
+                // Doc comments, attributes and decorators stay with the next definition.
+                preamble.get_or_insert(offset);
+            } else if !line.trim().is_empty() {
+                preamble = None;
+            }
+        }
+
+        // Merge neighbouring blocks while they fit.
+        let mut merged: Vec<(Range<usize>, Option<String>)> = Vec::new();
+        for (i, (start, section)) in blocks.iter().enumerate() {
+            let end = blocks.get(i + 1).map_or(text.len(), |(next, _)| *next);
+            if end <= *start || text[*start..end].trim().is_empty() {
+                continue;
+            }
+            if let Some((range, label)) = merged.last_mut()
+                && self.windows.count(&text[range.start..end]) <= self.windows.size
+            {
+                range.end = end;
+                if label.is_none() {
+                    *label = section.clone();
+                }
+                continue;
+            }
+            merged.push((*start..end, section.clone()));
+        }
+
+        Ok(merged
+            .into_iter()
+            .flat_map(|(range, section)| self.windows.split(text, range, section))
+            .collect())
+    }
+}
+
+/// Embedding-similarity breakpoints: sentences are embedded, and a chunk
+/// ends where the cosine distance to the next sentence is above the given
+/// percentile of all such distances in the text, or where it would outgrow
+/// the size limit.
+pub struct SimilarityChunker {
+    batcher: Arc<EmbeddingBatcher>,
+    windows: TokenChunker,
+    percentile: f32,
+}
+
+impl SimilarityChunker {
+    pub fn new(batcher: Arc<EmbeddingBatcher>, tokens: Arc<dyn TokenCounter>, size: usize, percentile: f32) -> Self {
+        Self { batcher, windows: TokenChunker::new(tokens, size, 0), percentile: percentile.clamp(0.0, 100.0) }
+    }
+}
+
+#[async_trait]
+impl Chunker for SimilarityChunker {
+    async fn chunk(&self, text: &str) -> Result<Vec<Chunk>> {
+        let sentences = sentences(text);
+        if sentences.len() < 3 {
+            return self.windows.chunk(text).await;
+        }
+        let vectors = self
+            .batcher
+            .embed_documents(sentences.iter().map(|r| text[r.clone()].to_string()).collect())
+            .await?;
+        let distances: Vec<f32> = vectors.windows(2).map(|pair| 1.0 - cosine(&pair[0], &pair[1])).collect();
+        let threshold = percentile(&distances, self.percentile);
+
+        let mut chunks = Vec::new();
+        let mut start = sentences[0].start;
+        let mut tokens = 0;
+        for (i, sentence) in sentences.iter().enumerate() {
+            let sentence_tokens = self.windows.count(&text[sentence.clone()]);
+            if i > 0 && tokens > 0 && tokens + sentence_tokens > self.windows.size {
+                chunks.extend(self.windows.split(text, start..sentence.start, None));
+                start = sentence.start;
+                tokens = 0;
+            }
+            tokens += sentence_tokens;
+            if distances.get(i).is_some_and(|&d| d > threshold) {
+                chunks.extend(self.windows.split(text, start..sentence.end, None));
+                start = sentences[i + 1].start;
+                tokens = 0;
+            }
+        }
+        if start < text.len() {
+            chunks.extend(self.windows.split(text, start..text.len(), None));
+        }
+        Ok(chunks)
+    }
+}
+
+/// Asks the LLM to re-emit the text separated by [`CHUNK_MARKER`]. Chunks
+/// found verbatim in the source get their byte range.
+pub struct LlmChunker {
+    llm: Arc<dyn LLMProvider>,
+}
+
+impl LlmChunker {
+    pub fn new(llm: Arc<dyn LLMProvider>) -> Self {
+        Self { llm }
+    }
+}
+
+#[async_trait]
+impl Chunker for LlmChunker {
+    async fn chunk(&self, text: &str) -> Result<Vec<Chunk>> {
+        let prompt = format!(
+            "Divide the following text into logical semantic chunks.\n\
+             Return each chunk separated by '{}'.\n\n\
+             Text: {}",
+            CHUNK_MARKER, text
+        );
+        let response = self.llm.complete(&prompt).await?;
+        let mut cursor = 0;
+        Ok(response
+            .content
+            .split(CHUNK_MARKER)
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|chunk| {
+                let range = text[cursor..].find(chunk).map(|i| cursor + i..cursor + i + chunk.len());
+                if let Some(r) = &range {
+                    cursor = r.end;
+                }
+                Chunk { text: chunk.to_string(), range, section: None }
+            })
+            .collect())
+    }
+}
+
+/// `range` without leading and trailing whitespace, or `None` if blank.
+fn trimmed(text: &str, range: Range<usize>) -> Option<Range<usize>> {
+    let slice = &text[range.clone()];
+    let start = range.start + (slice.len() - slice.trim_start().len());
+    let end = range.end - (slice.len() - slice.trim_end().len());
+    (start < end).then_some(start..end)
+}
+
+/// Lines of `text` with their byte offsets.
+fn lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.split_inclusive('\n').scan(0, |offset, line| {
+        let start = *offset;
+        *offset += line.len();
+        Some((start, line.trim_end_matches(['\n', '\r'])))
+    })
+}
+
+/// The level and title of an ATX heading line (`## Title`).
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let line = line.trim_start_matches(' ');
+    let level = line.chars().take_while(|&c| c == '#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    let rest = &line[level..];
+    if !rest.is_empty() && !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    Some((level, rest.trim().trim_end_matches('#').trim()))
+}
+
+/// Sentence ranges: text up to `.`, `!` or `?` followed by whitespace, or
+/// up to a blank line.
+fn sentences(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, n)| n);
+        let end = match (c, next) {
+            ('.' | '!' | '?', Some(n)) if n.is_whitespace() => i + c.len_utf8(),
+            ('\n', Some('\n')) => i,
+            _ => continue,
+        };
+        if let Some(range) = trimmed(text, start..end) {
+            ranges.push(range);
+        }
+        start = end;
+    }
+    if let Some(range) = trimmed(text, start..text.len()) {
+        ranges.push(range);
+    }
+    ranges
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let denominator = norm(a) * norm(b);
+    if denominator > 0.0 { dot / denominator } else { 0.0 }
+}
+
+/// Nearest-rank percentile of `values`.
+fn percentile(values: &[f32], p: f32) -> f32 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let rank = ((p / 100.0) * sorted.len() as f32).ceil() as usize;
+    sorted.get(rank.clamp(1, sorted.len().max(1)) - 1).copied().unwrap_or(f32::MAX)
+}
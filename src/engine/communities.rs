@@ -1,8 +1,8 @@
+use crate::engine::structured::{complete_structured, CommunitySummary};
 use crate::mcp::tools::McpContext;
 use crate::KnowledgeEvent;
 use std::sync::Arc;
 use tokio::sync::broadcast;
-use serde_json::json;
 
 pub struct CommunityService {
     context: Arc<McpContext>,
@@ -44,20 +44,14 @@ impl CommunityService {
         // Use the named "summary" template via TEMPLATE:<name> prefix.
         let prompt = format!("TEMPLATE:summary\n{}", context_text);
 
-        let response = self.context.model.complete(&prompt).await?;
-        let content = response.content;
-        
-        let json_str = if let Some(start) = content.find('{') {
-            if let Some(end) = content.rfind('}') { &content[start..=end] } else { &content[start..] }
-        } else { &content };
-
-        let data: serde_json::Value = serde_json::from_str(json_str).unwrap_or(json!({
-            "title": format!("Community {}", comm_id),
-            "summary": "Thematic group of related concepts."
-        }));
-
-        let title = data.get("title").and_then(|v| v.as_str()).unwrap_or("Untitled Cluster");
-        let summary = data.get("summary").and_then(|v| v.as_str()).unwrap_or("No summary available.");
+        // On failure the previous summary (if any) is kept rather than overwritten.
+        let output = complete_structured::<CommunitySummary>(
+            self.context.model.as_ref(),
+            &prompt,
+            self.context.config.extraction_retries,
+        ).await?;
+        let title = if output.value.title.is_empty() { "Untitled Cluster" } else { &output.value.title };
+        let summary = if output.value.summary.is_empty() { "No summary available." } else { &output.value.summary };
 
         self.context.db.upsert_community(comm_id, title, summary)?;
 
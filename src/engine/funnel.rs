@@ -1,6 +1,7 @@
 use crate::config::Config;
 use crate::storage::sqlite::SqliteDatabase;
 use crate::engine::vectors::{encode_bq, slice_vector};
+use crate::model::UnifiedModel;
 use anyhow::Result;
 use serde_json::{json, Value};
 use uuid::Uuid;
@@ -78,6 +79,13 @@ impl<'a> SearchFunnel<'a> {
         Ok(results.into_iter().take(top_k).collect())
     }
 
+    /// Embed `query` as a search query and run a hybrid search with it.
+    pub async fn recall(&self, model: &dyn UnifiedModel, query: &str, top_k: usize, namespace: &str) -> Result<Vec<FunnelResult>> {
+        let query_vector = model.embed_query(query).await
+            .map_err(|e| anyhow::anyhow!("Embedding failed: {}", e))?;
+        self.hybrid_search_with_namespace(&query_vector, top_k, namespace)
+    }
+
     pub fn hybrid_search_with_namespace(&self, query_vector: &[f32], top_k: usize, namespace: &str) -> Result<Vec<FunnelResult>> {
         // 1. Vector Search
         let mut results = self.search_with_namespace(query_vector, top_k, namespace)?;
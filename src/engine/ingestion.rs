@@ -1,31 +1,125 @@
-use crate::storage::sqlite::SqliteDatabase;
-use crate::engine::vectors::{encode_bq, slice_vector};
+use crate::config::{ChunkingConfig, ChunkingStrategy, ImageConfig, OntologyConfig, PdfConfig};
+use crate::engine::chunking::{
+    Chunker, CodeChunker, LlmChunker, MarkdownChunker, MarkerChunker, SimilarityChunker, TokenChunker, CHUNK_MARKER,
+};
+use crate::storage::sqlite::{ConflictRecord, ConflictStatus, SqliteDatabase};
+use crate::engine::loaders::{ImageLoader, LoaderRegistry, PdfLoader, IMAGE_MIMES};
+use crate::engine::ontology::{Ontology, OntologyReport};
+use crate::engine::structured::{complete_structured, ExtractedGraph, StructuredError};
+use crate::engine::vectors::{encode_bq, slice_vector, weighted_mean};
+use crate::model::tokens::{self, ApproxTokenCounter, TokenCounter};
+use crate::model::{EmbeddingBatcher, RetrievalEmbedder};
 use crate::KnowledgeEvent;
 use anyhow::Result;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use uuid::Uuid;
-use edgequake_llm::{LLMProvider, EmbeddingProvider};
+use edgequake_llm::LLMProvider;
 use serde_json::json;
 use std::path::Path;
 use tokio::sync::broadcast;
 
 pub struct IngestionPipeline {
-    embedder: Arc<dyn EmbeddingProvider>,
+    batcher: Arc<EmbeddingBatcher>,
+    tokens: Arc<dyn TokenCounter>,
     db: Arc<SqliteDatabase>,
     llm: Option<Arc<dyn LLMProvider>>,
     semantic_chunking: bool,
+    chunking: ChunkingConfig,
+    strategy: Option<ChunkingStrategy>,
+    loaders: Arc<LoaderRegistry>,
     event_tx: Option<broadcast::Sender<KnowledgeEvent>>,
+    ontologies: HashMap<String, OntologyConfig>,
+    max_retries: usize,
+    extract_graph: bool,
 }
 
 impl IngestionPipeline {
     pub fn new(
-        embedder: Arc<dyn EmbeddingProvider>, 
+        embedder: Arc<dyn RetrievalEmbedder>, 
         db: Arc<SqliteDatabase>,
         llm: Option<Arc<dyn LLMProvider>>,
         semantic_chunking: bool,
         event_tx: Option<broadcast::Sender<KnowledgeEvent>>
     ) -> Self {
-        Self { embedder, db, llm, semantic_chunking, event_tx }
+        let tokens = Arc::new(ApproxTokenCounter::new(embedder.max_tokens()));
+        let batcher = Arc::new(EmbeddingBatcher::new(embedder));
+        let loaders = Arc::new(LoaderRegistry::new(llm.clone()));
+        Self {
+            batcher, tokens, db, llm, semantic_chunking, event_tx, loaders,
+            chunking: ChunkingConfig::default(), strategy: None, ontologies: HashMap::new(), max_retries: 2,
+            extract_graph: true,
+        }
+    }
+
+    /// Check chunk lengths with the embedder's own tokenizer (see `UnifiedModel::token_counter`).
+    pub fn with_token_counter(mut self, tokens: Arc<dyn TokenCounter>) -> Self {
+        self.tokens = tokens;
+        self
+    }
+
+    /// Embed through a shared batcher, so concurrent ingestions are batched together.
+    pub fn with_batcher(mut self, batcher: Arc<EmbeddingBatcher>) -> Self {
+        self.batcher = batcher;
+        self
+    }
+
+    /// Chunking rules per file type (see `Config::chunking`).
+    pub fn with_chunking(mut self, chunking: ChunkingConfig) -> Self {
+        self.chunking = chunking;
+        self
+    }
+
+    /// Chunk everything this pipeline ingests with `strategy`, whatever the file type.
+    pub fn with_strategy(mut self, strategy: Option<ChunkingStrategy>) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Convert files with these loaders instead of the built-in ones.
+    pub fn with_loaders(mut self, loaders: Arc<LoaderRegistry>) -> Self {
+        self.loaders = loaders;
+        self
+    }
+
+    /// Read PDFs as configured (see `Config::pdf`), keeping any other custom loaders.
+    pub fn with_pdf(mut self, pdf: PdfConfig) -> Self {
+        let mut loaders = (*self.loaders).clone();
+        loaders.register("application/pdf", Arc::new(PdfLoader::new(self.llm.clone(), pdf)));
+        self.loaders = Arc::new(loaders);
+        self
+    }
+
+    /// Caption and OCR images as configured (see `Config::image`).
+    pub fn with_images(mut self, image: ImageConfig) -> Self {
+        let mut loaders = (*self.loaders).clone();
+        loaders.register_images(ImageLoader::new(self.llm.clone(), image));
+        self.loaders = Arc::new(loaders);
+        self
+    }
+
+    pub fn loaders(&self) -> &LoaderRegistry {
+        &self.loaders
+    }
+
+    /// Constrain graph extraction with per-namespace ontologies (see `Config::ontology`).
+    pub fn with_ontologies(mut self, ontologies: HashMap<String, OntologyConfig>) -> Self {
+        self.ontologies = ontologies;
+        self
+    }
+
+    /// Number of re-prompts after an unparseable extraction (see `Config::extraction_retries`).
+    pub fn with_max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Extract the knowledge graph of each chunk as it is stored (the
+    /// default). When off, documents are only embedded and stored, and the
+    /// caller runs `extract_graph` later (see `JobQueue`).
+    pub fn with_graph_extraction(mut self, extract_graph: bool) -> Self {
+        self.extract_graph = extract_graph;
+        self
     }
 
     pub async fn run(&self, text: &str, metadata: serde_json::Value) -> Result<Uuid> {
@@ -42,74 +136,172 @@ impl IngestionPipeline {
 
     pub async fn run_file(&self, path: &Path, metadata: serde_json::Value, namespace: &str) -> Result<Uuid> {
         eprintln!("DEBUG: [run_file] path={:?}, namespace={}", path, namespace);
-        
-        let content = if path.extension().and_then(|s| s.to_str()) == Some("pdf") {
-            if let Some(llm) = &self.llm {
-                eprintln!("DEBUG: [run_file] PDF conversion starting with provider: {} ({})", llm.name(), llm.model());
-                let config = edgequake_pdf2md::ConversionConfig::builder()
-                    .provider(llm.clone())
-                    .build()
-                    .map_err(|e| anyhow::anyhow!("Failed to build PDF config: {}", e))?;
-                
-                let output = edgequake_pdf2md::convert(path.to_string_lossy().as_ref(), &config).await
-                    .map_err(|e| anyhow::anyhow!("PDF conversion failed for {:?}: {}", path, e))?;
-                eprintln!("DEBUG: [run_file] PDF conversion complete. Pages processed: {}", output.stats.total_pages);
-                output.markdown
-            } else {
-                anyhow::bail!("LLM required for PDF extraction via edgequake-pdf2md");
-            }
-        } else {
-            // Fallback for non-PDF files: try reading as plain text
-            std::fs::read_to_string(path)
-                .map_err(|e| anyhow::anyhow!("Failed to read file as text {:?}: {}", path, e))?
-        };
 
-        eprintln!("DEBUG: Document content extracted ({} chars)", content.len());
+        let (loaded, format) = self.loaders.load(path).await?;
+        eprintln!("DEBUG: Document content extracted ({} chars, {})", loaded.text.len(), format);
 
         let mut file_metadata = metadata.clone();
         if let Some(obj) = file_metadata.as_object_mut() {
+            for (key, value) in loaded.metadata {
+                obj.entry(key).or_insert(value);
+            }
             obj.insert("source_file".to_string(), json!(path.to_string_lossy()));
+            obj.insert("format".to_string(), json!(format));
             if !obj.contains_key("title") {
                 obj.insert("title".to_string(), json!(path.file_name().unwrap_or_default().to_string_lossy()));
             }
         }
 
-        self.run_with_namespace(&content, file_metadata, namespace).await
+        let extension = path.extension().and_then(|s| s.to_str());
+        self.run_text(&loaded.text, &loaded.pages, file_metadata, namespace, extension).await
     }
 
+    /// Store an image as the text its caption and OCR produce, with its
+    /// path, hash, dimensions and capture time in the metadata.
     pub async fn run_image(&self, path: &Path, metadata: serde_json::Value, namespace: &str) -> Result<Uuid> {
+        let mime = self.loaders.mime_for(path);
+        if !IMAGE_MIMES.contains(&mime.as_str()) {
+            anyhow::bail!("{:?} is not a supported image ({})", path, mime);
+        }
         self.run_file(path, metadata, namespace).await
     }
 
     pub async fn run_with_namespace(&self, text: &str, metadata: serde_json::Value, namespace: &str) -> Result<Uuid> {
-        if text.contains("---CHUNK---") {
-            let chunks: Vec<&str> = text.split("---CHUNK---").map(|s| s.trim()).filter(|s| !s.is_empty()).collect();
-            let parent_id = Uuid::new_v4();
-            for chunk in chunks {
-                let mut chunk_meta = metadata.clone();
-                if let Some(obj) = chunk_meta.as_object_mut() {
-                    obj.insert("parent_id".to_string(), json!(parent_id.to_string()));
+        self.run_text(text, &[], metadata, namespace, None).await
+    }
+
+    /// The strategy for text from a file with `extension` (`None` for plain text).
+    pub fn strategy_for(&self, extension: Option<&str>) -> ChunkingStrategy {
+        if let Some(strategy) = self.strategy {
+            return strategy;
+        }
+        match self.chunking.strategy_for(extension) {
+            ChunkingStrategy::None if self.semantic_chunking => ChunkingStrategy::Llm,
+            strategy => strategy,
+        }
+    }
+
+    fn chunker(&self, strategy: ChunkingStrategy) -> Option<Box<dyn Chunker>> {
+        let size = self.chunking.chunk_tokens.min(self.tokens.token_budget());
+        let overlap = self.chunking.overlap_tokens;
+        let tokens = self.tokens.clone();
+        Some(match strategy {
+            ChunkingStrategy::None => return None,
+            ChunkingStrategy::Tokens => Box::new(TokenChunker::new(tokens, size, overlap)),
+            ChunkingStrategy::Markdown => Box::new(MarkdownChunker::new(tokens, size, overlap)),
+            ChunkingStrategy::Code => Box::new(CodeChunker::new(tokens, size, overlap)),
+            ChunkingStrategy::Similarity => Box::new(SimilarityChunker::new(
+                self.batcher.clone(), tokens, size, self.chunking.breakpoint_percentile,
+            )),
+            ChunkingStrategy::Llm => match &self.llm {
+                Some(llm) => Box::new(LlmChunker::new(llm.clone())),
+                None => {
+                    eprintln!("  ! Warning: LLM chunking needs an LLM extractor; storing the text as one chunk");
+                    return None;
                 }
-                self.process_chunk(chunk, chunk_meta, namespace).await?;
+            },
+        })
+    }
+
+    /// `pages` holds the byte offset where each page of `text` starts, if any.
+    async fn run_text(&self, text: &str, pages: &[usize], metadata: serde_json::Value, namespace: &str, extension: Option<&str>) -> Result<Uuid> {
+        // An explicit marker always wins; marker and LLM chunks are always
+        // stored under a parent, even when there is only one.
+        let (strategy, chunks) = if text.contains(CHUNK_MARKER) {
+            ("marker", MarkerChunker.chunk(text).await?)
+        } else {
+            let strategy = self.strategy_for(extension);
+            let Some(chunker) = self.chunker(strategy) else {
+                let ids = self.process_chunks(&[(text, metadata)], namespace).await?;
+                return Ok(ids[0]);
+            };
+            let chunks = chunker.chunk(text).await?;
+            if chunks.len() <= 1 && strategy != ChunkingStrategy::Llm {
+                let ids = self.process_chunks(&[(text, metadata)], namespace).await?;
+                return Ok(ids[0]);
             }
-            return Ok(parent_id);
-        }
+            (strategy.as_str(), chunks)
+        };
 
-        if self.semantic_chunking && self.llm.is_some() {
-            return self.run_semantic(text, metadata, namespace).await;
+        let parent_id = Uuid::new_v4();
+        let mut parent_meta = metadata.clone();
+        if let Some(obj) = parent_meta.as_object_mut() {
+            obj.insert("parent_id".to_string(), json!(parent_id.to_string()));
+        }
+        if strategy == ChunkingStrategy::Llm.as_str()
+            && let Some(llm) = &self.llm
+        {
+            let summary_prompt = format!("Provide a concise one-sentence summary of the following text:\n{}", text);
+            let summary_resp = llm.complete(&summary_prompt).await?;
+            if let Some(obj) = parent_meta.as_object_mut() {
+                obj.insert("parent_summary".to_string(), json!(summary_resp.content.trim()));
+            }
         }
 
-        self.process_chunk(text, metadata, namespace).await
+        let items: Vec<(&str, serde_json::Value)> = chunks
+            .iter()
+            .enumerate()
+            .map(|(i, chunk)| {
+                let mut chunk_meta = parent_meta.clone();
+                if let Some(obj) = chunk_meta.as_object_mut() {
+                    let mut chunk_info = chunk.metadata(i, chunks.len(), strategy);
+                    if let (Some(range), Some(info)) = (&chunk.range, chunk_info.as_object_mut())
+                        && !pages.is_empty()
+                    {
+                        // 1-based first and last page the chunk overlaps.
+                        let first = pages.partition_point(|&start| start <= range.start);
+                        let last = pages.partition_point(|&start| start < range.end.max(range.start + 1));
+                        info.insert("pages".to_string(), json!([first.max(1), last.max(1)]));
+                    }
+                    obj.insert("chunk".to_string(), chunk_info);
+                }
+                (chunk.text.as_str(), chunk_meta)
+            })
+            .collect();
+        self.process_chunks(&items, namespace).await?;
+        Ok(parent_id)
+    }
+
+    /// Embed all chunks of a document as one batch of documents, then store
+    /// each chunk and extract its graph. A chunk over the embedder's token
+    /// limit is embedded in overlapping windows whose vectors are averaged,
+    /// and its metadata records the split.
+    async fn process_chunks(&self, chunks: &[(&str, serde_json::Value)], namespace: &str) -> Result<Vec<Uuid>> {
+        eprintln!("DEBUG: Embedding {} chunk(s)...", chunks.len());
+        let checked: Vec<tokens::Windows> = chunks.iter().map(|(c, _)| tokens::windows(c, self.tokens.as_ref())).collect();
+        let inputs: Vec<String> = checked.iter().flat_map(|w| w.windows.iter().map(|t| t.to_string())).collect();
+        let mut vectors = self.batcher.embed_documents(inputs).await?.into_iter();
+
+        let mut ids = Vec::with_capacity(chunks.len());
+        for ((chunk, metadata), check) in chunks.iter().zip(&checked) {
+            let window_vectors: Vec<Vec<f32>> = vectors.by_ref().take(check.windows.len()).collect();
+            let mut chunk_meta = metadata.clone();
+            let v_full = if check.is_split() {
+                eprintln!(
+                    "  ! Warning: Chunk of {} tokens exceeds the embedding limit of {}; embedded in {} windows",
+                    check.tokens, self.tokens.token_budget(), check.windows.len()
+                );
+                if let Some(obj) = chunk_meta.as_object_mut() {
+                    obj.insert("embedding".to_string(), json!({
+                        "tokens": check.tokens,
+                        "token_limit": self.tokens.token_budget(),
+                        "windows": check.windows.len(),
+                        "truncated": false,
+                    }));
+                }
+                weighted_mean(&window_vectors, &check.window_tokens)
+            } else {
+                window_vectors.into_iter().next().unwrap_or_default()
+            };
+            ids.push(self.store_chunk(chunk, &v_full, chunk_meta, namespace).await?);
+        }
+        Ok(ids)
     }
 
-    async fn process_chunk(&self, text: &str, metadata: serde_json::Value, namespace: &str) -> Result<Uuid> {
+    async fn store_chunk(&self, text: &str, v_full: &[f32], metadata: serde_json::Value, namespace: &str) -> Result<Uuid> {
         let id = Uuid::new_v4();
-        eprintln!("DEBUG: Embedding chunk (len={})...", text.len());
-        let v_full = self.embedder.embed_one(text).await
-            .map_err(|e| anyhow::anyhow!("Embedding failed: {}", e))?;
-        
-        let v_short = slice_vector(&v_full, self.db.dimension() / 3);
-        let v_bit = encode_bq(&v_full);
+        let v_short = slice_vector(v_full, self.db.dimension() / 3);
+        let v_bit = encode_bq(v_full);
 
         let mut full_metadata = metadata.clone();
         if let Some(obj) = full_metadata.as_object_mut() {
@@ -123,7 +315,7 @@ impl IngestionPipeline {
                 if text.len() > 50 { &text[..50] } else { text }
             });
         
-        self.db.insert_document_with_namespace(id, title, text, &full_metadata, &v_full, &v_short, &v_bit, namespace)?;
+        self.db.insert_document_with_namespace(id, title, text, &full_metadata, v_full, &v_short, &v_bit, namespace)?;
 
         // Emit Event
         if let Some(tx) = &self.event_tx {
@@ -134,43 +326,32 @@ impl IngestionPipeline {
             });
         }
 
-        if let Some(llm) = &self.llm {
-            eprintln!("DEBUG: Extracting Knowledge Graph from chunk...");
-            self.extract_and_store_graph(text, id, llm, namespace).await?;
-            eprintln!("DEBUG: KG Extraction complete.");
+        if self.extract_graph
+            && let Err(e) = self.extract_graph(id, text, namespace).await
+        {
+            // A failed extraction is recorded on the chunk, which is kept.
+            if e.downcast_ref::<StructuredError>().is_none() {
+                return Err(e);
+            }
         }
         Ok(id)
     }
 
-    async fn run_semantic(&self, text: &str, metadata: serde_json::Value, namespace: &str) -> Result<Uuid> {
-        let llm = self.llm.as_ref().unwrap();
-        let prompt = format!(
-            "Divide the following text into logical semantic chunks.\n\
-             Return each chunk separated by '---CHUNK---'.\n\n\
-             Text: {}",
-            text
-        );
-        let response = llm.complete(&prompt).await?;
-        let chunks: Vec<&str> = response.content.split("---CHUNK---").map(|s| s.trim()).filter(|s| !s.is_empty()).collect();
-
-        let parent_id = Uuid::new_v4();
-        
-        let summary_prompt = format!("Provide a concise one-sentence summary of the following text:\n{}", text);
-        let summary_resp = llm.complete(&summary_prompt).await?;
-        let parent_summary = summary_resp.content.trim();
-
-        for chunk in chunks {
-            let mut chunk_meta = metadata.clone();
-            if let Some(obj) = chunk_meta.as_object_mut() {
-                obj.insert("parent_id".to_string(), json!(parent_id.to_string()));
-                obj.insert("parent_summary".to_string(), json!(parent_summary));
-            }
-            self.process_chunk(chunk, chunk_meta, namespace).await?;
+    /// Extract the knowledge graph of the stored chunk `doc_id` (no-op without an LLM).
+    /// Fails with a `StructuredError`, recorded on the chunk as
+    /// `extraction.status = "failed"`, when the LLM gives no usable graph.
+    pub async fn extract_graph(&self, doc_id: Uuid, text: &str, namespace: &str) -> Result<()> {
+        if let Some(llm) = &self.llm {
+            eprintln!("DEBUG: Extracting Knowledge Graph from chunk...");
+            self.extract_and_store_graph(text, doc_id, llm, namespace).await?;
+            eprintln!("DEBUG: KG Extraction complete.");
         }
-        Ok(parent_id)
+        Ok(())
     }
 
-    async fn extract_and_store_graph(&self, text: &str, _doc_id: Uuid, llm: &Arc<dyn LLMProvider>, namespace: &str) -> Result<()> {
+    async fn extract_and_store_graph(&self, text: &str, doc_id: Uuid, llm: &Arc<dyn LLMProvider>, namespace: &str) -> Result<()> {
+        let ontology = Ontology::for_namespace(&self.ontologies, namespace);
+
         let mut existing_context = String::new();
         let words: Vec<&str> = text.split_whitespace().collect();
         for word in words {
@@ -182,6 +363,7 @@ impl IngestionPipeline {
         }
 
         let context_prompt = if existing_context.is_empty() { "".to_string() } else { format!("\nEXISTING KNOWLEDGE:\n{}\n", existing_context) };
+        let ontology_prompt = ontology.as_ref().map(|o| o.prompt_section()).unwrap_or_default();
 
         let prompt = format!(
             "Extract entities and relationships from the following text.\n\
@@ -191,75 +373,200 @@ impl IngestionPipeline {
              KNOWLEDGE EVOLUTION:\n\
              - If a fact in the text updates, extends, or supersedes existing knowledge, use predicates like 'UPDATES', 'EXTENDS', or 'SUPERSEDES'.\n\
              - CONFLICT DETECTION: If the text directly CONTRADICTS existing knowledge provided below, list the conflict details in the 'conflicts' key.\n\
-             {}\n\
+               Each conflict should have: 'entity' (the existing entity name), 'existing' (the contradicted fact), 'claim' (the new statement), and optionally 'predicate'.\n\
+             {}{}\n\
              Text: {}\n\nJSON:",
+            ontology_prompt,
             context_prompt,
             text
         );
 
-        let response = llm.complete(&prompt).await?;
-        let content = response.content;
-        let json_str = if let Some(start) = content.find('{') {
-            if let Some(end) = content.rfind('}') { &content[start..=end] } else { &content[start..] }
-        } else { &content };
-
-        let graph: serde_json::Value = match serde_json::from_str(json_str) {
-            Ok(g) => g,
-            Err(_) => return Ok(()),
+        let graph: ExtractedGraph = match complete_structured(llm.as_ref(), &prompt, self.max_retries).await {
+            Ok(output) => {
+                if !output.errors.is_empty() {
+                    self.record_extraction(doc_id, "ok", output.attempts, &output.errors)?;
+                }
+                output.value
+            }
+            Err(e) => {
+                eprintln!("[extraction] {}: {}", doc_id, e);
+                self.record_extraction(doc_id, "failed", e.attempts, &e.errors)?;
+                return Err(e.into());
+            }
         };
 
-        if let Some(conflicts) = graph.get("conflicts").and_then(|v| v.as_array()) {
-            for conflict in conflicts { eprintln!("CONFLICT DETECTED: {}", conflict); }
+        // Resolve what each conflict contradicts before the new facts are stored.
+        let mut conflicts = Vec::new();
+        for raw in &graph.conflicts {
+            if let Some(conflict) = self.prepare_conflict(raw, doc_id, namespace)? {
+                conflicts.push(conflict);
+            }
         }
+        let mut claim_relationships: HashMap<(String, String), Uuid> = HashMap::new();
+
+        let mut report = OntologyReport::default();
+        let default_type = ontology.as_ref().map(|o| o.fallback_entity_type()).unwrap_or("Concept").to_string();
+        // Entities dropped by the ontology; relationships touching them are dropped too,
+        // so they are not re-created below under the fallback type.
+        let mut rejected: HashSet<String> = HashSet::new();
 
-        if let Some(entities) = graph.get("entities").and_then(|v| v.as_array()) {
-            for entity in entities {
-                let name = entity.get("name").and_then(|v| v.as_str()).unwrap_or("");
-                let etype = entity.get("type").and_then(|v| v.as_str()).unwrap_or("Concept");
-                let desc = entity.get("description").and_then(|v| v.as_str()).unwrap_or("");
-                if !name.is_empty()
-                    && let Ok(entity_id) = self.db.insert_entity_with_namespace(name, etype, desc, namespace) {
-                        // Emit Event
-                        if let Some(tx) = &self.event_tx {
-                            let _ = tx.send(KnowledgeEvent::EntityInserted { 
-                                id: entity_id, 
-                                name: name.to_string(), 
-                                namespace: namespace.to_string() 
-                            });
-                        }
+        for entity in &graph.entities {
+            let name = entity.name.as_str();
+            let raw_type = if entity.entity_type.is_empty() { default_type.as_str() } else { entity.entity_type.as_str() };
+            let desc = entity.description.as_str();
+            if name.is_empty() { continue; }
+            let etype = match &ontology {
+                Some(o) => match report.record("entity_type", raw_type, o.entity_type(raw_type)) {
+                    Some(t) => t,
+                    None => {
+                        rejected.insert(name.to_lowercase());
+                        continue;
                     }
+                },
+                None => raw_type.to_string(),
+            };
+            if let Ok(entity_id) = self.db.insert_entity_with_namespace(name, &etype, desc, namespace) {
+                self.db.add_entity_source(entity_id, doc_id)?;
+                // Emit Event
+                if let Some(tx) = &self.event_tx {
+                    let _ = tx.send(KnowledgeEvent::EntityInserted { 
+                        id: entity_id, 
+                        name: name.to_string(), 
+                        namespace: namespace.to_string() 
+                    });
+                }
             }
         }
 
-        if let Some(relationships) = graph.get("relationships").and_then(|v| v.as_array()) {
-            for rel in relationships {
-                let s_name = rel.get("source").and_then(|v| v.as_str()).unwrap_or("");
-                let t_name = rel.get("target").and_then(|v| v.as_str()).unwrap_or("");
-                let pred = rel.get("predicate").and_then(|v| v.as_str()).unwrap_or("");
-                let desc = rel.get("description").and_then(|v| v.as_str()).unwrap_or("");
-                
-                if s_name.is_empty() || t_name.is_empty() { continue; }
-
-                // Auto-upsert entities referenced in relationships that the LLM omitted from 'entities'.
-                let s_id = self.db.get_entity_by_name_with_namespace(s_name, namespace)?
-                    .map(|(id, _, _)| id)
-                    .or_else(|| self.db.insert_entity_with_namespace(s_name, "Concept", desc, namespace).ok());
-                let t_id = self.db.get_entity_by_name_with_namespace(t_name, namespace)?
-                    .map(|(id, _, _)| id)
-                    .or_else(|| self.db.insert_entity_with_namespace(t_name, "Concept", desc, namespace).ok());
-                
-                if let (Some(s_id), Some(t_id)) = (s_id, t_id)
-                    && self.db.insert_relationship(s_id, t_id, pred, desc).is_ok() {
-                        if let Some(tx) = &self.event_tx {
-                            let _ = tx.send(KnowledgeEvent::RelationshipInserted {
-                                source_id: s_id,
-                                target_id: t_id,
-                                predicate: pred.to_string()
-                            });
-                        }
+        for rel in &graph.relationships {
+            let s_name = rel.source.as_str();
+            let t_name = rel.target.as_str();
+            let raw_pred = rel.predicate.as_str();
+            let desc = rel.description.as_str();
+            
+            if s_name.is_empty() || t_name.is_empty() { continue; }
+            if rejected.contains(&s_name.to_lowercase()) || rejected.contains(&t_name.to_lowercase()) { continue; }
+
+            let pred = match &ontology {
+                Some(o) => match report.record("predicate", raw_pred, o.predicate(raw_pred)) {
+                    Some(p) => p,
+                    None => continue,
+                },
+                None => raw_pred.to_string(),
+            };
+
+            // Auto-upsert entities referenced in relationships that the LLM omitted from 'entities'.
+            let s_id = self.db.get_entity_by_name_with_namespace(s_name, namespace)?
+                .map(|(id, _, _)| id)
+                .or_else(|| self.db.insert_entity_with_namespace(s_name, &default_type, desc, namespace).ok());
+            let t_id = self.db.get_entity_by_name_with_namespace(t_name, namespace)?
+                .map(|(id, _, _)| id)
+                .or_else(|| self.db.insert_entity_with_namespace(t_name, &default_type, desc, namespace).ok());
+            
+            if let (Some(s_id), Some(t_id)) = (s_id, t_id)
+                && let Ok(rel_id) = self.db.insert_relationship(s_id, t_id, &pred, desc) {
+                    claim_relationships.insert((s_name.to_lowercase(), pred.to_lowercase()), rel_id);
+                    if let Some(tx) = &self.event_tx {
+                        let _ = tx.send(KnowledgeEvent::RelationshipInserted {
+                            source_id: s_id,
+                            target_id: t_id,
+                            predicate: pred.clone()
+                        });
                     }
+                }
+        }
+
+        for (mut conflict, predicate) in conflicts {
+            conflict.claim_entity_id = self.db.get_entity_by_name_with_namespace(&conflict.entity_name, namespace)?
+                .map(|(id, _, _)| id);
+            conflict.claim_relationship_id = predicate.and_then(|p| {
+                claim_relationships.get(&(conflict.entity_name.to_lowercase(), p.to_lowercase())).copied()
+            });
+            eprintln!("[conflicts] {} on '{}': {}", conflict.id, conflict.entity_name, conflict.claim);
+            self.db.insert_conflict(&conflict)?;
+            if let Some(tx) = &self.event_tx {
+                let _ = tx.send(KnowledgeEvent::ConflictDetected {
+                    id: conflict.id,
+                    namespace: namespace.to_string()
+                });
             }
         }
+
+        if !report.is_empty() {
+            eprintln!(
+                "[ontology] {}: {} value(s) mapped, {} rejected in namespace '{}'",
+                doc_id, report.mapped.len(), report.rejected.len(), namespace
+            );
+            self.db.merge_document_metadata(doc_id, &json!({"ontology_report": report.to_json()}))?;
+        }
         Ok(())
     }
+
+    /// Record extraction attempts and parse failures in the document's metadata.
+    fn record_extraction(&self, doc_id: Uuid, status: &str, attempts: usize, errors: &[String]) -> Result<()> {
+        self.db.merge_document_metadata(doc_id, &json!({
+            "extraction": {
+                "status": status,
+                "attempts": attempts,
+                "failures": errors.len(),
+                "last_error": errors.last(),
+            }
+        }))
+    }
+
+    /// Build a conflict record from one extractor entry, pointing at the facts it contradicts.
+    /// Also returns the contradicted predicate, used to link the claim's own relationship.
+    fn prepare_conflict(&self, raw: &serde_json::Value, doc_id: Uuid, namespace: &str) -> Result<Option<(ConflictRecord, Option<String>)>> {
+        let field = |keys: &[&str]| keys.iter()
+            .find_map(|k| raw.get(*k).and_then(|v| v.as_str()))
+            .unwrap_or("")
+            .to_string();
+        let (entity_name, existing, claim, predicate) = match raw.as_str() {
+            Some(text) => (String::new(), String::new(), text.to_string(), String::new()),
+            None => (
+                field(&["entity", "entity_name"]),
+                field(&["existing", "existing_fact"]),
+                field(&["claim", "new", "description"]),
+                field(&["predicate"]),
+            ),
+        };
+        if claim.is_empty() {
+            return Ok(None);
+        }
+
+        let entity_id = if entity_name.is_empty() {
+            None
+        } else {
+            self.db.get_entity_by_name_with_namespace(&entity_name, namespace)?.map(|(id, _, _)| id)
+        };
+        let relationship_id = match entity_id {
+            Some(id) if !predicate.is_empty() => self.db.find_relationship(id, &predicate)?,
+            _ => None,
+        };
+
+        let mut source_documents = vec![doc_id.to_string()];
+        if let Some(id) = entity_id {
+            for source in self.db.get_entity_sources(id)? {
+                if !source_documents.contains(&source) { source_documents.push(source); }
+            }
+        }
+
+        let record = ConflictRecord {
+            id: Uuid::new_v4(),
+            namespace: namespace.to_string(),
+            claim,
+            existing,
+            entity_name,
+            entity_id,
+            relationship_id,
+            claim_entity_id: None,
+            claim_relationship_id: None,
+            source_documents,
+            detected_at: std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs(),
+            status: ConflictStatus::Open,
+            resolution: None,
+            resolved_at: None,
+        };
+        Ok(Some((record, (!predicate.is_empty()).then_some(predicate))))
+    }
 }
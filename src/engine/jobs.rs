@@ -0,0 +1,220 @@
+use crate::engine::ingestion::IngestionPipeline;
+use crate::storage::sqlite::{JobRecord, JobStatus, SqliteDatabase};
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, Semaphore};
+use uuid::Uuid;
+
+pub const GRAPH_EXTRACTION: &str = "graph_extraction";
+/// A running job not renewed for this long is assumed to belong to a process
+/// that died, and is queued again by `resume`. Its owner renews it several
+/// times per period, even while a single chunk takes longer.
+pub const STALE_JOB_SECS: u64 = 600;
+
+/// Runs knowledge graph extraction for stored documents in the background,
+/// at most `workers` jobs at a time. Jobs live in the database and record
+/// their progress after every chunk, so `resume` picks up whatever a
+/// previous process left unfinished. A worker claims a job atomically before
+/// running it and holds it while it renews it, so processes sharing one
+/// database never run the same job twice; a worker whose job was taken from
+/// it stops.
+pub struct JobQueue {
+    db: Arc<SqliteDatabase>,
+    pipeline: Arc<IngestionPipeline>,
+    permits: Arc<Semaphore>,
+    progress_tx: broadcast::Sender<JobRecord>,
+    /// Jobs this process is running, handed back to the queue by `release`.
+    running: Mutex<HashSet<Uuid>>,
+    /// Whose jobs these are in the database.
+    owner: Uuid,
+    stale_after: u64,
+}
+
+impl JobQueue {
+    /// `pipeline` does the extraction; it should extract the graph as it stores.
+    pub fn new(db: Arc<SqliteDatabase>, pipeline: Arc<IngestionPipeline>, workers: usize) -> Self {
+        let (progress_tx, _) = broadcast::channel(100);
+        Self {
+            db,
+            pipeline,
+            permits: Arc::new(Semaphore::new(workers.max(1))),
+            progress_tx,
+            running: Mutex::new(HashSet::new()),
+            owner: Uuid::new_v4(),
+            stale_after: STALE_JOB_SECS,
+        }
+    }
+
+    /// Seconds without renewal after which `resume` takes another
+    /// process's running job for dead (default `STALE_JOB_SECS`).
+    pub fn with_stale_after(mut self, seconds: u64) -> Self {
+        self.stale_after = seconds.max(1);
+        self
+    }
+
+    /// Every status or progress change of every job.
+    pub fn subscribe(&self) -> broadcast::Receiver<JobRecord> {
+        self.progress_tx.subscribe()
+    }
+
+    /// Queue graph extraction for the chunks of the stored `document_id`.
+    pub fn enqueue(self: &Arc<Self>, document_id: Uuid, namespace: &str, progress_token: Option<Value>) -> Result<JobRecord> {
+        let items = self.db.list_document_chunks(document_id)?;
+        let now = now()?;
+        let job = JobRecord {
+            id: Uuid::new_v4(),
+            kind: GRAPH_EXTRACTION.to_string(),
+            namespace: namespace.to_string(),
+            document_id,
+            total: items.len(),
+            items,
+            status: JobStatus::Queued,
+            completed: 0,
+            failed: 0,
+            error: None,
+            progress_token,
+            created_at: now,
+            updated_at: now,
+        };
+        self.db.insert_job(&job)?;
+        self.spawn(job.id, false);
+        Ok(job)
+    }
+
+    /// Restart the jobs a previous process left queued, or running and then
+    /// not renewed for `STALE_JOB_SECS`.
+    pub fn resume(self: &Arc<Self>) -> Result<usize> {
+        self.db.requeue_stale_jobs(now()?.saturating_sub(self.stale_after))?;
+        let jobs: Vec<JobRecord> = self.db.list_unfinished_jobs()?.into_iter().filter(|job| job.status == JobStatus::Queued).collect();
+        for job in &jobs {
+            eprintln!("DEBUG: [jobs] resuming {} at {}/{}", job.id, job.completed, job.total);
+            self.spawn(job.id, true);
+        }
+        Ok(jobs.len())
+    }
+
+    /// Hand the jobs this process is running back to the queue, for the next
+    /// process to resume. Call before exiting.
+    pub fn release(&self) -> Result<usize> {
+        let ids: Vec<Uuid> = self.running.lock().unwrap_or_else(|e| e.into_inner()).drain().collect();
+        let mut released = 0;
+        for id in ids {
+            if self.db.requeue_job(id, self.owner)? {
+                released += 1;
+            }
+        }
+        Ok(released)
+    }
+
+    fn spawn(self: &Arc<Self>, id: Uuid, resumed: bool) {
+        let queue = self.clone();
+        tokio::spawn(async move {
+            let Ok(_permit) = queue.permits.clone().acquire_owned().await else { return };
+            if let Err(e) = queue.run(id, resumed).await {
+                eprintln!("  ! Warning: Job {} stopped: {}", id, e);
+            }
+            queue.running.lock().unwrap_or_else(|e| e.into_inner()).remove(&id);
+        });
+    }
+
+    async fn run(&self, id: Uuid, resumed: bool) -> Result<()> {
+        if !self.db.claim_job(id, self.owner, now()?)? {
+            return Ok(());
+        }
+        self.running.lock().unwrap_or_else(|e| e.into_inner()).insert(id);
+        let Some(mut job) = self.db.get_job(id)? else { return Ok(()) };
+        // The request that queued a resumed job belonged to another process.
+        if resumed {
+            job.progress_token = None;
+        }
+        if !self.save(&mut job)? {
+            return Ok(());
+        }
+
+        while job.completed < job.items.len() {
+            if !self.running.lock().unwrap_or_else(|e| e.into_inner()).contains(&id) {
+                return Ok(()); // released for another process
+            }
+            let chunk = job.items[job.completed];
+            // A chunk deleted since it was queued has nothing left to extract.
+            if let Some((content, _)) = self.db.get_document_content(chunk)? {
+                let Some(outcome) = self.renewing(id, self.pipeline.extract_graph(chunk, &content, &job.namespace)).await? else {
+                    return Ok(());
+                };
+                if let Err(e) = outcome {
+                    eprintln!("  ! Warning: Graph extraction failed for {}: {}", chunk, e);
+                    job.failed += 1;
+                    job.error = Some(e.to_string());
+                }
+            }
+            job.completed += 1;
+            if !self.save(&mut job)? {
+                return Ok(());
+            }
+        }
+
+        job.status = if job.failed > 0 { JobStatus::Failed } else { JobStatus::Done };
+        self.save(&mut job)?;
+        Ok(())
+    }
+
+    /// Run `work` for job `id`, renewing the job while it runs. `None` if the
+    /// job stopped being ours meanwhile (released, or taken for stale).
+    async fn renewing<T>(&self, id: Uuid, work: impl std::future::Future<Output = T>) -> Result<Option<T>> {
+        tokio::pin!(work);
+        let mut renewals = tokio::time::interval(Duration::from_millis(self.stale_after * 1000 / 4));
+        renewals.tick().await; // the first tick is immediate
+        loop {
+            tokio::select! {
+                outcome = &mut work => return Ok(Some(outcome)),
+                _ = renewals.tick() => {
+                    if !self.db.renew_job(id, self.owner, now()?)? {
+                        eprintln!("  ! Warning: Job {} was taken over; stopping", id);
+                        return Ok(None);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Save `job` and announce it. False if the job is no longer ours.
+    fn save(&self, job: &mut JobRecord) -> Result<bool> {
+        job.updated_at = now()?;
+        if !self.db.update_job(job, self.owner)? {
+            return Ok(false);
+        }
+        let _ = self.progress_tx.send(job.clone());
+        Ok(true)
+    }
+}
+
+/// The MCP `notifications/progress` message for a job queued with a progress token.
+pub fn progress_notification(job: &JobRecord) -> Option<Value> {
+    let token = job.progress_token.as_ref()?;
+    let message = match job.status {
+        JobStatus::Queued => "Graph extraction queued".to_string(),
+        JobStatus::Done => format!("Graph extraction done ({} chunk(s))", job.total),
+        JobStatus::Failed => format!(
+            "Graph extraction finished with {} failed chunk(s): {}",
+            job.failed, job.error.as_deref().unwrap_or("unknown error")
+        ),
+        JobStatus::Running => format!("Graph extraction: {}/{} chunk(s)", job.completed, job.total),
+    };
+    Some(json!({
+        "jsonrpc": "2.0",
+        "method": "notifications/progress",
+        "params": {
+            "progressToken": token,
+            "progress": job.completed,
+            "total": job.total,
+            "message": message
+        }
+    }))
+}
+
+fn now() -> Result<u64> {
+    Ok(std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs())
+}
@@ -0,0 +1,816 @@
+use crate::config::{ImageConfig, PdfConfig, PdfExtraction};
+use crate::engine::chunking::CHUNK_MARKER;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use edgequake_llm::{ChatMessage, ImageData, LLMProvider};
+use scraper::{ElementRef, Html, Node, Selector};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Leading bytes checked for NULs before unknown content is treated as text.
+const SNIFF_BYTES: usize = 8000;
+/// Rows per chunk of a CSV / TSV table.
+pub const CSV_ROWS_PER_CHUNK: usize = 20;
+/// Longest notebook cell output kept, in characters.
+const NOTEBOOK_OUTPUT_CHARS: usize = 500;
+
+/// A file converted to text, ready for chunking.
+#[derive(Debug, Clone, Default)]
+pub struct Loaded {
+    pub text: String,
+    /// Merged into the document's metadata; keys given by the caller win.
+    pub metadata: Map<String, Value>,
+    /// Byte offset in `text` where each page starts, for paged formats.
+    pub pages: Vec<usize>,
+}
+
+impl Loaded {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self { text: text.into(), ..Self::default() }
+    }
+}
+
+/// Converts the bytes of one file format to text.
+#[async_trait]
+pub trait Loader: Send + Sync {
+    /// Recorded as `format` in the document's metadata.
+    fn name(&self) -> &'static str;
+    async fn load(&self, path: &Path, bytes: &[u8]) -> Result<Loaded>;
+}
+
+/// A file with no loader that does not look like text.
+#[derive(Debug)]
+pub struct UnsupportedFormat {
+    pub path: String,
+    pub mime: String,
+}
+
+impl std::fmt::Display for UnsupportedFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Skipping {}: unsupported binary format ({})", self.path, self.mime)
+    }
+}
+
+impl std::error::Error for UnsupportedFormat {}
+
+/// Picks a loader by MIME type, derived from the file extension. Files
+/// without one are read as UTF-8 text unless they look binary.
+#[derive(Clone)]
+pub struct LoaderRegistry {
+    by_mime: HashMap<String, Arc<dyn Loader>>,
+    /// Extensions whose MIME type is not (or not usefully) known to `mime_guess`.
+    extensions: HashMap<String, String>,
+    text: Arc<dyn Loader>,
+}
+
+impl LoaderRegistry {
+    /// A registry with only the plain-text fallback.
+    pub fn empty() -> Self {
+        Self { by_mime: HashMap::new(), extensions: HashMap::new(), text: Arc::new(TextLoader) }
+    }
+
+    /// The built-in loaders. PDFs are read from their text layer, and `llm`
+    /// (when there is one) converts pages without one. Images need an
+    /// `ImageConfig` declaring a vision-capable LLM or an OCR command (see
+    /// `register_images`).
+    pub fn new(llm: Option<Arc<dyn LLMProvider>>) -> Self {
+        let mut registry = Self::empty();
+        let markdown: Arc<dyn Loader> = Arc::new(MarkdownLoader);
+        let html: Arc<dyn Loader> = Arc::new(HtmlLoader);
+        let table: Arc<dyn Loader> = Arc::new(CsvLoader);
+        registry.register("text/markdown", markdown.clone());
+        registry.register("text/x-markdown", markdown);
+        registry.register("text/html", html.clone());
+        registry.register("application/xhtml+xml", html);
+        registry.register("application/x-ipynb+json", Arc::new(NotebookLoader));
+        registry.register("text/csv", table.clone());
+        registry.register("text/tab-separated-values", table);
+        registry.register("application/json", Arc::new(JsonLoader));
+        registry.register("application/pdf", Arc::new(PdfLoader::new(llm.clone(), PdfConfig::default())));
+        registry.register_images(ImageLoader::new(llm, ImageConfig::default()));
+        registry.register_extension("mdx", "text/markdown");
+        registry.register_extension("ipynb", "application/x-ipynb+json");
+        registry
+    }
+
+    /// Use `loader` for files of MIME type `mime`, replacing any earlier one.
+    pub fn register(&mut self, mime: &str, loader: Arc<dyn Loader>) {
+        self.by_mime.insert(mime.to_string(), loader);
+    }
+
+    /// Use `loader` for every image type, or for none when it has no way to
+    /// describe them (images are then skipped as unsupported binaries).
+    pub fn register_images(&mut self, loader: ImageLoader) {
+        if loader.can_describe() {
+            let loader: Arc<dyn Loader> = Arc::new(loader);
+            for mime in IMAGE_MIMES {
+                self.register(mime, loader.clone());
+            }
+        } else {
+            for mime in IMAGE_MIMES {
+                self.by_mime.remove(*mime);
+            }
+        }
+    }
+
+    /// Treat files with `extension` as MIME type `mime`.
+    pub fn register_extension(&mut self, extension: &str, mime: &str) {
+        self.extensions.insert(extension.to_ascii_lowercase(), mime.to_string());
+    }
+
+    pub fn mime_for(&self, path: &Path) -> String {
+        let extension = path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase());
+        if let Some(mime) = extension.as_ref().and_then(|e| self.extensions.get(e)) {
+            return mime.clone();
+        }
+        mime_guess::from_path(path)
+            .first_raw()
+            .unwrap_or("application/octet-stream")
+            .to_string()
+    }
+
+    /// The loader for `path`, or `None` for content that cannot be ingested.
+    pub fn loader_for(&self, path: &Path, bytes: &[u8]) -> Option<Arc<dyn Loader>> {
+        let mime = self.mime_for(path);
+        if let Some(loader) = self.by_mime.get(&mime) {
+            return Some(loader.clone());
+        }
+        let binary = bytes[..bytes.len().min(SNIFF_BYTES)].contains(&0) || std::str::from_utf8(bytes).is_err();
+        (!binary).then(|| self.text.clone())
+    }
+
+    /// Read and convert `path`. Fails with [`UnsupportedFormat`] for binaries
+    /// without a loader.
+    pub async fn load(&self, path: &Path) -> Result<(Loaded, &'static str)> {
+        let bytes = std::fs::read(path).with_context(|| format!("Failed to read {:?}", path))?;
+        let Some(loader) = self.loader_for(path, &bytes) else {
+            return Err(UnsupportedFormat { path: path.to_string_lossy().to_string(), mime: self.mime_for(path) }.into());
+        };
+        let loaded = loader.load(path, &bytes).await?;
+        Ok((loaded, loader.name()))
+    }
+}
+
+impl Default for LoaderRegistry {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+fn utf8(path: &Path, bytes: &[u8]) -> Result<String> {
+    String::from_utf8(bytes.to_vec()).map_err(|_| anyhow::anyhow!("Failed to read file as text {:?}: not valid UTF-8", path))
+}
+
+/// Plain UTF-8 text, as is.
+pub struct TextLoader;
+
+#[async_trait]
+impl Loader for TextLoader {
+    fn name(&self) -> &'static str { "text" }
+
+    async fn load(&self, path: &Path, bytes: &[u8]) -> Result<Loaded> {
+        Ok(Loaded::text(utf8(path, bytes)?))
+    }
+}
+
+/// Markdown with optional YAML front matter (between `---` lines at the top
+/// of the file), whose keys become metadata.
+pub struct MarkdownLoader;
+
+#[async_trait]
+impl Loader for MarkdownLoader {
+    fn name(&self) -> &'static str { "markdown" }
+
+    async fn load(&self, path: &Path, bytes: &[u8]) -> Result<Loaded> {
+        let text = utf8(path, bytes)?;
+        let Some((front, body)) = split_front_matter(&text) else {
+            return Ok(Loaded::text(text));
+        };
+        let metadata = match serde_yaml::from_str::<serde_yaml::Value>(front).map(serde_json::to_value) {
+            Ok(Ok(Value::Object(map))) => map,
+            Ok(Ok(Value::Null)) => Map::new(),
+            _ => {
+                eprintln!("  ! Warning: Ignoring front matter of {:?}: not a YAML mapping", path);
+                Map::new()
+            }
+        };
+        Ok(Loaded { text: body.to_string(), metadata, ..Loaded::default() })
+    }
+}
+
+/// (front matter, body) when `text` opens with a `---` fenced block.
+pub fn split_front_matter(text: &str) -> Option<(&str, &str)> {
+    let rest = text.strip_prefix("---\n").or_else(|| text.strip_prefix("---\r\n"))?;
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if matches!(line.trim_end(), "---" | "...") {
+            return Some((&rest[..offset], rest[offset + line.len()..].trim_start_matches(['\r', '\n'])));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// HTML as Markdown-like text: scripts and styles dropped, headings kept as
+/// `#` lines so the document can be chunked by section, list items as `-`.
+/// The `<title>`, description and language become metadata.
+pub struct HtmlLoader;
+
+const HTML_SKIPPED: &[&str] = &["head", "script", "style", "noscript", "template", "svg", "iframe"];
+const HTML_BLOCKS: &[&str] = &[
+    "p", "div", "section", "article", "main", "header", "footer", "nav", "aside", "blockquote", "ul", "ol",
+    "table", "tr", "form", "figure", "dl", "dt", "dd", "hr",
+];
+
+#[async_trait]
+impl Loader for HtmlLoader {
+    fn name(&self) -> &'static str { "html" }
+
+    async fn load(&self, path: &Path, bytes: &[u8]) -> Result<Loaded> {
+        let source = String::from_utf8_lossy(bytes);
+        let document = Html::parse_document(&source);
+        let mut metadata = Map::new();
+        let select = |css: &str| Selector::parse(css).ok().and_then(|s| document.select(&s).next());
+        if let Some(title) = select("title").map(|t| collapse(&t.text().collect::<String>())).filter(|t| !t.is_empty()) {
+            metadata.insert("title".to_string(), json!(title));
+        }
+        if let Some(description) = select("meta[name=description]").and_then(|m| m.value().attr("content")) {
+            metadata.insert("description".to_string(), json!(description.trim()));
+        }
+        if let Some(lang) = select("html").and_then(|h| h.value().attr("lang")) {
+            metadata.insert("lang".to_string(), json!(lang));
+        }
+
+        let mut text = String::new();
+        html_text(document.root_element(), &mut text);
+        let text = tidy(&text);
+        if text.is_empty() {
+            anyhow::bail!("No text found in {:?}", path);
+        }
+        Ok(Loaded { text, metadata, ..Loaded::default() })
+    }
+}
+
+/// Trim lines and collapse blank runs, leaving fenced blocks as they are.
+fn tidy(text: &str) -> String {
+    let mut out = String::new();
+    let mut fenced = false;
+    let mut blank = false;
+    for line in text.lines() {
+        let trimmed = line.trim();
+        let fence = trimmed.starts_with("```");
+        if !fenced && !fence && trimmed.is_empty() {
+            blank = !out.is_empty();
+            continue;
+        }
+        if blank {
+            out.push('\n');
+            blank = false;
+        }
+        out.push_str(if fenced { line.trim_end() } else { trimmed });
+        out.push('\n');
+        if fence {
+            fenced = !fenced;
+        }
+    }
+    out.trim_end().to_string()
+}
+
+fn collapse(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn html_text(element: ElementRef<'_>, out: &mut String) {
+    let name = element.value().name();
+    if HTML_SKIPPED.contains(&name) {
+        return;
+    }
+    match name {
+        "pre" => {
+            out.push_str("\n\n```\n");
+            out.push_str(element.text().collect::<String>().trim_matches('\n'));
+            out.push_str("\n```\n\n");
+            return;
+        }
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+            let level = name[1..].parse().unwrap_or(1);
+            out.push_str(&format!("\n\n{} {}\n\n", "#".repeat(level), collapse(&element.text().collect::<String>())));
+            return;
+        }
+        "br" => out.push('\n'),
+        "li" => out.push_str("\n- "),
+        "td" | "th" => out.push_str(" | "),
+        _ if HTML_BLOCKS.contains(&name) => out.push_str("\n\n"),
+        _ => {}
+    }
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) => {
+                // Collapse whitespace but keep the boundaries between inline runs.
+                let collapsed = collapse(text);
+                if collapsed.is_empty() {
+                    if !text.is_empty() && !out.ends_with([' ', '\n']) {
+                        out.push(' ');
+                    }
+                    continue;
+                }
+                if text.starts_with(char::is_whitespace) && !out.ends_with([' ', '\n']) {
+                    out.push(' ');
+                }
+                out.push_str(&collapsed);
+                if text.ends_with(char::is_whitespace) {
+                    out.push(' ');
+                }
+            }
+            Node::Element(_) => {
+                if let Some(child) = ElementRef::wrap(child) {
+                    html_text(child, out);
+                }
+            }
+            _ => {}
+        }
+    }
+    if HTML_BLOCKS.contains(&name) {
+        out.push_str("\n\n");
+    }
+}
+
+/// Jupyter notebooks: Markdown cells as is, code cells fenced in the
+/// kernel's language, followed by their (shortened) text output.
+pub struct NotebookLoader;
+
+#[async_trait]
+impl Loader for NotebookLoader {
+    fn name(&self) -> &'static str { "notebook" }
+
+    async fn load(&self, path: &Path, bytes: &[u8]) -> Result<Loaded> {
+        let notebook: Value = serde_json::from_slice(bytes).with_context(|| format!("Invalid notebook {:?}", path))?;
+        let language = notebook.pointer("/metadata/kernelspec/language")
+            .or_else(|| notebook.pointer("/metadata/language_info/name"))
+            .and_then(|l| l.as_str())
+            .unwrap_or("")
+            .to_string();
+        let cells = notebook.get("cells").and_then(|c| c.as_array()).cloned().unwrap_or_default();
+
+        let mut blocks = Vec::new();
+        let mut code_cells = 0;
+        for cell in &cells {
+            let source = cell_text(cell.get("source"));
+            if source.trim().is_empty() {
+                continue;
+            }
+            match cell.get("cell_type").and_then(|t| t.as_str()) {
+                Some("code") => {
+                    code_cells += 1;
+                    let mut block = format!("```{}\n{}\n```", language, source.trim_end());
+                    let output: String = cell.get("outputs").and_then(|o| o.as_array()).into_iter().flatten()
+                        .map(|o| cell_text(o.get("text").or_else(|| o.pointer("/data/text~1plain"))))
+                        .collect();
+                    if !output.trim().is_empty() {
+                        let shortened: String = output.trim_end().chars().take(NOTEBOOK_OUTPUT_CHARS).collect();
+                        block.push_str(&format!("\n\nOutput:\n```\n{}\n```", shortened));
+                    }
+                    blocks.push(block);
+                }
+                Some("raw") => {}
+                _ => blocks.push(source.trim_end().to_string()),
+            }
+        }
+
+        let mut metadata = Map::new();
+        metadata.insert("notebook".to_string(), json!({"cells": cells.len(), "code_cells": code_cells, "language": language}));
+        Ok(Loaded { text: blocks.join("\n\n"), metadata, ..Loaded::default() })
+    }
+}
+
+/// Notebook text fields are a string or a list of lines.
+fn cell_text(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Array(lines)) => lines.iter().filter_map(|l| l.as_str()).collect(),
+        _ => String::new(),
+    }
+}
+
+/// CSV and TSV tables, one `column: value` line per row, split every
+/// [`CSV_ROWS_PER_CHUNK`] rows with explicit chunk markers.
+pub struct CsvLoader;
+
+#[async_trait]
+impl Loader for CsvLoader {
+    fn name(&self) -> &'static str { "table" }
+
+    async fn load(&self, path: &Path, bytes: &[u8]) -> Result<Loaded> {
+        let text = utf8(path, bytes)?;
+        let tsv = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("tsv"));
+        let mut records = parse_delimited(text.trim_start_matches('\u{feff}'), if tsv { '\t' } else { ',' }).into_iter();
+        let header = records.next().unwrap_or_default();
+        let rows: Vec<String> = records
+            .filter(|record| record.iter().any(|field| !field.trim().is_empty()))
+            .map(|record| {
+                record
+                    .iter()
+                    .enumerate()
+                    .map(|(i, value)| match header.get(i).filter(|h| !h.is_empty()) {
+                        Some(column) => format!("{}: {}", column, value),
+                        None => value.clone(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" | ")
+            })
+            .collect();
+
+        let chunks: Vec<String> = rows.chunks(CSV_ROWS_PER_CHUNK).map(|rows| rows.join("\n")).collect();
+        let mut metadata = Map::new();
+        metadata.insert("table".to_string(), json!({"columns": header, "rows": rows.len()}));
+        Ok(Loaded { text: chunks.join(&format!("\n{}\n", CHUNK_MARKER)), metadata, ..Loaded::default() })
+    }
+}
+
+/// RFC 4180 records: quoted fields may hold delimiters, newlines and `""`.
+pub fn parse_delimited(text: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                '"' => quoted = false,
+                _ => field.push(c),
+            }
+        } else if c == '"' && field.is_empty() {
+            quoted = true;
+        } else if c == delimiter {
+            record.push(std::mem::take(&mut field));
+        } else if c == '\n' || c == '\r' {
+            if c == '\r' && chars.peek() == Some(&'\n') {
+                chars.next();
+            }
+            record.push(std::mem::take(&mut field));
+            records.push(std::mem::take(&mut record));
+        } else {
+            field.push(c);
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+/// JSON flattened to one `path: value` line per scalar, e.g.
+/// `servers[0].port: 8080`, with object keys in sorted order.
+pub struct JsonLoader;
+
+#[async_trait]
+impl Loader for JsonLoader {
+    fn name(&self) -> &'static str { "json" }
+
+    async fn load(&self, path: &Path, bytes: &[u8]) -> Result<Loaded> {
+        let value: Value = serde_json::from_slice(bytes).with_context(|| format!("Invalid JSON in {:?}", path))?;
+        let mut lines = Vec::new();
+        flatten_json(&value, String::new(), &mut lines);
+        Ok(Loaded::text(lines.join("\n")))
+    }
+}
+
+pub fn flatten_json(value: &Value, prefix: String, lines: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                flatten_json(child, path, lines);
+            }
+        }
+        Value::Array(items) => {
+            for (i, child) in items.iter().enumerate() {
+                flatten_json(child, format!("{}[{}]", prefix, i), lines);
+            }
+        }
+        Value::String(s) if prefix.is_empty() => lines.push(s.clone()),
+        Value::String(s) => lines.push(format!("{}: {}", prefix, s)),
+        scalar if prefix.is_empty() => lines.push(scalar.to_string()),
+        scalar => lines.push(format!("{}: {}", prefix, scalar)),
+    }
+}
+
+/// PDFs: the text layer page by page (pure Rust), with the LLM converter
+/// (edgequake-pdf2md) for pages without one or for every page, depending on
+/// [`PdfExtraction`]. Page start offsets are kept so chunks can record the
+/// pages they span.
+pub struct PdfLoader {
+    llm: Option<Arc<dyn LLMProvider>>,
+    config: PdfConfig,
+}
+
+impl PdfLoader {
+    pub fn new(llm: Option<Arc<dyn LLMProvider>>, config: PdfConfig) -> Self {
+        Self { llm, config }
+    }
+
+    /// Markdown of the given 1-based pages (all when `None`), by page number.
+    async fn convert(&self, path: &Path, pages: Option<Vec<usize>>) -> Result<HashMap<usize, String>> {
+        let Some(llm) = &self.llm else {
+            anyhow::bail!("LLM required for PDF extraction via edgequake-pdf2md");
+        };
+        eprintln!("DEBUG: [run_file] PDF conversion starting with provider: {} ({})", llm.name(), llm.model());
+        let selection = pages.map_or(edgequake_pdf2md::PageSelection::All, edgequake_pdf2md::PageSelection::Set);
+        let config = edgequake_pdf2md::ConversionConfig::builder()
+            .provider(llm.clone())
+            .pages(selection)
+            .build()
+            .map_err(|e| anyhow::anyhow!("Failed to build PDF config: {}", e))?;
+
+        let output = edgequake_pdf2md::convert(path.to_string_lossy().as_ref(), &config).await
+            .map_err(|e| anyhow::anyhow!("PDF conversion failed for {:?}: {}", path, e))?;
+        eprintln!("DEBUG: [run_file] PDF conversion complete. Pages processed: {}", output.stats.total_pages);
+        Ok(output.pages.into_iter().map(|page| (page.page_num, page.markdown)).collect())
+    }
+}
+
+/// Text layer of each page, in order.
+pub fn pdf_text_pages(bytes: &[u8]) -> Result<Vec<String>> {
+    // pdf-extract panics on some malformed files; treat that as a failure.
+    let bytes = bytes.to_vec();
+    let pages = std::panic::catch_unwind(move || pdf_extract::extract_text_from_mem_by_pages(&bytes))
+        .map_err(|_| anyhow::anyhow!("PDF text extraction crashed"))?
+        .map_err(|e| anyhow::anyhow!("PDF text extraction failed: {}", e))?;
+    Ok(pages.iter().map(|page| tidy(page)).collect())
+}
+
+#[async_trait]
+impl Loader for PdfLoader {
+    fn name(&self) -> &'static str { "pdf" }
+
+    async fn load(&self, path: &Path, bytes: &[u8]) -> Result<Loaded> {
+        let mut pages: Vec<String> = match self.config.extraction {
+            PdfExtraction::Llm => Vec::new(),
+            _ => {
+                let owned = bytes.to_vec();
+                match tokio::task::spawn_blocking(move || pdf_text_pages(&owned)).await? {
+                    Ok(pages) => pages,
+                    Err(e) if self.config.extraction == PdfExtraction::Auto && self.llm.is_some() => {
+                        eprintln!("  ! Warning: {} in {:?}; converting with the LLM", e, path);
+                        Vec::new()
+                    }
+                    Err(e) => return Err(e.context(format!("Cannot read {:?}", path))),
+                }
+            }
+        };
+
+        let mut llm_pages = 0;
+        let mut missing = Vec::new();
+        if pages.is_empty() {
+            // Explicitly requested, or no usable text layer at all.
+            let converted = self.convert(path, None).await?;
+            let count = converted.keys().copied().max().unwrap_or(0);
+            pages = (1..=count).map(|n| converted.get(&n).cloned().unwrap_or_default()).collect();
+            llm_pages = count;
+        } else {
+            let scanned: Vec<usize> = pages
+                .iter()
+                .enumerate()
+                .filter(|(_, text)| text.chars().filter(|c| !c.is_whitespace()).count() < self.config.min_page_chars)
+                .map(|(i, _)| i + 1)
+                .collect();
+            if !scanned.is_empty() {
+                match (self.config.extraction, &self.llm) {
+                    (PdfExtraction::Auto, Some(_)) => {
+                        for (page, markdown) in self.convert(path, Some(scanned.clone())).await? {
+                            if let Some(text) = pages.get_mut(page.wrapping_sub(1)) {
+                                *text = markdown;
+                                llm_pages += 1;
+                            }
+                        }
+                    }
+                    _ => {
+                        eprintln!(
+                            "  ! Warning: {} page(s) of {:?} have no text layer ({:?}); they are left out",
+                            scanned.len(), path, scanned
+                        );
+                        missing = scanned;
+                    }
+                }
+            }
+        }
+
+        let mut text = String::new();
+        let mut starts = Vec::with_capacity(pages.len());
+        for page in &pages {
+            if !text.is_empty() {
+                text.push_str("\n\n");
+            }
+            starts.push(text.len());
+            text.push_str(page.trim());
+        }
+        if text.trim().is_empty() {
+            anyhow::bail!("No text found in {:?} (scanned PDF without an LLM extractor?)", path);
+        }
+
+        let mut metadata = Map::new();
+        metadata.insert("page_count".to_string(), json!(pages.len()));
+        metadata.insert("pdf_extraction".to_string(), json!({
+            "text_pages": pages.len() - llm_pages - missing.len(),
+            "llm_pages": llm_pages,
+            "missing_pages": missing,
+        }));
+        Ok(Loaded { text, metadata, pages: starts })
+    }
+}
+
+/// MIME types handled by [`ImageLoader`].
+pub const IMAGE_MIMES: &[&str] = &["image/png", "image/jpeg", "image/gif", "image/webp", "image/bmp", "image/tiff"];
+
+const CAPTION_PROMPT: &str = "Describe this image so it can be found again by a text search: what it shows, \
+its purpose (screenshot, diagram, chart, photo, ...), and the key labels, names and numbers. \
+Transcribe any visible text verbatim. Answer with the description only.";
+
+/// Images: the text is a caption from the (vision-capable) LLM extractor
+/// and/or the output of a local OCR command; the metadata records the file,
+/// its hash, dimensions and EXIF capture time.
+pub struct ImageLoader {
+    llm: Option<Arc<dyn LLMProvider>>,
+    config: ImageConfig,
+}
+
+impl ImageLoader {
+    pub fn new(llm: Option<Arc<dyn LLMProvider>>, config: ImageConfig) -> Self {
+        Self { llm, config }
+    }
+
+    /// Whether anything is configured to turn an image into text.
+    pub fn can_describe(&self) -> bool {
+        self.captioner().is_some() || !self.config.ocr_command.is_empty()
+    }
+
+    /// The LLM to caption with: only one declared to accept images.
+    fn captioner(&self) -> Option<&Arc<dyn LLMProvider>> {
+        self.llm.as_ref().filter(|_| self.config.caption && self.config.vision)
+    }
+
+    async fn caption(&self, llm: &Arc<dyn LLMProvider>, bytes: &[u8], mime: &str) -> Result<String> {
+        let max_side = self.config.max_side.max(1);
+        let owned = bytes.to_vec();
+        let mime = mime.to_string();
+        // Vision APIs take few formats and bill by size: send large or
+        // unusual images as a downscaled PNG.
+        let (data, mime) = tokio::task::spawn_blocking(move || -> Result<(Vec<u8>, String)> {
+            let (width, height) = image::ImageReader::new(std::io::Cursor::new(&owned)).with_guessed_format()?.into_dimensions()?;
+            let supported = matches!(mime.as_str(), "image/png" | "image/jpeg" | "image/gif" | "image/webp");
+            if supported && width.max(height) <= max_side {
+                return Ok((owned, mime));
+            }
+            let resized = image::load_from_memory(&owned)?.thumbnail(max_side, max_side);
+            let mut png = Vec::new();
+            resized.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+            Ok((png, "image/png".to_string()))
+        })
+        .await??;
+
+        use base64::Engine as _;
+        let encoded = base64::engine::general_purpose::STANDARD.encode(&data);
+        let message = ChatMessage::user_with_images(CAPTION_PROMPT, vec![ImageData::new(encoded, mime)]);
+        let response = llm.chat(&[message], None).await.map_err(|e| anyhow::anyhow!("Image captioning failed: {}", e))?;
+        Ok(response.content.trim().to_string())
+    }
+
+    async fn ocr(&self, path: &Path) -> Result<String> {
+        let path = path.to_string_lossy();
+        let args: Vec<String> = self.config.ocr_command.iter().map(|arg| arg.replace("{path}", &path)).collect();
+        let output = tokio::process::Command::new(&args[0])
+            .args(&args[1..])
+            .output()
+            .await
+            .with_context(|| format!("Failed to run OCR command {:?}", args[0]))?;
+        if !output.status.success() {
+            anyhow::bail!("OCR command {:?} failed: {}", args[0], String::from_utf8_lossy(&output.stderr).trim());
+        }
+        Ok(tidy(&String::from_utf8_lossy(&output.stdout)))
+    }
+}
+
+#[async_trait]
+impl Loader for ImageLoader {
+    fn name(&self) -> &'static str { "image" }
+
+    async fn load(&self, path: &Path, bytes: &[u8]) -> Result<Loaded> {
+        let owned = bytes.to_vec();
+        let (format, width, height, taken_at) = tokio::task::spawn_blocking(move || -> Result<_> {
+            use image::ImageDecoder as _;
+            let reader = image::ImageReader::new(std::io::Cursor::new(&owned)).with_guessed_format()?;
+            let format = reader.format().map(|f| f.extensions_str()[0]).unwrap_or("unknown");
+            let mut decoder = reader.into_decoder()?;
+            let (width, height) = decoder.dimensions();
+            let taken_at = decoder.exif_metadata().ok().flatten().and_then(|exif| exif_datetime(&exif));
+            Ok((format, width, height, taken_at))
+        })
+        .await?
+        .with_context(|| format!("Cannot read image {:?}", path))?;
+
+        let mime = mime_guess::from_path(path).first_raw().unwrap_or("image/png");
+        let caption = match self.captioner() {
+            Some(llm) => match self.caption(llm, bytes, mime).await {
+                Ok(caption) => Some(caption),
+                Err(e) => {
+                    eprintln!("  ! Warning: {} for {:?}", e, path);
+                    None
+                }
+            },
+            _ => None,
+        };
+        let ocr = if self.config.ocr_command.is_empty() {
+            None
+        } else {
+            match self.ocr(path).await {
+                Ok(text) => Some(text),
+                Err(e) => {
+                    eprintln!("  ! Warning: {} for {:?}", e, path);
+                    None
+                }
+            }
+        };
+
+        let mut parts = Vec::new();
+        if let Some(caption) = caption.as_deref().filter(|c| !c.is_empty()) {
+            parts.push(caption.to_string());
+        }
+        if let Some(ocr) = ocr.as_deref().filter(|t| !t.is_empty()) {
+            parts.push(format!("Text in the image:\n{}", ocr));
+        }
+        if parts.is_empty() {
+            anyhow::bail!("No text for image {:?} (no vision-capable LLM extractor or OCR command produced any)", path);
+        }
+
+        let mut info = json!({
+            "path": std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()).to_string_lossy(),
+            "sha256": hex::encode(Sha256::digest(bytes)),
+            "format": format,
+            "width": width,
+            "height": height,
+            "ocr": ocr.is_some(),
+        });
+        if let Some(taken_at) = taken_at {
+            info["taken_at"] = json!(taken_at);
+        }
+        if let (Some(llm), Some(_)) = (&self.llm, &caption) {
+            info["caption_model"] = json!(format!("{}/{}", llm.name(), llm.model()));
+        }
+        let mut metadata = Map::new();
+        metadata.insert("image".to_string(), info);
+        Ok(Loaded { text: parts.join("\n\n"), metadata, pages: Vec::new() })
+    }
+}
+
+/// Capture time (`DateTimeOriginal`, else `DateTime`) from a raw EXIF block,
+/// as `YYYY-MM-DDTHH:MM:SS`.
+pub fn exif_datetime(exif: &[u8]) -> Option<String> {
+    let tiff = exif.strip_prefix(b"Exif\0\0").unwrap_or(exif);
+    let little = match tiff.get(..4)? {
+        b"II*\0" => true,
+        b"MM\0*" => false,
+        _ => return None,
+    };
+    let u16_at = |at: usize| -> Option<u16> {
+        let b: [u8; 2] = tiff.get(at..at + 2)?.try_into().ok()?;
+        Some(if little { u16::from_le_bytes(b) } else { u16::from_be_bytes(b) })
+    };
+    let u32_at = |at: usize| -> Option<u32> {
+        let b: [u8; 4] = tiff.get(at..at + 4)?.try_into().ok()?;
+        Some(if little { u32::from_le_bytes(b) } else { u32::from_be_bytes(b) })
+    };
+    // (tag, type, count, value or offset) of each entry of the IFD at `at`.
+    let entries = |at: usize| -> Vec<(u16, u16, u32, u32)> {
+        let count = u16_at(at).unwrap_or(0) as usize;
+        (0..count)
+            .filter_map(|i| {
+                let entry = at + 2 + i * 12;
+                Some((u16_at(entry)?, u16_at(entry + 2)?, u32_at(entry + 4)?, u32_at(entry + 8)?))
+            })
+            .collect()
+    };
+    let ascii = |count: u32, offset: u32| -> Option<String> {
+        // Dates are 20 bytes, so always stored at an offset.
+        let raw = tiff.get(offset as usize..offset as usize + count as usize)?;
+        let text = std::str::from_utf8(raw).ok()?.trim_end_matches('\0').trim();
+        let (date, time) = text.split_once(' ')?;
+        (date.len() == 10 && time.len() == 8).then(|| format!("{}T{}", date.replace(':', "-"), time))
+    };
+
+    let ifd0 = entries(u32_at(4)? as usize);
+    let exif_ifd = ifd0.iter().find(|e| e.0 == 0x8769).map(|e| entries(e.3 as usize)).unwrap_or_default();
+    exif_ifd
+        .iter()
+        .find(|e| e.0 == 0x9003 && e.1 == 2)
+        .or_else(|| ifd0.iter().find(|e| e.0 == 0x0132 && e.1 == 2))
+        .and_then(|e| ascii(e.2, e.3))
+}
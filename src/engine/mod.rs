@@ -1,10 +1,16 @@
+pub mod chunking;
 pub mod ingestion;
+pub mod jobs;
+pub mod loaders;
+pub mod sources;
 pub mod funnel;
 pub mod vectors;
 pub mod git;
 pub mod shell;
+pub mod watch;
 pub mod graph;
 pub mod decay;
 pub mod communities;
 pub mod conversation;
-
+pub mod ontology;
+pub mod structured;
@@ -0,0 +1,154 @@
+use crate::config::{normalize_term, OntologyConfig, OntologyTerm, UnknownTermPolicy};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// Result of checking one extracted value against the ontology.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TermMatch {
+    /// The value (or one of its aliases) is allowed; carries the canonical spelling.
+    Accepted(String),
+    /// The value was unknown and replaced by the fallback.
+    Mapped(String),
+    /// The value was unknown and the policy is `reject`.
+    Rejected,
+}
+
+impl TermMatch {
+    pub fn value(&self) -> Option<&str> {
+        match self {
+            TermMatch::Accepted(v) | TermMatch::Mapped(v) => Some(v),
+            TermMatch::Rejected => None,
+        }
+    }
+}
+
+/// Normalises extracted entity types and predicates for one namespace.
+pub struct Ontology {
+    config: OntologyConfig,
+    entity_types: HashMap<String, String>,
+    predicates: HashMap<String, String>,
+}
+
+impl Ontology {
+    pub fn new(config: OntologyConfig) -> Self {
+        let entity_types = index_terms(&config.entity_types);
+        let predicates = index_terms(&config.predicates);
+        Self { config, entity_types, predicates }
+    }
+
+    /// Ontology for `namespace` in `ontologies`, falling back to the `"*"` entry.
+    pub fn for_namespace(ontologies: &HashMap<String, OntologyConfig>, namespace: &str) -> Option<Self> {
+        ontologies
+            .get(namespace)
+            .or_else(|| ontologies.get("*"))
+            .cloned()
+            .map(Self::new)
+    }
+
+    pub fn fallback_entity_type(&self) -> &str {
+        &self.config.fallback_entity_type
+    }
+
+    pub fn entity_type(&self, raw: &str) -> TermMatch {
+        self.check(raw, &self.entity_types, &self.config.fallback_entity_type)
+    }
+
+    pub fn predicate(&self, raw: &str) -> TermMatch {
+        self.check(raw, &self.predicates, &self.config.fallback_predicate)
+    }
+
+    fn check(&self, raw: &str, index: &HashMap<String, String>, fallback: &str) -> TermMatch {
+        if index.is_empty() {
+            return TermMatch::Accepted(raw.to_string());
+        }
+        let key = normalize_term(raw);
+        if let Some(canonical) = index.get(&key) {
+            return TermMatch::Accepted(canonical.clone());
+        }
+        // The fallback is always allowed, even if it is not listed explicitly.
+        if key == normalize_term(fallback) {
+            return TermMatch::Accepted(fallback.to_string());
+        }
+        match self.config.on_unknown {
+            UnknownTermPolicy::Map => TermMatch::Mapped(fallback.to_string()),
+            UnknownTermPolicy::Reject => TermMatch::Rejected,
+        }
+    }
+
+    /// Instructions appended to the extraction prompt. The `ALLOWED ...:` lines
+    /// are also picked up by `ArchConfig::format_prompt` for NuExtract templates.
+    pub fn prompt_section(&self) -> String {
+        let mut section = String::from("\nONTOLOGY:\n");
+        if !self.config.entity_types.is_empty() {
+            section.push_str(&format!("ALLOWED ENTITY TYPES: {}\n", join_names(&self.config.entity_types)));
+            section.push_str(&describe_terms(&self.config.entity_types));
+        }
+        if !self.config.predicates.is_empty() {
+            section.push_str(&format!("ALLOWED PREDICATES: {}\n", join_names(&self.config.predicates)));
+            section.push_str(&describe_terms(&self.config.predicates));
+        }
+        section.push_str("Use only the values listed above.\n");
+        section
+    }
+}
+
+fn index_terms(terms: &[OntologyTerm]) -> HashMap<String, String> {
+    let mut index = HashMap::new();
+    for term in terms {
+        index.insert(normalize_term(&term.name), term.name.clone());
+        for alias in &term.aliases {
+            index.insert(normalize_term(alias), term.name.clone());
+        }
+    }
+    index
+}
+
+fn join_names(terms: &[OntologyTerm]) -> String {
+    terms.iter().map(|t| t.name.as_str()).collect::<Vec<_>>().join(", ")
+}
+
+fn describe_terms(terms: &[OntologyTerm]) -> String {
+    terms
+        .iter()
+        .filter_map(|t| t.description.as_ref().map(|d| format!("- {}: {}\n", t.name, d)))
+        .collect()
+}
+
+/// Values that did not match the ontology during one extraction.
+#[derive(Debug, Default, Clone)]
+pub struct OntologyReport {
+    pub mapped: Vec<(String, String, String)>,
+    pub rejected: Vec<(String, String)>,
+}
+
+impl OntologyReport {
+    /// Record a match; returns the value to store, or `None` if rejected.
+    pub fn record(&mut self, kind: &str, raw: &str, outcome: TermMatch) -> Option<String> {
+        match outcome {
+            TermMatch::Accepted(v) => Some(v),
+            TermMatch::Mapped(v) => {
+                self.mapped.push((kind.to_string(), raw.to_string(), v.clone()));
+                Some(v)
+            }
+            TermMatch::Rejected => {
+                self.rejected.push((kind.to_string(), raw.to_string()));
+                None
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mapped.is_empty() && self.rejected.is_empty()
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "mapped": self.mapped.iter()
+                .map(|(kind, from, to)| json!({"kind": kind, "from": from, "to": to}))
+                .collect::<Vec<_>>(),
+            "rejected": self.rejected.iter()
+                .map(|(kind, value)| json!({"kind": kind, "value": value}))
+                .collect::<Vec<_>>(),
+        })
+    }
+}
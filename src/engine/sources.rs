@@ -0,0 +1,183 @@
+use crate::engine::ingestion::IngestionPipeline;
+use crate::storage::sqlite::{SourceRecord, SqliteDatabase};
+use anyhow::{Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// What happened to one file during a sync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    /// First time this file is indexed.
+    Added,
+    /// The content changed; a new version was ingested.
+    Updated,
+    /// Same content hash as the last ingestion.
+    Unchanged,
+    /// A binary format without a loader.
+    Skipped,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct IngestReport {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub unchanged: usize,
+    pub skipped: usize,
+    /// Tracked files that no longer exist; their documents left search.
+    pub deleted: Vec<String>,
+    /// (path, error) of files that could not be ingested.
+    pub failed: Vec<(String, String)>,
+}
+
+/// Indexes a directory tree and keeps the index in step with it: files are
+/// tracked by path and content hash, so unchanged files are skipped, changed
+/// ones become new versions of their document and removed ones leave search.
+pub struct DirectoryIngest {
+    root: PathBuf,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    gitignore: bool,
+}
+
+impl DirectoryIngest {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into(), include: Vec::new(), exclude: Vec::new(), gitignore: true }
+    }
+
+    /// Only index files whose path relative to the root matches one of these globs.
+    pub fn with_include(mut self, globs: Vec<String>) -> Self {
+        self.include = globs;
+        self
+    }
+
+    /// Never index files whose path relative to the root matches one of these globs.
+    pub fn with_exclude(mut self, globs: Vec<String>) -> Self {
+        self.exclude = globs;
+        self
+    }
+
+    /// Honour `.gitignore`, `.ignore` and git's global excludes (on by default).
+    pub fn with_gitignore(mut self, gitignore: bool) -> Self {
+        self.gitignore = gitignore;
+        self
+    }
+
+    pub fn root(&self) -> Result<PathBuf> {
+        std::fs::canonicalize(&self.root).with_context(|| format!("Cannot read directory {:?}", self.root))
+    }
+
+    /// Files to index, sorted. Hidden files and directories are never walked.
+    pub fn files(&self) -> Result<Vec<PathBuf>> {
+        let root = self.root()?;
+        let include = build_globs(&self.include)?;
+        let exclude = build_globs(&self.exclude)?;
+        let walker = ignore::WalkBuilder::new(&root)
+            .git_ignore(self.gitignore)
+            .git_global(self.gitignore)
+            .git_exclude(self.gitignore)
+            .ignore(self.gitignore)
+            .require_git(false)
+            .build();
+
+        let mut files = Vec::new();
+        for entry in walker {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    eprintln!("  ! Warning: Skipping unreadable entry: {}", e);
+                    continue;
+                }
+            };
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+            let relative = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+            if include.as_ref().is_some_and(|globs| !globs.is_match(relative))
+                || exclude.as_ref().is_some_and(|globs| globs.is_match(relative))
+            {
+                continue;
+            }
+            files.push(entry.into_path());
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// Ingest new and changed files and retire the documents of deleted ones.
+    /// A file that fails is reported and retried on the next run.
+    pub async fn run(&self, pipeline: &IngestionPipeline, db: &SqliteDatabase, namespace: &str) -> Result<IngestReport> {
+        let root = self.root()?;
+        let mut report = IngestReport::default();
+
+        for path in self.files()? {
+            let display = relative_title(&root, &path);
+            match self.sync_file(pipeline, db, &path, namespace).await {
+                Ok(FileStatus::Added) => report.added.push(display),
+                Ok(FileStatus::Updated) => report.updated.push(display),
+                Ok(FileStatus::Unchanged) => report.unchanged += 1,
+                Ok(FileStatus::Skipped) => report.skipped += 1,
+                Err(e) => {
+                    eprintln!("  ! Warning: Failed to ingest {}: {}", display, e);
+                    report.failed.push((display, e.to_string()));
+                }
+            }
+        }
+
+        let prefix = format!("{}{}", root.to_string_lossy(), std::path::MAIN_SEPARATOR);
+        for source in db.list_sources(&prefix, namespace)? {
+            if !Path::new(&source.path).exists() {
+                db.remove_source(&source.path, namespace)?;
+                report.deleted.push(relative_title(&root, Path::new(&source.path)));
+            }
+        }
+        Ok(report)
+    }
+
+    /// Bring one file under the root up to date.
+    pub async fn sync_file(&self, pipeline: &IngestionPipeline, db: &SqliteDatabase, path: &Path, namespace: &str) -> Result<FileStatus> {
+        let root = self.root()?;
+        let path = std::fs::canonicalize(path).with_context(|| format!("Cannot read {:?}", path))?;
+        let bytes = std::fs::read(&path).with_context(|| format!("Cannot read {:?}", path))?;
+        if pipeline.loaders().loader_for(&path, &bytes).is_none() {
+            return Ok(FileStatus::Skipped);
+        }
+
+        let key = path.to_string_lossy().to_string();
+        let content_hash = hex::encode(Sha256::digest(&bytes));
+        let previous = db.get_source(&key, namespace)?;
+        if previous.as_ref().is_some_and(|p| p.content_hash == content_hash) {
+            return Ok(FileStatus::Unchanged);
+        }
+
+        let metadata = json!({"title": relative_title(&root, &path), "content_hash": content_hash});
+        let document_id = pipeline.run_file(&path, metadata, namespace).await?;
+        db.upsert_source(&SourceRecord {
+            path: key,
+            namespace: namespace.to_string(),
+            content_hash,
+            document_id,
+            indexed_at: std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs(),
+        })?;
+        Ok(if previous.is_some() { FileStatus::Updated } else { FileStatus::Added })
+    }
+}
+
+/// Path relative to the root with `/` separators, used as the document title.
+fn relative_title(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/")
+}
+
+fn build_globs(globs: &[String]) -> Result<Option<GlobSet>> {
+    if globs.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob).with_context(|| format!("Invalid glob '{}'", glob))?);
+    }
+    Ok(Some(builder.build()?))
+}
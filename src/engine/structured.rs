@@ -0,0 +1,286 @@
+use edgequake_llm::LLMProvider;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use std::fmt;
+
+// ── Schemas ───────────────────────────────────────────────────────────────────
+
+/// Output of the graph extraction prompt.
+#[derive(Debug, Default, Deserialize)]
+pub struct ExtractedGraph {
+    #[serde(default, deserialize_with = "lenient_vec")]
+    pub entities: Vec<ExtractedEntity>,
+    #[serde(default, deserialize_with = "lenient_vec")]
+    pub relationships: Vec<ExtractedRelationship>,
+    /// Kept loosely typed: models emit either strings or objects here.
+    #[serde(default, deserialize_with = "lenient_vec")]
+    pub conflicts: Vec<Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ExtractedEntity {
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub name: String,
+    #[serde(default, rename = "type", deserialize_with = "lenient_string")]
+    pub entity_type: String,
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub description: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ExtractedRelationship {
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub source: String,
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub target: String,
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub predicate: String,
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub description: String,
+}
+
+/// Output of the community `summary` template.
+#[derive(Debug, Deserialize)]
+pub struct CommunitySummary {
+    #[serde(deserialize_with = "lenient_string")]
+    pub title: String,
+    #[serde(deserialize_with = "lenient_string")]
+    pub summary: String,
+}
+
+/// Accept strings, numbers and booleans as text; `null` becomes empty.
+fn lenient_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::String(s) => s,
+        Value::Null => String::new(),
+        other @ (Value::Number(_) | Value::Bool(_)) => other.to_string(),
+        other => return Err(serde::de::Error::custom(format!("expected a string, found {}", other))),
+    })
+}
+
+/// Accept `null` as an empty list and a single object as a one-element list.
+fn lenient_vec<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    let items = match Value::deserialize(deserializer)? {
+        Value::Null => return Ok(Vec::new()),
+        Value::Array(items) => items,
+        single => vec![single],
+    };
+    items
+        .into_iter()
+        .map(|item| serde_json::from_value(item).map_err(serde::de::Error::custom))
+        .collect()
+}
+
+// ── Parsing ───────────────────────────────────────────────────────────────────
+
+/// Parse `raw` model output into `T`, repairing common JSON defects first if
+/// the output does not parse as-is.
+pub fn parse_structured<T: DeserializeOwned>(raw: &str) -> anyhow::Result<T> {
+    if let Ok(value) = serde_json::from_str::<T>(raw.trim()) {
+        return Ok(value);
+    }
+    let repaired = repair_json(raw);
+    serde_json::from_str::<T>(&repaired)
+        .map_err(|e| anyhow::anyhow!("{} (in {})", e, preview(&repaired, 200)))
+}
+
+/// Best-effort cleanup of LLM JSON output:
+/// - strips markdown code fences and any prose around the first JSON value,
+/// - removes trailing commas before `}` / `]`,
+/// - closes strings, objects and arrays left open by truncated output,
+///   dropping a dangling key or partial element if needed.
+pub fn repair_json(raw: &str) -> String {
+    let text = strip_code_fence(raw);
+    let Some(start) = text.find(['{', '[']) else {
+        return text.trim().to_string();
+    };
+
+    let mut out = String::with_capacity(text.len());
+    let mut stack: Vec<char> = Vec::new();
+    // Points where the value could be cut back to if the tail is unusable.
+    let mut cut_points: Vec<(usize, Vec<char>)> = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for c in text[start..].chars() {
+        if in_string {
+            out.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                out.push(c);
+            }
+            '{' | '[' => {
+                stack.push(if c == '{' { '}' } else { ']' });
+                out.push(c);
+                cut_points.push((out.len(), stack.clone()));
+            }
+            '}' | ']' => {
+                trim_trailing_comma(&mut out);
+                if stack.last() == Some(&c) {
+                    stack.pop();
+                }
+                out.push(c);
+                if stack.is_empty() {
+                    return out;
+                }
+                cut_points.push((out.len(), stack.clone()));
+            }
+            ',' => {
+                cut_points.push((out.len(), stack.clone()));
+                out.push(c);
+            }
+            _ => out.push(c),
+        }
+    }
+
+    // Truncated output: close what is open, and if that still does not
+    // parse, fall back to the last complete element.
+    if in_string {
+        if escaped {
+            out.pop();
+        }
+        out.push('"');
+    }
+    let closed = close(&out, &stack);
+    if serde_json::from_str::<Value>(&closed).is_ok() {
+        return closed;
+    }
+    for (pos, stack) in cut_points.iter().rev() {
+        let candidate = close(&out[..*pos], stack);
+        if serde_json::from_str::<Value>(&candidate).is_ok() {
+            return candidate;
+        }
+    }
+    closed
+}
+
+fn strip_code_fence(raw: &str) -> &str {
+    let Some(open) = raw.find("```") else { return raw };
+    let after = &raw[open + 3..];
+    // Skip the language tag on the fence line.
+    let body = after.find('\n').map(|nl| &after[nl + 1..]).unwrap_or(after);
+    match body.find("```") {
+        Some(close) => &body[..close],
+        None => body,
+    }
+}
+
+fn trim_trailing_comma(out: &mut String) {
+    let trimmed = out.trim_end().len();
+    if out[..trimmed].ends_with(',') {
+        out.truncate(trimmed - 1);
+    }
+}
+
+fn close(prefix: &str, stack: &[char]) -> String {
+    let mut s = prefix.trim_end().to_string();
+    trim_trailing_comma(&mut s);
+    if s.ends_with(':') {
+        s.push_str("null");
+    }
+    s.extend(stack.iter().rev());
+    s
+}
+
+fn preview(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        text.to_string()
+    } else {
+        format!("{}...", text.chars().take(max_chars).collect::<String>())
+    }
+}
+
+// ── Completion with retry ─────────────────────────────────────────────────────
+
+/// A successfully parsed completion, with the failures that preceded it.
+#[derive(Debug)]
+pub struct StructuredOutput<T> {
+    pub value: T,
+    pub attempts: usize,
+    pub errors: Vec<String>,
+}
+
+/// Every attempt failed (or the provider itself errored).
+#[derive(Debug)]
+pub struct StructuredError {
+    pub attempts: usize,
+    pub errors: Vec<String>,
+    pub last_output: String,
+}
+
+impl fmt::Display for StructuredError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "structured output failed after {} attempt(s): {}",
+            self.attempts,
+            self.errors.last().map(String::as_str).unwrap_or("no output")
+        )
+    }
+}
+
+impl std::error::Error for StructuredError {}
+
+/// Complete `prompt` and parse the reply as `T`, retrying up to `max_retries`
+/// times with the parse error fed back to the model.
+///
+/// Prompts using a named template (`TEMPLATE:<name>\n...`) are retried
+/// unchanged, since template-driven models only see the template's `{text}`.
+pub async fn complete_structured<T: DeserializeOwned>(
+    llm: &dyn LLMProvider,
+    prompt: &str,
+    max_retries: usize,
+) -> Result<StructuredOutput<T>, StructuredError> {
+    let mut errors = Vec::new();
+    let mut last_output = String::new();
+    let mut current = prompt.to_string();
+
+    for attempt in 1..=max_retries + 1 {
+        let content = match llm.complete(&current).await {
+            Ok(response) => response.content,
+            Err(e) => {
+                errors.push(format!("LLM error: {}", e));
+                return Err(StructuredError { attempts: attempt, errors, last_output });
+            }
+        };
+        match parse_structured::<T>(&content) {
+            Ok(value) => return Ok(StructuredOutput { value, attempts: attempt, errors }),
+            Err(e) => {
+                errors.push(e.to_string());
+                current = with_feedback(prompt, &e.to_string(), &content);
+                last_output = content;
+            }
+        }
+    }
+
+    Err(StructuredError { attempts: max_retries + 1, errors, last_output })
+}
+
+fn with_feedback(prompt: &str, error: &str, previous: &str) -> String {
+    if prompt.starts_with("TEMPLATE:") {
+        return prompt.to_string();
+    }
+    format!(
+        "Your previous answer could not be parsed as JSON: {}\n\
+         Previous answer:\n{}\n\
+         Reply again with a single valid JSON object and nothing else.\n\n{}",
+        error,
+        preview(previous, 500),
+        prompt
+    )
+}
@@ -28,3 +28,20 @@ pub fn slice_vector(vector: &[f32], target_dim: usize) -> Vec<f32> {
         sliced.to_vec()
     }
 }
+
+/// Weighted mean of equally sized vectors, re-normalized to unit length.
+/// Used to combine the sliding-window embeddings of one long text.
+pub fn weighted_mean(vectors: &[Vec<f32>], weights: &[usize]) -> Vec<f32> {
+    let dim = vectors.first().map(Vec::len).unwrap_or(0);
+    let mut mean = vec![0.0f32; dim];
+    for (vector, &weight) in vectors.iter().zip(weights) {
+        for (m, x) in mean.iter_mut().zip(vector) {
+            *m += x * weight as f32;
+        }
+    }
+    let norm: f32 = mean.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        mean.iter_mut().for_each(|x| *x /= norm);
+    }
+    mean
+}
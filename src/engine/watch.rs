@@ -0,0 +1,172 @@
+use crate::config::WatchConfig;
+use crate::engine::ingestion::IngestionPipeline;
+use crate::engine::sources::{DirectoryIngest, FileStatus};
+use crate::mcp::tools::McpContext;
+use crate::storage::sqlite::SqliteDatabase;
+use anyhow::Result;
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+type EventSender = mpsc::UnboundedSender<notify::Result<notify::Event>>;
+
+pub async fn spawn_file_observer(context: Arc<McpContext>) {
+    if context.config.watch.paths.is_empty() {
+        return;
+    }
+    let pipeline = Arc::new(context.get_pipeline());
+    if let Err(e) = watch_paths(context.config.watch.clone(), pipeline, context.db.clone()) {
+        eprintln!("  ! Warning: File observer disabled: {}", e);
+    }
+}
+
+/// Watch the configured directories and keep their files ingested into
+/// `config.namespace`: files changed while nobody was watching are caught up
+/// first, then each file is re-ingested once it has been quiet for
+/// `debounce_ms`. Unchanged content is skipped by hash, changed content
+/// becomes a new document version, and deleted files leave search.
+pub fn watch_paths(config: WatchConfig, pipeline: Arc<IngestionPipeline>, db: Arc<SqliteDatabase>) -> Result<JoinHandle<()>> {
+    let mut roots = Vec::new();
+    for path in &config.paths {
+        if !path.is_dir() {
+            eprintln!("  ! Warning: Not watching {:?}: not a directory", path);
+            continue;
+        }
+        let ingest = DirectoryIngest::new(path)
+            .with_include(config.include.clone())
+            .with_exclude(config.exclude.clone())
+            .with_gitignore(config.gitignore);
+        roots.push((ingest.root()?, ingest));
+    }
+    if roots.is_empty() {
+        anyhow::bail!("no directory to watch");
+    }
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let dirs: Vec<&Path> = roots.iter().map(|(root, _)| root.as_path()).collect();
+    let watcher = start_watcher(&config, &dirs, tx)?;
+    let debounce = Duration::from_millis(config.debounce_ms.max(1));
+    let namespace = config.namespace.clone();
+
+    Ok(tokio::spawn(async move {
+        // Dropping the watcher stops the events.
+        let _watcher = watcher;
+        for (root, ingest) in &roots {
+            match ingest.run(&pipeline, &db, &namespace).await {
+                Ok(report) => eprintln!(
+                    "DEBUG: [watch] {}: {} added, {} updated, {} deleted",
+                    root.display(), report.added.len(), report.updated.len(), report.deleted.len()
+                ),
+                Err(e) => eprintln!("  ! Warning: Initial sync of {} failed: {}", root.display(), e),
+            }
+        }
+
+        let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+        loop {
+            match tokio::time::timeout(debounce, rx.recv()).await {
+                Ok(Some(Ok(event))) => {
+                    if !matches!(event.kind, EventKind::Access(_)) {
+                        for path in event.paths {
+                            pending.insert(path, Instant::now());
+                        }
+                    }
+                }
+                Ok(Some(Err(e))) => eprintln!("  ! Warning: File watch error: {}", e),
+                Ok(None) => break,
+                Err(_) => {}
+            }
+
+            let now = Instant::now();
+            let ready: Vec<PathBuf> = pending
+                .iter()
+                .filter(|(_, changed)| now.duration_since(**changed) >= debounce)
+                .map(|(path, _)| path.clone())
+                .collect();
+            for path in &ready {
+                pending.remove(path);
+            }
+            for (root, ingest) in &roots {
+                let changed: Vec<&PathBuf> = ready.iter().filter(|p| p.starts_with(root)).collect();
+                if !changed.is_empty() {
+                    sync_changes(ingest, &changed, &pipeline, &db, &namespace).await;
+                }
+            }
+        }
+    }))
+}
+
+/// Native events where available, polling otherwise (or when asked to).
+fn start_watcher(config: &WatchConfig, dirs: &[&Path], tx: EventSender) -> Result<Box<dyn Watcher + Send>> {
+    if !config.poll {
+        let native = notify::recommended_watcher(handler(tx.clone())).and_then(|mut watcher| {
+            for dir in dirs {
+                watcher.watch(dir, RecursiveMode::Recursive)?;
+            }
+            Ok(watcher)
+        });
+        match native {
+            Ok(watcher) => return Ok(Box::new(watcher)),
+            Err(e) => eprintln!(
+                "  ! Warning: Native file events unavailable ({}); polling every {}s",
+                e, config.poll_interval_seconds
+            ),
+        }
+    }
+    let poll = notify::Config::default().with_poll_interval(Duration::from_secs(config.poll_interval_seconds.max(1)));
+    let mut watcher = notify::PollWatcher::new(handler(tx), poll)?;
+    for dir in dirs {
+        watcher.watch(dir, RecursiveMode::Recursive)?;
+    }
+    Ok(Box::new(watcher))
+}
+
+fn handler(tx: EventSender) -> impl FnMut(notify::Result<notify::Event>) + Send + 'static {
+    move |event| {
+        let _ = tx.send(event);
+    }
+}
+
+/// Re-ingest the changed files that are still indexable and retire those
+/// that are gone. A changed directory (e.g. moved in or out) covers every
+/// file under it.
+async fn sync_changes(ingest: &DirectoryIngest, changed: &[&PathBuf], pipeline: &IngestionPipeline, db: &SqliteDatabase, namespace: &str) {
+    let files: HashSet<PathBuf> = match ingest.files() {
+        Ok(files) => files.into_iter().collect(),
+        Err(e) => {
+            eprintln!("  ! Warning: Cannot list watched files: {}", e);
+            return;
+        }
+    };
+
+    let mut to_sync: Vec<&PathBuf> = Vec::new();
+    for path in changed {
+        if files.contains(*path) {
+            to_sync.push(path);
+        } else if path.is_dir() {
+            to_sync.extend(files.iter().filter(|f| f.starts_with(path)));
+        } else if !path.exists() {
+            let key = path.to_string_lossy();
+            let sources = db.list_sources(&key, namespace).unwrap_or_default();
+            for source in sources.iter().filter(|s| Path::new(&s.path).starts_with(path)) {
+                match db.remove_source(&source.path, namespace) {
+                    Ok(()) => eprintln!("DEBUG: [watch] removed {}", source.path),
+                    Err(e) => eprintln!("  ! Warning: Failed to retire {}: {}", source.path, e),
+                }
+            }
+        }
+    }
+
+    to_sync.sort();
+    to_sync.dedup();
+    for path in to_sync {
+        match ingest.sync_file(pipeline, db, path, namespace).await {
+            Ok(FileStatus::Added | FileStatus::Updated) => eprintln!("DEBUG: [watch] re-ingested {}", path.display()),
+            Ok(_) => {}
+            Err(e) => eprintln!("  ! Warning: Failed to ingest {}: {}", path.display(), e),
+        }
+    }
+}
@@ -14,4 +14,5 @@ pub enum KnowledgeEvent {
     EntityInserted { id: Uuid, name: String, namespace: String },
     RelationshipInserted { source_id: Uuid, target_id: Uuid, predicate: String },
     CommunitiesUpdated { comm_ids: Vec<String> },
+    ConflictDetected { id: Uuid, namespace: String },
 }
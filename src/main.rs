@@ -1,23 +1,46 @@
 use anyhow::Result;
-use local_memory::mcp::tools::{call_tool, list_tools, list_resources, read_resource, McpContext};
+use clap::Parser;
+use local_memory::mcp::http::{serve_http, Bind};
+use local_memory::mcp::server::serve;
+use local_memory::mcp::tools::McpContext;
 use local_memory::model::{check_llm_connectivity, get_unified_model};
 use local_memory::storage::SqliteDatabase;
 use local_memory::engine::git::spawn_git_observer;
 use local_memory::engine::shell::spawn_shell_observer;
+use local_memory::engine::watch::spawn_file_observer;
 use local_memory::engine::conversation::spawn_conversation_observer;
 use local_memory::engine::graph::spawn_graph_observer;
 use local_memory::engine::communities::spawn_community_service;
 use local_memory::engine::decay::spawn_decay_service;
 use local_memory::KnowledgeEvent;
-use serde_json::{json, Value};
-use std::io::{self, BufRead};
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::{broadcast, RwLock};
 use tokio::time::{Duration, Instant};
 use local_memory::config::Config;
 
+#[derive(Parser)]
+#[command(name = "local-memory")]
+#[command(about = "Local Memory MCP server (stdio by default)", long_about = None)]
+#[command(version)]
+struct Args {
+    /// Serve MCP over streamable HTTP on ADDR instead of stdio, so several
+    /// clients can share one daemon
+    #[arg(long, value_name = "ADDR", num_args = 0..=1, default_missing_value = "127.0.0.1:7821", conflicts_with = "unix")]
+    http: Option<SocketAddr>,
+    /// Serve MCP over streamable HTTP on a Unix socket instead of stdio
+    #[arg(long, value_name = "PATH")]
+    unix: Option<PathBuf>,
+    /// Let --http listen on an address other than loopback. There is no
+    /// authentication: anyone who can reach it can read and write the memory
+    #[arg(long, requires = "http")]
+    allow_remote: bool,
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
+    let args = Args::parse();
     let config = Config::load();
 
     eprintln!("--- Local Memory Readiness Check ---");
@@ -64,30 +87,40 @@ async fn main() -> Result<()> {
 
     eprintln!("--- Readiness Check Complete ---\n");
 
-    let context = Arc::new(McpContext {
-        db,
-        model,
-        config,
-        event_tx: event_tx.clone(),
-    });
+    let context = Arc::new(McpContext::new(db, model, config, event_tx.clone()));
 
     // 4. Spawn Observers (Opt-in)
     if context.config.enable_observers {
-        eprintln!("  ✓ Starting background observers (Git, Shell, Conversation, Graph, Community)");
+        eprintln!("  ✓ Starting background observers (Git, Shell, Files, Conversation, Graph, Community)");
         spawn_git_observer(context.clone()).await;
         spawn_shell_observer(context.clone()).await;
+        spawn_file_observer(context.clone()).await;
         spawn_conversation_observer(context.clone()).await;
         spawn_graph_observer(context.clone(), event_tx.subscribe()).await;
         spawn_community_service(context.clone(), event_tx.subscribe()).await;
         spawn_decay_service(context.clone()).await;
     }
 
-    // 5. Idle Timeout Monitor
+    // 5. Background jobs left unfinished by the last run
+    match context.jobs.resume() {
+        Ok(0) => {}
+        Ok(n) => eprintln!("  ✓ Resuming {} unfinished job(s)", n),
+        Err(e) => eprintln!("  ! Warning: Cannot resume jobs: {}", e),
+    }
+
+    // 6. Idle Timeout Monitor (stdio only: an HTTP daemon stays up for the
+    // clients that share it, even while their sessions are quiet)
+    let bind = match (args.http, args.unix) {
+        (Some(addr), _) => Some(Bind::Tcp(addr)),
+        (None, Some(path)) => Some(Bind::Unix(path)),
+        (None, None) => None,
+    };
     let last_activity = Arc::new(RwLock::new(Instant::now()));
     let monitor_last_activity = last_activity.clone();
+    let monitor_context = context.clone();
     let idle_timeout = context.config.idle_timeout_seconds;
 
-    if idle_timeout > 0 {
+    if idle_timeout > 0 && bind.is_none() {
         tokio::spawn(async move {
             let timeout_duration = Duration::from_secs(idle_timeout);
             loop {
@@ -95,99 +128,31 @@ async fn main() -> Result<()> {
                 let last = *monitor_last_activity.read().await;
                 if last.elapsed() > timeout_duration {
                     eprintln!("[idle] Timeout reached ({}s). Terminating.", idle_timeout);
+                    release_jobs(&monitor_context);
                     std::process::exit(0);
                 }
             }
         });
     }
 
-    let stdin = io::stdin();
-    let mut handle = stdin.lock();
-    let mut line = String::new();
-
-    while handle.read_line(&mut line)? > 0 {
-        let trimmed = line.trim();
-        if trimmed.is_empty() {
-            line.clear();
-            continue;
-        }
-
-        {
-            let mut last = last_activity.write().await;
-            *last = Instant::now();
-        }
-
-        let response = handle_request(trimmed, &context).await;
-        if let Some(resp) = response {
-            println!("{}", serde_json::to_string(&resp)?);
-        }
-        
-        line.clear();
-    }
-
-    Ok(())
-}
-
-async fn handle_request(line: &str, context: &McpContext) -> Option<Value> {
-    let request: Value = match serde_json::from_str(line) {
-        Ok(req) => req,
-        Err(e) => {
-            return Some(json!({
-                "jsonrpc": "2.0",
-                "error": {"code": -32700, "message": format!("Parse error: {}", e)},
-                "id": null
-            }));
-        }
-    };
-
-    let id = request.get("id").cloned();
-    let method = request.get("method").and_then(|m| m.as_str()).unwrap_or("");
-    let is_notification = id.is_none();
-
-    let result = match method {
-        "initialize" => Ok(json!({
-            "protocolVersion": "2024-11-05",
-            "capabilities": {
-                "tools": {"listChanged": true},
-                "resources": {"subscribe": true, "listChanged": true}
-            },
-            "serverInfo": {"name": "local-memory", "version": "0.3.0-supermemory"}
-        })),
-        "initialized" => return None,
-        "tools/list" => Ok(json!({"tools": list_tools()})),
-        "tools/call" => {
-            let name = request.get("params").and_then(|p| p.get("name")).and_then(|n| n.as_str()).unwrap_or("");
-            let arguments = request.get("params").and_then(|p| p.get("arguments")).cloned().unwrap_or(json!({}));
-            call_tool(name, arguments, context).await
-        }
-        "resources/list" => Ok(json!({"resources": list_resources()})),
-        "resources/read" => {
-            let uri = request.get("params").and_then(|p| p.get("uri")).and_then(|u| u.as_str()).unwrap_or("");
-            read_resource(uri, context).await
-        }
-        _ => {
-            if is_notification { return None; }
-            Ok(json!({"status": "ok", "message": format!("Method '{}' received", method)}))
+    // 7. Transport
+    let jobs_context = context.clone();
+    let result = match bind {
+        Some(bind) => serve_http(bind, args.allow_remote, context, last_activity).await,
+        None => {
+            let stdin = tokio::io::BufReader::new(tokio::io::stdin());
+            serve(stdin, tokio::io::stdout(), context, last_activity).await
         }
     };
+    release_jobs(&jobs_context);
+    result
+}
 
-    if is_notification {
-        None
-    } else {
-        match result {
-            Ok(res) => Some(json!({
-                "jsonrpc": "2.0",
-                "id": id.unwrap(),
-                "result": res
-            })),
-            Err(e) => {
-                eprintln!("ERROR: {}", e);
-                Some(json!({
-                    "jsonrpc": "2.0",
-                    "id": id.unwrap(),
-                    "error": {"code": -32603, "message": format!("{}", e)}
-                }))
-            }
-        }
+/// Hand unfinished jobs back to the queue so the next process resumes them.
+fn release_jobs(context: &McpContext) {
+    match context.jobs.release() {
+        Ok(0) => {}
+        Ok(n) => eprintln!("  ✓ Left {} unfinished job(s) for the next run", n),
+        Err(e) => eprintln!("  ! Warning: Cannot release jobs: {}", e),
     }
 }
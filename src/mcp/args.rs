@@ -0,0 +1,122 @@
+use crate::config::ChunkingStrategy;
+use crate::mcp::server::RpcError;
+use crate::storage::sqlite::{ConflictResolution, ConflictStatus, JobStatus};
+use schemars::generate::SchemaSettings;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// `inputSchema` for a tool taking `T`: inline (no `$defs`), without the
+/// meta-schema, title or the struct's own doc comment.
+pub fn input_schema<T: JsonSchema>() -> Value {
+    let mut settings = SchemaSettings::draft2020_12();
+    settings.meta_schema = None;
+    settings.inline_subschemas = true;
+    let mut schema = settings.into_generator().into_root_schema_for::<T>();
+    schema.remove("title");
+    schema.remove("description");
+    schema.to_value()
+}
+
+/// Decode the `arguments` of `tool`. Unknown fields and wrong types are
+/// rejected as invalid params, naming the offending field.
+pub fn parse_args<T: DeserializeOwned>(tool: &str, arguments: Value) -> Result<T, RpcError> {
+    let arguments = match arguments {
+        Value::Null => Value::Object(Map::new()),
+        arguments => arguments,
+    };
+    serde_json::from_value(arguments)
+        .map_err(|e| RpcError::invalid_params(format!("Invalid arguments for '{}': {}", tool, e)))
+}
+
+fn default_namespace() -> String { "default".to_string() }
+fn default_top_k() -> usize { 5 }
+fn default_limit() -> usize { 20 }
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MemorizeArgs {
+    /// The text content to remember
+    pub text: String,
+    /// Optional metadata associated with the memory
+    pub metadata: Option<Map<String, Value>>,
+    /// Namespace for isolation
+    #[serde(default = "default_namespace")]
+    pub namespace: String,
+    /// Optional chunking strategy (default: by file type, then config)
+    pub chunking: Option<ChunkingStrategy>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RecallArgs {
+    /// The search query
+    pub query: String,
+    /// The number of results to return
+    #[serde(default = "default_top_k")]
+    pub top_k: usize,
+    /// Namespace to search in
+    #[serde(default = "default_namespace")]
+    pub namespace: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct GlobalSearchArgs {
+    /// The thematic search query
+    pub query: String,
+    /// Only communities of this namespace are searched
+    #[serde(default = "default_namespace")]
+    pub namespace: String,
+}
+
+/// Arguments of `explore` and `forget`.
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct EntityArgs {
+    /// The name of the entity
+    pub entity_name: String,
+    /// Namespace the entity lives in
+    #[serde(default = "default_namespace")]
+    pub namespace: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct JobStatusArgs {
+    /// The job returned by memorize (omit to list jobs)
+    pub job_id: Option<uuid::Uuid>,
+    /// Filter when listing
+    pub status: Option<JobStatus>,
+    /// Max jobs to list
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictAction {
+    #[default]
+    List,
+    Resolve,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ConflictsArgs {
+    /// What to do
+    #[serde(default)]
+    pub action: ConflictAction,
+    /// Filter for 'list' (every status if omitted)
+    pub status: Option<ConflictStatus>,
+    /// Optional namespace filter for 'list'
+    pub namespace: Option<String>,
+    /// Max conflicts to list
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+    /// Conflict to resolve (required for 'resolve')
+    pub conflict_id: Option<uuid::Uuid>,
+    /// Which side wins (required for 'resolve')
+    pub resolution: Option<ConflictResolution>,
+}
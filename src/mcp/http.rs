@@ -0,0 +1,324 @@
+use crate::mcp::server::{cancel, handle_request, Client, InFlight, PROTOCOL_VERSIONS};
+use crate::mcp::tools::McpContext;
+use anyhow::{Context, Result};
+use axum::extract::State;
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, RwLock, Semaphore};
+use tokio::time::Instant;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::StreamExt;
+
+/// Header carrying the session assigned on `initialize`.
+pub const SESSION_HEADER: &str = "mcp-session-id";
+
+/// Header carrying the negotiated protocol revision on later requests.
+pub const PROTOCOL_VERSION_HEADER: &str = "mcp-protocol-version";
+
+/// Where the HTTP transport listens.
+#[derive(Debug, Clone)]
+pub enum Bind {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+/// One client of the shared daemon.
+struct Session {
+    in_flight: InFlight,
+    client: Client,
+    events: broadcast::Sender<Value>,
+    last_seen: Mutex<Instant>,
+}
+
+impl Session {
+    fn touch(&self) {
+        *self.last_seen.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+    }
+
+    /// No request for `timeout` and no open event stream.
+    fn idle(&self, timeout: Duration) -> bool {
+        self.events.receiver_count() == 0
+            && self.last_seen.lock().unwrap_or_else(|e| e.into_inner()).elapsed() >= timeout
+    }
+
+    fn close(&self) {
+        for (_, handle) in self.in_flight.lock().unwrap_or_else(|e| e.into_inner()).drain() {
+            handle.abort();
+        }
+    }
+}
+
+type Sessions = Arc<Mutex<HashMap<String, Arc<Session>>>>;
+
+#[derive(Clone)]
+struct HttpState {
+    context: Arc<McpContext>,
+    permits: Arc<Semaphore>,
+    sessions: Sessions,
+    last_activity: Arc<RwLock<Instant>>,
+}
+
+impl HttpState {
+    fn session(&self, headers: &HeaderMap) -> Result<Arc<Session>, (StatusCode, &'static str)> {
+        let Some(id) = headers.get(SESSION_HEADER).and_then(|v| v.to_str().ok()) else {
+            return Err((StatusCode::BAD_REQUEST, "Missing Mcp-Session-Id header"));
+        };
+        let session = self.sessions.lock().unwrap_or_else(|e| e.into_inner()).get(id).cloned()
+            .ok_or((StatusCode::NOT_FOUND, "Unknown or expired session"))?;
+        session.touch();
+        Ok(session)
+    }
+}
+
+/// The MCP streamable HTTP endpoint at `/mcp`: POST JSON-RPC messages
+/// (answered as `application/json`), GET an SSE stream of the session's
+/// notifications, DELETE to end the session. Sessions without a request
+/// or an open stream for `config.session_timeout_seconds` are dropped.
+pub fn router(context: Arc<McpContext>, last_activity: Arc<RwLock<Instant>>) -> Router {
+    let state = HttpState {
+        permits: Arc::new(Semaphore::new(context.config.max_concurrent_requests.max(1))),
+        sessions: Arc::new(Mutex::new(HashMap::new())),
+        context,
+        last_activity,
+    };
+    tokio::spawn(route_job_updates(state.context.clone(), state.sessions.clone()));
+    if state.context.config.session_timeout_seconds > 0 {
+        let timeout = Duration::from_secs(state.context.config.session_timeout_seconds);
+        tokio::spawn(expire_sessions(state.sessions.clone(), timeout));
+    }
+    Router::new()
+        .route("/mcp", post(post_messages).get(open_events).delete(end_session))
+        .with_state(state)
+}
+
+/// Serve [`router`] on `bind` until the process exits. A TCP address other
+/// than loopback is refused unless `allow_remote`: there is no
+/// authentication, so anyone who can reach it can read the memory.
+pub async fn serve_http(bind: Bind, allow_remote: bool, context: Arc<McpContext>, last_activity: Arc<RwLock<Instant>>) -> Result<()> {
+    let app = router(context, last_activity);
+    match bind {
+        Bind::Tcp(addr) => {
+            if !addr.ip().is_loopback() {
+                if !allow_remote {
+                    anyhow::bail!("Refusing to serve on non-loopback address {} without --allow-remote", addr);
+                }
+                eprintln!("  ! Warning: HTTP transport bound to non-loopback address {}; memory is readable by anyone who can reach it", addr);
+            }
+            let listener = tokio::net::TcpListener::bind(addr).await.with_context(|| format!("Cannot bind {}", addr))?;
+            eprintln!("  ✓ MCP over HTTP at http://{}/mcp", listener.local_addr()?);
+            axum::serve(listener, app).await?;
+        }
+        Bind::Unix(path) => {
+            remove_stale_socket(&path)?;
+            let listener = tokio::net::UnixListener::bind(&path).with_context(|| format!("Cannot bind {}", path.display()))?;
+            {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+            }
+            eprintln!("  ✓ MCP over HTTP at unix:{}", path.display());
+            axum::serve(listener, app).await?;
+        }
+    }
+    Ok(())
+}
+
+/// A socket left behind by a previous run would make bind fail, so remove
+/// it; anything else at `path` is left alone and reported.
+fn remove_stale_socket(path: &std::path::Path) -> Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => {
+            std::fs::remove_file(path).with_context(|| format!("Cannot replace {}", path.display()))
+        }
+        Ok(_) => anyhow::bail!("{} exists and is not a socket; refusing to replace it", path.display()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e).with_context(|| format!("Cannot inspect {}", path.display())),
+    }
+}
+
+/// Browsers send `Origin`; only pages served from this machine may talk to
+/// the daemon (DNS rebinding protection).
+fn origin_allowed(headers: &HeaderMap) -> bool {
+    let Some(origin) = headers.get("origin").and_then(|v| v.to_str().ok()) else { return true };
+    let host = origin.split("://").nth(1).unwrap_or(origin);
+    let host = match host.strip_prefix('[') {
+        Some(v6) => v6.split(']').next().unwrap_or(""),
+        None => host.split(':').next().unwrap_or(""),
+    };
+    matches!(host, "localhost" | "127.0.0.1" | "::1")
+}
+
+async fn post_messages(State(state): State<HttpState>, headers: HeaderMap, body: String) -> Response {
+    if !origin_allowed(&headers) {
+        return (StatusCode::FORBIDDEN, "Origin not allowed").into_response();
+    }
+    if let Some(version) = headers.get(PROTOCOL_VERSION_HEADER)
+        && !version.to_str().is_ok_and(|v| PROTOCOL_VERSIONS.contains(&v))
+    {
+        return (StatusCode::BAD_REQUEST, "Unsupported MCP-Protocol-Version").into_response();
+    }
+    *state.last_activity.write().await = Instant::now();
+
+    let message: Value = match serde_json::from_str(&body) {
+        Ok(message) => message,
+        Err(e) => {
+            return (StatusCode::BAD_REQUEST, Json(json!({
+                "jsonrpc": "2.0",
+                "error": {"code": -32700, "message": format!("Parse error: {}", e)},
+                "id": null
+            }))).into_response();
+        }
+    };
+    let (messages, batch) = match message {
+        Value::Array(messages) => (messages, true),
+        message => (vec![message], false),
+    };
+
+    let initializing = messages.iter().any(|m| m.get("method").and_then(|m| m.as_str()) == Some("initialize"));
+    let (session_id, session) = if initializing {
+        if batch {
+            return (StatusCode::BAD_REQUEST, "initialize must not be batched").into_response();
+        }
+        let id = uuid::Uuid::new_v4().to_string();
+        let session = Arc::new(Session {
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+            client: Client::text_only(),
+            events: broadcast::channel(100).0,
+            last_seen: Mutex::new(Instant::now()),
+        });
+        state.sessions.lock().unwrap_or_else(|e| e.into_inner()).insert(id.clone(), session.clone());
+        eprintln!("DEBUG: [http] session {} opened", id);
+        (Some(id), session)
+    } else {
+        match state.session(&headers) {
+            Ok(session) => (None, session),
+            Err(rejection) => return rejection.into_response(),
+        }
+    };
+
+    let mut tasks = Vec::new();
+    for message in messages {
+        if message.get("method").and_then(|m| m.as_str()) == Some("notifications/cancelled") {
+            if let Some(id) = message.pointer("/params/requestId") {
+                cancel(&session.in_flight, id);
+            }
+            continue;
+        }
+        tasks.push(spawn_request(&state, &session, message));
+    }
+    let mut responses = Vec::new();
+    for task in tasks {
+        // A cancelled request is aborted and gets no response.
+        if let Ok(Some(response)) = task.await {
+            responses.push(response);
+        }
+    }
+
+    let mut response = match (responses.is_empty(), batch) {
+        (true, _) => StatusCode::ACCEPTED.into_response(),
+        (false, true) => Json(Value::Array(responses)).into_response(),
+        (false, false) => Json(responses.remove(0)).into_response(),
+    };
+    if let Some(id) = session_id
+        && let Ok(value) = HeaderValue::from_str(&id)
+    {
+        response.headers_mut().insert(SESSION_HEADER, value);
+    }
+    response
+}
+
+fn spawn_request(state: &HttpState, session: &Arc<Session>, request: Value) -> tokio::task::JoinHandle<Option<Value>> {
+    let key = request.get("id").map(|id| id.to_string());
+    let context = state.context.clone();
+    let permits = state.permits.clone();
+    let tracked = session.in_flight.clone();
+    let owner = session.clone();
+    let task_key = key.clone();
+    // Register before the task can finish, so it always finds its own entry.
+    let mut registry = session.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+    let handle = tokio::spawn(async move {
+        let _permit = permits.acquire_owned().await.ok()?;
+        let response = handle_request(&request, &context, &owner.client).await;
+        if let Some(key) = task_key {
+            tracked.lock().unwrap_or_else(|e| e.into_inner()).remove(&key);
+        }
+        response
+    });
+    if let Some(key) = key {
+        registry.insert(key, handle.abort_handle());
+    }
+    handle
+}
+
+async fn open_events(State(state): State<HttpState>, headers: HeaderMap) -> Response {
+    if !origin_allowed(&headers) {
+        return (StatusCode::FORBIDDEN, "Origin not allowed").into_response();
+    }
+    let session = match state.session(&headers) {
+        Ok(session) => session,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let events = BroadcastStream::new(session.events.subscribe())
+        .filter_map(|message| message.ok())
+        .map(|message| Event::default().event("message").json_data(message));
+    Sse::new(events).keep_alive(KeepAlive::default()).into_response()
+}
+
+async fn end_session(State(state): State<HttpState>, headers: HeaderMap) -> Response {
+    if !origin_allowed(&headers) {
+        return (StatusCode::FORBIDDEN, "Origin not allowed").into_response();
+    }
+    let Some(id) = headers.get(SESSION_HEADER).and_then(|v| v.to_str().ok()) else {
+        return (StatusCode::BAD_REQUEST, "Missing Mcp-Session-Id header").into_response();
+    };
+    let Some(session) = state.sessions.lock().unwrap_or_else(|e| e.into_inner()).remove(id) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    session.close();
+    eprintln!("DEBUG: [http] session {} closed", id);
+    StatusCode::OK.into_response()
+}
+
+/// Send each job's notifications to the session that queued it.
+async fn route_job_updates(context: Arc<McpContext>, sessions: Sessions) {
+    let mut progress = context.jobs.subscribe();
+    loop {
+        match progress.recv().await {
+            Ok(job) => {
+                let sessions = sessions.lock().unwrap_or_else(|e| e.into_inner());
+                for session in sessions.values() {
+                    for notification in session.client.job_notifications(&job) {
+                        let _ = session.events.send(notification);
+                    }
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+/// Drop sessions idle for `timeout`, as if the client had sent DELETE.
+async fn expire_sessions(sessions: Sessions, timeout: Duration) {
+    let mut ticks = tokio::time::interval((timeout / 4).max(Duration::from_secs(1)));
+    loop {
+        ticks.tick().await;
+        sessions.lock().unwrap_or_else(|e| e.into_inner()).retain(|id, session| {
+            if !session.idle(timeout) {
+                return true;
+            }
+            session.close();
+            eprintln!("DEBUG: [http] session {} expired", id);
+            false
+        });
+    }
+}
@@ -1 +1,4 @@
 pub mod tools;
+pub mod server;
+pub mod http;
+pub mod args;
@@ -0,0 +1,420 @@
+use crate::engine::jobs::progress_notification;
+use crate::storage::sqlite::{JobRecord, JobStatus};
+use crate::mcp::tools::{call_tool_with_progress, list_resources, list_tools, read_resource, McpContext};
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::{broadcast, mpsc, RwLock, Semaphore};
+use tokio::task::{AbortHandle, JoinSet};
+use tokio::time::Instant;
+use uuid::Uuid;
+
+/// In-flight requests by JSON-encoded id, for `notifications/cancelled`.
+pub(crate) type InFlight = Arc<Mutex<HashMap<String, AbortHandle>>>;
+
+/// Serve JSON-RPC over newline-delimited `input` / `output` (stdio for
+/// MCP). Requests run concurrently, at most `config.max_concurrent_requests`
+/// at a time; every message goes out through one writer task, so lines never
+/// interleave. Returns once `input` is closed and the requests already read
+/// are answered.
+pub async fn serve<R, W>(input: R, output: W, context: Arc<McpContext>, last_activity: Arc<RwLock<Instant>>) -> Result<()>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let (out_tx, out_rx) = mpsc::unbounded_channel::<Value>();
+    let writer = tokio::spawn(write_messages(output, out_rx));
+    let client = Arc::new(Client::default());
+    let progress = tokio::spawn(forward_progress(context.clone(), client.clone(), out_tx.clone()));
+
+    let permits = Arc::new(Semaphore::new(context.config.max_concurrent_requests.max(1)));
+    let in_flight: InFlight = Arc::new(Mutex::new(HashMap::new()));
+    let mut tasks = JoinSet::new();
+    let mut lines = input.lines();
+
+    while let Some(line) = lines.next_line().await? {
+        while tasks.try_join_next().is_some() {}
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        *last_activity.write().await = Instant::now();
+
+        let request: Value = match serde_json::from_str(trimmed) {
+            Ok(request) => request,
+            Err(e) => {
+                let _ = out_tx.send(error_response(Value::Null, &RpcError::new(PARSE_ERROR, format!("Parse error: {}", e))));
+                continue;
+            }
+        };
+
+        if request.get("method").and_then(|m| m.as_str()) == Some("notifications/cancelled") {
+            if let Some(id) = request.pointer("/params/requestId") {
+                cancel(&in_flight, id);
+            }
+            continue;
+        }
+
+        let key = request.get("id").map(|id| id.to_string());
+        let context = context.clone();
+        let client = client.clone();
+        let permits = permits.clone();
+        let out_tx = out_tx.clone();
+        let tracked = in_flight.clone();
+        let task_key = key.clone();
+        // Register before the task can finish, so it always finds its own entry.
+        let mut registry = in_flight.lock().unwrap_or_else(|e| e.into_inner());
+        let handle = tasks.spawn(async move {
+            let Ok(_permit) = permits.acquire_owned().await else { return };
+            let response = handle_request(&request, &context, &client).await;
+            if let Some(key) = task_key {
+                tracked.lock().unwrap_or_else(|e| e.into_inner()).remove(&key);
+            }
+            if let Some(response) = response {
+                let _ = out_tx.send(response);
+            }
+        });
+        if let Some(key) = key {
+            registry.insert(key, handle);
+        }
+        drop(registry);
+    }
+
+    while tasks.join_next().await.is_some() {}
+    progress.abort();
+    drop(out_tx);
+    writer.await??;
+    Ok(())
+}
+
+/// Abort the in-flight request `id`. Per MCP, it gets no response.
+pub(crate) fn cancel(in_flight: &InFlight, id: &Value) {
+    let handle = in_flight.lock().unwrap_or_else(|e| e.into_inner()).remove(&id.to_string());
+    match handle {
+        Some(handle) => {
+            eprintln!("DEBUG: [server] request {} cancelled", id);
+            handle.abort();
+        }
+        None => eprintln!("DEBUG: [server] cancel for unknown or finished request {}", id),
+    }
+}
+
+async fn write_messages<W: AsyncWrite + Unpin>(mut output: W, mut rx: mpsc::UnboundedReceiver<Value>) -> Result<()> {
+    while let Some(message) = rx.recv().await {
+        let mut line = serde_json::to_vec(&message)?;
+        line.push(b'\n');
+        output.write_all(&line).await?;
+        output.flush().await?;
+    }
+    Ok(())
+}
+
+async fn forward_progress(context: Arc<McpContext>, client: Arc<Client>, out_tx: mpsc::UnboundedSender<Value>) {
+    let mut progress = context.jobs.subscribe();
+    loop {
+        match progress.recv().await {
+            Ok(job) => {
+                for notification in client.job_notifications(&job) {
+                    let _ = out_tx.send(notification);
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+/// Protocol revisions this server speaks, newest first.
+pub const PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// Items per `tools/list` / `resources/list` page.
+pub const PAGE_SIZE: usize = 50;
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+/// MCP's code for `resources/read` of an unknown URI.
+pub const RESOURCE_NOT_FOUND: i64 = -32002;
+
+/// A JSON-RPC error object. Tools and resources return it (wrapped in
+/// `anyhow::Error`) for mistakes in the request itself; any other error is
+/// a failure while serving it.
+#[derive(Debug, Clone)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self { code, message: message.into(), data: None }
+    }
+
+    pub fn invalid_params(message: impl Into<String>) -> Self {
+        Self::new(INVALID_PARAMS, message)
+    }
+
+    pub fn with_data(mut self, data: Value) -> Self {
+        self.data = Some(data);
+        self
+    }
+
+    /// The `RpcError` inside `error`, or an internal error carrying its message.
+    pub fn from_anyhow(error: anyhow::Error) -> Self {
+        match error.downcast::<RpcError>() {
+            Ok(rpc) => rpc,
+            Err(error) => Self::new(INTERNAL_ERROR, error.to_string()),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let mut error = json!({"code": self.code, "message": self.message});
+        if let Some(data) = &self.data {
+            error["data"] = data.clone();
+        }
+        error
+    }
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+/// Severity for `logging/setLevel` and `notifications/message` (RFC 5424).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Notice,
+    Warning,
+    Error,
+    Critical,
+    Alert,
+    Emergency,
+}
+
+impl LogLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Notice => "notice",
+            LogLevel::Warning => "warning",
+            LogLevel::Error => "error",
+            LogLevel::Critical => "critical",
+            LogLevel::Alert => "alert",
+            LogLevel::Emergency => "emergency",
+        }
+    }
+}
+
+impl std::str::FromStr for LogLevel {
+    type Err = RpcError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            LogLevel::Debug, LogLevel::Info, LogLevel::Notice, LogLevel::Warning,
+            LogLevel::Error, LogLevel::Critical, LogLevel::Alert, LogLevel::Emergency,
+        ]
+        .into_iter()
+        .find(|level| level.as_str() == s)
+        .ok_or_else(|| RpcError::invalid_params(format!("Unknown log level '{}'", s)))
+    }
+}
+
+/// One connected client: the jobs its `memorize` calls queued, whose
+/// notifications go to it alone, and the level it set with `logging/setLevel`.
+#[derive(Debug, Default)]
+pub struct Client {
+    log_level: std::sync::RwLock<Option<LogLevel>>,
+    jobs: Mutex<HashSet<Uuid>>,
+    text_only: bool,
+}
+
+impl Client {
+    /// A client that may not run where the server does (HTTP): the text it
+    /// gives `memorize` is stored as is, never read as a path on this machine.
+    pub fn text_only() -> Self {
+        Self { text_only: true, ..Self::default() }
+    }
+
+    pub fn is_text_only(&self) -> bool {
+        self.text_only
+    }
+
+    pub fn set_log_level(&self, level: LogLevel) {
+        *self.log_level.write().unwrap_or_else(|e| e.into_inner()) = Some(level);
+    }
+
+    /// Route `job`'s notifications to this client.
+    pub fn own_job(&self, job: Uuid) {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner()).insert(job);
+    }
+
+    /// What this client should hear about `job`: progress under its token
+    /// and, once it finishes, a log message at the client's level. Nothing
+    /// for jobs queued by other clients.
+    pub fn job_notifications(&self, job: &JobRecord) -> Vec<Value> {
+        {
+            let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+            if !jobs.contains(&job.id) {
+                return Vec::new();
+            }
+            if job.status.is_finished() {
+                jobs.remove(&job.id);
+            }
+        }
+        let level = *self.log_level.read().unwrap_or_else(|e| e.into_inner());
+        progress_notification(job).into_iter().chain(job_log_message(job, level)).collect()
+    }
+}
+
+/// A `notifications/message` for a finished job, if its severity reaches
+/// `minimum` (`None` until the client calls `logging/setLevel`).
+pub fn job_log_message(job: &JobRecord, minimum: Option<LogLevel>) -> Option<Value> {
+    let (level, text) = match job.status {
+        JobStatus::Done => (LogLevel::Info, format!("Graph extraction job {} done ({} chunk(s))", job.id, job.total)),
+        JobStatus::Failed => (LogLevel::Error, format!(
+            "Graph extraction job {} finished with {} failed chunk(s): {}",
+            job.id, job.failed, job.error.as_deref().unwrap_or("unknown error")
+        )),
+        _ => return None,
+    };
+    if level < minimum? {
+        return None;
+    }
+    Some(json!({
+        "jsonrpc": "2.0",
+        "method": "notifications/message",
+        "params": {"level": level.as_str(), "logger": "local-memory", "data": text}
+    }))
+}
+
+/// One page of `items` starting at `cursor` (a cursor this server handed
+/// out), and the cursor of the page after it.
+pub fn paginate(items: Vec<Value>, cursor: Option<&Value>, page_size: usize) -> Result<(Vec<Value>, Option<String>), RpcError> {
+    let start = match cursor {
+        None | Some(Value::Null) => 0,
+        Some(cursor) => cursor.as_str()
+            .and_then(|c| c.parse::<usize>().ok())
+            .filter(|&start| start <= items.len())
+            .ok_or_else(|| RpcError::invalid_params(format!("Invalid cursor: {}", cursor)))?,
+    };
+    let end = (start + page_size.max(1)).min(items.len());
+    let next = (end < items.len()).then(|| end.to_string());
+    Ok((items.into_iter().skip(start).take(end - start).collect(), next))
+}
+
+fn list_page(key: &str, items: Value, params: &Value) -> Result<Value, RpcError> {
+    let items = items.as_array().cloned().unwrap_or_default();
+    let (page, next) = paginate(items, params.get("cursor"), PAGE_SIZE)?;
+    let mut result = json!({key: page});
+    if let Some(next) = next {
+        result["nextCursor"] = json!(next);
+    }
+    Ok(result)
+}
+
+/// The protocol revision to answer `initialize` with: the client's if we
+/// speak it, otherwise our newest.
+pub fn negotiate_protocol_version(requested: Option<&str>) -> &'static str {
+    requested
+        .and_then(|requested| PROTOCOL_VERSIONS.iter().find(|v| **v == requested))
+        .copied()
+        .unwrap_or(PROTOCOL_VERSIONS[0])
+}
+
+fn error_response(id: Value, error: &RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": error.to_json()
+    })
+}
+
+/// Answer one JSON-RPC message from `client`; `None` for notifications.
+pub async fn handle_request(request: &Value, context: &McpContext, client: &Client) -> Option<Value> {
+    let id = request.get("id").cloned();
+    let Some(method) = request.get("method").and_then(|m| m.as_str()) else {
+        // A reply to a server-to-client request; we send none, so it is dropped.
+        if request.get("result").is_some() || request.get("error").is_some() {
+            return None;
+        }
+        return Some(error_response(id.unwrap_or(Value::Null), &RpcError::new(INVALID_REQUEST, "Invalid request: missing 'method'")));
+    };
+    if request.get("jsonrpc").and_then(|v| v.as_str()) != Some("2.0") {
+        return Some(error_response(id.unwrap_or(Value::Null), &RpcError::new(INVALID_REQUEST, "Invalid request: 'jsonrpc' must be \"2.0\"")));
+    }
+    let Some(id) = id else {
+        // Notifications get no reply; `initialized` and `cancelled` need no work here.
+        return None;
+    };
+    let params = request.get("params").cloned().unwrap_or(json!({}));
+
+    let result = match method {
+        "initialize" => Ok(json!({
+            "protocolVersion": negotiate_protocol_version(params.get("protocolVersion").and_then(|v| v.as_str())),
+            "capabilities": {
+                "tools": {"listChanged": false},
+                "resources": {"subscribe": false, "listChanged": false},
+                "logging": {}
+            },
+            "serverInfo": {"name": "local-memory", "version": "0.3.0-supermemory"}
+        })),
+        "ping" => Ok(json!({})),
+        "tools/list" => list_page("tools", list_tools(), &params),
+        "tools/call" => match params.get("name").and_then(|n| n.as_str()) {
+            None => Err(RpcError::invalid_params("Missing 'name'")),
+            Some(name) => {
+                let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+                let progress_token = params.pointer("/_meta/progressToken").cloned();
+                match call_tool_with_progress(name, arguments, progress_token, Some(client), context).await {
+                    Ok(result) => Ok(result),
+                    Err(e) => match e.downcast::<RpcError>() {
+                        Ok(rpc) => Err(rpc),
+                        // The tool ran and failed: report it to the model, not as a protocol error.
+                        Err(e) => {
+                            eprintln!("ERROR: tool '{}' failed: {:#}", name, e);
+                            Ok(json!({"content": [{"type": "text", "text": format!("{:#}", e)}], "isError": true}))
+                        }
+                    },
+                }
+            }
+        },
+        "resources/list" => list_page("resources", list_resources(), &params),
+        "resources/templates/list" => Ok(json!({"resourceTemplates": []})),
+        "resources/read" => match params.get("uri").and_then(|u| u.as_str()) {
+            None => Err(RpcError::invalid_params("Missing 'uri'")),
+            Some(uri) => read_resource(uri, context).await.map_err(RpcError::from_anyhow),
+        },
+        "logging/setLevel" => params.get("level").and_then(|l| l.as_str())
+            .ok_or_else(|| RpcError::invalid_params("Missing 'level'"))
+            .and_then(|level| level.parse::<LogLevel>())
+            .map(|level| {
+                client.set_log_level(level);
+                json!({})
+            }),
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("Method not found: {}", method))),
+    };
+
+    match result {
+        Ok(res) => Some(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": res
+        })),
+        Err(e) => {
+            eprintln!("ERROR: {}: {}", method, e);
+            Some(error_response(id, &e))
+        }
+    }
+}
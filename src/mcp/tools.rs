@@ -1,9 +1,15 @@
 use crate::engine::funnel::SearchFunnel;
 use crate::engine::ingestion::IngestionPipeline;
-use crate::storage::sqlite::SqliteDatabase;
-use crate::model::UnifiedModel;
+use crate::engine::jobs::JobQueue;
+use crate::mcp::args::{
+    input_schema, parse_args, ConflictAction, ConflictsArgs, EntityArgs, GlobalSearchArgs, JobStatusArgs, MemorizeArgs, RecallArgs,
+};
+use crate::mcp::server::{Client, RpcError, RESOURCE_NOT_FOUND};
+use crate::storage::sqlite::{ConflictStatus, SqliteDatabase};
+use crate::model::{EmbeddingBatcher, UnifiedModel};
 use crate::KnowledgeEvent;
 use anyhow::{anyhow, Result};
+use schemars::JsonSchema;
 use serde_json::{json, Value};
 use std::sync::Arc;
 use tokio::sync::broadcast;
@@ -13,17 +19,27 @@ pub struct McpContext {
     pub model: Arc<dyn UnifiedModel>,
     pub config: crate::config::Config,
     pub event_tx: broadcast::Sender<KnowledgeEvent>,
+    /// Shared by every pipeline, so concurrent `memorize` calls embed together.
+    pub batcher: Arc<EmbeddingBatcher>,
+    /// Background graph extraction for `memorize`.
+    pub jobs: Arc<JobQueue>,
 }
 
 impl McpContext {
+    pub fn new(
+        db: Arc<SqliteDatabase>,
+        model: Arc<dyn UnifiedModel>,
+        config: crate::config::Config,
+        event_tx: broadcast::Sender<KnowledgeEvent>,
+    ) -> Self {
+        let batcher = Arc::new(EmbeddingBatcher::new(model.clone()).with_batch_size(config.embedding_batch_size));
+        let pipeline = build_pipeline(&db, &model, &config, &event_tx, &batcher);
+        let jobs = Arc::new(JobQueue::new(db.clone(), Arc::new(pipeline), config.extraction_workers));
+        Self { db, model, config, event_tx, batcher, jobs }
+    }
+
     pub fn get_pipeline(&self) -> IngestionPipeline {
-        IngestionPipeline::new(
-            self.model.clone(), 
-            self.db.clone(), 
-            Some(self.model.clone()),
-            self.config.semantic_chunking,
-            Some(self.event_tx.clone())
-        )
+        build_pipeline(&self.db, &self.model, &self.config, &self.event_tx, &self.batcher)
     }
 
     pub fn get_funnel(&self) -> SearchFunnel<'_> {
@@ -31,70 +47,42 @@ impl McpContext {
     }
 }
 
+fn build_pipeline(
+    db: &Arc<SqliteDatabase>,
+    model: &Arc<dyn UnifiedModel>,
+    config: &crate::config::Config,
+    event_tx: &broadcast::Sender<KnowledgeEvent>,
+    batcher: &Arc<EmbeddingBatcher>,
+) -> IngestionPipeline {
+    IngestionPipeline::new(
+        model.clone(),
+        db.clone(),
+        Some(model.clone()),
+        config.semantic_chunking,
+        Some(event_tx.clone())
+    )
+    .with_chunking(config.chunking.clone())
+    .with_pdf(config.pdf.clone())
+    .with_images(config.image.clone())
+    .with_ontologies(config.ontology.clone())
+    .with_max_retries(config.extraction_retries)
+    .with_batcher(batcher.clone())
+    .with_token_counter(model.token_counter())
+}
+
+fn tool<T: JsonSchema>(name: &str, description: &str) -> Value {
+    json!({"name": name, "description": description, "inputSchema": input_schema::<T>()})
+}
+
 pub fn list_tools() -> Value {
     json!([
-        {
-            "name": "memorize",
-            "description": "Insert a new memory into the local database and extract knowledge graph",
-            "inputSchema": {
-                "type": "object",
-                "properties": {
-                    "text": { "type": "string", "description": "The text content to remember" },
-                    "metadata": { "type": "object", "description": "Optional metadata associated with the memory" },
-                    "namespace": { "type": "string", "description": "Optional namespace for isolation (default: 'default')" }
-                },
-                "required": ["text"]
-            }
-        },
-        {
-            "name": "recall",
-            "description": "Search for relevant memories using hybrid vector and graph search",
-            "inputSchema": {
-                "type": "object",
-                "properties": {
-                    "query": { "type": "string", "description": "The search query" },
-                    "top_k": { "type": "integer", "description": "The number of results to return", "default": 5 },
-                    "namespace": { "type": "string", "description": "Optional namespace to filter by" }
-                },
-                "required": ["query"]
-            }
-        },
-        {
-            "name": "memory_global_search",
-            "description": "Perform a holistic search over community summaries to answer broad thematic questions",
-            "inputSchema": {
-                "type": "object",
-                "properties": {
-                    "query": { "type": "string", "description": "The thematic search query" },
-                    "namespace": { "type": "string", "description": "Optional namespace (default: 'default')" }
-                },
-                "required": ["query"]
-            }
-        },
-        {
-            "name": "explore",
-            "description": "Explore an entity's neighborhood in the knowledge graph",
-            "inputSchema": {
-                "type": "object",
-                "properties": {
-                    "entity_name": { "type": "string", "description": "The name of the entity to explore" },
-                    "namespace": { "type": "string", "description": "Optional namespace" }
-                },
-                "required": ["entity_name"]
-            }
-        },
-        {
-            "name": "forget",
-            "description": "Explicitly forget an entity, setting its decay factor to 0 and removing it",
-            "inputSchema": {
-                "type": "object",
-                "properties": {
-                    "entity_name": { "type": "string", "description": "The name of the entity to forget" },
-                    "namespace": { "type": "string", "description": "Optional namespace" }
-                },
-                "required": ["entity_name"]
-            }
-        }
+        tool::<MemorizeArgs>("memorize", "Insert a new memory into the local database. The memory is searchable on return; its knowledge graph is extracted in the background by a job (see memory_job_status)"),
+        tool::<RecallArgs>("recall", "Search for relevant memories using hybrid vector and graph search"),
+        tool::<GlobalSearchArgs>("memory_global_search", "Perform a holistic search over community summaries to answer broad thematic questions"),
+        tool::<EntityArgs>("explore", "Explore an entity's neighborhood in the knowledge graph"),
+        tool::<EntityArgs>("forget", "Explicitly forget an entity, setting its decay factor to 0 and removing it"),
+        tool::<JobStatusArgs>("memory_job_status", "Show the progress of a background knowledge graph extraction job, or list recent jobs"),
+        tool::<ConflictsArgs>("memory_conflicts", "List contradictions detected between new and existing knowledge, or resolve one (the losing fact is marked superseded)"),
     ])
 }
 
@@ -105,33 +93,64 @@ pub fn list_resources() -> Value {
             "name": "Current Memory Context",
             "description": "Proactive context containing recent architectural decisions and session history",
             "mimeType": "application/json"
+        },
+        {
+            "uri": "local-memory://conflicts",
+            "name": "Open Conflicts",
+            "description": "Unresolved contradictions between newly ingested and existing knowledge",
+            "mimeType": "application/json"
         }
     ])
 }
 
 pub async fn call_tool(name: &str, arguments: Value, context: &McpContext) -> Result<Value> {
+    call_tool_with_progress(name, arguments, None, None, context).await
+}
+
+/// Like `call_tool`, with the request's `_meta.progressToken`: background
+/// work it starts reports progress under that token, to `client` alone.
+pub async fn call_tool_with_progress(
+    name: &str,
+    arguments: Value,
+    progress_token: Option<Value>,
+    client: Option<&Client>,
+    context: &McpContext,
+) -> Result<Value> {
     match name {
         "memorize" => {
-            let text = arguments.get("text").and_then(|v| v.as_str())
-                .ok_or_else(|| anyhow!("Missing 'text' argument"))?;
-            let metadata = arguments.get("metadata").cloned().unwrap_or(json!({}));
-            let namespace = arguments.get("namespace").and_then(|v| v.as_str()).unwrap_or("default");
+            let args: MemorizeArgs = parse_args(name, arguments)?;
+            let metadata = Value::Object(args.metadata.unwrap_or_default());
 
-            let id = context.get_pipeline().run_auto(text, metadata, namespace).await?;
+            let pipeline = context.get_pipeline().with_strategy(args.chunking).with_graph_extraction(false);
+            let id = if client.is_some_and(Client::is_text_only) {
+                pipeline.run_with_namespace(&args.text, metadata, &args.namespace).await?
+            } else {
+                pipeline.run_auto(&args.text, metadata, &args.namespace).await?
+            };
+            let job = context.jobs.enqueue(id, &args.namespace, progress_token)?;
+            if let Some(client) = client {
+                client.own_job(job.id);
+            }
             Ok(json!({
-                "content": [{"type": "text", "text": format!("Memory inserted and knowledge graph updated. ID: {}", id)}]
+                "content": [{"type": "text", "text": format!(
+                    "Memory inserted. ID: {}. Knowledge graph extraction queued as job {} ({} chunk(s)).",
+                    id, job.id, job.total
+                )}]
+            }))
+        }
+        "memory_job_status" => {
+            let args: JobStatusArgs = parse_args(name, arguments)?;
+            let result = match args.job_id {
+                Some(id) => json!(context.db.get_job(id)?.ok_or_else(|| anyhow!("Job not found: {}", id))?),
+                None => json!(context.db.list_jobs(args.status, args.limit)?),
+            };
+            Ok(json!({
+                "content": [{"type": "text", "text": serde_json::to_string_pretty(&result)?}]
             }))
         }
         "recall" => {
-            let query = arguments.get("query").and_then(|v| v.as_str())
-                .ok_or_else(|| anyhow!("Missing 'query' argument"))?;
-            let top_k = arguments.get("top_k").and_then(|v| v.as_u64()).unwrap_or(5) as usize;
-            let namespace = arguments.get("namespace").and_then(|v| v.as_str()).unwrap_or("default");
-
-            let query_vector = context.model.embed_one(query).await
-                .map_err(|e| anyhow!("Embedding failed: {}", e))?;
-            
-            let results = context.get_funnel().hybrid_search_with_namespace(&query_vector, top_k, namespace)?;
+            let args: RecallArgs = parse_args(name, arguments)?;
+            let results = context.get_funnel().recall(context.model.as_ref(), &args.query, args.top_k, &args.namespace).await?;
 
             let formatted_results: Vec<Value> = results.into_iter()
                 .map(|r| json!({"id": r.id, "score": r.score, "metadata": r.metadata, "context": r.context}))
@@ -142,40 +161,50 @@ pub async fn call_tool(name: &str, arguments: Value, context: &McpContext) -> Re
             }))
         }
         "memory_global_search" => {
-            let query = arguments.get("query").and_then(|v| v.as_str())
-                .ok_or_else(|| anyhow!("Missing 'query' argument"))?;
-            
-            let results = handle_global_search(query, context).await?;
+            let args: GlobalSearchArgs = parse_args(name, arguments)?;
+            let results = handle_global_search(&args.query, &args.namespace, context).await?;
             Ok(json!({
                 "content": [{"type": "text", "text": serde_json::to_string_pretty(&results)?}]
             }))
         }
         "explore" => {
-            let entity_name = arguments.get("entity_name").and_then(|v| v.as_str())
-                .ok_or_else(|| anyhow!("Missing 'entity_name' argument"))?;
-            let namespace = arguments.get("namespace").and_then(|v| v.as_str()).unwrap_or("default");
-
-            let neighborhood = context.db.get_neighborhood_with_namespace(entity_name, namespace)?;
+            let args: EntityArgs = parse_args(name, arguments)?;
+            let neighborhood = context.db.get_neighborhood_with_namespace(&args.entity_name, &args.namespace)?;
             Ok(json!({
                 "content": [{"type": "text", "text": serde_json::to_string_pretty(&neighborhood)?}]
             }))
         }
         "forget" => {
-            let entity_name = arguments.get("entity_name").and_then(|v| v.as_str())
-                .ok_or_else(|| anyhow!("Missing 'entity_name' argument"))?;
-            let namespace = arguments.get("namespace").and_then(|v| v.as_str()).unwrap_or("default");
-
-            context.db.forget_entity(entity_name, namespace)?;
+            let args: EntityArgs = parse_args(name, arguments)?;
+            context.db.forget_entity(&args.entity_name, &args.namespace)?;
+            Ok(json!({
+                "content": [{"type": "text", "text": format!("Entity '{}' forgotten successfully.", args.entity_name)}]
+            }))
+        }
+        "memory_conflicts" => {
+            let args: ConflictsArgs = parse_args(name, arguments)?;
+            let result = match args.action {
+                ConflictAction::List => {
+                    json!(context.db.list_conflicts(args.status, args.namespace.as_deref(), args.limit)?)
+                }
+                ConflictAction::Resolve => {
+                    let id = args.conflict_id
+                        .ok_or_else(|| RpcError::invalid_params("Missing 'conflict_id' argument"))?;
+                    let resolution = args.resolution
+                        .ok_or_else(|| RpcError::invalid_params("Missing 'resolution' argument"))?;
+                    json!(context.db.resolve_conflict(id, resolution)?)
+                }
+            };
             Ok(json!({
-                "content": [{"type": "text", "text": format!("Entity '{}' forgotten successfully.", entity_name)}]
+                "content": [{"type": "text", "text": serde_json::to_string_pretty(&result)?}]
             }))
         }
-        _ => Err(anyhow!("Unknown tool: {}", name)),
+        _ => Err(RpcError::invalid_params(format!("Unknown tool: {}", name)).into()),
     }
 }
 
-async fn handle_global_search(query: &str, context: &McpContext) -> Result<Value> {
-    let summaries_list = context.db.list_community_summaries(20)?;
+async fn handle_global_search(query: &str, namespace: &str, context: &McpContext) -> Result<Value> {
+    let summaries_list = context.db.list_community_summaries_with_namespace(namespace, 20)?;
     let summaries: Vec<String> = summaries_list.into_iter()
         .map(|(_, title, summary)| format!("### {}\n{}", title, summary))
         .collect();
@@ -214,6 +243,18 @@ pub async fn read_resource(uri: &str, context: &McpContext) -> Result<Value> {
                 ]
             }))
         }
-        _ => Err(anyhow!("Unknown resource: {}", uri)),
+        "local-memory://conflicts" => {
+            let open = context.db.list_conflicts(Some(ConflictStatus::Open), None, 50)?;
+            Ok(json!({
+                "contents": [
+                    {
+                        "uri": uri,
+                        "mimeType": "application/json",
+                        "text": serde_json::to_string_pretty(&open)?
+                    }
+                ]
+            }))
+        }
+        _ => Err(RpcError::new(RESOURCE_NOT_FOUND, format!("Resource not found: {}", uri)).with_data(json!({"uri": uri})).into()),
     }
 }
@@ -2,22 +2,50 @@ use anyhow::Result;
 use std::sync::Arc;
 use async_trait::async_trait;
 use edgequake_llm::{LLMProvider, EmbeddingProvider, LLMResponse, LlmError, ChatMessage, CompletionOptions};
+use crate::model::tokens::{ApproxTokenCounter, TokenCounter};
+
+/// An embedder that knows search queries from stored documents.
+///
+/// Retrieval models embed the two differently (e.g. nomic's `search_query: `
+/// / `search_document: ` prefixes). Embedders with task prefixes implement
+/// both methods; the defaults embed the text as given, which is right for
+/// models without them. `EmbeddingProvider::embed` and `embed_one` always
+/// embed the text as given.
+#[async_trait]
+pub trait RetrievalEmbedder: EmbeddingProvider {
+    /// Embed a search query.
+    async fn embed_query(&self, query: &str) -> Result<Vec<f32>, LlmError> {
+        self.embed_one(query).await
+    }
+
+    /// Embed texts to be stored, one vector per text, whatever their number.
+    async fn embed_documents(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, LlmError> {
+        self.embed(texts).await
+    }
+}
 
 /// A unified interface for models that can perform both text generation and embedding.
 #[async_trait]
-pub trait UnifiedModel: LLMProvider + EmbeddingProvider {
+pub trait UnifiedModel: LLMProvider + RetrievalEmbedder {
     /// Prepare the model (download files, pull images, etc.)
     async fn prepare(&self) -> Result<()>;
+
+    /// Tokenizer-aware length checks for texts to be embedded.
+    fn token_counter(&self) -> Arc<dyn TokenCounter> {
+        Arc::new(ApproxTokenCounter::new(self.max_tokens()))
+    }
 }
 
 /// A generic wrapper that combines any LLMProvider and EmbeddingProvider into a UnifiedModel.
 pub struct GenericUnifiedModel {
     pub llm: Arc<dyn LLMProvider>,
-    pub embedder: Arc<dyn EmbeddingProvider>,
+    pub embedder: Arc<dyn RetrievalEmbedder>,
     /// List of (model_name, base_url) to pull via Ollama during prepare()
     pub prepare_list: Vec<(String, String)>,
     /// Override dimension (used when the provider's intrinsic dimension() is wrong, e.g. OpenAI-compatible with custom models)
     pub override_dimension: Option<usize>,
+    /// Token counter of the embedder; estimated from `max_tokens()` when unset
+    pub token_counter: Option<Arc<dyn TokenCounter>>,
 }
 
 #[async_trait]
@@ -45,6 +73,19 @@ impl EmbeddingProvider for GenericUnifiedModel {
     async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, LlmError> {
         self.embedder.embed(texts).await
     }
+    async fn embed_one(&self, text: &str) -> Result<Vec<f32>, LlmError> {
+        self.embedder.embed_one(text).await
+    }
+}
+
+#[async_trait]
+impl RetrievalEmbedder for GenericUnifiedModel {
+    async fn embed_query(&self, query: &str) -> Result<Vec<f32>, LlmError> {
+        self.embedder.embed_query(query).await
+    }
+    async fn embed_documents(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, LlmError> {
+        self.embedder.embed_documents(texts).await
+    }
 }
 
 #[async_trait]
@@ -55,6 +96,12 @@ impl UnifiedModel for GenericUnifiedModel {
         }
         Ok(())
     }
+
+    fn token_counter(&self) -> Arc<dyn TokenCounter> {
+        self.token_counter
+            .clone()
+            .unwrap_or_else(|| Arc::new(ApproxTokenCounter::new(self.max_tokens())))
+    }
 }
 
 /// Utility to check if an LLM provider is reachable
@@ -0,0 +1,182 @@
+//! Coalescing of document embedding requests into bounded batches.
+
+use anyhow::Result;
+use std::collections::BTreeSet;
+use std::sync::{Arc, OnceLock};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::model::base::RetrievalEmbedder;
+
+pub const DEFAULT_BATCH_SIZE: usize = 32;
+/// Padded size of one batch (longest text × batch size), in estimated tokens.
+pub const DEFAULT_BATCH_TOKENS: usize = 16_384;
+/// Rough characters per token, for bounding batches before tokenization.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Split items into batches of at most `max_batch` items and `max_padded`
+/// padded units (longest item × batch size), grouping items of similar
+/// length so short texts are not padded to the length of long ones.
+/// Returns indices into `lengths`; an item longer than `max_padded` gets a
+/// batch of its own.
+pub fn group_by_length(lengths: &[usize], max_batch: usize, max_padded: usize) -> Vec<Vec<usize>> {
+    let mut order: Vec<usize> = (0..lengths.len()).collect();
+    order.sort_by_key(|&i| lengths[i]);
+    let mut batches: Vec<Vec<usize>> = Vec::new();
+    let mut current: Vec<usize> = Vec::new();
+    for i in order {
+        // Sorted ascending, so the new item is the longest in the batch.
+        let padded = lengths[i].max(1) * (current.len() + 1);
+        if !current.is_empty() && (current.len() >= max_batch.max(1) || padded > max_padded) {
+            batches.push(std::mem::take(&mut current));
+        }
+        current.push(i);
+    }
+    if !current.is_empty() {
+        batches.push(current);
+    }
+    batches
+}
+
+struct Request {
+    texts: Vec<String>,
+    reply: oneshot::Sender<Result<Vec<Vec<f32>>, String>>,
+}
+
+/// Embeds documents through one provider in size-bounded batches, merging
+/// requests that arrive while a batch is running (e.g. concurrent
+/// `memorize` calls sharing one batcher). Batches go through
+/// `RetrievalEmbedder::embed_documents`.
+pub struct EmbeddingBatcher {
+    embedder: Arc<dyn RetrievalEmbedder>,
+    batch_size: usize,
+    batch_tokens: usize,
+    tx: OnceLock<mpsc::UnboundedSender<Request>>,
+}
+
+impl EmbeddingBatcher {
+    pub fn new(embedder: Arc<dyn RetrievalEmbedder>) -> Self {
+        Self { embedder, batch_size: DEFAULT_BATCH_SIZE, batch_tokens: DEFAULT_BATCH_TOKENS, tx: OnceLock::new() }
+    }
+
+    /// Maximum number of texts sent to the provider at once (see `Config::embedding_batch_size`).
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Maximum padded size of a batch in estimated tokens; a longer text is sent alone.
+    pub fn with_batch_tokens(mut self, batch_tokens: usize) -> Self {
+        self.batch_tokens = batch_tokens.max(1);
+        self
+    }
+
+    /// Embed `texts` as documents, one vector per text, in order.
+    pub async fn embed_documents(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let (reply, response) = oneshot::channel();
+        self.sender()
+            .send(Request { texts, reply })
+            .map_err(|_| anyhow::anyhow!("Embedding batcher stopped"))?;
+        response
+            .await
+            .map_err(|_| anyhow::anyhow!("Embedding batcher stopped"))?
+            .map_err(|e| anyhow::anyhow!("Embedding failed: {}", e))
+    }
+
+    /// The worker is spawned on first use, so a batcher can be built outside
+    /// a runtime. It exits once the batcher is dropped.
+    fn sender(&self) -> &mpsc::UnboundedSender<Request> {
+        self.tx.get_or_init(|| {
+            let (tx, rx) = mpsc::unbounded_channel();
+            tokio::spawn(run_worker(self.embedder.clone(), self.batch_size, self.batch_tokens, rx));
+            tx
+        })
+    }
+}
+
+async fn run_worker(
+    embedder: Arc<dyn RetrievalEmbedder>,
+    batch_size: usize,
+    batch_tokens: usize,
+    mut rx: mpsc::UnboundedReceiver<Request>,
+) {
+    while let Some(first) = rx.recv().await {
+        // Take everything queued while the previous batch was embedding.
+        let mut requests = vec![first];
+        let mut queued = requests[0].texts.len();
+        while queued < batch_size
+            && let Ok(request) = rx.try_recv()
+        {
+            queued += request.texts.len();
+            requests.push(request);
+        }
+
+        let texts: Vec<&String> = requests.iter().flat_map(|r| r.texts.iter()).collect();
+        let owners: Vec<usize> = requests
+            .iter()
+            .enumerate()
+            .flat_map(|(r, request)| std::iter::repeat_n(r, request.texts.len()))
+            .collect();
+        let lengths: Vec<usize> = texts.iter().map(|t| t.chars().count()).collect();
+        let max_padded = batch_tokens.saturating_mul(CHARS_PER_TOKEN);
+        let mut vectors: Vec<Option<Vec<f32>>> = vec![None; texts.len()];
+        let mut errors: Vec<Option<String>> = vec![None; requests.len()];
+
+        // A failed batch mixing several requests is retried per request, so
+        // one bad input only fails the request it came from.
+        let mut retry = BTreeSet::new();
+        for batch in group_by_length(&lengths, batch_size, max_padded) {
+            if let Err(e) = embed_batch(embedder.as_ref(), &texts, &batch, &mut vectors).await {
+                let involved: BTreeSet<usize> = batch.iter().map(|&i| owners[i]).collect();
+                match involved.first() {
+                    Some(&only) if involved.len() == 1 => errors[only] = Some(e),
+                    _ => retry.extend(involved),
+                }
+            }
+        }
+        for r in retry {
+            if errors[r].is_some() {
+                continue;
+            }
+            let pending: Vec<usize> = (0..texts.len()).filter(|&i| owners[i] == r && vectors[i].is_none()).collect();
+            let pending_lengths: Vec<usize> = pending.iter().map(|&i| lengths[i]).collect();
+            for group in group_by_length(&pending_lengths, batch_size, max_padded) {
+                let batch: Vec<usize> = group.iter().map(|&g| pending[g]).collect();
+                if let Err(e) = embed_batch(embedder.as_ref(), &texts, &batch, &mut vectors).await {
+                    errors[r] = Some(e);
+                    break;
+                }
+            }
+        }
+
+        let mut vectors = vectors.into_iter();
+        for (request, error) in requests.into_iter().zip(errors) {
+            let own: Vec<Option<Vec<f32>>> = vectors.by_ref().take(request.texts.len()).collect();
+            let result = match error {
+                Some(e) => Err(e),
+                None => Ok(own.into_iter().map(Option::unwrap_or_default).collect()),
+            };
+            let _ = request.reply.send(result);
+        }
+    }
+}
+
+/// Embed `texts[i]` for every `i` in `batch` into `vectors[i]`.
+async fn embed_batch(
+    embedder: &dyn RetrievalEmbedder,
+    texts: &[&String],
+    batch: &[usize],
+    vectors: &mut [Option<Vec<f32>>],
+) -> Result<(), String> {
+    let inputs: Vec<String> = batch.iter().map(|&i| texts[i].clone()).collect();
+    let out = embedder.embed_documents(&inputs).await.map_err(|e| e.to_string())?;
+    if out.len() != inputs.len() {
+        return Err(format!("provider returned {} vectors for {} texts", out.len(), inputs.len()));
+    }
+    for (&i, vector) in batch.iter().zip(out) {
+        vectors[i] = Some(vector);
+    }
+    Ok(())
+}
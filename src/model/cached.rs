@@ -0,0 +1,119 @@
+use async_trait::async_trait;
+use edgequake_llm::{EmbeddingProvider, LlmError};
+use std::sync::Arc;
+
+use crate::model::base::RetrievalEmbedder;
+use crate::storage::EmbeddingCache;
+
+/// A `RetrievalEmbedder` that serves repeated queries and documents from an
+/// `EmbeddingCache` and only sends the rest to the wrapped embedder. Entries
+/// are keyed by the prefix the wrapped embedder applies to each kind; raw
+/// `embed` / `embed_one` calls are not cached.
+pub struct CachedEmbedder {
+    inner: Arc<dyn RetrievalEmbedder>,
+    cache: Arc<EmbeddingCache>,
+    model_key: String,
+    query_prefix: String,
+    document_prefix: String,
+}
+
+impl CachedEmbedder {
+    pub fn new(inner: Arc<dyn RetrievalEmbedder>, cache: Arc<EmbeddingCache>) -> Self {
+        let model_key = format!("{}:{}@{}", inner.name(), inner.model(), inner.dimension());
+        Self { inner, cache, model_key, query_prefix: String::new(), document_prefix: String::new() }
+    }
+
+    /// Prefixes the wrapped provider prepends (see `EmbeddingConfig`).
+    pub fn with_prefixes(mut self, query_prefix: &str, document_prefix: &str) -> Self {
+        self.query_prefix = query_prefix.to_string();
+        self.document_prefix = document_prefix.to_string();
+        self
+    }
+
+    /// The model key the cache's entries and counters are stored under.
+    pub fn model_key(&self) -> &str {
+        &self.model_key
+    }
+
+    fn lookup(&self, prefix: &str, text: &str) -> Option<Vec<f32>> {
+        self.cache.get(&self.model_key, prefix, text).unwrap_or_else(|e| {
+            eprintln!("  ! Warning: Embedding cache lookup failed: {}", e);
+            None
+        })
+    }
+
+    fn store(&self, prefix: &str, text: &str, vector: &[f32]) {
+        if let Err(e) = self.cache.put(&self.model_key, prefix, text, vector) {
+            eprintln!("  ! Warning: Embedding cache write failed: {}", e);
+        }
+    }
+
+    fn record(&self, hits: usize, misses: usize) {
+        if let Err(e) = self.cache.record(&self.model_key, hits, misses) {
+            eprintln!("  ! Warning: Embedding cache stats update failed: {}", e);
+        }
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for CachedEmbedder {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+    fn dimension(&self) -> usize {
+        self.inner.dimension()
+    }
+    fn max_tokens(&self) -> usize {
+        self.inner.max_tokens()
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, LlmError> {
+        self.inner.embed(texts).await
+    }
+
+    async fn embed_one(&self, text: &str) -> Result<Vec<f32>, LlmError> {
+        self.inner.embed_one(text).await
+    }
+}
+
+#[async_trait]
+impl RetrievalEmbedder for CachedEmbedder {
+    async fn embed_documents(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, LlmError> {
+        let prefix = &self.document_prefix;
+        let mut vectors: Vec<Option<Vec<f32>>> = texts.iter().map(|t| self.lookup(prefix, t)).collect();
+        let missing: Vec<usize> = (0..texts.len()).filter(|&i| vectors[i].is_none()).collect();
+
+        if !missing.is_empty() {
+            let batch: Vec<String> = missing.iter().map(|&i| texts[i].clone()).collect();
+            let embedded = self.inner.embed_documents(&batch).await?;
+            if embedded.len() != batch.len() {
+                return Err(LlmError::Unknown(format!(
+                    "Embedding provider returned {} vectors for {} texts",
+                    embedded.len(),
+                    batch.len()
+                )));
+            }
+            for (&i, vector) in missing.iter().zip(embedded) {
+                self.store(prefix, &texts[i], &vector);
+                vectors[i] = Some(vector);
+            }
+        }
+        self.record(texts.len() - missing.len(), missing.len());
+        Ok(vectors.into_iter().map(|v| v.unwrap_or_default()).collect())
+    }
+
+    async fn embed_query(&self, query: &str) -> Result<Vec<f32>, LlmError> {
+        let prefix = &self.query_prefix;
+        if let Some(vector) = self.lookup(prefix, query) {
+            self.record(1, 0);
+            return Ok(vector);
+        }
+        let vector = self.inner.embed_query(query).await?;
+        self.store(prefix, query, &vector);
+        self.record(0, 1);
+        Ok(vector)
+    }
+}
@@ -1,6 +1,7 @@
 use async_trait::async_trait;
-use candle_core::Device;
-use edgequake_llm::LlmError;
+use candle_core::{Device, Tensor, D};
+use candle_transformers::generation::{LogitsProcessor, Sampling};
+use edgequake_llm::{CompletionOptions, LlmError};
 use tokenizers::Tokenizer;
 
 /// Discriminates which role a loaded backend serves.
@@ -10,6 +11,107 @@ pub enum ModelRole {
     Generation,
 }
 
+/// Per-call sampling parameters passed to `ModelBackend::generate`.
+///
+/// Unset fields fall back to the architecture defaults in `models.yaml`;
+/// with no temperature the backend decodes greedily.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GenerateOptions {
+    pub max_new_tokens: Option<usize>,
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub seed: Option<u64>,
+    /// Generation stops at the first occurrence of any of these strings,
+    /// which are not included in the output.
+    pub stop: Vec<String>,
+}
+
+impl GenerateOptions {
+    /// Seed used when neither the call nor the architecture sets one.
+    pub const DEFAULT_SEED: u64 = 42;
+
+    pub fn from_completion(options: &CompletionOptions) -> Self {
+        Self {
+            max_new_tokens: options.max_tokens,
+            temperature: options.temperature.map(f64::from),
+            top_p: options.top_p.map(f64::from),
+            seed: None,
+            stop: options.stop.clone().unwrap_or_default(),
+        }
+    }
+
+    /// The sampling strategy these options describe. A zero or missing
+    /// temperature means greedy (arg-max) decoding.
+    pub fn sampling(&self) -> Sampling {
+        match self.temperature {
+            Some(temperature) if temperature > 0.0 => match self.top_p {
+                Some(p) if p > 0.0 && p < 1.0 => Sampling::TopP { p, temperature },
+                _ => Sampling::All { temperature },
+            },
+            _ => Sampling::ArgMax,
+        }
+    }
+
+    pub fn logits_processor(&self) -> LogitsProcessor {
+        LogitsProcessor::from_sampling(self.seed.unwrap_or(Self::DEFAULT_SEED), self.sampling())
+    }
+
+    /// Byte offset of the earliest stop string in `text`, if any.
+    pub fn stop_position(&self, text: &str) -> Option<usize> {
+        self.stop
+            .iter()
+            .filter(|s| !s.is_empty())
+            .filter_map(|s| text.find(s.as_str()))
+            .min()
+    }
+
+    /// Cut `text` at the earliest stop string.
+    pub fn truncate_at_stop(&self, mut text: String) -> String {
+        if let Some(pos) = self.stop_position(&text) {
+            text.truncate(pos);
+        }
+        text
+    }
+}
+
+/// How token states are reduced to one embedding vector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pooling {
+    /// Average over all tokens (nomic, e5, gte, jina).
+    #[default]
+    Mean,
+    /// The first (`[CLS]`) token's state (bge, gte-modernbert).
+    Cls,
+}
+
+impl Pooling {
+    /// Reduce `(1, tokens, hidden)` states to one L2-normalised vector.
+    pub fn apply(&self, hidden: &Tensor) -> candle_core::Result<Vec<f32>> {
+        let pooled = match self {
+            Pooling::Mean => hidden.mean(1)?,
+            Pooling::Cls => hidden.narrow(1, 0, 1)?.squeeze(1)?,
+        };
+        let norm = pooled.sqr()?.sum_keepdim(D::Minus1)?.sqrt()?;
+        pooled.broadcast_div(&norm)?.squeeze(0)?.to_vec1::<f32>()
+    }
+
+    /// Reduce padded `(batch, tokens, hidden)` states to one L2-normalised
+    /// vector per sequence; `mask` is `(batch, tokens)`, 1 for real tokens.
+    pub fn apply_masked(&self, hidden: &Tensor, mask: &Tensor) -> candle_core::Result<Vec<Vec<f32>>> {
+        let pooled = match self {
+            Pooling::Mean => {
+                let mask = mask.to_dtype(hidden.dtype())?.unsqueeze(D::Minus1)?;
+                let summed = hidden.broadcast_mul(&mask)?.sum(1)?;
+                summed.broadcast_div(&mask.sum(1)?)?
+            }
+            Pooling::Cls => hidden.narrow(1, 0, 1)?.squeeze(1)?,
+        };
+        let norm = pooled.sqr()?.sum_keepdim(D::Minus1)?.sqrt()?;
+        pooled.broadcast_div(&norm)?.to_dtype(candle_core::DType::F32)?.to_vec2::<f32>()
+    }
+}
+
 /// Unified trait implemented by every architecture-specific backend.
 ///
 /// A backend owns the loaded model weights and device, but borrows the
@@ -25,11 +127,19 @@ pub trait ModelBackend: Send + Sync {
         0
     }
 
-    /// Run text generation and return the decoded output string.
+    /// Longest input, in tokens, the model accepts (its position table),
+    /// or `None` when it has no hard limit.
+    fn max_positions(&self) -> Option<usize> {
+        None
+    }
+
+    /// Run text generation with the given sampling `options` and return the
+    /// decoded output string.
     /// Returns an error when `role() != Generation`.
     async fn generate(
         &self,
         prompt: &str,
+        options: &GenerateOptions,
         tokenizer: &Tokenizer,
         device: &Device,
     ) -> Result<String, LlmError>;
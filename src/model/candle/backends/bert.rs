@@ -8,15 +8,20 @@ use serde_json::json;
 use std::path::Path;
 use tokenizers::Tokenizer;
 
-use crate::model::candle::backend::{ModelBackend, ModelRole};
+use crate::model::candle::backend::{GenerateOptions, ModelBackend, ModelRole, Pooling};
+use crate::model::candle::backends::common::{embed_padded, load_tensors};
 
+/// BERT-family encoders: NomicBERT checkpoints (remapped below) and standard
+/// BERT ones such as bge, e5 and gte.
 pub struct BertBackend {
     model: BertModel,
     dimension: usize,
+    max_positions: usize,
+    pooling: Pooling,
 }
 
 impl BertBackend {
-    pub fn load(model_dir: &Path, device: &Device) -> Result<Self> {
+    pub fn load(model_dir: &Path, device: &Device, pooling: Pooling) -> Result<Self> {
         let config_str = std::fs::read_to_string(model_dir.join("config.json"))?;
         let mut config_val: serde_json::Value = serde_json::from_str(&config_str)?;
         let map = config_val
@@ -58,12 +63,12 @@ impl BertBackend {
         let intermediate_size = config.intermediate_size;
 
         // Load and remap tensors from the NomicBERT safetensors layout.
-        let raw_tensors =
-            candle_core::safetensors::load(model_dir.join("model.safetensors"), device)?;
+        let raw_tensors = load_tensors(model_dir, device)?;
         let mut tensors = std::collections::HashMap::new();
 
         for (name, tensor) in raw_tensors {
-            let mut mapped = name.clone();
+            // Some checkpoints nest everything under the `bert.` prefix.
+            let mut mapped = name.strip_prefix("bert.").unwrap_or(&name).to_string();
             if mapped.starts_with("encoder.layers.") {
                 mapped = mapped.replace("encoder.layers.", "encoder.layer.");
             }
@@ -120,8 +125,8 @@ impl BertBackend {
             let prefix = format!("encoder.layer.{}", i);
             for suffix in ["query", "key", "value"] {
                 let name = format!("{}.attention.self.{}.bias", prefix, suffix);
-                if !tensors.contains_key(&name) {
-                    tensors.insert(name, Tensor::zeros(hidden_size, DType::F32, device)?);
+                if let std::collections::hash_map::Entry::Vacant(e) = tensors.entry(name) {
+                    e.insert(Tensor::zeros(hidden_size, DType::F32, device)?);
                 }
             }
             let other_biases = [
@@ -136,8 +141,8 @@ impl BertBackend {
                 (format!("{}.output.dense.bias", prefix), hidden_size),
             ];
             for (name, size) in other_biases {
-                if !tensors.contains_key(&name) {
-                    tensors.insert(name, Tensor::zeros(size, DType::F32, device)?);
+                if let std::collections::hash_map::Entry::Vacant(e) = tensors.entry(name) {
+                    e.insert(Tensor::zeros(size, DType::F32, device)?);
                 }
             }
         }
@@ -148,6 +153,8 @@ impl BertBackend {
         Ok(Self {
             model,
             dimension: hidden_size,
+            max_positions: config.max_position_embeddings,
+            pooling,
         })
     }
 }
@@ -161,10 +168,14 @@ impl ModelBackend for BertBackend {
     fn dimension(&self) -> usize {
         self.dimension
     }
+    fn max_positions(&self) -> Option<usize> {
+        Some(self.max_positions)
+    }
 
     async fn generate(
         &self,
         _prompt: &str,
+        _options: &GenerateOptions,
         _tokenizer: &Tokenizer,
         _device: &Device,
     ) -> Result<String, LlmError> {
@@ -176,50 +187,12 @@ impl ModelBackend for BertBackend {
     async fn embed_batch(
         &self,
         token_ids: &[Vec<u32>],
-        type_ids: &[Vec<u32>],
+        _type_ids: &[Vec<u32>],
         device: &Device,
     ) -> Result<Vec<Vec<f32>>, LlmError> {
-        let mut results = Vec::with_capacity(token_ids.len());
-        for (ids, tids) in token_ids.iter().zip(type_ids.iter()) {
-            let input_ids = Tensor::new(ids.as_slice(), device)
-                .map_err(|e| LlmError::Unknown(e.to_string()))?
-                .unsqueeze(0)
-                .map_err(|e| LlmError::Unknown(e.to_string()))?;
-            let token_type_ids = Tensor::new(tids.as_slice(), device)
-                .map_err(|e| LlmError::Unknown(e.to_string()))?
-                .unsqueeze(0)
-                .map_err(|e| LlmError::Unknown(e.to_string()))?;
-
-            let embeddings = self
-                .model
-                .forward(&input_ids, &token_type_ids, None)
-                .map_err(|e| LlmError::Unknown(e.to_string()))?;
-
-            let (_n_batch, n_tokens, _hidden) = embeddings
-                .dims3()
-                .map_err(|e| LlmError::Unknown(e.to_string()))?;
-            let sum = embeddings
-                .sum(1)
-                .map_err(|e| LlmError::Unknown(e.to_string()))?;
-            let mean = (sum / (n_tokens as f64))
-                .map_err(|e| LlmError::Unknown(e.to_string()))?;
-            let norm = mean
-                .sqr()
-                .map_err(|e| LlmError::Unknown(e.to_string()))?
-                .sum_keepdim(1)
-                .map_err(|e| LlmError::Unknown(e.to_string()))?
-                .sqrt()
-                .map_err(|e| LlmError::Unknown(e.to_string()))?;
-            let normalized = mean
-                .broadcast_div(&norm)
-                .map_err(|e| LlmError::Unknown(e.to_string()))?;
-            let vec = normalized
-                .squeeze(0)
-                .map_err(|e| LlmError::Unknown(e.to_string()))?
-                .to_vec1::<f32>()
-                .map_err(|e| LlmError::Unknown(e.to_string()))?;
-            results.push(vec);
-        }
-        Ok(results)
+        embed_padded(token_ids, device, self.pooling, |input_ids, mask| {
+            // Single-segment inputs: every token type id is 0.
+            self.model.forward(input_ids, &input_ids.zeros_like()?, Some(mask))
+        })
     }
 }
@@ -0,0 +1,150 @@
+//! Loops shared by the architecture backends.
+
+use candle_core::{Device, Tensor};
+use edgequake_llm::LlmError;
+use std::path::Path;
+use std::sync::OnceLock;
+use tokenizers::Tokenizer;
+
+use crate::model::candle::backend::{GenerateOptions, Pooling};
+use crate::model::candle::constrained::{self, ConstrainedDecoder};
+use crate::model::candle::registry::GenerationConfig;
+
+pub(crate) fn err(e: impl std::fmt::Display) -> LlmError {
+    LlmError::Unknown(e.to_string())
+}
+
+/// Autoregressive decoding shared by every generation backend.
+///
+/// `forward(ids, seqlen_offset)` runs the model on `ids` (the whole prompt on
+/// the first call, then one token at a time) and returns the logits for the
+/// last position. Backends reset their KV cache before calling this.
+pub(crate) fn run_generation(
+    prompt: &str,
+    options: &GenerateOptions,
+    gen_config: &GenerationConfig,
+    token_texts: &OnceLock<Vec<String>>,
+    tokenizer: &Tokenizer,
+    mut forward: impl FnMut(&[u32], usize) -> candle_core::Result<Tensor>,
+) -> Result<String, LlmError> {
+    let options = gen_config.resolve_options(options);
+    let eos_tokens = gen_config.eos_token_ids(tokenizer);
+    let max_new_tokens = options.max_new_tokens.unwrap_or(gen_config.max_new_tokens);
+
+    let encoding = tokenizer.encode(prompt, true).map_err(err)?;
+    let mut input: Vec<u32> = encoding.get_ids().to_vec();
+    let mut offset = 0;
+    let mut generated: Vec<u32> = Vec::new();
+    let mut logits_processor = options.logits_processor();
+    let mut constraint = if gen_config.constrained_decoding {
+        constrained::decoder_for_prompt(prompt, token_texts, tokenizer)
+    } else {
+        None
+    };
+
+    for _ in 0..max_new_tokens {
+        let logits = forward(&input, offset)
+            .and_then(|l| l.flatten_all())
+            .map_err(err)?;
+        let token = constrained::sample(&mut logits_processor, &logits, constraint.as_ref())
+            .map_err(err)?;
+        if eos_tokens.contains(&token) {
+            break;
+        }
+        generated.push(token);
+        if should_stop(&generated, &options, constraint.as_mut(), tokenizer)? {
+            break;
+        }
+        offset += input.len();
+        input = vec![token];
+    }
+
+    let output = tokenizer.decode(&generated, true).map_err(err)?;
+    let output = options.truncate_at_stop(output);
+    Ok(match &constraint {
+        Some(c) => c.finish(output),
+        None => output,
+    })
+}
+
+/// Feed the tokens generated so far to the JSON constraint (if any) and
+/// report whether generation should stop: the constrained value is complete
+/// or a stop string has appeared.
+fn should_stop(
+    generated: &[u32],
+    options: &GenerateOptions,
+    constraint: Option<&mut ConstrainedDecoder>,
+    tokenizer: &Tokenizer,
+) -> Result<bool, LlmError> {
+    if constraint.is_none() && options.stop.is_empty() {
+        return Ok(false);
+    }
+    let text = tokenizer.decode(generated, true).map_err(err)?;
+    if let Some(c) = constraint {
+        c.advance(&text);
+        if c.is_complete() {
+            return Ok(true);
+        }
+    }
+    Ok(options.stop_position(&text).is_some())
+}
+
+/// Run an encoder over each tokenised sequence and pool the result, for
+/// models whose forward pass takes no attention mask.
+pub(crate) fn embed_each(
+    token_ids: &[Vec<u32>],
+    device: &Device,
+    pooling: Pooling,
+    mut forward: impl FnMut(&Tensor) -> candle_core::Result<Tensor>,
+) -> Result<Vec<Vec<f32>>, LlmError> {
+    token_ids
+        .iter()
+        .map(|ids| {
+            let input_ids = Tensor::new(ids.as_slice(), device)?.unsqueeze(0)?;
+            pooling.apply(&forward(&input_ids)?)
+        })
+        .collect::<candle_core::Result<_>>()
+        .map_err(err)
+}
+
+/// Run an encoder over sequences padded to a common length, with an
+/// attention mask, and pool each sequence over its real tokens only.
+pub(crate) fn embed_padded(
+    token_ids: &[Vec<u32>],
+    device: &Device,
+    pooling: Pooling,
+    forward: impl FnOnce(&Tensor, &Tensor) -> candle_core::Result<Tensor>,
+) -> Result<Vec<Vec<f32>>, LlmError> {
+    if token_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let max_len = token_ids.iter().map(Vec::len).max().unwrap_or(0).max(1);
+    let mut ids = Vec::with_capacity(token_ids.len() * max_len);
+    let mut mask = Vec::with_capacity(token_ids.len() * max_len);
+    for seq in token_ids {
+        ids.extend_from_slice(seq);
+        ids.extend(std::iter::repeat_n(0u32, max_len - seq.len()));
+        mask.extend(std::iter::repeat_n(1u32, seq.len()));
+        mask.extend(std::iter::repeat_n(0u32, max_len - seq.len()));
+    }
+    (|| {
+        let shape = (token_ids.len(), max_len);
+        let ids = Tensor::from_vec(ids, shape, device)?;
+        let mask = Tensor::from_vec(mask, shape, device)?;
+        pooling.apply_masked(&forward(&ids, &mask)?, &mask)
+    })()
+    .map_err(err)
+}
+
+/// Load every safetensors file of a model into one tensor map: either
+/// `model.safetensors` or the shards listed in `model.safetensors.index.json`.
+pub(crate) fn load_tensors(
+    model_dir: &Path,
+    device: &Device,
+) -> anyhow::Result<std::collections::HashMap<String, Tensor>> {
+    let mut tensors = std::collections::HashMap::new();
+    for file in crate::model::candle::loader::weight_files(model_dir)? {
+        tensors.extend(candle_core::safetensors::load(&file, device)?);
+    }
+    Ok(tensors)
+}
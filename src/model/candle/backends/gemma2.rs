@@ -0,0 +1,78 @@
+use async_trait::async_trait;
+use anyhow::Result;
+use candle_core::{DType, Device, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::models::gemma2::{Model as Gemma2Model, Config as Gemma2Config};
+use edgequake_llm::LlmError;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use tokenizers::Tokenizer;
+
+use crate::model::candle::backend::{GenerateOptions, ModelBackend, ModelRole};
+use crate::model::candle::backends::common::run_generation;
+use crate::model::candle::loader::weight_files;
+use crate::model::candle::registry::GenerationConfig;
+
+pub struct Gemma2Backend {
+    model: Mutex<Gemma2Model>,
+    gen_config: GenerationConfig,
+    token_texts: OnceLock<Vec<String>>,
+}
+
+impl Gemma2Backend {
+    pub fn load(
+        model_dir: &Path,
+        device: &Device,
+        dtype: DType,
+        gen_config: GenerationConfig,
+    ) -> Result<Self> {
+        let config_str = std::fs::read_to_string(model_dir.join("config.json"))?;
+        let config: Gemma2Config = serde_json::from_str(&config_str)?;
+        let vb = unsafe {
+            VarBuilder::from_mmaped_safetensors(
+                &weight_files(model_dir)?,
+                dtype,
+                device,
+            )?
+        };
+        let model = Gemma2Model::new(false, &config, vb)?;
+        Ok(Self {
+            model: Mutex::new(model),
+            gen_config,
+            token_texts: OnceLock::new(),
+        })
+    }
+}
+
+#[async_trait]
+impl ModelBackend for Gemma2Backend {
+    fn role(&self) -> ModelRole {
+        ModelRole::Generation
+    }
+
+    async fn generate(
+        &self,
+        prompt: &str,
+        options: &GenerateOptions,
+        tokenizer: &Tokenizer,
+        device: &Device,
+    ) -> Result<String, LlmError> {
+        let mut model = self.model.lock().unwrap();
+        model.clear_kv_cache();
+        run_generation(prompt, options, &self.gen_config, &self.token_texts, tokenizer, |ids, offset| {
+            let input = Tensor::new(ids, device)?.unsqueeze(0)?;
+            model.forward(&input, offset)
+        })
+    }
+
+    async fn embed_batch(
+        &self,
+        _token_ids: &[Vec<u32>],
+        _type_ids: &[Vec<u32>],
+        _device: &Device,
+    ) -> Result<Vec<Vec<f32>>, LlmError> {
+        Err(LlmError::Unknown(
+            "Gemma2Backend does not support embedding".to_string(),
+        ))
+    }
+}
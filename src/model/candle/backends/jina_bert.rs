@@ -0,0 +1,73 @@
+use async_trait::async_trait;
+use anyhow::Result;
+use candle_core::{DType, Device, Module};
+use candle_nn::VarBuilder;
+use candle_transformers::models::jina_bert::{BertModel, Config as JinaConfig};
+use edgequake_llm::LlmError;
+use std::path::Path;
+use tokenizers::Tokenizer;
+
+use crate::model::candle::backend::{GenerateOptions, ModelBackend, ModelRole, Pooling};
+use crate::model::candle::backends::common::{embed_each, load_tensors};
+
+/// Jina embeddings v2 (BERT with ALiBi attention, up to 8k tokens).
+pub struct JinaBertBackend {
+    model: BertModel,
+    dimension: usize,
+    max_positions: usize,
+    pooling: Pooling,
+}
+
+impl JinaBertBackend {
+    pub fn load(model_dir: &Path, device: &Device, pooling: Pooling) -> Result<Self> {
+        let config_str = std::fs::read_to_string(model_dir.join("config.json"))?;
+        let config: JinaConfig = serde_json::from_str(&config_str)?;
+        let tensors = load_tensors(model_dir, device)?
+            .into_iter()
+            .map(|(name, t)| (name.strip_prefix("bert.").unwrap_or(&name).to_string(), t))
+            .collect();
+        let vb = VarBuilder::from_tensors(tensors, DType::F32, device);
+        let model = BertModel::new(vb, &config)?;
+        Ok(Self {
+            model,
+            dimension: config.hidden_size,
+            max_positions: config.max_position_embeddings,
+            pooling,
+        })
+    }
+}
+
+#[async_trait]
+impl ModelBackend for JinaBertBackend {
+    fn role(&self) -> ModelRole {
+        ModelRole::Embedding
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+    fn max_positions(&self) -> Option<usize> {
+        Some(self.max_positions)
+    }
+
+    async fn generate(
+        &self,
+        _prompt: &str,
+        _options: &GenerateOptions,
+        _tokenizer: &Tokenizer,
+        _device: &Device,
+    ) -> Result<String, LlmError> {
+        Err(LlmError::Unknown(
+            "JinaBertBackend does not support text generation".to_string(),
+        ))
+    }
+
+    async fn embed_batch(
+        &self,
+        token_ids: &[Vec<u32>],
+        _type_ids: &[Vec<u32>],
+        device: &Device,
+    ) -> Result<Vec<Vec<f32>>, LlmError> {
+        embed_each(token_ids, device, self.pooling, |input_ids| self.model.forward(input_ids))
+    }
+}
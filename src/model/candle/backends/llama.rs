@@ -0,0 +1,80 @@
+use async_trait::async_trait;
+use anyhow::Result;
+use candle_core::{DType, Device, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::models::llama::{Cache, Config as LlamaRuntimeConfig, Llama, LlamaConfig};
+use edgequake_llm::LlmError;
+use std::path::Path;
+use std::sync::OnceLock;
+use tokenizers::Tokenizer;
+
+use crate::model::candle::backend::{GenerateOptions, ModelBackend, ModelRole};
+use crate::model::candle::backends::common::{err, run_generation};
+use crate::model::candle::loader::weight_files;
+use crate::model::candle::registry::GenerationConfig;
+
+/// Llama 2/3 family (including Llama 3.2 1B/3B and SmolLM).
+pub struct LlamaBackend {
+    model: Llama,
+    config: LlamaRuntimeConfig,
+    dtype: DType,
+    gen_config: GenerationConfig,
+    token_texts: OnceLock<Vec<String>>,
+}
+
+impl LlamaBackend {
+    pub fn load(
+        model_dir: &Path,
+        device: &Device,
+        dtype: DType,
+        gen_config: GenerationConfig,
+    ) -> Result<Self> {
+        let config_str = std::fs::read_to_string(model_dir.join("config.json"))?;
+        let config: LlamaConfig = serde_json::from_str(&config_str)?;
+        let config = config.into_config(false);
+        let vb = unsafe {
+            VarBuilder::from_mmaped_safetensors(&weight_files(model_dir)?, dtype, device)?
+        };
+        let model = Llama::load(vb, &config)?;
+        Ok(Self {
+            model,
+            config,
+            dtype,
+            gen_config,
+            token_texts: OnceLock::new(),
+        })
+    }
+}
+
+#[async_trait]
+impl ModelBackend for LlamaBackend {
+    fn role(&self) -> ModelRole {
+        ModelRole::Generation
+    }
+
+    async fn generate(
+        &self,
+        prompt: &str,
+        options: &GenerateOptions,
+        tokenizer: &Tokenizer,
+        device: &Device,
+    ) -> Result<String, LlmError> {
+        // The KV cache lives outside the model, so each call gets a fresh one.
+        let mut cache = Cache::new(true, self.dtype, &self.config, device).map_err(err)?;
+        run_generation(prompt, options, &self.gen_config, &self.token_texts, tokenizer, |ids, offset| {
+            let input = Tensor::new(ids, device)?.unsqueeze(0)?;
+            self.model.forward(&input, offset, &mut cache)
+        })
+    }
+
+    async fn embed_batch(
+        &self,
+        _token_ids: &[Vec<u32>],
+        _type_ids: &[Vec<u32>],
+        _device: &Device,
+    ) -> Result<Vec<Vec<f32>>, LlmError> {
+        Err(LlmError::Unknown(
+            "LlamaBackend does not support embedding".to_string(),
+        ))
+    }
+}
@@ -0,0 +1,78 @@
+use async_trait::async_trait;
+use anyhow::Result;
+use candle_core::{DType, Device, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::models::mistral::{Model as MistralModel, Config as MistralConfig};
+use edgequake_llm::LlmError;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use tokenizers::Tokenizer;
+
+use crate::model::candle::backend::{GenerateOptions, ModelBackend, ModelRole};
+use crate::model::candle::backends::common::run_generation;
+use crate::model::candle::loader::weight_files;
+use crate::model::candle::registry::GenerationConfig;
+
+pub struct MistralBackend {
+    model: Mutex<MistralModel>,
+    gen_config: GenerationConfig,
+    token_texts: OnceLock<Vec<String>>,
+}
+
+impl MistralBackend {
+    pub fn load(
+        model_dir: &Path,
+        device: &Device,
+        dtype: DType,
+        gen_config: GenerationConfig,
+    ) -> Result<Self> {
+        let config_str = std::fs::read_to_string(model_dir.join("config.json"))?;
+        let config: MistralConfig = serde_json::from_str(&config_str)?;
+        let vb = unsafe {
+            VarBuilder::from_mmaped_safetensors(
+                &weight_files(model_dir)?,
+                dtype,
+                device,
+            )?
+        };
+        let model = MistralModel::new(&config, vb)?;
+        Ok(Self {
+            model: Mutex::new(model),
+            gen_config,
+            token_texts: OnceLock::new(),
+        })
+    }
+}
+
+#[async_trait]
+impl ModelBackend for MistralBackend {
+    fn role(&self) -> ModelRole {
+        ModelRole::Generation
+    }
+
+    async fn generate(
+        &self,
+        prompt: &str,
+        options: &GenerateOptions,
+        tokenizer: &Tokenizer,
+        device: &Device,
+    ) -> Result<String, LlmError> {
+        let mut model = self.model.lock().unwrap();
+        model.clear_kv_cache();
+        run_generation(prompt, options, &self.gen_config, &self.token_texts, tokenizer, |ids, offset| {
+            let input = Tensor::new(ids, device)?.unsqueeze(0)?;
+            model.forward(&input, offset)
+        })
+    }
+
+    async fn embed_batch(
+        &self,
+        _token_ids: &[Vec<u32>],
+        _type_ids: &[Vec<u32>],
+        _device: &Device,
+    ) -> Result<Vec<Vec<f32>>, LlmError> {
+        Err(LlmError::Unknown(
+            "MistralBackend does not support embedding".to_string(),
+        ))
+    }
+}
@@ -1,7 +1,20 @@
 pub mod bert;
+pub(crate) mod common;
+pub mod gemma2;
+pub mod jina_bert;
+pub mod llama;
+pub mod mistral;
+pub mod modernbert;
 pub mod phi3;
+pub mod quantized;
 pub mod qwen2;
 
 pub use bert::BertBackend;
+pub use gemma2::Gemma2Backend;
+pub use jina_bert::JinaBertBackend;
+pub use llama::LlamaBackend;
+pub use mistral::MistralBackend;
+pub use modernbert::ModernBertBackend;
 pub use phi3::Phi3Backend;
+pub use quantized::QuantizedBackend;
 pub use qwen2::Qwen2Backend;
@@ -0,0 +1,79 @@
+use async_trait::async_trait;
+use anyhow::Result;
+use candle_core::{DType, Device};
+use candle_nn::VarBuilder;
+use candle_transformers::models::modernbert::{Config as ModernBertConfig, ModernBert};
+use edgequake_llm::LlmError;
+use std::path::Path;
+use tokenizers::Tokenizer;
+
+use crate::model::candle::backend::{GenerateOptions, ModelBackend, ModelRole, Pooling};
+use crate::model::candle::backends::common::{embed_padded, load_tensors};
+
+/// ModernBERT encoders (nomic modernbert-embed, gte-modernbert).
+pub struct ModernBertBackend {
+    model: ModernBert,
+    dimension: usize,
+    max_positions: usize,
+    pooling: Pooling,
+}
+
+impl ModernBertBackend {
+    pub fn load(model_dir: &Path, device: &Device, pooling: Pooling) -> Result<Self> {
+        let config_str = std::fs::read_to_string(model_dir.join("config.json"))?;
+        let config: ModernBertConfig = serde_json::from_str(&config_str)?;
+        // Sentence-transformers exports drop the `model.` prefix candle expects.
+        let tensors = load_tensors(model_dir, device)?
+            .into_iter()
+            .map(|(name, t)| match name.starts_with("model.") || name.starts_with("head.") {
+                true => (name, t),
+                false => (format!("model.{}", name), t),
+            })
+            .collect();
+        let vb = VarBuilder::from_tensors(tensors, DType::F32, device);
+        let model = ModernBert::load(vb, &config)?;
+        Ok(Self {
+            model,
+            dimension: config.hidden_size,
+            max_positions: config.max_position_embeddings,
+            pooling,
+        })
+    }
+}
+
+#[async_trait]
+impl ModelBackend for ModernBertBackend {
+    fn role(&self) -> ModelRole {
+        ModelRole::Embedding
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+    fn max_positions(&self) -> Option<usize> {
+        Some(self.max_positions)
+    }
+
+    async fn generate(
+        &self,
+        _prompt: &str,
+        _options: &GenerateOptions,
+        _tokenizer: &Tokenizer,
+        _device: &Device,
+    ) -> Result<String, LlmError> {
+        Err(LlmError::Unknown(
+            "ModernBertBackend does not support text generation".to_string(),
+        ))
+    }
+
+    async fn embed_batch(
+        &self,
+        token_ids: &[Vec<u32>],
+        _type_ids: &[Vec<u32>],
+        device: &Device,
+    ) -> Result<Vec<Vec<f32>>, LlmError> {
+        embed_padded(token_ids, device, self.pooling, |input_ids, mask| {
+            self.model.forward(input_ids, mask)
+        })
+    }
+}
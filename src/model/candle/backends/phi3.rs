@@ -1,30 +1,37 @@
 use async_trait::async_trait;
 use anyhow::Result;
-use candle_core::{Device, Tensor};
+use candle_core::{DType, Device, Tensor};
 use candle_nn::VarBuilder;
 use candle_transformers::models::phi3::{Model as Phi3Model, Config as Phi3Config};
-use candle_transformers::generation::LogitsProcessor;
 use edgequake_llm::LlmError;
 use std::path::Path;
-use std::sync::Mutex;
+use std::sync::{Mutex, OnceLock};
 use tokenizers::Tokenizer;
 
-use crate::model::candle::backend::{ModelBackend, ModelRole};
+use crate::model::candle::backend::{GenerateOptions, ModelBackend, ModelRole};
+use crate::model::candle::backends::common::run_generation;
+use crate::model::candle::loader::weight_files;
 use crate::model::candle::registry::GenerationConfig;
 
 pub struct Phi3Backend {
     model: Mutex<Phi3Model>,
     gen_config: GenerationConfig,
+    token_texts: OnceLock<Vec<String>>,
 }
 
 impl Phi3Backend {
-    pub fn load(model_dir: &Path, device: &Device, gen_config: GenerationConfig) -> Result<Self> {
+    pub fn load(
+        model_dir: &Path,
+        device: &Device,
+        dtype: DType,
+        gen_config: GenerationConfig,
+    ) -> Result<Self> {
         let config_str = std::fs::read_to_string(model_dir.join("config.json"))?;
         let config: Phi3Config = serde_json::from_str(&config_str)?;
         let vb = unsafe {
             VarBuilder::from_mmaped_safetensors(
-                &[model_dir.join("model.safetensors")],
-                candle_core::DType::F32,
+                &weight_files(model_dir)?,
+                dtype,
                 device,
             )?
         };
@@ -32,6 +39,7 @@ impl Phi3Backend {
         Ok(Self {
             model: Mutex::new(model),
             gen_config,
+            token_texts: OnceLock::new(),
         })
     }
 }
@@ -45,51 +53,16 @@ impl ModelBackend for Phi3Backend {
     async fn generate(
         &self,
         prompt: &str,
+        options: &GenerateOptions,
         tokenizer: &Tokenizer,
         device: &Device,
     ) -> Result<String, LlmError> {
-        let vocab = tokenizer.get_vocab(true);
-        let eos_token = self
-            .gen_config
-            .eos_tokens
-            .iter()
-            .find_map(|t| vocab.get(t.as_str()).cloned())
-            .unwrap_or(self.gen_config.eos_fallback_id);
-
-        let tokens = tokenizer
-            .encode(prompt, true)
-            .map_err(|e| LlmError::Unknown(e.to_string()))?;
-        let mut token_ids = tokens.get_ids().to_vec();
-        let mut generated = Vec::new();
-        let mut logits_processor = LogitsProcessor::new(42, None, None);
-
         let mut model = self.model.lock().unwrap();
-        for _ in 0..self.gen_config.max_new_tokens {
-            let input = Tensor::new(token_ids.as_slice(), device)
-                .map_err(|e| LlmError::Unknown(e.to_string()))?
-                .unsqueeze(0)
-                .map_err(|e| LlmError::Unknown(e.to_string()))?;
-            // seqlen_offset for Phi3: offset into the sequence for KV-cache.
-            let offset = token_ids.len() - generated.len();
-            let logits = model
-                .forward(&input, offset)
-                .map_err(|e| LlmError::Unknown(e.to_string()))?;
-            let logits = logits
-                .squeeze(0)
-                .map_err(|e| LlmError::Unknown(e.to_string()))?;
-            let token = logits_processor
-                .sample(&logits)
-                .map_err(|e| LlmError::Unknown(e.to_string()))?;
-            if token == eos_token {
-                break;
-            }
-            generated.push(token);
-            token_ids.push(token);
-        }
-
-        tokenizer
-            .decode(&generated, true)
-            .map_err(|e| LlmError::Unknown(e.to_string()))
+        model.clear_kv_cache();
+        run_generation(prompt, options, &self.gen_config, &self.token_texts, tokenizer, |ids, offset| {
+            let input = Tensor::new(ids, device)?.unsqueeze(0)?;
+            model.forward(&input, offset)
+        })
     }
 
     async fn embed_batch(
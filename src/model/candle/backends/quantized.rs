@@ -0,0 +1,110 @@
+use async_trait::async_trait;
+use anyhow::Result;
+use candle_core::quantized::gguf_file;
+use candle_core::{Device, Tensor};
+use candle_transformers::models::{quantized_llama, quantized_phi3, quantized_qwen2};
+use edgequake_llm::LlmError;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use tokenizers::Tokenizer;
+
+use crate::model::candle::backend::{GenerateOptions, ModelBackend, ModelRole};
+use crate::model::candle::backends::common::run_generation;
+use crate::model::candle::registry::{ArchKind, GenerationConfig};
+
+enum QuantizedModel {
+    /// Llama and Mistral share the `llama` GGUF layout.
+    Llama(quantized_llama::ModelWeights),
+    Qwen2(quantized_qwen2::ModelWeights),
+    Phi3(quantized_phi3::ModelWeights),
+}
+
+impl QuantizedModel {
+    /// Every quantized model resets its KV cache when `offset` is 0.
+    fn forward(&mut self, input: &Tensor, offset: usize) -> candle_core::Result<Tensor> {
+        match self {
+            QuantizedModel::Llama(m) => m.forward(input, offset),
+            QuantizedModel::Qwen2(m) => m.forward(input, offset),
+            QuantizedModel::Phi3(m) => m.forward(input, offset),
+        }
+    }
+}
+
+/// GGUF-quantized generation models (Llama, Mistral, Qwen2, Phi3).
+pub struct QuantizedBackend {
+    model: Mutex<QuantizedModel>,
+    gen_config: GenerationConfig,
+    token_texts: OnceLock<Vec<String>>,
+}
+
+impl QuantizedBackend {
+    /// Kinds with a quantized implementation.
+    pub const SUPPORTED: [ArchKind; 4] =
+        [ArchKind::Llama, ArchKind::Mistral, ArchKind::Qwen2, ArchKind::Phi3];
+
+    pub fn load(
+        gguf_path: &Path,
+        kind: ArchKind,
+        device: &Device,
+        gen_config: GenerationConfig,
+    ) -> Result<Self> {
+        if !Self::SUPPORTED.contains(&kind) {
+            anyhow::bail!(
+                "GGUF weights are not supported for '{}' models (supported: llama, mistral, qwen2, phi3)",
+                kind.as_str()
+            );
+        }
+        let mut file = std::fs::File::open(gguf_path)
+            .map_err(|e| anyhow::anyhow!("{}: {}", gguf_path.display(), e))?;
+        let content = gguf_file::Content::read(&mut file)
+            .map_err(|e| anyhow::anyhow!("{}: {}", gguf_path.display(), e))?;
+        let model = match kind {
+            ArchKind::Qwen2 => QuantizedModel::Qwen2(quantized_qwen2::ModelWeights::from_gguf(
+                content, &mut file, device,
+            )?),
+            ArchKind::Phi3 => QuantizedModel::Phi3(quantized_phi3::ModelWeights::from_gguf(
+                false, content, &mut file, device,
+            )?),
+            _ => QuantizedModel::Llama(quantized_llama::ModelWeights::from_gguf(
+                content, &mut file, device,
+            )?),
+        };
+        Ok(Self {
+            model: Mutex::new(model),
+            gen_config,
+            token_texts: OnceLock::new(),
+        })
+    }
+}
+
+#[async_trait]
+impl ModelBackend for QuantizedBackend {
+    fn role(&self) -> ModelRole {
+        ModelRole::Generation
+    }
+
+    async fn generate(
+        &self,
+        prompt: &str,
+        options: &GenerateOptions,
+        tokenizer: &Tokenizer,
+        device: &Device,
+    ) -> Result<String, LlmError> {
+        let mut model = self.model.lock().unwrap();
+        run_generation(prompt, options, &self.gen_config, &self.token_texts, tokenizer, |ids, offset| {
+            let input = Tensor::new(ids, device)?.unsqueeze(0)?;
+            model.forward(&input, offset)
+        })
+    }
+
+    async fn embed_batch(
+        &self,
+        _token_ids: &[Vec<u32>],
+        _type_ids: &[Vec<u32>],
+        _device: &Device,
+    ) -> Result<Vec<Vec<f32>>, LlmError> {
+        Err(LlmError::Unknown(
+            "QuantizedBackend does not support embedding".to_string(),
+        ))
+    }
+}
@@ -0,0 +1,571 @@
+//! JSON-schema constrained decoding for the Candle generation backends.
+//!
+//! NuExtract-style prompts embed a JSON skeleton after a `Template:` header
+//! (see the `prompt_templates` in `models.yaml`). That skeleton is parsed into
+//! a [`Schema`], and a character-level automaton ([`JsonState`]) tracks the
+//! output generated so far. At every decoding step tokens whose text would
+//! leave the schema are masked out of the logits, and once the root value is
+//! closed generation stops. If `max_new_tokens` runs out first, the minimal
+//! completion that closes every open string/array/object is appended, so the
+//! result always parses.
+
+use candle_core::Tensor;
+use candle_transformers::generation::LogitsProcessor;
+use std::sync::{Arc, OnceLock};
+use tokenizers::Tokenizer;
+
+/// Longest run of whitespace accepted between JSON tokens.
+const MAX_WHITESPACE_RUN: usize = 32;
+/// Allowed tokens kept per step; the rest of the vocabulary is masked.
+const MAX_CANDIDATES: usize = 32;
+
+/// Shape of the expected output, derived from a NuExtract template.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schema {
+    /// Object with exactly these keys, in this order.
+    Object(Vec<(String, Schema)>),
+    Array(Box<Schema>),
+    String,
+    /// A string restricted to one of the listed values.
+    Enum(Vec<String>),
+    Number,
+    Bool,
+}
+
+impl Schema {
+    /// Parse the template skeleton following the first `Template:` header in
+    /// `prompt`. Returns `None` when the prompt carries no template.
+    pub fn from_prompt(prompt: &str) -> Option<Schema> {
+        let header = prompt.find("Template:")?;
+        let rest = &prompt[header..];
+        let start = rest.find('{')?;
+        let mut parser = TemplateParser { chars: rest[start..].chars().collect(), pos: 0 };
+        parser.value()
+    }
+
+    /// Map a NuExtract leaf type name onto a schema.
+    fn leaf(type_name: &str) -> Schema {
+        match type_name {
+            "integer" | "number" => Schema::Number,
+            "boolean" => Schema::Bool,
+            _ => Schema::String,
+        }
+    }
+
+    fn is_type_name(value: &str) -> bool {
+        matches!(
+            value,
+            "string" | "verbatim-string" | "integer" | "number" | "boolean" | "date-time"
+        )
+    }
+
+    /// Shortest valid JSON text for this schema.
+    fn default_text(&self) -> String {
+        match self {
+            Schema::Object(fields) => {
+                let body: Vec<String> = fields
+                    .iter()
+                    .map(|(k, v)| format!("{}:{}", quote(k), v.default_text()))
+                    .collect();
+                format!("{{{}}}", body.join(","))
+            }
+            Schema::Array(_) => "[]".to_string(),
+            _ => "null".to_string(),
+        }
+    }
+}
+
+fn quote(s: &str) -> String {
+    serde_json::to_string(s).unwrap_or_else(|_| format!("\"{}\"", s))
+}
+
+/// Minimal JSON parser that keeps object keys in template order.
+struct TemplateParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl TemplateParser {
+    fn skip_ws(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn eat(&mut self, c: char) -> Option<()> {
+        self.skip_ws();
+        (self.chars.get(self.pos) == Some(&c)).then(|| self.pos += 1)
+    }
+
+    fn string(&mut self) -> Option<String> {
+        self.eat('"')?;
+        let mut out = String::new();
+        loop {
+            let c = *self.chars.get(self.pos)?;
+            self.pos += 1;
+            match c {
+                '"' => return Some(out),
+                '\\' => {
+                    out.push(*self.chars.get(self.pos)?);
+                    self.pos += 1;
+                }
+                _ => out.push(c),
+            }
+        }
+    }
+
+    fn value(&mut self) -> Option<Schema> {
+        self.skip_ws();
+        match self.chars.get(self.pos)? {
+            '{' => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                if self.eat('}').is_some() {
+                    return Some(Schema::Object(fields));
+                }
+                loop {
+                    let key = self.string()?;
+                    self.eat(':')?;
+                    fields.push((key, self.value()?));
+                    if self.eat(',').is_none() {
+                        self.eat('}')?;
+                        return Some(Schema::Object(fields));
+                    }
+                }
+            }
+            '[' => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                if self.eat(']').is_none() {
+                    loop {
+                        self.skip_ws();
+                        if self.chars.get(self.pos) == Some(&'"') {
+                            items.push(Err(self.string()?));
+                        } else {
+                            items.push(Ok(self.value()?));
+                        }
+                        if self.eat(',').is_none() {
+                            self.eat(']')?;
+                            break;
+                        }
+                    }
+                }
+                // `["string"]` is a list of a leaf type, `[{...}]` a list of
+                // objects, and `["A", "B"]` a choice between literal values.
+                match items.as_slice() {
+                    [] => Some(Schema::Array(Box::new(Schema::String))),
+                    [Ok(item)] => Some(Schema::Array(Box::new(item.clone()))),
+                    [Err(name)] if Schema::is_type_name(name) => {
+                        Some(Schema::Array(Box::new(Schema::leaf(name))))
+                    }
+                    _ => items
+                        .into_iter()
+                        .map(|i| i.err())
+                        .collect::<Option<Vec<_>>>()
+                        .map(Schema::Enum),
+                }
+            }
+            '"' => self.string().map(|name| Schema::leaf(&name)),
+            _ => None,
+        }
+    }
+}
+
+// ── Automaton ─────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone)]
+enum Frame {
+    /// Expecting the start of a value.
+    Value(Arc<Schema>),
+    /// Fixed text that must follow (keys, `:`, `null`, `true`, ...).
+    Literal { text: Arc<str>, pos: usize },
+    /// Inside a free string, after the opening quote.
+    Str { escape: Escape },
+    /// Inside an enum string; `typed` is the content so far.
+    EnumStr { options: Arc<Vec<String>>, typed: String },
+    Num { digits: bool, dot: bool, trailing_dot: bool },
+    /// After an object field's value: `,` + next key, or `}` when done.
+    ObjectRest { fields: Arc<Vec<(String, Schema)>>, next: usize },
+    /// Just after `[`: an item or `]`.
+    ArrayStart(Arc<Schema>),
+    /// After an array item: `,` or `]`.
+    ArrayRest(Arc<Schema>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Escape {
+    None,
+    Backslash,
+    /// Hex digits still expected in a `\uXXXX` escape.
+    Unicode(u8),
+}
+
+/// Incremental validator for JSON text against a [`Schema`].
+#[derive(Debug, Clone)]
+pub struct JsonState {
+    stack: Vec<Frame>,
+    whitespace_run: usize,
+}
+
+impl JsonState {
+    pub fn new(schema: &Schema) -> Self {
+        Self { stack: vec![Frame::Value(Arc::new(schema.clone()))], whitespace_run: 0 }
+    }
+
+    /// True once the root value has been closed.
+    pub fn is_complete(&self) -> bool {
+        self.stack.is_empty()
+    }
+
+    /// Feed `text`; returns false (leaving the state unspecified) if any
+    /// character is not allowed at its position.
+    pub fn feed(&mut self, text: &str) -> bool {
+        text.chars().all(|c| self.push(c))
+    }
+
+    fn whitespace(&mut self) -> bool {
+        self.whitespace_run += 1;
+        self.whitespace_run <= MAX_WHITESPACE_RUN
+    }
+
+    fn push(&mut self, c: char) -> bool {
+        let Some(frame) = self.stack.pop() else {
+            return c.is_whitespace() && self.whitespace();
+        };
+        if !c.is_whitespace() {
+            self.whitespace_run = 0;
+        }
+        match frame {
+            Frame::Value(schema) => {
+                if c.is_whitespace() {
+                    self.stack.push(Frame::Value(schema));
+                    return self.whitespace();
+                }
+                self.start_value(&schema, c)
+            }
+            Frame::Literal { text, pos } => {
+                if pos == 0 && c.is_whitespace() {
+                    self.stack.push(Frame::Literal { text, pos });
+                    return self.whitespace();
+                }
+                if text[pos..].starts_with(c) {
+                    let pos = pos + c.len_utf8();
+                    if pos < text.len() {
+                        self.stack.push(Frame::Literal { text, pos });
+                    }
+                    true
+                } else {
+                    false
+                }
+            }
+            Frame::Str { escape } => {
+                let next = match escape {
+                    Escape::Backslash if c == 'u' => Escape::Unicode(4),
+                    Escape::Backslash if "\"\\/bfnrt".contains(c) => Escape::None,
+                    Escape::Backslash => return false,
+                    Escape::Unicode(n) if c.is_ascii_hexdigit() => {
+                        if n > 1 { Escape::Unicode(n - 1) } else { Escape::None }
+                    }
+                    Escape::Unicode(_) => return false,
+                    Escape::None if c == '"' => return true,
+                    Escape::None if c == '\\' => Escape::Backslash,
+                    Escape::None if (c as u32) < 0x20 => return false,
+                    Escape::None => Escape::None,
+                };
+                self.stack.push(Frame::Str { escape: next });
+                true
+            }
+            Frame::EnumStr { options, mut typed } => {
+                if c == '"' {
+                    return options.contains(&typed);
+                }
+                typed.push(c);
+                let ok = options.iter().any(|o| o.starts_with(&typed));
+                self.stack.push(Frame::EnumStr { options, typed });
+                ok
+            }
+            Frame::Num { digits, dot, trailing_dot } => {
+                if c.is_ascii_digit() {
+                    self.stack.push(Frame::Num { digits: true, dot, trailing_dot: false });
+                    true
+                } else if c == '.' && digits && !dot {
+                    self.stack.push(Frame::Num { digits, dot: true, trailing_dot: true });
+                    true
+                } else if digits && !trailing_dot {
+                    // The number ended; this character belongs to the parent.
+                    self.push(c)
+                } else {
+                    false
+                }
+            }
+            Frame::ObjectRest { fields, next } => {
+                if c.is_whitespace() {
+                    self.stack.push(Frame::ObjectRest { fields, next });
+                    return self.whitespace();
+                }
+                if next == fields.len() {
+                    return c == '}';
+                }
+                if c != ',' {
+                    return false;
+                }
+                let (key, value) = &fields[next];
+                let value = Arc::new(value.clone());
+                let key: Arc<str> = Arc::from(quote(key));
+                self.stack.push(Frame::ObjectRest { fields: fields.clone(), next: next + 1 });
+                self.stack.push(Frame::Value(value));
+                self.stack.push(Frame::Literal { text: Arc::from(":"), pos: 0 });
+                self.stack.push(Frame::Literal { text: key, pos: 0 });
+                true
+            }
+            Frame::ArrayStart(item) => {
+                if c.is_whitespace() {
+                    self.stack.push(Frame::ArrayStart(item));
+                    return self.whitespace();
+                }
+                if c == ']' {
+                    return true;
+                }
+                self.stack.push(Frame::ArrayRest(item.clone()));
+                self.start_value(&item, c)
+            }
+            Frame::ArrayRest(item) => {
+                if c.is_whitespace() {
+                    self.stack.push(Frame::ArrayRest(item));
+                    return self.whitespace();
+                }
+                match c {
+                    ']' => true,
+                    ',' => {
+                        self.stack.push(Frame::ArrayRest(item.clone()));
+                        self.stack.push(Frame::Value(item));
+                        true
+                    }
+                    _ => false,
+                }
+            }
+        }
+    }
+
+    /// Handle the first (non-whitespace) character of a value.
+    fn start_value(&mut self, schema: &Schema, c: char) -> bool {
+        let leaf = !matches!(schema, Schema::Object(_) | Schema::Array(_));
+        if leaf && c == 'n' {
+            self.stack.push(Frame::Literal { text: Arc::from("null"), pos: 1 });
+            return true;
+        }
+        match schema {
+            Schema::Object(fields) if c == '{' => {
+                if fields.is_empty() {
+                    self.stack.push(Frame::Literal { text: Arc::from("}"), pos: 0 });
+                    return true;
+                }
+                let fields = Arc::new(fields.clone());
+                let (key, value) = &fields[0];
+                let value = Arc::new(value.clone());
+                let key: Arc<str> = Arc::from(quote(key));
+                self.stack.push(Frame::ObjectRest { fields, next: 1 });
+                self.stack.push(Frame::Value(value));
+                self.stack.push(Frame::Literal { text: Arc::from(":"), pos: 0 });
+                self.stack.push(Frame::Literal { text: key, pos: 0 });
+                true
+            }
+            Schema::Array(item) if c == '[' => {
+                self.stack.push(Frame::ArrayStart(Arc::new((**item).clone())));
+                true
+            }
+            Schema::String if c == '"' => {
+                self.stack.push(Frame::Str { escape: Escape::None });
+                true
+            }
+            Schema::Enum(options) if c == '"' => {
+                self.stack.push(Frame::EnumStr { options: Arc::new(options.clone()), typed: String::new() });
+                true
+            }
+            Schema::Number if c == '-' || c.is_ascii_digit() => {
+                self.stack.push(Frame::Num { digits: c != '-', dot: false, trailing_dot: false });
+                true
+            }
+            Schema::Bool if c == 't' || c == 'f' => {
+                let text = if c == 't' { "true" } else { "false" };
+                self.stack.push(Frame::Literal { text: Arc::from(text), pos: 1 });
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Shortest text that closes every open value.
+    pub fn completion(&self) -> String {
+        let mut out = String::new();
+        for frame in self.stack.iter().rev() {
+            match frame {
+                Frame::Value(schema) => out.push_str(&schema.default_text()),
+                Frame::Literal { text, pos } => out.push_str(&text[*pos..]),
+                Frame::Str { escape } => {
+                    match escape {
+                        Escape::None => {}
+                        Escape::Backslash => out.push('\\'),
+                        Escape::Unicode(n) => out.push_str(&"0".repeat(*n as usize)),
+                    }
+                    out.push('"');
+                }
+                Frame::EnumStr { options, typed } => {
+                    let option = options.iter().find(|o| o.starts_with(typed.as_str()));
+                    out.push_str(option.map(|o| &o[typed.len()..]).unwrap_or(""));
+                    out.push('"');
+                }
+                Frame::Num { digits, trailing_dot, .. } => {
+                    if !digits || *trailing_dot {
+                        out.push('0');
+                    }
+                }
+                Frame::ObjectRest { fields, next } => {
+                    for (key, value) in &fields[*next..] {
+                        out.push_str(&format!(",{}:{}", quote(key), value.default_text()));
+                    }
+                    out.push('}');
+                }
+                Frame::ArrayStart(_) | Frame::ArrayRest(_) => out.push(']'),
+            }
+        }
+        out
+    }
+}
+
+// ── Decoding ──────────────────────────────────────────────────────────────────
+
+/// Decoded text of every vocabulary entry; empty for special tokens.
+pub fn token_texts(tokenizer: &Tokenizer) -> Vec<String> {
+    let size = tokenizer.get_vocab_size(true);
+    (0..size as u32)
+        .map(|id| {
+            let mut text = tokenizer.decode(&[id], true).unwrap_or_default();
+            // SentencePiece decoders drop the leading space of a lone token.
+            if tokenizer.id_to_token(id).is_some_and(|p| p.starts_with('\u{2581}')) && !text.starts_with(' ') {
+                text.insert(0, ' ');
+            }
+            text
+        })
+        .collect()
+}
+
+/// Decoder for `prompt` if it carries a template skeleton. `cache` holds the
+/// vocabulary texts, built on first use and reused across calls.
+pub fn decoder_for_prompt<'a>(
+    prompt: &str,
+    cache: &'a OnceLock<Vec<String>>,
+    tokenizer: &Tokenizer,
+) -> Option<ConstrainedDecoder<'a>> {
+    let schema = Schema::from_prompt(prompt)?;
+    Some(ConstrainedDecoder::new(schema, cache.get_or_init(|| token_texts(tokenizer))))
+}
+
+/// Sample the next token from `logits` (shape `[vocab]`), restricted by
+/// `constraint` when present.
+pub fn sample(
+    processor: &mut LogitsProcessor,
+    logits: &Tensor,
+    constraint: Option<&ConstrainedDecoder>,
+) -> candle_core::Result<u32> {
+    let Some(constraint) = constraint else {
+        return processor.sample(logits);
+    };
+    let mut values = logits.to_dtype(candle_core::DType::F32)?.to_vec1::<f32>()?;
+    constraint.mask_logits(&mut values);
+    let len = values.len();
+    processor.sample(&Tensor::from_vec(values, len, logits.device())?)
+}
+
+/// Masks logits so the generated text stays within a [`Schema`].
+pub struct ConstrainedDecoder<'a> {
+    schema: Schema,
+    state: JsonState,
+    token_texts: &'a [String],
+    decoded: String,
+    /// False once the output left the schema (only possible if masking had
+    /// to be skipped); the output is then returned unchanged.
+    valid: bool,
+}
+
+impl<'a> ConstrainedDecoder<'a> {
+    pub fn new(schema: Schema, token_texts: &'a [String]) -> Self {
+        let state = JsonState::new(&schema);
+        Self { schema, state, token_texts, decoded: String::new(), valid: true }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.valid && self.state.is_complete()
+    }
+
+    /// Set every disallowed token's logit to `-inf`, keeping at most
+    /// `MAX_CANDIDATES` of the highest-scoring allowed tokens. If nothing is
+    /// allowed the logits are left untouched and `false` is returned.
+    pub fn mask_logits(&self, logits: &mut [f32]) -> bool {
+        let by_logit = |a: &usize, b: &usize| logits[*b].total_cmp(&logits[*a]);
+        let mut order: Vec<usize> = (0..logits.len().min(self.token_texts.len())).collect();
+
+        // Rank only as much of the vocabulary as needed: take the best
+        // `window` tokens, sort just those, and widen if too few are allowed.
+        let mut allowed = Vec::with_capacity(MAX_CANDIDATES);
+        let mut start = 0;
+        let mut window = MAX_CANDIDATES * 4;
+        while start < order.len() && allowed.len() < MAX_CANDIDATES {
+            let rest = &mut order[start..];
+            let take = window.min(rest.len());
+            if take < rest.len() {
+                rest.select_nth_unstable_by(take - 1, by_logit);
+            }
+            let best = &mut rest[..take];
+            best.sort_unstable_by(by_logit);
+            for &id in best.iter() {
+                if self.allows(&self.token_texts[id]) {
+                    allowed.push(id);
+                    if allowed.len() == MAX_CANDIDATES {
+                        break;
+                    }
+                }
+            }
+            start += take;
+            window *= 2;
+        }
+        if allowed.is_empty() {
+            return false;
+        }
+        let mut masked = vec![f32::NEG_INFINITY; logits.len()];
+        for id in allowed {
+            masked[id] = logits[id];
+        }
+        logits.copy_from_slice(&masked);
+        true
+    }
+
+    fn allows(&self, text: &str) -> bool {
+        if text.is_empty() {
+            return false;
+        }
+        let mut state = self.state.clone();
+        state.feed(text)
+    }
+
+    /// Advance to the full decoded output so far. Falls back to re-validating
+    /// from scratch when detokenisation rewrote earlier text.
+    pub fn advance(&mut self, decoded: &str) {
+        self.valid = match decoded.strip_prefix(self.decoded.as_str()) {
+            Some(suffix) => self.valid && self.state.feed(suffix),
+            None => {
+                self.state = JsonState::new(&self.schema);
+                self.state.feed(decoded)
+            }
+        };
+        self.decoded = decoded.to_string();
+    }
+
+    /// Close anything left open (e.g. when `max_new_tokens` ran out).
+    pub fn finish(&self, mut output: String) -> String {
+        if self.valid && !self.state.is_complete() {
+            output.push_str(&self.state.completion());
+        }
+        output
+    }
+}
@@ -0,0 +1,303 @@
+//! Downloads from a HuggingFace-compatible hub.
+//!
+//! Files are streamed to `<name>.part` next to their target and renamed into
+//! place only once complete and verified, so an interrupted download never
+//! leaves a truncated model file behind and is resumed on the next attempt.
+
+use anyhow::Result;
+use futures_util::StreamExt;
+use reqwest::header::{AUTHORIZATION, CONTENT_LENGTH, ETAG, LOCATION, RANGE};
+use reqwest::StatusCode;
+use sha2::{Digest, Sha256};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+
+pub const DEFAULT_ENDPOINT: &str = "https://huggingface.co";
+pub const DOWNLOAD_ATTEMPTS: usize = 3;
+/// Relative redirects (e.g. a renamed repository) followed for metadata.
+const MAX_METADATA_REDIRECTS: usize = 5;
+
+/// What the hub reports about a file before it is downloaded.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileMetadata {
+    /// SHA-256 of the content (known for LFS files, which hold the weights).
+    pub sha256: Option<String>,
+    pub size: Option<u64>,
+}
+
+/// Client for one hub endpoint, with optional token and offline mode.
+#[derive(Debug, Clone)]
+pub struct HubClient {
+    endpoint: String,
+    token: Option<String>,
+    offline: bool,
+    client: reqwest::Client,
+    /// Metadata requests must not follow the redirect to the CDN, which
+    /// drops the `X-Linked-*` headers.
+    head_client: reqwest::Client,
+}
+
+impl HubClient {
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        Self {
+            endpoint: endpoint.into().trim_end_matches('/').to_string(),
+            token: None,
+            offline: false,
+            client: reqwest::Client::new(),
+            head_client: reqwest::Client::builder()
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .expect("reqwest client"),
+        }
+    }
+
+    /// Settings from the standard `HF_ENDPOINT`, `HF_TOKEN` (or the token
+    /// saved by `huggingface-cli login`) and `HF_HUB_OFFLINE` variables.
+    pub fn from_env() -> Self {
+        let endpoint = std::env::var("HF_ENDPOINT").unwrap_or_else(|_| DEFAULT_ENDPOINT.to_string());
+        let offline = std::env::var("HF_HUB_OFFLINE")
+            .is_ok_and(|v| matches!(v.to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"));
+        Self::new(endpoint).with_token(env_token()).with_offline(offline)
+    }
+
+    /// [`HubClient::from_env`], also offline when the config sets `offline`.
+    pub fn for_config(config: &Config) -> Self {
+        let hub = Self::from_env();
+        let offline = hub.offline || config.offline;
+        hub.with_offline(offline)
+    }
+
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        self.token = token.filter(|t| !t.is_empty());
+        self
+    }
+
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    pub fn file_url(&self, repo: &str, file: &str) -> String {
+        format!("{}/{}/resolve/main/{}", self.endpoint, repo, file)
+    }
+
+    fn authorized(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.token {
+            Some(token) => request.header(AUTHORIZATION, format!("Bearer {}", token)),
+            None => request,
+        }
+    }
+
+    fn ensure_online(&self, url: &str) -> Result<()> {
+        if self.offline {
+            anyhow::bail!("Offline mode: not fetching {}", url);
+        }
+        Ok(())
+    }
+
+    /// The file's checksum and size, or `None` if the hub does not have it.
+    pub async fn metadata(&self, repo: &str, file: &str) -> Result<Option<FileMetadata>> {
+        let mut url = self.file_url(repo, file);
+        self.ensure_online(&url)?;
+        let mut redirects = 0;
+        let response = loop {
+            let response = self.authorized(self.head_client.head(&url)).send().await?;
+            // Like huggingface_hub, follow relative redirects; an absolute one
+            // points at the CDN and already carries the `X-Linked-*` headers.
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .and_then(|v| v.to_str().ok())
+                .filter(|l| l.starts_with('/'));
+            match location {
+                Some(location)
+                    if response.status().is_redirection()
+                        && !response.headers().contains_key("x-linked-size")
+                        && redirects < MAX_METADATA_REDIRECTS =>
+                {
+                    url = response.url().join(location)?.to_string();
+                    redirects += 1;
+                }
+                _ => break response,
+            }
+        };
+        let status = response.status();
+        if status == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        check_access(status, &url)?;
+        if !status.is_success() && !status.is_redirection() {
+            anyhow::bail!("Failed to fetch metadata for {}: {}", url, status);
+        }
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.trim_start_matches("W/").trim_matches('"').to_string())
+        };
+        // LFS files carry their SHA-256 in `X-Linked-Etag`; a plain `ETag` is
+        // only a checksum when it has the SHA-256 shape.
+        let sha256 = header("x-linked-etag")
+            .or_else(|| header(ETAG.as_str()))
+            .filter(|tag| is_sha256(tag));
+        // A redirect's own `Content-Length` is that of its (empty) body.
+        let size = header("x-linked-size")
+            .or_else(|| header(CONTENT_LENGTH.as_str()).filter(|_| status.is_success()))
+            .and_then(|s| s.parse().ok());
+        Ok(Some(FileMetadata { sha256, size }))
+    }
+
+    /// Download `file` from `repo` to `target`, resuming a previous partial
+    /// download and verifying it against the hub's checksum and size.
+    /// Interrupted transfers are resumed up to [`DOWNLOAD_ATTEMPTS`] times.
+    pub async fn download(&self, repo: &str, file: &str, target: &Path) -> Result<()> {
+        let mut attempt = 1;
+        loop {
+            match self.download_once(repo, file, target).await {
+                // Only a surviving part file means there is something to resume.
+                Err(e) if attempt < DOWNLOAD_ATTEMPTS && part_path(target).exists() => {
+                    eprintln!("  ! {} (resuming, attempt {}/{})", e, attempt + 1, DOWNLOAD_ATTEMPTS);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn download_once(&self, repo: &str, file: &str, target: &Path) -> Result<()> {
+        let url = self.file_url(repo, file);
+        self.ensure_online(&url)?;
+        let metadata = self
+            .metadata(repo, file)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("{} not found on {}", file, self.endpoint))?;
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let part = part_path(target);
+        let mut hasher = Sha256::new();
+        let mut written = existing_prefix(&part, &metadata, &mut hasher)?;
+
+        let mut request = self.authorized(self.client.get(&url));
+        if written > 0 {
+            request = request.header(RANGE, format!("bytes={}-", written));
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        check_access(status, &url)?;
+        let mut out = match status {
+            StatusCode::PARTIAL_CONTENT => OpenOptions::new().append(true).open(&part)?,
+            // The part file already holds everything (or more): re-verify it.
+            StatusCode::RANGE_NOT_SATISFIABLE if written > 0 => OpenOptions::new().append(true).open(&part)?,
+            s if s.is_success() => {
+                // The server ignored the range; start over.
+                hasher = Sha256::new();
+                written = 0;
+                File::create(&part)?
+            }
+            s => anyhow::bail!("Failed to download {}: {}", url, s),
+        };
+        if status != StatusCode::RANGE_NOT_SATISFIABLE {
+            let mut stream = response.bytes_stream();
+            while let Some(chunk) = stream.next().await {
+                let chunk = chunk?;
+                out.write_all(&chunk)?;
+                hasher.update(&chunk);
+                written += chunk.len() as u64;
+            }
+        }
+        out.sync_all()?;
+        drop(out);
+
+        if let Some(size) = metadata.size
+            && written != size
+        {
+            if written > size {
+                std::fs::remove_file(&part)?;
+            }
+            anyhow::bail!("Incomplete download of {}: got {} of {} bytes", url, written, size);
+        }
+        if let Some(expected) = &metadata.sha256 {
+            let actual = hex::encode(hasher.finalize());
+            if !actual.eq_ignore_ascii_case(expected) {
+                std::fs::remove_file(&part)?;
+                anyhow::bail!("Checksum mismatch for {}: expected sha256 {}, got {}", url, expected, actual);
+            }
+        }
+        std::fs::rename(&part, target)?;
+        Ok(())
+    }
+}
+
+/// `target` with `.part` appended to its file name.
+pub fn part_path(target: &Path) -> PathBuf {
+    let mut name = target.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    target.with_file_name(name)
+}
+
+/// Hash the bytes already in `part` and return how many there are, or 0 if
+/// the part file is missing or larger than the file it belongs to.
+fn existing_prefix(part: &Path, metadata: &FileMetadata, hasher: &mut Sha256) -> Result<u64> {
+    let Ok(len) = part.metadata().map(|m| m.len()) else { return Ok(0) };
+    if metadata.size.is_some_and(|size| len > size) {
+        std::fs::remove_file(part)?;
+        return Ok(0);
+    }
+    let mut file = File::open(part)?;
+    let mut buf = vec![0u8; 1 << 20];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(len)
+}
+
+fn check_access(status: StatusCode, url: &str) -> Result<()> {
+    if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+        anyhow::bail!(
+            "Access to {} denied ({}); gated or private models need HF_TOKEN",
+            url,
+            status
+        );
+    }
+    Ok(())
+}
+
+fn is_sha256(tag: &str) -> bool {
+    tag.len() == 64 && tag.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn env_token() -> Option<String> {
+    if let Some(token) = ["HF_TOKEN", "HUGGING_FACE_HUB_TOKEN"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|t| !t.is_empty()))
+    {
+        return Some(token);
+    }
+    let token_file = std::env::var("HF_TOKEN_PATH").map(PathBuf::from).ok().or_else(|| {
+        std::env::var("HF_HOME")
+            .map(|home| PathBuf::from(home).join("token"))
+            .ok()
+            .or_else(|| home::home_dir().map(|h| h.join(".cache/huggingface/token")))
+    })?;
+    std::fs::read_to_string(token_file)
+        .ok()
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+}
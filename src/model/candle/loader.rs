@@ -1,11 +1,35 @@
-use std::fs::File;
-use std::io::Write;
 use std::path::{Path, PathBuf};
 use anyhow::Result;
+use candle_core::DType;
 use indicatif::{ProgressBar, ProgressStyle};
-use futures_util::StreamExt;
+
+use crate::config::{ExtractorConfig, ModelDType};
+use crate::model::candle::hub::HubClient;
 
 const MODEL_FILES: [&str; 3] = ["config.json", "tokenizer.json", "model.safetensors"];
+const WEIGHTS_INDEX: &str = "model.safetensors.index.json";
+
+/// The safetensors files holding a model's weights: `model.safetensors`, or
+/// the shards named in `model.safetensors.index.json` for larger models.
+pub fn weight_files(model_dir: &Path) -> Result<Vec<PathBuf>> {
+    let index_path = model_dir.join(WEIGHTS_INDEX);
+    if !index_path.exists() {
+        return Ok(vec![model_dir.join("model.safetensors")]);
+    }
+    let index: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&index_path)?)?;
+    let weight_map = index
+        .get("weight_map")
+        .and_then(|m| m.as_object())
+        .ok_or_else(|| anyhow::anyhow!("{} has no weight_map", index_path.display()))?;
+    let mut shards: Vec<&str> = weight_map.values().filter_map(|v| v.as_str()).collect();
+    shards.sort_unstable();
+    shards.dedup();
+    Ok(shards.into_iter().map(|f| model_dir.join(f)).collect())
+}
+
+fn non_empty(path: &Path) -> bool {
+    path.exists() && path.metadata().map(|m| m.len() > 0).unwrap_or(false)
+}
 
 /// Returns the local directory path where a model's files are stored.
 pub fn get_model_dir(base_path: &Path, model_name: &str) -> PathBuf {
@@ -13,47 +37,157 @@ pub fn get_model_dir(base_path: &Path, model_name: &str) -> PathBuf {
     base_path.join(safe_name)
 }
 
+/// How a model's weights are stored.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Weights {
+    /// `model.safetensors` (or its shards), loaded at the given precision.
+    Safetensors(DType),
+    /// A single quantized GGUF file downloaded from `repo`.
+    Gguf { repo: String, file: String },
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights::Safetensors(DType::F32)
+    }
+}
+
+impl Weights {
+    /// The weights selected by an extractor's `dtype` / `gguf` settings.
+    pub fn for_extractor(config: &ExtractorConfig) -> Self {
+        match &config.gguf {
+            Some(gguf) => Weights::Gguf {
+                repo: gguf.repo.clone().unwrap_or_else(|| config.name.clone()),
+                file: gguf.file.clone(),
+            },
+            None => Weights::Safetensors(match config.dtype {
+                ModelDType::F32 => DType::F32,
+                ModelDType::F16 => DType::F16,
+                ModelDType::Bf16 => DType::BF16,
+            }),
+        }
+    }
+}
+
+/// Files `weights` needs that are missing (or empty) in `model_dir`, relative
+/// to it. Files are renamed into place only once complete, so presence means
+/// a finished download.
+pub fn missing_files(model_dir: &Path, weights: &Weights) -> Vec<String> {
+    let absent = |f: &str| !non_empty(&model_dir.join(f));
+    match weights {
+        Weights::Gguf { file, .. } => ["tokenizer.json", file.as_str()]
+            .into_iter()
+            .filter(|f| absent(f))
+            .map(String::from)
+            .collect(),
+        Weights::Safetensors(_) => {
+            let mut missing: Vec<String> =
+                MODEL_FILES[..2].iter().filter(|f| absent(f)).map(|f| f.to_string()).collect();
+            if model_dir.join(WEIGHTS_INDEX).exists() {
+                match shard_names(model_dir) {
+                    Ok(shards) => missing.extend(shards.into_iter().filter(|f| absent(f))),
+                    Err(_) => missing.push(WEIGHTS_INDEX.to_string()),
+                }
+            } else if absent("model.safetensors") {
+                missing.push("model.safetensors".to_string());
+            }
+            missing
+        }
+    }
+}
+
+fn shard_names(model_dir: &Path) -> Result<Vec<String>> {
+    Ok(weight_files(model_dir)?
+        .iter()
+        .filter_map(|f| f.strip_prefix(model_dir).ok())
+        .map(|f| f.to_string_lossy().into_owned())
+        .collect())
+}
+
 /// Ensures all required model files are present. Downloads them from
 /// HuggingFace Hub if `auto_download` is true and any file is missing.
 pub async fn ensure_model_files(
     model_name: &str,
     base_path: &Path,
     auto_download: bool,
+) -> Result<PathBuf> {
+    ensure_weights(model_name, base_path, &Weights::default(), auto_download).await
+}
+
+/// Like [`ensure_model_files`], for the files `weights` needs: a GGUF model
+/// only needs the tokenizer and the named GGUF file. The hub is configured
+/// from the environment (see [`HubClient::from_env`]).
+pub async fn ensure_weights(
+    model_name: &str,
+    base_path: &Path,
+    weights: &Weights,
+    auto_download: bool,
+) -> Result<PathBuf> {
+    ensure_weights_from(&HubClient::from_env(), model_name, base_path, weights, auto_download).await
+}
+
+/// Ensure `weights` for `model_name` are present, downloading missing files
+/// from `hub`. Sharded checkpoints are resolved through their index file.
+/// In offline mode nothing is fetched and missing files are an error.
+pub async fn ensure_weights_from(
+    hub: &HubClient,
+    model_name: &str,
+    base_path: &Path,
+    weights: &Weights,
+    auto_download: bool,
 ) -> Result<PathBuf> {
     let model_dir = get_model_dir(base_path, model_name);
-    let is_complete = MODEL_FILES.iter().all(|f| {
-        let p = model_dir.join(f);
-        p.exists() && p.metadata().map(|m| m.len() > 0).unwrap_or(false)
-    });
-    if is_complete {
+    let missing = missing_files(&model_dir, weights);
+    if missing.is_empty() {
         return Ok(model_dir);
     }
+    if hub.is_offline() {
+        anyhow::bail!(
+            "Offline mode: model '{}' is missing {} in {:?}",
+            model_name,
+            missing.join(", "),
+            model_dir
+        );
+    }
     if !auto_download {
         anyhow::bail!("Model files missing or corrupt in {:?}", model_dir);
     }
-    eprintln!("Downloading model '{}'...", model_name);
+    eprintln!("Downloading model '{}' from {}...", model_name, hub.endpoint());
     std::fs::create_dir_all(&model_dir)?;
-    let client = reqwest::Client::new();
-    let base_url = format!("https://huggingface.co/{}/resolve/main", model_name);
-    let pb = ProgressBar::new(MODEL_FILES.len() as u64);
+
+    let mut files: Vec<(&str, String)> = match weights {
+        Weights::Gguf { repo, file } => {
+            vec![(model_name, "tokenizer.json".to_string()), (repo.as_str(), file.clone())]
+        }
+        Weights::Safetensors(_) => {
+            let mut files: Vec<(&str, String)> =
+                MODEL_FILES[..2].iter().map(|f| (model_name, f.to_string())).collect();
+            let index_path = model_dir.join(WEIGHTS_INDEX);
+            let sharded = index_path.exists()
+                || (!non_empty(&model_dir.join("model.safetensors"))
+                    && hub.metadata(model_name, WEIGHTS_INDEX).await?.is_some());
+            if sharded {
+                if !index_path.exists() {
+                    hub.download(model_name, WEIGHTS_INDEX, &index_path).await?;
+                }
+                files.extend(shard_names(&model_dir)?.into_iter().map(|f| (model_name, f)));
+            } else {
+                files.push((model_name, "model.safetensors".to_string()));
+            }
+            files
+        }
+    };
+    files.retain(|(_, f)| !non_empty(&model_dir.join(f)));
+
+    let pb = ProgressBar::new(files.len() as u64);
     pb.set_style(
         ProgressStyle::default_bar()
             .template("{msg} [{bar:40.cyan/blue}] {pos}/{len}")
             .unwrap(),
     );
-    for filename in MODEL_FILES.iter() {
-        let url = format!("{}/{}", base_url, filename);
-        let target_path = model_dir.join(filename);
-        let response = client.get(&url).send().await?;
-        if !response.status().is_success() {
-            anyhow::bail!("Failed to download {}: {}", filename, response.status());
-        }
-        let mut file = File::create(target_path)?;
-        let mut stream = response.bytes_stream();
-        while let Some(item) = stream.next().await {
-            let chunk = item?;
-            file.write_all(&chunk)?;
-        }
+    for (repo, filename) in &files {
+        pb.set_message(filename.clone());
+        hub.download(repo, filename, &model_dir.join(filename)).await?;
         pb.inc(1);
     }
     pb.finish_with_message("Download complete");
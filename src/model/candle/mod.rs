@@ -7,20 +7,32 @@ use edgequake_llm::{
 };
 use tokenizers::Tokenizer;
 
+use crate::model::base::RetrievalEmbedder;
+use crate::model::tokens::{TokenCounter, TokenizerCounter};
+
 pub mod backend;
 pub mod backends;
+pub mod constrained;
+pub mod hub;
 pub mod loader;
 pub mod registry;
 
-pub use loader::{ensure_model_files, get_model_dir, pub_test_missing_files, pub_test_model_exists};
-pub use registry::{ArchConfig, ArchKind, ModelRegistry};
-pub use backend::{ModelBackend, ModelRole};
+pub use hub::HubClient;
+pub use loader::{
+    ensure_model_files, ensure_weights, ensure_weights_from, get_model_dir, missing_files,
+    pub_test_missing_files, pub_test_model_exists, Weights,
+};
+pub use registry::{ArchConfig, ArchKind, ChatTemplate, ModelRegistry};
+pub use backend::{GenerateOptions, ModelBackend, ModelRole, Pooling};
 
-use backends::{BertBackend, Phi3Backend, Qwen2Backend};
+use backends::{
+    BertBackend, Gemma2Backend, JinaBertBackend, LlamaBackend, MistralBackend, ModernBertBackend,
+    Phi3Backend, QuantizedBackend, Qwen2Backend,
+};
 /// A unified local provider backed by the Candle framework.
 ///
-/// Handles both embedding (BERT-family) and generation (Phi3/Qwen2-family)
-/// through a single struct. Construction is complete after `load()` — no
+/// Handles both embedding (BERT, JinaBERT, ModernBERT) and generation (Phi3,
+/// Qwen2, Llama, Mistral, Gemma2) through a single struct. Construction is complete after `load()` — no
 /// separate `prepare()` call is needed.
 pub struct CandleProvider {
     model_name: String,
@@ -28,6 +40,7 @@ pub struct CandleProvider {
     tokenizer: Tokenizer,
     backend: Box<dyn ModelBackend>,
     arch_config: ArchConfig,
+    max_tokens: Option<usize>,
 }
 
 impl CandleProvider {
@@ -42,32 +55,76 @@ impl CandleProvider {
         auto_download: bool,
         registry: &ModelRegistry,
     ) -> Result<Self> {
-        let model_dir = ensure_model_files(model_name, model_path, auto_download).await?;
-        let device = Device::Cpu;
+        Self::load_with_weights(
+            model_name,
+            model_path,
+            auto_download,
+            registry,
+            &Weights::default(),
+            &HubClient::from_env(),
+        )
+        .await
+    }
 
+    /// Like [`CandleProvider::load`], with generation weights at another
+    /// precision or from a quantized GGUF file, fetched from `hub`. Embedding
+    /// models always load F32 safetensors.
+    pub async fn load_with_weights(
+        model_name: &str,
+        model_path: &Path,
+        auto_download: bool,
+        registry: &ModelRegistry,
+        weights: &Weights,
+        hub: &HubClient,
+    ) -> Result<Self> {
         let resolved = registry.resolve(model_name)?;
+        if matches!(resolved.config, ArchConfig::Embedding(_)) && *weights != Weights::default() {
+            anyhow::bail!("'{}' is an embedding model; dtype and gguf only apply to generation models", model_name);
+        }
+        let model_dir =
+            ensure_weights_from(hub, model_name, model_path, weights, auto_download).await?;
+        let device = Device::Cpu;
 
         let tokenizer = Tokenizer::from_file(model_dir.join("tokenizer.json"))
             .map_err(anyhow::Error::msg)?;
 
-        let backend: Box<dyn ModelBackend> = match resolved.arch {
-            ArchKind::Bert => {
-                Box::new(BertBackend::load(&model_dir, &device)?)
+        let dtype = match weights {
+            Weights::Safetensors(dtype) => *dtype,
+            Weights::Gguf { .. } => candle_core::DType::F32,
+        };
+        let backend: Box<dyn ModelBackend> = match (&resolved.config, resolved.arch) {
+            (ArchConfig::Generation(g), kind) if let Weights::Gguf { file, .. } = weights => {
+                Box::new(QuantizedBackend::load(&model_dir.join(file), kind, &device, g.clone())?)
+            }
+            (ArchConfig::Embedding(e), ArchKind::Bert) => {
+                Box::new(BertBackend::load(&model_dir, &device, e.pooling)?)
+            }
+            (ArchConfig::Embedding(e), ArchKind::JinaBert) => {
+                Box::new(JinaBertBackend::load(&model_dir, &device, e.pooling)?)
+            }
+            (ArchConfig::Embedding(e), ArchKind::ModernBert) => {
+                Box::new(ModernBertBackend::load(&model_dir, &device, e.pooling)?)
+            }
+            (ArchConfig::Generation(g), ArchKind::Phi3) => {
+                Box::new(Phi3Backend::load(&model_dir, &device, dtype, g.clone())?)
+            }
+            (ArchConfig::Generation(g), ArchKind::Qwen2) => {
+                Box::new(Qwen2Backend::load(&model_dir, &device, dtype, g.clone())?)
             }
-            ArchKind::Phi3 => {
-                let gen_cfg = match &resolved.config {
-                    ArchConfig::Generation(g) => g.clone(),
-                    _ => anyhow::bail!("Expected generation config for Phi3"),
-                };
-                Box::new(Phi3Backend::load(&model_dir, &device, gen_cfg)?)
+            (ArchConfig::Generation(g), ArchKind::Llama) => {
+                Box::new(LlamaBackend::load(&model_dir, &device, dtype, g.clone())?)
             }
-            ArchKind::Qwen2 => {
-                let gen_cfg = match &resolved.config {
-                    ArchConfig::Generation(g) => g.clone(),
-                    _ => anyhow::bail!("Expected generation config for Qwen2"),
-                };
-                Box::new(Qwen2Backend::load(&model_dir, &device, gen_cfg)?)
+            (ArchConfig::Generation(g), ArchKind::Mistral) => {
+                Box::new(MistralBackend::load(&model_dir, &device, dtype, g.clone())?)
             }
+            (ArchConfig::Generation(g), ArchKind::Gemma2) => {
+                Box::new(Gemma2Backend::load(&model_dir, &device, dtype, g.clone())?)
+            }
+            (_, kind) => anyhow::bail!(
+                "Architecture '{}' ({}) has a role its backend does not support",
+                resolved.arch_name,
+                kind.as_str()
+            ),
         };
 
         Ok(Self {
@@ -76,9 +133,39 @@ impl CandleProvider {
             tokenizer,
             backend,
             arch_config: resolved.config,
+            max_tokens: None,
         })
     }
 
+    /// Replace the registry's query / document prefixes (see
+    /// `ModelConfig::query_prefix`). No-op for generation models.
+    pub fn with_prefixes(mut self, query_prefix: &str, document_prefix: &str) -> Self {
+        if let ArchConfig::Embedding(e) = &mut self.arch_config {
+            e.query_prefix = query_prefix.to_string();
+            e.document_prefix = document_prefix.to_string();
+        }
+        self
+    }
+
+    /// Lower the input limit below the model's position table (see
+    /// `ModelConfig::max_tokens`).
+    pub fn with_max_tokens(mut self, max_tokens: Option<usize>) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    /// Exact token counts for ingestion, with a budget that leaves room for
+    /// special tokens and the document prefix.
+    pub fn token_counter(&self) -> TokenizerCounter {
+        let overhead = |text: &str, special: bool| self.tokenizer.encode(text, special).map(|e| e.len()).unwrap_or(0);
+        let prefix = match &self.arch_config {
+            ArchConfig::Embedding(e) => overhead(&e.document_prefix, false),
+            ArchConfig::Generation(_) => 0,
+        };
+        let budget = EmbeddingProvider::max_tokens(self).saturating_sub(overhead("", true) + prefix);
+        TokenizerCounter::new(self.tokenizer.clone(), budget)
+    }
+
     /// Test-only constructor that accepts a pre-built backend.
     /// Avoids the need for real model files or network access in tests.
     /// Do not use in production code.
@@ -95,6 +182,7 @@ impl CandleProvider {
             tokenizer,
             backend,
             arch_config,
+            max_tokens: None,
         }
     }
 }
@@ -114,20 +202,91 @@ impl LLMProvider for CandleProvider {
     }
 
     async fn complete(&self, prompt: &str) -> Result<LLMResponse, LlmError> {
+        self.complete_with_options(prompt, &CompletionOptions::default())
+            .await
+    }
+
+    /// Sampling options are honoured; a `system_prompt` switches to the
+    /// architecture's chat template with the prompt as the user turn.
+    async fn complete_with_options(
+        &self,
+        prompt: &str,
+        options: &CompletionOptions,
+    ) -> Result<LLMResponse, LlmError> {
+        self.ensure_generation()?;
+        let final_prompt = match (&options.system_prompt, self.chat_template()) {
+            (Some(system), Some(template)) => {
+                template.render(Some(system), &[ChatMessage::user(prompt)])
+            }
+            _ => self
+                .arch_config
+                .format_prompt(prompt)
+                .map_err(|e| LlmError::InvalidRequest(e.to_string()))?,
+        };
+        self.generate_response(&final_prompt, options).await
+    }
+
+    async fn chat(
+        &self,
+        messages: &[ChatMessage],
+        options: Option<&CompletionOptions>,
+    ) -> Result<LLMResponse, LlmError> {
+        self.ensure_generation()?;
+        if messages.iter().any(|m| m.images.as_ref().is_some_and(|images| !images.is_empty())) {
+            return Err(LlmError::Unknown(format!(
+                "Model '{}' cannot read images",
+                self.model_name
+            )));
+        }
+        let template = self.chat_template().ok_or_else(|| {
+            LlmError::Unknown(format!(
+                "Model '{}' has no chat_template in models.yaml",
+                self.model_name
+            ))
+        })?;
+        let default_options = CompletionOptions::default();
+        let options = options.unwrap_or(&default_options);
+        let prompt = template.render(options.system_prompt.as_deref(), messages);
+        self.generate_response(&prompt, options).await
+    }
+}
+
+impl CandleProvider {
+    fn ensure_generation(&self) -> Result<(), LlmError> {
         if self.backend.role() != ModelRole::Generation {
             return Err(LlmError::Unknown(format!(
                 "Model '{}' is an embedding model and cannot generate text",
                 self.model_name
             )));
         }
-        let final_prompt = self.arch_config.format_prompt(prompt);
+        Ok(())
+    }
+
+    fn chat_template(&self) -> Option<&ChatTemplate> {
+        match &self.arch_config {
+            ArchConfig::Generation(g) => g.chat_template.as_ref(),
+            ArchConfig::Embedding(_) => None,
+        }
+    }
+
+    /// Run the backend on an already-rendered prompt and count tokens.
+    async fn generate_response(
+        &self,
+        final_prompt: &str,
+        options: &CompletionOptions,
+    ) -> Result<LLMResponse, LlmError> {
         let content = self
             .backend
-            .generate(&final_prompt, &self.tokenizer, &self.device)
+            .generate(
+                final_prompt,
+                &GenerateOptions::from_completion(options),
+                &self.tokenizer,
+                &self.device,
+            )
             .await?;
         let prompt_tokens = self
             .tokenizer
-            .encode(final_prompt.as_str(), false)
+            .encode(final_prompt, false)
             .map(|e| e.len())
             .unwrap_or(0);
         let completion_tokens = self
@@ -149,23 +308,6 @@ impl LLMProvider for CandleProvider {
             thinking_content: None,
         })
     }
-
-    async fn complete_with_options(
-        &self,
-        prompt: &str,
-        _options: &CompletionOptions,
-    ) -> Result<LLMResponse, LlmError> {
-        self.complete(prompt).await
-    }
-
-    async fn chat(
-        &self,
-        messages: &[ChatMessage],
-        _options: Option<&CompletionOptions>,
-    ) -> Result<LLMResponse, LlmError> {
-        let last = messages.last().map(|m| m.content.as_str()).unwrap_or("");
-        self.complete(last).await
-    }
 }
 
 // ── EmbeddingProvider ─────────────────────────────────────────────────────────
@@ -182,48 +324,108 @@ impl EmbeddingProvider for CandleProvider {
         self.backend.dimension()
     }
     fn max_tokens(&self) -> usize {
-        2048
+        let limit = self.backend.max_positions().unwrap_or(DEFAULT_MAX_TOKENS);
+        self.max_tokens.map_or(limit, |m| m.min(limit))
     }
 
     async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, LlmError> {
+        self.embed_prefixed(texts, "").await
+    }
+}
+
+#[async_trait]
+impl RetrievalEmbedder for CandleProvider {
+    async fn embed_query(&self, query: &str) -> Result<Vec<f32>, LlmError> {
+        self.embed_prefixed(&[query.to_string()], &self.embedding_config()?.query_prefix)
+            .await?
+            .pop()
+            .ok_or_else(|| LlmError::Unknown("Empty embedding response".to_string()))
+    }
+
+    async fn embed_documents(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, LlmError> {
+        self.embed_prefixed(texts, &self.embedding_config()?.document_prefix).await
+    }
+}
+
+/// Input limit of backends without a position table.
+const DEFAULT_MAX_TOKENS: usize = 2048;
+/// Most sequences sent to the backend in one forward pass.
+const EMBED_BATCH_SIZE: usize = 32;
+/// Most padded tokens (longest sequence × batch size) in one forward pass.
+const EMBED_BATCH_TOKENS: usize = 16_384;
+
+impl CandleProvider {
+    /// The prefixes and pooling of an embedding model; an error for generation models.
+    fn embedding_config(&self) -> Result<&registry::EmbeddingConfig, LlmError> {
         if self.backend.role() != ModelRole::Embedding {
             return Err(LlmError::Unknown(format!(
                 "Model '{}' is a generation model and cannot produce embeddings",
                 self.model_name
             )));
         }
-        let embed_cfg = match &self.arch_config {
-            ArchConfig::Embedding(e) => e,
-            _ => {
-                return Err(LlmError::Unknown(
-                    "Expected embedding arch config".to_string(),
-                ))
-            }
-        };
+        match &self.arch_config {
+            ArchConfig::Embedding(e) => Ok(e),
+            _ => Err(LlmError::Unknown(
+                "Expected embedding arch config".to_string(),
+            )),
+        }
+    }
 
-        let mut token_ids_batch = Vec::with_capacity(texts.len());
-        let mut type_ids_batch = Vec::with_capacity(texts.len());
+    /// Embed `texts`, each with `prefix` prepended before tokenising.
+    async fn embed_prefixed(&self, texts: &[String], prefix: &str) -> Result<Vec<Vec<f32>>, LlmError> {
+        self.embedding_config()?;
 
-        for (i, text) in texts.iter().enumerate() {
-            // Single-text calls are queries; batches are documents.
-            let prefix = if texts.len() == 1 {
-                embed_cfg.query_prefix.as_str()
-            } else {
-                embed_cfg.document_prefix.as_str()
-            };
+        let mut token_ids = Vec::with_capacity(texts.len());
+        let mut type_ids = Vec::with_capacity(texts.len());
+        for text in texts {
             let prefixed = format!("{}{}", prefix, text);
             let tokens = self
                 .tokenizer
                 .encode(prefixed.as_str(), true)
                 .map_err(|e| LlmError::Unknown(e.to_string()))?;
-            token_ids_batch.push(tokens.get_ids().to_vec());
-            type_ids_batch.push(tokens.get_type_ids().to_vec());
-            let _ = i; // suppress unused warning
+            let mut ids = tokens.get_ids().to_vec();
+            let mut types = tokens.get_type_ids().to_vec();
+            // Ingestion windows long texts to fit, so this only catches
+            // direct callers; the closing special token is kept.
+            let limit = EmbeddingProvider::max_tokens(self);
+            if ids.len() > limit {
+                eprintln!(
+                    "  ! Warning: Input of {} tokens truncated to {} for '{}'",
+                    ids.len(),
+                    limit,
+                    self.model_name
+                );
+                let last = (tokens.get_special_tokens_mask().last() == Some(&1)).then(|| ids[ids.len() - 1]);
+                ids.truncate(limit);
+                types.truncate(limit);
+                if let (Some(last), Some(end)) = (last, ids.last_mut()) {
+                    *end = last;
+                }
+            }
+            token_ids.push(ids);
+            type_ids.push(types);
         }
 
-        self.backend
-            .embed_batch(&token_ids_batch, &type_ids_batch, &self.device)
-            .await
+        // Sequences of similar length share a forward pass, so little of each
+        // batch is padding.
+        let lengths: Vec<usize> = token_ids.iter().map(Vec::len).collect();
+        let mut vectors = vec![Vec::new(); texts.len()];
+        for group in crate::model::batching::group_by_length(&lengths, EMBED_BATCH_SIZE, EMBED_BATCH_TOKENS) {
+            let ids: Vec<Vec<u32>> = group.iter().map(|&i| std::mem::take(&mut token_ids[i])).collect();
+            let types: Vec<Vec<u32>> = group.iter().map(|&i| std::mem::take(&mut type_ids[i])).collect();
+            let embedded = self.backend.embed_batch(&ids, &types, &self.device).await?;
+            if embedded.len() != group.len() {
+                return Err(LlmError::Unknown(format!(
+                    "Backend returned {} embeddings for {} sequences",
+                    embedded.len(),
+                    group.len()
+                )));
+            }
+            for (&i, vector) in group.iter().zip(embedded) {
+                vectors[i] = vector;
+            }
+        }
+        Ok(vectors)
     }
 }
 
@@ -235,4 +437,8 @@ impl crate::model::UnifiedModel for CandleProvider {
     async fn prepare(&self) -> Result<()> {
         Ok(())
     }
+
+    fn token_counter(&self) -> std::sync::Arc<dyn TokenCounter> {
+        std::sync::Arc::new(CandleProvider::token_counter(self))
+    }
 }
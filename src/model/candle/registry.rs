@@ -1,13 +1,21 @@
 use anyhow::{Context, Result};
+use edgequake_llm::{ChatMessage, ChatRole};
 use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
 use std::collections::HashMap;
+use std::path::Path;
+
+use crate::model::candle::backend::{GenerateOptions, ModelRole, Pooling};
 
 // The YAML is embedded at compile time so there is no runtime file-path dependency.
 const MODELS_YAML: &str = include_str!("../../../models.yaml");
 
+/// User registry merged over the embedded one when `Config::models_file` is unset.
+pub const USER_MODELS_FILE: &str = ".local-memory/models.yaml";
+
 // ── Raw serde types (mirror the YAML schema exactly) ─────────────────────────
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug)]
 struct RawRegistry {
     architectures: HashMap<String, RawArchConfig>,
     models: HashMap<String, RawModelEntry>,
@@ -15,43 +23,291 @@ struct RawRegistry {
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 struct RawArchConfig {
     role: String,
+    /// Backend implementation; defaults to the architecture's own name so
+    /// user-defined architectures can reuse e.g. the `qwen2` backend.
+    kind: Option<String>,
     // embedding-only fields
     query_prefix: Option<String>,
     document_prefix: Option<String>,
+    pooling: Option<Pooling>,
     // generation-only fields
     max_new_tokens: Option<usize>,
     eos_tokens: Option<Vec<String>>,
     eos_fallback_id: Option<u32>,
+    #[serde(default)]
+    constrained_decoding: bool,
+    temperature: Option<f64>,
+    top_p: Option<f64>,
+    seed: Option<u64>,
+    chat_template: Option<ChatTemplate>,
     prompt_templates: Option<HashMap<String, String>>,
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 struct RawModelEntry {
     arch: String,
     prompt_template: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 struct RawPattern {
     contains: String,
     arch: String,
     prompt_template: Option<String>,
 }
 
+impl RawRegistry {
+    /// Deserialize each entry separately so errors name the offending key.
+    fn from_doc(doc: &Mapping) -> Result<Self> {
+        for key in doc.keys() {
+            let key = key.as_str().unwrap_or_default();
+            if !["architectures", "models", "patterns"].contains(&key) {
+                anyhow::bail!("unknown top-level key '{}'", key);
+            }
+        }
+
+        let mut architectures = HashMap::new();
+        for (name, value) in section_mapping(doc, "architectures")?.into_iter().flatten() {
+            let name = key_string(name, "architectures")?;
+            let arch: RawArchConfig = serde_yaml::from_value(value.clone())
+                .with_context(|| format!("architectures.{}", name))?;
+            architectures.insert(name, arch);
+        }
+
+        let mut models = HashMap::new();
+        for (name, value) in section_mapping(doc, "models")?.into_iter().flatten() {
+            let name = key_string(name, "models")?;
+            let entry: RawModelEntry = serde_yaml::from_value(value.clone())
+                .with_context(|| format!("models.\"{}\"", name))?;
+            models.insert(name, entry);
+        }
+
+        let mut patterns = Vec::new();
+        if let Some(value) = doc.get("patterns") {
+            let list = value.as_sequence().context("patterns: expected a list")?;
+            for (i, value) in list.iter().enumerate() {
+                let pattern: RawPattern = serde_yaml::from_value(value.clone())
+                    .with_context(|| format!("patterns[{}]", i))?;
+                patterns.push(pattern);
+            }
+        }
+
+        let raw = Self { architectures, models, patterns };
+        raw.validate()?;
+        Ok(raw)
+    }
+
+    /// Cross-reference checks serde cannot express.
+    fn validate(&self) -> Result<()> {
+        for (name, arch) in &self.architectures {
+            let kind = arch_kind(arch.kind.as_deref().unwrap_or(name)).with_context(|| {
+                format!(
+                    "architectures.{}.kind: unknown backend '{}' (expected one of: {})",
+                    name,
+                    arch.kind.as_deref().unwrap_or(name),
+                    ArchKind::ALL.iter().map(|k| k.as_str()).collect::<Vec<_>>().join(", ")
+                )
+            })?;
+            let expected = match kind.role() {
+                ModelRole::Embedding => "embedding",
+                ModelRole::Generation => "generation",
+            };
+            if arch.role != expected && ["embedding", "generation"].contains(&arch.role.as_str()) {
+                anyhow::bail!(
+                    "architectures.{}.role: backend '{}' is a {} backend, not {}",
+                    name, kind.as_str(), expected, arch.role
+                );
+            }
+            match arch.role.as_str() {
+                "embedding" => {}
+                "generation" => {
+                    let templates = arch.prompt_templates.as_ref().filter(|t| !t.is_empty());
+                    let templates = templates.with_context(|| {
+                        format!("architectures.{}.prompt_templates: a generation architecture needs at least one template", name)
+                    })?;
+                    for (template_name, template) in templates {
+                        if !template.contains("{text}") {
+                            anyhow::bail!(
+                                "architectures.{}.prompt_templates.{}: missing {{text}} placeholder",
+                                name, template_name
+                            );
+                        }
+                    }
+                }
+                other => anyhow::bail!(
+                    "architectures.{}.role: unknown role '{}' (expected embedding or generation)",
+                    name, other
+                ),
+            }
+        }
+
+        let entries = self
+            .models
+            .iter()
+            .map(|(name, m)| (format!("models.\"{}\"", name), &m.arch, &m.prompt_template))
+            .chain(self.patterns.iter().enumerate().map(|(i, p)| {
+                (format!("patterns[{}]", i), &p.arch, &p.prompt_template)
+            }));
+        for (path, arch_name, template) in entries {
+            let arch = self.architectures.get(arch_name).with_context(|| {
+                format!("{}.arch: unknown architecture '{}'", path, arch_name)
+            })?;
+            if let Some(template) = template
+                && arch.role == "generation"
+                && !arch.prompt_templates.as_ref().is_some_and(|t| t.contains_key(template))
+            {
+                anyhow::bail!(
+                    "{}.prompt_template: architecture '{}' has no template '{}'",
+                    path, arch_name, template
+                );
+            }
+        }
+        for (i, pattern) in self.patterns.iter().enumerate() {
+            if pattern.contains.trim().is_empty() {
+                anyhow::bail!("patterns[{}].contains: must not be empty", i);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn section_mapping<'a>(doc: &'a Mapping, section: &str) -> Result<Option<&'a Mapping>> {
+    match doc.get(section) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => value
+            .as_mapping()
+            .map(Some)
+            .with_context(|| format!("{}: expected a mapping", section)),
+    }
+}
+
+fn key_string(key: &Value, section: &str) -> Result<String> {
+    key.as_str()
+        .map(str::to_string)
+        .with_context(|| format!("{}: keys must be strings, found {:?}", section, key))
+}
+
+/// Merge a user registry document over `base`:
+/// - architectures are merged field by field (`prompt_templates` by template name),
+/// - models are added or replaced,
+/// - patterns are tried before the embedded ones.
+fn merge_registry(base: &mut Mapping, overlay: Mapping) -> Result<()> {
+    for (key, value) in overlay {
+        let section = key_string(&key, "top level")?;
+        if value.is_null() {
+            continue;
+        }
+        match section.as_str() {
+            "architectures" | "models" => {
+                let entries = value
+                    .as_mapping()
+                    .with_context(|| format!("{}: expected a mapping", section))?;
+                let target = base
+                    .entry(key.clone())
+                    .or_insert_with(|| Value::Mapping(Mapping::new()));
+                let target = target.as_mapping_mut().context("embedded registry is malformed")?;
+                for (name, entry) in entries {
+                    match (target.get_mut(name), entry) {
+                        (Some(Value::Mapping(existing)), Value::Mapping(fields)) if section == "architectures" => {
+                            merge_arch(existing, fields);
+                        }
+                        _ => {
+                            target.insert(name.clone(), entry.clone());
+                        }
+                    }
+                }
+            }
+            "patterns" => {
+                let mut patterns = value
+                    .as_sequence()
+                    .context("patterns: expected a list")?
+                    .clone();
+                if let Some(Value::Sequence(existing)) = base.get("patterns") {
+                    patterns.extend(existing.iter().cloned());
+                }
+                base.insert(key, Value::Sequence(patterns));
+            }
+            other => anyhow::bail!("unknown top-level key '{}'", other),
+        }
+    }
+    Ok(())
+}
+
+fn merge_arch(existing: &mut Mapping, fields: &Mapping) {
+    for (field, value) in fields {
+        if field.as_str() == Some("prompt_templates")
+            && let (Some(Value::Mapping(current)), Value::Mapping(added)) = (existing.get_mut(field), value)
+        {
+            current.extend(added.iter().map(|(k, v)| (k.clone(), v.clone())));
+            continue;
+        }
+        existing.insert(field.clone(), value.clone());
+    }
+}
+
 // ── Public domain types ───────────────────────────────────────────────────────
 
 /// Identifies which architecture family a model uses.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ArchKind {
     Bert,
+    JinaBert,
+    ModernBert,
     Phi3,
     Qwen2,
+    Llama,
+    Mistral,
+    Gemma2,
+}
+
+impl ArchKind {
+    pub const ALL: [ArchKind; 8] = [
+        ArchKind::Bert,
+        ArchKind::JinaBert,
+        ArchKind::ModernBert,
+        ArchKind::Phi3,
+        ArchKind::Qwen2,
+        ArchKind::Llama,
+        ArchKind::Mistral,
+        ArchKind::Gemma2,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ArchKind::Bert => "bert",
+            ArchKind::JinaBert => "jina_bert",
+            ArchKind::ModernBert => "modernbert",
+            ArchKind::Phi3 => "phi3",
+            ArchKind::Qwen2 => "qwen2",
+            ArchKind::Llama => "llama",
+            ArchKind::Mistral => "mistral",
+            ArchKind::Gemma2 => "gemma2",
+        }
+    }
+}
+
+impl ArchKind {
+    /// The role the backend implements.
+    pub fn role(&self) -> ModelRole {
+        match self {
+            ArchKind::Bert | ArchKind::JinaBert | ArchKind::ModernBert => ModelRole::Embedding,
+            _ => ModelRole::Generation,
+        }
+    }
+}
+
+fn arch_kind(name: &str) -> Option<ArchKind> {
+    ArchKind::ALL.into_iter().find(|k| k.as_str() == name)
 }
 
 /// All configuration parameters needed to drive a specific architecture.
 #[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
 pub enum ArchConfig {
     Embedding(EmbeddingConfig),
     Generation(GenerationConfig),
@@ -61,6 +317,7 @@ pub enum ArchConfig {
 pub struct EmbeddingConfig {
     pub query_prefix: String,
     pub document_prefix: String,
+    pub pooling: Pooling,
 }
 
 #[derive(Debug, Clone)]
@@ -68,9 +325,127 @@ pub struct GenerationConfig {
     pub max_new_tokens: usize,
     pub eos_tokens: Vec<String>,
     pub eos_fallback_id: u32,
-    /// The prompt template string for the resolved template name.
-    /// Contains `{text}` as the substitution placeholder.
-    pub prompt_template: String,
+    /// Mask logits so output follows the JSON skeleton in the prompt's
+    /// `Template:` section (see `candle::constrained`).
+    pub constrained_decoding: bool,
+    /// Default sampling parameters, overridden per call by `CompletionOptions`.
+    /// No temperature means greedy decoding.
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub seed: Option<u64>,
+    /// How `chat` renders a message list. `None` means the architecture
+    /// only supports single-prompt completion.
+    pub chat_template: Option<ChatTemplate>,
+    /// Every named prompt template of the architecture. Each contains `{text}`
+    /// as the substitution placeholder.
+    pub prompt_templates: HashMap<String, String>,
+    /// Template used when the prompt does not name one (the model entry's
+    /// `prompt_template`, or `extraction`).
+    pub default_template: String,
+}
+
+/// Per-role turn formats for rendering a conversation into one prompt.
+///
+/// Each turn string contains `{content}`. Architectures without a system
+/// turn leave `system` unset; system messages are then folded into the
+/// next user turn. Tool results use `tool`, falling back to `user`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChatTemplate {
+    pub system: Option<String>,
+    pub user: String,
+    pub assistant: String,
+    pub tool: Option<String>,
+    /// Appended after the last message to open the assistant's turn.
+    pub generation_prompt: String,
+}
+
+impl ChatTemplate {
+    /// Render `messages` (with an optional leading `system_prompt`) and open
+    /// the assistant turn.
+    pub fn render(&self, system_prompt: Option<&str>, messages: &[ChatMessage]) -> String {
+        let mut out = String::new();
+        let mut pending_system: Vec<&str> = Vec::new();
+        if let Some(system) = system_prompt
+            && !messages.iter().any(|m| matches!(m.role, ChatRole::System))
+        {
+            pending_system.push(system);
+        }
+
+        for message in messages {
+            let turn = match message.role {
+                ChatRole::System => {
+                    pending_system.push(&message.content);
+                    continue;
+                }
+                ChatRole::User => &self.user,
+                ChatRole::Assistant => &self.assistant,
+                ChatRole::Tool | ChatRole::Function => self.tool.as_ref().unwrap_or(&self.user),
+            };
+            let mut content = message.content.clone();
+            if !pending_system.is_empty() {
+                let system = pending_system.join("\n\n");
+                match &self.system {
+                    Some(system_turn) => out.push_str(&system_turn.replace("{content}", &system)),
+                    None if matches!(message.role, ChatRole::User) => {
+                        content = format!("{}\n\n{}", system, content);
+                    }
+                    None => out.push_str(&self.user.replace("{content}", &system)),
+                }
+                pending_system.clear();
+            }
+            out.push_str(&turn.replace("{content}", &content));
+        }
+
+        // A system prompt with no following turn still has to be rendered.
+        if !pending_system.is_empty() {
+            let turn = self.system.as_ref().unwrap_or(&self.user);
+            out.push_str(&turn.replace("{content}", &pending_system.join("\n\n")));
+        }
+        out.push_str(&self.generation_prompt);
+        out
+    }
+}
+
+impl GenerationConfig {
+    /// Look up a named prompt template.
+    pub fn template(&self, name: &str) -> Result<&str> {
+        self.prompt_templates
+            .get(name)
+            .map(String::as_str)
+            .with_context(|| {
+                let mut known: Vec<&str> = self.prompt_templates.keys().map(String::as_str).collect();
+                known.sort_unstable();
+                format!(
+                    "Unknown prompt template '{}' (available: {})",
+                    name,
+                    known.join(", ")
+                )
+            })
+    }
+
+    /// Ids of every configured EOS token present in `tokenizer`'s vocabulary,
+    /// or `eos_fallback_id` if none are.
+    pub fn eos_token_ids(&self, tokenizer: &tokenizers::Tokenizer) -> Vec<u32> {
+        let vocab = tokenizer.get_vocab(true);
+        let ids: Vec<u32> = self
+            .eos_tokens
+            .iter()
+            .filter_map(|t| vocab.get(t.as_str()).copied())
+            .collect();
+        if ids.is_empty() { vec![self.eos_fallback_id] } else { ids }
+    }
+
+    /// Fill unset fields of per-call `options` from this architecture's defaults.
+    pub fn resolve_options(&self, options: &GenerateOptions) -> GenerateOptions {
+        GenerateOptions {
+            max_new_tokens: Some(options.max_new_tokens.unwrap_or(self.max_new_tokens)),
+            temperature: options.temperature.or(self.temperature),
+            top_p: options.top_p.or(self.top_p),
+            seed: options.seed.or(self.seed),
+            stop: options.stop.clone(),
+        }
+    }
 }
 
 impl ArchConfig {
@@ -78,61 +453,190 @@ impl ArchConfig {
     ///
     /// Rules (in priority order):
     /// 1. If the prompt already contains arch-specific markers (`<|im_start|>`,
-    ///    `<|input|>`) it is returned unchanged — the caller already formatted it.
+    ///    `<|input|>`, or the chat template's user-turn opener) it is returned
+    ///    unchanged — the caller already formatted it.
     /// 2. If the prompt starts with `"TEMPLATE:<name>\n"` the named template is
-    ///    used and the remainder is treated as `{text}`.
-    /// 3. Otherwise the default template (stored in the config) is used, with
-    ///    the raw prompt as `{text}` (stripping a trailing `"Text: "` prefix if
-    ///    present, for backwards-compatibility with `ingestion.rs`).
-    pub fn format_prompt(&self, prompt: &str) -> String {
+    ///    used and the remainder is treated as `{text}`. Unknown names are an
+    ///    error.
+    /// 3. Otherwise the default template is used, with the raw prompt as
+    ///    `{text}` (stripping a trailing `"Text: "` prefix if present, for
+    ///    backwards-compatibility with `ingestion.rs`).
+    pub fn format_prompt(&self, prompt: &str) -> Result<String> {
         match self {
-            ArchConfig::Embedding(_) => prompt.to_string(),
+            ArchConfig::Embedding(_) => Ok(prompt.to_string()),
             ArchConfig::Generation(cfg) => {
                 // Already formatted — pass through.
-                if prompt.contains("<|im_start|>") || prompt.contains("<|input|>") {
-                    return prompt.to_string();
+                let user_turn = cfg
+                    .chat_template
+                    .as_ref()
+                    .and_then(|t| t.user.split("{content}").next())
+                    .map(str::trim)
+                    .filter(|marker| !marker.is_empty());
+                if prompt.contains("<|im_start|>")
+                    || prompt.contains("<|input|>")
+                    || user_turn.is_some_and(|marker| prompt.contains(marker))
+                {
+                    return Ok(prompt.to_string());
                 }
-                // Named template override from caller.
-                // `ingestion.rs` / `communities.rs` use "TEMPLATE:summary\n{ctx}".
-                if let Some(rest) = prompt.strip_prefix("TEMPLATE:") {
-                    if let Some(nl) = rest.find('\n') {
-                        let _name = &rest[..nl]; // reserved for future multi-template support
+                // Named template selected by the caller,
+                // e.g. `communities.rs` sends "TEMPLATE:summary\n{ctx}".
+                if let Some(rest) = prompt.strip_prefix("TEMPLATE:")
+                    && let Some(nl) = rest.find('\n') {
+                        let template = cfg.template(rest[..nl].trim())?;
                         let text = rest[nl + 1..].trim();
-                        return cfg.prompt_template.replace("{text}", text);
+                        return Ok(render_template(template, text, prompt));
                     }
-                }
                 // Default: strip legacy "Text: " prefix if present.
                 let text = if let Some(pos) = prompt.rfind("Text: ") {
                     prompt[pos + 6..].trim()
                 } else {
                     prompt.trim()
                 };
-                cfg.prompt_template.replace("{text}", text)
+                Ok(render_template(cfg.template(&cfg.default_template)?, text, prompt))
             }
         }
     }
 }
 
+/// Substitute `{entity_types}`, `{predicates}` and `{text}` into `template`.
+///
+/// The ontology placeholders become a NuExtract enum (`["Person","Place"]`)
+/// when the raw prompt carries `ALLOWED ENTITY TYPES:` / `ALLOWED PREDICATES:`
+/// lines (see `engine::ontology`), and `"string"` otherwise. `{text}` is
+/// substituted last so placeholders inside the user's text are left alone.
+fn render_template(template: &str, text: &str, raw_prompt: &str) -> String {
+    template
+        .replace("{entity_types}", &allowed_values(raw_prompt, "ALLOWED ENTITY TYPES:"))
+        .replace("{predicates}", &allowed_values(raw_prompt, "ALLOWED PREDICATES:"))
+        .replace("{text}", text)
+}
+
+fn allowed_values(prompt: &str, marker: &str) -> String {
+    prompt
+        .lines()
+        .find_map(|line| line.trim().strip_prefix(marker))
+        .map(|list| {
+            let values: Vec<&str> = list.split(',').map(str::trim).filter(|v| !v.is_empty()).collect();
+            serde_json::to_string(&values).unwrap_or_else(|_| "\"string\"".to_string())
+        })
+        .unwrap_or_else(|| "\"string\"".to_string())
+}
+
 /// The resolved entry for a model (arch kind + full arch config).
 #[derive(Debug, Clone)]
 pub struct ResolvedModel {
     pub arch: ArchKind,
+    /// Name of the `architectures:` entry (differs from `arch` for
+    /// user-defined architectures that set `kind`).
+    pub arch_name: String,
+    pub matched_by: MatchSource,
     pub config: ArchConfig,
 }
 
+/// Which registry entry a model name resolved through.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MatchSource {
+    Exact,
+    /// The `contains` substring of the first matching pattern.
+    Pattern(String),
+}
+
+/// A `models:` or `patterns:` entry, for listing.
+#[derive(Debug, Clone)]
+pub struct RegistryEntry {
+    /// Model name, or the `contains` substring for patterns.
+    pub key: String,
+    pub arch: String,
+    pub prompt_template: Option<String>,
+}
+
 // ── Registry ──────────────────────────────────────────────────────────────────
 
 /// Parses `models.yaml` and resolves model names to their architecture + config.
 pub struct ModelRegistry {
+    /// The merged YAML document; user registries are layered onto it.
+    doc: Mapping,
     raw: RawRegistry,
 }
 
 impl ModelRegistry {
     /// Load and parse the embedded `models.yaml`.
     pub fn load() -> Result<Self> {
-        let raw: RawRegistry =
+        let doc: Mapping =
             serde_yaml::from_str(MODELS_YAML).context("Failed to parse embedded models.yaml")?;
-        Ok(Self { raw })
+        let raw = RawRegistry::from_doc(&doc).context("Invalid embedded models.yaml")?;
+        Ok(Self { doc, raw })
+    }
+
+    /// Load the embedded registry, then the user registry named by `config`.
+    ///
+    /// `config.models_file` must exist when set; otherwise
+    /// `~/.local-memory/models.yaml` is merged if present.
+    pub fn for_config(config: &crate::config::Config) -> Result<Self> {
+        let mut registry = Self::load()?;
+        let user_file = match &config.models_file {
+            Some(path) => Some(path.clone()),
+            None => home::home_dir()
+                .map(|h| h.join(USER_MODELS_FILE))
+                .filter(|p| p.exists()),
+        };
+        if let Some(path) = user_file {
+            registry = registry.with_user_file(&path)?;
+        }
+        Ok(registry)
+    }
+
+    /// Merge a user registry file over the current one.
+    pub fn with_user_file(self, path: &Path) -> Result<Self> {
+        let yaml = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read model registry {}", path.display()))?;
+        self.with_user_yaml(&yaml)
+            .with_context(|| format!("Invalid model registry {}", path.display()))
+    }
+
+    /// Merge a user registry document (same schema as `models.yaml`, every
+    /// section optional) over the current one.
+    pub fn with_user_yaml(mut self, yaml: &str) -> Result<Self> {
+        let overlay: Value = serde_yaml::from_str(yaml)?;
+        let overlay = match overlay {
+            Value::Null => Mapping::new(),
+            Value::Mapping(m) => m,
+            _ => anyhow::bail!("expected a mapping with architectures, models and/or patterns"),
+        };
+        let mut doc = self.doc.clone();
+        merge_registry(&mut doc, overlay)?;
+        self.raw = RawRegistry::from_doc(&doc)?;
+        self.doc = doc;
+        Ok(self)
+    }
+
+    /// Exact-name entries, sorted by model name.
+    pub fn models(&self) -> Vec<RegistryEntry> {
+        let mut entries: Vec<RegistryEntry> = self
+            .raw
+            .models
+            .iter()
+            .map(|(name, m)| RegistryEntry {
+                key: name.clone(),
+                arch: m.arch.clone(),
+                prompt_template: m.prompt_template.clone(),
+            })
+            .collect();
+        entries.sort_by(|a, b| a.key.cmp(&b.key));
+        entries
+    }
+
+    /// Substring patterns, in match order.
+    pub fn patterns(&self) -> Vec<RegistryEntry> {
+        self.raw
+            .patterns
+            .iter()
+            .map(|p| RegistryEntry {
+                key: p.contains.clone(),
+                arch: p.arch.clone(),
+                prompt_template: p.prompt_template.clone(),
+            })
+            .collect()
     }
 
     /// Resolve a model name to its `ArchKind` and `ArchConfig`.
@@ -142,13 +646,14 @@ impl ModelRegistry {
     /// 2. First matching pattern in `patterns:` (substring `contains` check, lowercased).
     pub fn resolve(&self, model_name: &str) -> Result<ResolvedModel> {
         // 1. Exact match.
-        let (arch_name, template_name) = if let Some(entry) = self.raw.models.get(model_name) {
+        let (arch_name, template_name, matched_by) = if let Some(entry) = self.raw.models.get(model_name) {
             (
                 entry.arch.clone(),
                 entry
                     .prompt_template
                     .clone()
                     .unwrap_or_else(|| "extraction".to_string()),
+                MatchSource::Exact,
             )
         } else {
             // 2. Pattern match (lowercased).
@@ -157,12 +662,12 @@ impl ModelRegistry {
                 .raw
                 .patterns
                 .iter()
-                .find(|p| lower.contains(&p.contains))
+                .find(|p| lower.contains(&p.contains.to_lowercase()))
                 .with_context(|| {
                     format!(
                         "No architecture mapping found for model '{}'. \
-                         Add it to models.yaml.",
-                        model_name
+                         Add it to ~/{} (see `lmcli models list`).",
+                        model_name, USER_MODELS_FILE
                     )
                 })?;
             (
@@ -171,6 +676,7 @@ impl ModelRegistry {
                     .prompt_template
                     .clone()
                     .unwrap_or_else(|| "extraction".to_string()),
+                MatchSource::Pattern(matched.contains.clone()),
             )
         };
 
@@ -179,12 +685,10 @@ impl ModelRegistry {
                 format!("Architecture '{}' not defined in models.yaml", arch_name)
             })?;
 
-        let arch_kind = match arch_name.as_str() {
-            "bert" => ArchKind::Bert,
-            "phi3" => ArchKind::Phi3,
-            "qwen2" => ArchKind::Qwen2,
-            other => anyhow::bail!("Unknown architecture '{}' in models.yaml", other),
-        };
+        let kind_name = raw_arch.kind.as_deref().unwrap_or(&arch_name);
+        let arch_kind = arch_kind(kind_name).with_context(|| {
+            format!("Unknown architecture '{}' in models.yaml", kind_name)
+        })?;
 
         let config = match raw_arch.role.as_str() {
             "embedding" => ArchConfig::Embedding(EmbeddingConfig {
@@ -196,22 +700,30 @@ impl ModelRegistry {
                     .document_prefix
                     .clone()
                     .unwrap_or_else(|| "search_document: ".to_string()),
+                pooling: raw_arch.pooling.unwrap_or_default(),
             }),
             "generation" => {
-                let templates = raw_arch.prompt_templates.as_ref().with_context(|| {
-                    format!("Architecture '{}' has no prompt_templates", arch_name)
-                })?;
-                let template = templates.get(&template_name).with_context(|| {
-                    format!(
+                let templates = raw_arch.prompt_templates.clone().unwrap_or_default();
+                if templates.is_empty() {
+                    anyhow::bail!("Architecture '{}' has no prompt_templates", arch_name);
+                }
+                if !templates.contains_key(&template_name) {
+                    anyhow::bail!(
                         "Template '{}' not found in architecture '{}'",
                         template_name, arch_name
-                    )
-                })?;
+                    );
+                }
                 ArchConfig::Generation(GenerationConfig {
                     max_new_tokens: raw_arch.max_new_tokens.unwrap_or(512),
                     eos_tokens: raw_arch.eos_tokens.clone().unwrap_or_default(),
                     eos_fallback_id: raw_arch.eos_fallback_id.unwrap_or(0),
-                    prompt_template: template.clone(),
+                    constrained_decoding: raw_arch.constrained_decoding,
+                    temperature: raw_arch.temperature,
+                    top_p: raw_arch.top_p,
+                    seed: raw_arch.seed,
+                    chat_template: raw_arch.chat_template.clone(),
+                    prompt_templates: templates,
+                    default_template: template_name,
                 })
             }
             other => anyhow::bail!("Unknown role '{}' in models.yaml", other),
@@ -219,6 +731,8 @@ impl ModelRegistry {
 
         Ok(ResolvedModel {
             arch: arch_kind,
+            arch_name,
+            matched_by,
             config,
         })
     }
@@ -1,18 +1,26 @@
 use std::sync::Arc;
 use std::collections::HashMap;
 use edgequake_llm::{LLMProvider, OpenAIProvider, OllamaProvider, EmbeddingProvider};
-use crate::config::{Config, ExtractorProvider, ModelProvider};
+use crate::config::{Config, ExtractorProvider, ModelConfig, ModelProvider};
 use anyhow::Result;
 
 pub mod base;
+pub mod batching;
+pub mod cached;
 pub mod candle;
 pub mod ollama;
 pub mod auth;
+pub mod prefixed;
+pub mod tokens;
 
 // Re-export common types
-pub use base::{UnifiedModel, GenericUnifiedModel, check_llm_connectivity, check_embedding_connectivity};
+pub use base::{UnifiedModel, GenericUnifiedModel, RetrievalEmbedder, check_llm_connectivity, check_embedding_connectivity};
+pub use batching::EmbeddingBatcher;
+pub use cached::CachedEmbedder;
 pub use candle::CandleProvider;
 pub use ollama::pull_ollama_model;
+pub use prefixed::PrefixedEmbedder;
+pub use tokens::{ApproxTokenCounter, TokenCounter, TokenizerCounter};
 
 use async_trait::async_trait;
 use edgequake_llm::LlmError;
@@ -226,21 +234,49 @@ impl LLMProvider for GeminiLLMProvider {
     }
 }
 
+/// Query and document prefixes for an embedding model: the configured ones,
+/// else those of the registry architecture matching its name (so Ollama's
+/// `nomic-embed-text` gets nomic's prefixes), else none.
+pub fn embedding_prefixes(model: &ModelConfig, registry: &candle::ModelRegistry) -> (String, String) {
+    let (query, document) = match registry.resolve(&model.name).map(|r| r.config) {
+        Ok(candle::ArchConfig::Embedding(e)) => (e.query_prefix, e.document_prefix),
+        _ => (String::new(), String::new()),
+    };
+    (
+        model.query_prefix.clone().unwrap_or(query),
+        model.document_prefix.clone().unwrap_or(document),
+    )
+}
+
 /// Unified factory to get a complete UnifiedModel (Embedding + LLM)
 pub async fn get_unified_model(config: &Config) -> Result<Arc<dyn UnifiedModel>> {
     let mut prepare_list = Vec::new();
-    let registry = candle::ModelRegistry::load()?;
+    let registry = candle::ModelRegistry::for_config(config)?;
+    let hub = candle::HubClient::for_config(config);
 
     // 1. Resolve Embedder
-    let embedder: Arc<dyn EmbeddingProvider> = match config.embedding.provider {
+    let (query_prefix, document_prefix) = embedding_prefixes(&config.embedding, &registry);
+    let mut token_counter: Option<Arc<dyn TokenCounter>> = None;
+    // Candle applies the prefixes while tokenising; remote providers embed
+    // raw text, so theirs are prepended before it is sent.
+    let remote = |inner: Arc<dyn EmbeddingProvider>| -> Arc<dyn RetrievalEmbedder> {
+        Arc::new(PrefixedEmbedder::new(inner, &query_prefix, &document_prefix))
+    };
+    let embedder: Arc<dyn RetrievalEmbedder> = match config.embedding.provider {
         ModelProvider::HuggingFace => {
-            let p = CandleProvider::load(
+            let p = CandleProvider::load_with_weights(
                 &config.embedding.name,
                 &config.model_path,
                 config.embedding.auto_download,
                 &registry,
+                &candle::Weights::default(),
+                &hub,
             )
             .await?;
+            let p = p
+                .with_prefixes(&query_prefix, &document_prefix)
+                .with_max_tokens(config.embedding.max_tokens);
+            token_counter = Some(Arc::new(p.token_counter()));
             Arc::new(p)
         }
         ModelProvider::Ollama => {
@@ -252,12 +288,12 @@ pub async fn get_unified_model(config: &Config) -> Result<Arc<dyn UnifiedModel>>
             if config.embedding.auto_download {
                 prepare_list.push((config.embedding.name.clone(), host.clone()));
             }
-            Arc::new(
+            remote(Arc::new(
                 OllamaProvider::builder()
                     .host(host)
                     .embedding_model(&config.embedding.name)
                     .build()?,
-            )
+            ))
         }
         ModelProvider::Local => {
             anyhow::bail!("Local provider not yet implemented for standalone embedding");
@@ -275,10 +311,10 @@ pub async fn get_unified_model(config: &Config) -> Result<Arc<dyn UnifiedModel>>
                 .base_url
                 .clone()
                 .unwrap_or_else(|| "https://api.openai.com/v1".to_string());
-            Arc::new(
+            remote(Arc::new(
                 OpenAIProvider::compatible(api_key, base_url)
                     .with_embedding_model(&config.embedding.name),
-            )
+            ))
         }
         ModelProvider::Gemini => {
             let api_key = config
@@ -286,29 +322,42 @@ pub async fn get_unified_model(config: &Config) -> Result<Arc<dyn UnifiedModel>>
                 .api_key
                 .clone()
                 .or_else(|| std::env::var("GOOGLE_API_KEY").ok())
-                .or_else(|| auth::get_google_token())
+                .or_else(auth::get_google_token)
                 .ok_or_else(|| anyhow::anyhow!("Missing Google API key for Gemini embedding. (Checked config api_key, GOOGLE_API_KEY, and OpenCode auth.json)"))?;
             
             if api_key.starts_with("AIza") {
-                Arc::new(GeminiEmbeddingProvider {
+                remote(Arc::new(GeminiEmbeddingProvider {
                     api_key,
                     model: config.embedding.name.clone(),
                     dimension: config.embedding.dimension,
-                })
+                }))
             } else {
                 let base_url = config
                     .embedding
                     .base_url
                     .clone()
                     .unwrap_or_else(|| "https://generativelanguage.googleapis.com/v1beta/openai".to_string());
-                Arc::new(
+                remote(Arc::new(
                     OpenAIProvider::compatible(api_key, base_url)
                         .with_embedding_model(&config.embedding.name),
-                )
+                ))
             }
         }
     };
 
+    // Remote tokenizers are not available locally, so their counts are estimated.
+    let token_counter = token_counter.unwrap_or_else(|| {
+        let limit = config.embedding.max_tokens.map_or(embedder.max_tokens(), |m| m.min(embedder.max_tokens()));
+        Arc::new(ApproxTokenCounter::new(limit))
+    });
+
+    let embedder: Arc<dyn RetrievalEmbedder> = if config.embedding_cache {
+        let cache = Arc::new(crate::storage::EmbeddingCache::open_in(&config.storage_path)?);
+        Arc::new(CachedEmbedder::new(embedder, cache).with_prefixes(&query_prefix, &document_prefix))
+    } else {
+        embedder
+    };
+
     // 2. Resolve LLM Extractor
     let llm: Arc<dyn LLMProvider> = if let Some(ext_config) = &config.llm_extractor {
         eprintln!("DEBUG: Resolving LLM: {:?} ({})", ext_config.provider, ext_config.name);
@@ -348,7 +397,7 @@ pub async fn get_unified_model(config: &Config) -> Result<Arc<dyn UnifiedModel>>
                     .api_key
                     .clone()
                     .or_else(|| std::env::var("GOOGLE_API_KEY").ok())
-                    .or_else(|| auth::get_google_token())
+                    .or_else(auth::get_google_token)
                     .ok_or_else(|| anyhow::anyhow!("Missing Google API key for Gemini LLM. (Checked config api_key, GOOGLE_API_KEY, and OpenCode auth.json)"))?;
                 
                 if api_key.starts_with("AIza") {
@@ -367,11 +416,13 @@ pub async fn get_unified_model(config: &Config) -> Result<Arc<dyn UnifiedModel>>
                 }
             }
             ExtractorProvider::HuggingFace => {
-                let p = CandleProvider::load(
+                let p = CandleProvider::load_with_weights(
                     &ext_config.name,
                     &config.model_path,
                     ext_config.auto_download,
                     &registry,
+                    &candle::Weights::for_extractor(ext_config),
+                    &hub,
                 )
                 .await?;
                 Arc::new(p)
@@ -382,11 +433,13 @@ pub async fn get_unified_model(config: &Config) -> Result<Arc<dyn UnifiedModel>>
         }
     } else {
         // Default LLM: NuExtract-1.5 local
-        let p = CandleProvider::load(
+        let p = CandleProvider::load_with_weights(
             "numind/NuExtract-1.5",
             &config.model_path,
             true,
             &registry,
+            &candle::Weights::default(),
+            &hub,
         )
         .await?;
         Arc::new(p)
@@ -402,6 +455,7 @@ pub async fn get_unified_model(config: &Config) -> Result<Arc<dyn UnifiedModel>>
         } else {
             None
         },
+        token_counter: Some(token_counter),
     }))
 }
 
@@ -416,10 +470,7 @@ pub fn get_llm_provider(config: &Config) -> Option<Arc<dyn LLMProvider + Send +
                     .or_else(|| std::env::var("OPENAI_API_KEY").ok())
                     .or_else(|| auth::get_opencode_key("opencode"));
 
-                let key = match key {
-                    Some(k) => k,
-                    None => return None,
-                };
+                let key = key?;
 
                 let mut p = OpenAIProvider::new(key);
                 p = p.with_model(&ext_config.name);
@@ -430,12 +481,9 @@ pub fn get_llm_provider(config: &Config) -> Option<Arc<dyn LLMProvider + Send +
                     .api_key
                     .clone()
                     .or_else(|| std::env::var("GOOGLE_API_KEY").ok())
-                    .or_else(|| auth::get_google_token());
+                    .or_else(auth::get_google_token);
                 
-                let key = match key {
-                    Some(k) => k,
-                    None => return None,
-                };
+                let key = key?;
 
                 if key.starts_with("AIza") {
                     return Some(Arc::new(GeminiLLMProvider {
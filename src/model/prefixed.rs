@@ -0,0 +1,55 @@
+use async_trait::async_trait;
+use edgequake_llm::{EmbeddingProvider, LlmError};
+use std::sync::Arc;
+
+use crate::model::base::RetrievalEmbedder;
+
+/// Makes a provider that embeds raw text (Ollama and OpenAI-compatible
+/// servers) a `RetrievalEmbedder` by prepending the task prefixes itself.
+pub struct PrefixedEmbedder {
+    inner: Arc<dyn EmbeddingProvider>,
+    query_prefix: String,
+    document_prefix: String,
+}
+
+impl PrefixedEmbedder {
+    pub fn new(inner: Arc<dyn EmbeddingProvider>, query_prefix: &str, document_prefix: &str) -> Self {
+        Self { inner, query_prefix: query_prefix.to_string(), document_prefix: document_prefix.to_string() }
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for PrefixedEmbedder {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+    fn dimension(&self) -> usize {
+        self.inner.dimension()
+    }
+    fn max_tokens(&self) -> usize {
+        self.inner.max_tokens()
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, LlmError> {
+        self.inner.embed(texts).await
+    }
+
+    async fn embed_one(&self, text: &str) -> Result<Vec<f32>, LlmError> {
+        self.inner.embed_one(text).await
+    }
+}
+
+#[async_trait]
+impl RetrievalEmbedder for PrefixedEmbedder {
+    async fn embed_query(&self, query: &str) -> Result<Vec<f32>, LlmError> {
+        self.inner.embed_one(&format!("{}{}", self.query_prefix, query)).await
+    }
+
+    async fn embed_documents(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, LlmError> {
+        let prefixed: Vec<String> = texts.iter().map(|t| format!("{}{}", self.document_prefix, t)).collect();
+        self.inner.embed(&prefixed).await
+    }
+}
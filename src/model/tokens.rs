@@ -0,0 +1,140 @@
+//! Token-aware length checks for embedding inputs.
+//!
+//! Encoders have a hard input limit (512 positions for most BERT models), and
+//! remote providers truncate or reject longer inputs. Texts over the limit
+//! are cut into overlapping windows that each fit; the pipeline embeds every
+//! window and averages them into one vector.
+
+use std::ops::Range;
+use tokenizers::Tokenizer;
+
+/// Splits text into an embedding model's tokens.
+pub trait TokenCounter: Send + Sync {
+    /// Byte ranges of the tokens of `text`, excluding special tokens.
+    fn token_spans(&self, text: &str) -> Vec<Range<usize>>;
+
+    /// Most text tokens one embedding input holds, after special tokens and
+    /// the document prefix.
+    fn token_budget(&self) -> usize;
+}
+
+/// Estimate for providers whose tokenizer is not available locally: every
+/// [`ApproxTokenCounter::CHARS_PER_TOKEN`] characters of a word count as a
+/// token, which overestimates most tokenizers on prose.
+pub struct ApproxTokenCounter {
+    budget: usize,
+}
+
+impl ApproxTokenCounter {
+    pub const CHARS_PER_TOKEN: usize = 3;
+
+    pub fn new(budget: usize) -> Self {
+        Self { budget: budget.max(1) }
+    }
+}
+
+impl TokenCounter for ApproxTokenCounter {
+    fn token_spans(&self, text: &str) -> Vec<Range<usize>> {
+        let mut spans = Vec::new();
+        let mut start = None;
+        let mut chars = 0;
+        for (i, c) in text.char_indices() {
+            if c.is_whitespace() {
+                if let Some(s) = start.take() {
+                    spans.push(s..i);
+                }
+                chars = 0;
+                continue;
+            }
+            if chars == Self::CHARS_PER_TOKEN
+                && let Some(s) = start.take()
+            {
+                spans.push(s..i);
+                chars = 0;
+            }
+            start.get_or_insert(i);
+            chars += 1;
+        }
+        if let Some(s) = start {
+            spans.push(s..text.len());
+        }
+        spans
+    }
+
+    fn token_budget(&self) -> usize {
+        self.budget
+    }
+}
+
+/// Exact counts from the model's own tokenizer (local Candle models).
+pub struct TokenizerCounter {
+    tokenizer: Tokenizer,
+    budget: usize,
+}
+
+impl TokenizerCounter {
+    pub fn new(tokenizer: Tokenizer, budget: usize) -> Self {
+        Self { tokenizer, budget: budget.max(1) }
+    }
+}
+
+impl TokenCounter for TokenizerCounter {
+    fn token_spans(&self, text: &str) -> Vec<Range<usize>> {
+        let Ok(encoding) = self.tokenizer.encode(text, false) else {
+            return ApproxTokenCounter::new(self.budget).token_spans(text);
+        };
+        encoding
+            .get_offsets()
+            .iter()
+            .zip(encoding.get_special_tokens_mask())
+            .filter(|(_, special)| **special == 0)
+            .map(|(&(start, end), _)| start..end)
+            .filter(|span| span.end <= text.len() && text.is_char_boundary(span.start) && text.is_char_boundary(span.end))
+            .collect()
+    }
+
+    fn token_budget(&self) -> usize {
+        self.budget
+    }
+}
+
+/// The result of checking one text against a model's input limit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Windows<'a> {
+    /// Tokens in the whole text.
+    pub tokens: usize,
+    /// The text itself when it fits, else overlapping slices that each fit.
+    pub windows: Vec<&'a str>,
+    /// Tokens in each window, for weighting their embeddings.
+    pub window_tokens: Vec<usize>,
+}
+
+impl Windows<'_> {
+    pub fn is_split(&self) -> bool {
+        self.windows.len() > 1
+    }
+}
+
+/// Cut `text` into windows of at most `counter.token_budget()` tokens, each
+/// overlapping the previous one by an eighth of the budget.
+pub fn windows<'a>(text: &'a str, counter: &dyn TokenCounter) -> Windows<'a> {
+    let spans = counter.token_spans(text);
+    let budget = counter.token_budget().max(1);
+    if spans.len() <= budget {
+        return Windows { tokens: spans.len(), windows: vec![text], window_tokens: vec![spans.len()] };
+    }
+    let stride = (budget - budget / 8).max(1);
+    let mut windows = Vec::new();
+    let mut window_tokens = Vec::new();
+    let mut start = 0;
+    loop {
+        let end = (start + budget).min(spans.len());
+        windows.push(&text[spans[start].start..spans[end - 1].end]);
+        window_tokens.push(end - start);
+        if end == spans.len() {
+            break;
+        }
+        start += stride;
+    }
+    Windows { tokens: spans.len(), windows, window_tokens }
+}
@@ -0,0 +1,131 @@
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Sidecar database file, next to `local-memory.db` in `storage_path`.
+pub const EMBEDDING_CACHE_FILE: &str = "embedding_cache.db";
+
+/// Lifetime counters of one model's cache.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EmbeddingCacheStats {
+    pub model: String,
+    pub entries: i64,
+    pub hits: i64,
+    pub misses: i64,
+}
+
+impl EmbeddingCacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 { 0.0 } else { self.hits as f64 / lookups as f64 }
+    }
+}
+
+/// Persistent text → vector cache. Entries are keyed by a hash of the model
+/// key, the prefix the provider prepends (query or document) and the text,
+/// so switching models or prefixes never returns a stale vector.
+pub struct EmbeddingCache {
+    conn: Mutex<Connection>,
+}
+
+impl EmbeddingCache {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        let _ = conn.pragma_update(None, "journal_mode", "WAL");
+        let cache = Self { conn: Mutex::new(conn) };
+        cache.initialize()?;
+        Ok(cache)
+    }
+
+    /// Open `embedding_cache.db` in `storage_path`, creating the directory.
+    pub fn open_in(storage_path: &Path) -> Result<Self> {
+        std::fs::create_dir_all(storage_path)?;
+        Self::open(storage_path.join(EMBEDDING_CACHE_FILE))
+    }
+
+    fn initialize(&self) -> Result<()> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS embedding_cache (
+                key TEXT PRIMARY KEY,
+                model TEXT,
+                vector BLOB,
+                created_at INTEGER
+            )",
+            [],
+        )?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS embedding_cache_stats (
+                model TEXT PRIMARY KEY,
+                hits INTEGER DEFAULT 0,
+                misses INTEGER DEFAULT 0
+            )",
+            [],
+        )?;
+        Ok(())
+    }
+
+    pub fn key(model: &str, prefix: &str, text: &str) -> String {
+        let mut hasher = Sha256::new();
+        for part in [model, prefix, text] {
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part.as_bytes());
+        }
+        hex::encode(hasher.finalize())
+    }
+
+    pub fn get(&self, model: &str, prefix: &str, text: &str) -> Result<Option<Vec<f32>>> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        let blob: Option<Vec<u8>> = conn
+            .query_row(
+                "SELECT vector FROM embedding_cache WHERE key = ?",
+                params![Self::key(model, prefix, text)],
+                |r| r.get(0),
+            )
+            .optional()?;
+        Ok(blob.map(|b| {
+            b.chunks_exact(4)
+                .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+                .collect()
+        }))
+    }
+
+    pub fn put(&self, model: &str, prefix: &str, text: &str, vector: &[f32]) -> Result<()> {
+        let blob: Vec<u8> = vector.iter().flat_map(|x| x.to_le_bytes()).collect();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs();
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        conn.execute(
+            "INSERT OR REPLACE INTO embedding_cache (key, model, vector, created_at) VALUES (?, ?, ?, ?)",
+            params![Self::key(model, prefix, text), model, blob, now],
+        )?;
+        Ok(())
+    }
+
+    /// Add to the lifetime hit / miss counters of `model`.
+    pub fn record(&self, model: &str, hits: usize, misses: usize) -> Result<()> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        conn.execute(
+            "INSERT INTO embedding_cache_stats (model, hits, misses) VALUES (?1, ?2, ?3)
+             ON CONFLICT(model) DO UPDATE SET hits = hits + ?2, misses = misses + ?3",
+            params![model, hits as i64, misses as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Per-model entry counts and hit / miss counters.
+    pub fn stats(&self) -> Result<Vec<EmbeddingCacheStats>> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        let mut stmt = conn.prepare(
+            "SELECT s.model, (SELECT count(*) FROM embedding_cache c WHERE c.model = s.model), s.hits, s.misses
+             FROM embedding_cache_stats s ORDER BY s.model",
+        )?;
+        let rows = stmt.query_map([], |r| {
+            Ok(EmbeddingCacheStats { model: r.get(0)?, entries: r.get(1)?, hits: r.get(2)?, misses: r.get(3)? })
+        })?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+}
@@ -1,5 +1,7 @@
 pub mod sqlite;
 pub mod registry;
+pub mod embedding_cache;
 
 pub use sqlite::SqliteDatabase;
 pub use registry::Registry;
+pub use embedding_cache::{EmbeddingCache, EmbeddingCacheStats};
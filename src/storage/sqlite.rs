@@ -3,9 +3,183 @@ use rusqlite::{params, Connection};
 use sqlite_vec::sqlite3_vec_init;
 use uuid::Uuid;
 use zerocopy::IntoBytes;
+use serde::Serialize;
 use serde_json::{json, Value};
 use std::sync::Mutex;
 
+/// A contradiction between newly ingested text and existing knowledge.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConflictRecord {
+    pub id: Uuid,
+    pub namespace: String,
+    /// The new statement that contradicts existing knowledge.
+    pub claim: String,
+    /// The existing fact it contradicts, as reported by the extractor.
+    pub existing: String,
+    pub entity_name: String,
+    /// Contradicted entity / relationship (the existing fact).
+    pub entity_id: Option<Uuid>,
+    pub relationship_id: Option<Uuid>,
+    /// Entity / relationship created from the new claim.
+    pub claim_entity_id: Option<Uuid>,
+    pub claim_relationship_id: Option<Uuid>,
+    pub source_documents: Vec<String>,
+    pub detected_at: u64,
+    pub status: ConflictStatus,
+    pub resolution: Option<ConflictResolution>,
+    pub resolved_at: Option<u64>,
+}
+
+/// A file indexed by directory ingestion, as of its last ingested content.
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceRecord {
+    /// Absolute path, as stored in the documents' `source_file`.
+    pub path: String,
+    pub namespace: String,
+    /// SHA-256 of the file's bytes.
+    pub content_hash: String,
+    /// Document (or chunk parent) holding the latest version.
+    pub document_id: Uuid,
+    pub indexed_at: u64,
+}
+
+/// A background job over the chunks of one stored document: knowledge
+/// graph extraction for `memorize`. Progress is saved after every chunk, so
+/// an interrupted job resumes where it stopped.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobRecord {
+    pub id: Uuid,
+    pub kind: String,
+    pub namespace: String,
+    /// Document (or chunk parent) the job works on.
+    pub document_id: Uuid,
+    /// Chunks to process, in order; the first `completed` are done.
+    #[serde(skip)]
+    pub items: Vec<Uuid>,
+    pub status: JobStatus,
+    pub total: usize,
+    pub completed: usize,
+    /// Chunks whose processing failed; the last error is kept.
+    pub failed: usize,
+    pub error: Option<String>,
+    /// MCP progress token of the request that queued the job.
+    pub progress_token: Option<Value>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+/// Where a background job is in its lifecycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, serde::Deserialize, schemars::JsonSchema, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Done => "done",
+            JobStatus::Failed => "failed",
+        }
+    }
+
+    /// Whether the job has stopped for good.
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobStatus::Done | JobStatus::Failed)
+    }
+}
+
+/// Where a conflict is in its resolution workflow.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, serde::Deserialize, schemars::JsonSchema, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictStatus {
+    #[default]
+    Open,
+    Resolved,
+    Dismissed,
+}
+
+impl ConflictStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConflictStatus::Open => "open",
+            ConflictStatus::Resolved => "resolved",
+            ConflictStatus::Dismissed => "dismissed",
+        }
+    }
+}
+
+impl std::fmt::Display for ConflictStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// How a conflict is settled. The losing fact is marked as superseded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, serde::Deserialize, schemars::JsonSchema, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+pub enum ConflictResolution {
+    /// The new claim wins; the existing fact is superseded.
+    #[serde(alias = "accept", alias = "new")]
+    #[value(alias = "accept", alias = "new")]
+    AcceptClaim,
+    /// The existing fact wins; the new claim is superseded.
+    #[serde(alias = "keep", alias = "existing")]
+    #[value(alias = "keep", alias = "existing")]
+    KeepExisting,
+    /// Not a real conflict; nothing is superseded.
+    Dismiss,
+}
+
+impl ConflictResolution {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConflictResolution::AcceptClaim => "accept_claim",
+            ConflictResolution::KeepExisting => "keep_existing",
+            ConflictResolution::Dismiss => "dismiss",
+        }
+    }
+
+    /// The status a conflict settled this way ends up in.
+    pub fn status(&self) -> ConflictStatus {
+        match self {
+            ConflictResolution::Dismiss => ConflictStatus::Dismissed,
+            _ => ConflictStatus::Resolved,
+        }
+    }
+}
+
+/// Status enums are stored as their `as_str` names.
+macro_rules! sql_value_enum {
+    ($($ty:ty),*) => {$(
+        impl rusqlite::ToSql for $ty {
+            fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+                Ok(self.as_str().into())
+            }
+        }
+
+        impl rusqlite::types::FromSql for $ty {
+            fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+                let name = value.as_str()?;
+                <$ty as clap::ValueEnum>::from_str(name, false)
+                    .map_err(|e| rusqlite::types::FromSqlError::Other(e.into()))
+            }
+        }
+    )*};
+}
+
+sql_value_enum!(JobStatus, ConflictStatus, ConflictResolution);
+
+fn parse_opt_uuid(value: Option<String>) -> Option<Uuid> {
+    value.and_then(|v| Uuid::parse_str(&v).ok())
+}
+
 pub struct SqliteDatabase {
     conn: Mutex<Connection>,
     dimension: usize,
@@ -103,6 +277,60 @@ impl SqliteDatabase {
             []
         )?;
 
+        // Contradictions detected during extraction, with their resolution state
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS conflicts (
+                id TEXT PRIMARY KEY,
+                namespace TEXT DEFAULT 'default',
+                claim TEXT,
+                existing TEXT,
+                entity_name TEXT,
+                entity_id TEXT,
+                relationship_id TEXT,
+                claim_entity_id TEXT,
+                claim_relationship_id TEXT,
+                source_documents TEXT,
+                detected_at INTEGER,
+                status TEXT DEFAULT 'open',
+                resolution TEXT,
+                resolved_at INTEGER
+            )",
+            []
+        )?;
+
+        // Files indexed from directories, with the hash of their last ingested content
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS sources (
+                path TEXT,
+                namespace TEXT DEFAULT 'default',
+                content_hash TEXT,
+                document_id TEXT,
+                indexed_at INTEGER,
+                PRIMARY KEY(path, namespace)
+            )",
+            []
+        )?;
+
+        // Background jobs (graph extraction), resumed after a restart
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS jobs (
+                id TEXT PRIMARY KEY,
+                kind TEXT,
+                namespace TEXT DEFAULT 'default',
+                document_id TEXT,
+                items TEXT,
+                status TEXT DEFAULT 'queued',
+                completed INTEGER DEFAULT 0,
+                failed INTEGER DEFAULT 0,
+                error TEXT,
+                progress_token TEXT,
+                owner TEXT,
+                created_at INTEGER,
+                updated_at INTEGER
+            )",
+            []
+        )?;
+
         // MULTI-STAGE VECTOR TABLES
         let s2_dim = self.dimension / 3;
 
@@ -141,6 +369,9 @@ impl SqliteDatabase {
             }
 
             if table == "entities" {
+                if !columns.contains(&"superseded_by".to_string()) {
+                    conn.execute("ALTER TABLE entities ADD COLUMN superseded_by TEXT", [])?;
+                }
                 if !columns.contains(&"community_id".to_string()) {
                     conn.execute("ALTER TABLE entities ADD COLUMN community_id TEXT", [])?;
                 }
@@ -166,6 +397,20 @@ impl SqliteDatabase {
                 }
             }
         }
+
+        let mut info = conn.prepare("PRAGMA table_info(relationships)")?;
+        let columns: Vec<String> = info.query_map([], |row| row.get(1))?
+            .collect::<Result<Vec<_>, _>>()?;
+        if !columns.contains(&"superseded_by".to_string()) {
+            conn.execute("ALTER TABLE relationships ADD COLUMN superseded_by TEXT", [])?;
+        }
+
+        let mut info = conn.prepare("PRAGMA table_info(jobs)")?;
+        let columns: Vec<String> = info.query_map([], |row| row.get(1))?
+            .collect::<Result<Vec<_>, _>>()?;
+        if !columns.contains(&"owner".to_string()) {
+            conn.execute("ALTER TABLE jobs ADD COLUMN owner TEXT", [])?;
+        }
         Ok(())
     }
 
@@ -191,9 +436,35 @@ impl SqliteDatabase {
 
         let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
 
-        let (stable_id, version) = if !title.is_empty() && title != "Untitled" {
-            let mut stmt = conn.prepare("SELECT stable_id, MAX(version) FROM documents WHERE title = ? AND namespace = ? GROUP BY stable_id")?;
-            let existing: Option<(String, i32)> = stmt.query_row(params![title, namespace], |row| Ok((row.get(0)?, row.get(1)?))).ok();
+        // Chunks of one document share its version; a file is versioned by
+        // its path, anything else by its title.
+        let sibling: Option<(String, i32)> = match parent_id {
+            Some(parent_id) => conn
+                .query_row(
+                    "SELECT stable_id, version FROM documents WHERE parent_id = ? AND namespace = ? LIMIT 1",
+                    params![parent_id, namespace],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .ok(),
+            None => None,
+        };
+        let source_file = metadata.get("source_file").and_then(|v| v.as_str());
+
+        let (stable_id, version) = if let Some(sibling) = sibling {
+            sibling
+        } else if source_file.is_some() || (!title.is_empty() && title != "Untitled") {
+            let existing: Option<(String, i32)> = match source_file {
+                Some(source_file) => conn.query_row(
+                    "SELECT stable_id, MAX(version) FROM documents WHERE json_extract(metadata, '$.source_file') = ? AND namespace = ? GROUP BY stable_id",
+                    params![source_file, namespace],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                ),
+                None => conn.query_row(
+                    "SELECT stable_id, MAX(version) FROM documents WHERE title = ? AND namespace = ? GROUP BY stable_id",
+                    params![title, namespace],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                ),
+            }.ok();
 
             if let Some((s_id, v)) = existing {
                 conn.execute(
@@ -299,6 +570,31 @@ impl SqliteDatabase {
         }
     }
 
+    /// Shallow-merge the keys of `patch` into a document's metadata.
+    pub fn merge_document_metadata(&self, id: Uuid, patch: &Value) -> Result<()> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        let meta_str: String = conn.query_row("SELECT metadata FROM documents WHERE id = ?", params![id.to_string()], |r| r.get(0))?;
+        let mut meta: Value = serde_json::from_str(&meta_str)?;
+        if let (Some(obj), Some(patch_obj)) = (meta.as_object_mut(), patch.as_object()) {
+            for (k, v) in patch_obj {
+                obj.insert(k.clone(), v.clone());
+            }
+        }
+        conn.execute("UPDATE documents SET metadata = ? WHERE id = ?", params![serde_json::to_string(&meta)?, id.to_string()])?;
+        Ok(())
+    }
+
+    /// Documents whose graph extraction produced no parseable output.
+    pub fn count_failed_extractions(&self) -> Result<i64> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        let count: i64 = conn.query_row(
+            "SELECT count(*) FROM documents WHERE json_extract(metadata, '$.extraction.status') = 'failed'",
+            [],
+            |r| r.get(0),
+        )?;
+        Ok(count)
+    }
+
     pub fn insert_entity_with_namespace(&self, name: &str, entity_type: &str, description: &str, namespace: &str) -> Result<Uuid> {
         let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
         let mut stmt = conn.prepare("SELECT version FROM entities WHERE name = ? AND type = ? AND namespace = ? AND is_latest = 1")?;
@@ -404,20 +700,194 @@ impl SqliteDatabase {
         Ok(())
     }
 
-    pub fn insert_relationship(&self, source_id: Uuid, target_id: Uuid, predicate: &str, description: &str) -> Result<()> {
+    pub fn insert_relationship(&self, source_id: Uuid, target_id: Uuid, predicate: &str, description: &str) -> Result<Uuid> {
         let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        let id = Uuid::new_v4();
         conn.execute(
             "INSERT INTO relationships (id, source_id, target_id, predicate, description, metadata) VALUES (?, ?, ?, ?, ?, ?)",
-            params![Uuid::new_v4().to_string(), source_id.to_string(), target_id.to_string(), predicate, description, "{}"],
+            params![id.to_string(), source_id.to_string(), target_id.to_string(), predicate, description, "{}"],
+        )?;
+        Ok(id)
+    }
+
+    /// Most recent active relationship of `source_id` with the given predicate.
+    pub fn find_relationship(&self, source_id: Uuid, predicate: &str) -> Result<Option<Uuid>> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        let id: Option<String> = conn.query_row(
+            "SELECT id FROM relationships WHERE source_id = ? AND predicate = ? AND superseded_by IS NULL ORDER BY rowid DESC LIMIT 1",
+            params![source_id.to_string(), predicate],
+            |r| r.get(0),
+        ).ok();
+        Ok(parse_opt_uuid(id))
+    }
+
+    /// Record that `doc_id` mentioned this entity (stored in `metadata.source_documents`).
+    pub fn add_entity_source(&self, entity_id: Uuid, doc_id: Uuid) -> Result<()> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        let meta_str: String = conn.query_row("SELECT metadata FROM entities WHERE id = ?", params![entity_id.to_string()], |r| r.get(0))?;
+        let mut meta: Value = serde_json::from_str(&meta_str).unwrap_or_else(|_| json!({}));
+        if let Some(obj) = meta.as_object_mut() {
+            let sources = obj.entry("source_documents").or_insert_with(|| json!([]));
+            if let Some(list) = sources.as_array_mut() && !list.contains(&json!(doc_id.to_string())) {
+                list.push(json!(doc_id.to_string()));
+            }
+        }
+        conn.execute("UPDATE entities SET metadata = ? WHERE id = ?", params![serde_json::to_string(&meta)?, entity_id.to_string()])?;
+        Ok(())
+    }
+
+    pub fn get_entity_sources(&self, entity_id: Uuid) -> Result<Vec<String>> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        let meta_str: Option<String> = conn.query_row("SELECT metadata FROM entities WHERE id = ?", params![entity_id.to_string()], |r| r.get(0)).ok();
+        let meta: Value = meta_str.and_then(|m| serde_json::from_str(&m).ok()).unwrap_or_else(|| json!({}));
+        Ok(meta.get("source_documents")
+            .and_then(|v| v.as_array())
+            .map(|list| list.iter().filter_map(|v| v.as_str().map(String::from)).collect())
+            .unwrap_or_default())
+    }
+
+    pub fn insert_conflict(&self, conflict: &ConflictRecord) -> Result<()> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        conn.execute(
+            "INSERT INTO conflicts (id, namespace, claim, existing, entity_name, entity_id, relationship_id, claim_entity_id, claim_relationship_id, source_documents, detected_at, status, resolution, resolved_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                conflict.id.to_string(),
+                conflict.namespace,
+                conflict.claim,
+                conflict.existing,
+                conflict.entity_name,
+                conflict.entity_id.map(|id| id.to_string()),
+                conflict.relationship_id.map(|id| id.to_string()),
+                conflict.claim_entity_id.map(|id| id.to_string()),
+                conflict.claim_relationship_id.map(|id| id.to_string()),
+                serde_json::to_string(&conflict.source_documents)?,
+                conflict.detected_at,
+                conflict.status,
+                conflict.resolution,
+                conflict.resolved_at,
+            ],
         )?;
         Ok(())
     }
 
+    /// List conflicts, newest first, of every status when `status` is `None`.
+    pub fn list_conflicts(&self, status: Option<ConflictStatus>, namespace: Option<&str>, limit: usize) -> Result<Vec<ConflictRecord>> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        let mut stmt = conn.prepare(
+            "SELECT id, namespace, claim, existing, entity_name, entity_id, relationship_id, claim_entity_id, claim_relationship_id, source_documents, detected_at, status, resolution, resolved_at
+             FROM conflicts WHERE (?1 IS NULL OR status = ?1) AND (?2 IS NULL OR namespace = ?2)
+             ORDER BY detected_at DESC, rowid DESC LIMIT ?3"
+        )?;
+        let rows = stmt.query_map(params![status, namespace, limit], Self::conflict_from_row)?;
+        let mut results = Vec::new();
+        for row in rows { results.push(row?); }
+        Ok(results)
+    }
+
+    pub fn get_conflict(&self, id: Uuid) -> Result<Option<ConflictRecord>> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        Self::query_conflict(&conn, id)
+    }
+
+    fn query_conflict(conn: &Connection, id: Uuid) -> Result<Option<ConflictRecord>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, namespace, claim, existing, entity_name, entity_id, relationship_id, claim_entity_id, claim_relationship_id, source_documents, detected_at, status, resolution, resolved_at
+             FROM conflicts WHERE id = ?"
+        )?;
+        let mut rows = stmt.query_map(params![id.to_string()], Self::conflict_from_row)?;
+        Ok(rows.next().transpose()?)
+    }
+
+    fn conflict_from_row(row: &rusqlite::Row) -> rusqlite::Result<ConflictRecord> {
+        let id: String = row.get(0)?;
+        let sources: Option<String> = row.get(9)?;
+        Ok(ConflictRecord {
+            id: Uuid::parse_str(&id).unwrap_or_default(),
+            namespace: row.get(1)?,
+            claim: row.get(2)?,
+            existing: row.get(3)?,
+            entity_name: row.get(4)?,
+            entity_id: parse_opt_uuid(row.get(5)?),
+            relationship_id: parse_opt_uuid(row.get(6)?),
+            claim_entity_id: parse_opt_uuid(row.get(7)?),
+            claim_relationship_id: parse_opt_uuid(row.get(8)?),
+            source_documents: sources.and_then(|s| serde_json::from_str(&s).ok()).unwrap_or_default(),
+            detected_at: row.get(10)?,
+            status: row.get(11)?,
+            resolution: row.get(12)?,
+            resolved_at: row.get(13)?,
+        })
+    }
+
+    /// Settle an open conflict and mark the losing entity / relationship as superseded.
+    pub fn resolve_conflict(&self, id: Uuid, resolution: ConflictResolution) -> Result<ConflictRecord> {
+        let mut conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        let tx = conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+        let marker = id.to_string();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs();
+
+        // Claim the conflict before touching any facts: of two resolvers
+        // (possibly in different processes) only one moves it out of "open".
+        let claimed = tx.execute(
+            "UPDATE conflicts SET status = ?, resolution = ?, resolved_at = ? WHERE id = ? AND status = ?",
+            params![resolution.status(), resolution, now, marker, ConflictStatus::Open],
+        )?;
+        let conflict = Self::query_conflict(&tx, id)?
+            .ok_or_else(|| anyhow::anyhow!("Conflict not found: {}", id))?;
+        if claimed == 0 {
+            anyhow::bail!("Conflict {} is already {}", id, conflict.status);
+        }
+
+        // Entity versions only swap when the claim actually produced a different row.
+        let distinct_entities = match (conflict.entity_id, conflict.claim_entity_id) {
+            (Some(old), Some(new)) if old != new => Some((old, new)),
+            _ => None,
+        };
+
+        let (losing_entity, winning_entity, losing_relationship) = match resolution {
+            ConflictResolution::AcceptClaim => (
+                distinct_entities.map(|(old, _)| old),
+                distinct_entities.map(|(_, new)| new),
+                conflict.relationship_id,
+            ),
+            ConflictResolution::KeepExisting => (
+                distinct_entities.map(|(_, new)| new),
+                distinct_entities.map(|(old, _)| old),
+                conflict.claim_relationship_id,
+            ),
+            ConflictResolution::Dismiss => (None, None, None),
+        };
+
+        if let Some(loser) = losing_entity {
+            tx.execute("UPDATE entities SET is_latest = 0, superseded_by = ? WHERE id = ?", params![marker, loser.to_string()])?;
+        }
+        if let Some(winner) = winning_entity {
+            tx.execute("UPDATE entities SET is_latest = 1, superseded_by = NULL WHERE id = ?", params![winner.to_string()])?;
+        }
+        if let Some(loser) = losing_relationship {
+            tx.execute("UPDATE relationships SET superseded_by = ? WHERE id = ?", params![marker, loser.to_string()])?;
+        }
+
+        tx.commit()?;
+        drop(conn);
+
+        self.get_conflict(id)?.ok_or_else(|| anyhow::anyhow!("Conflict not found: {}", id))
+    }
+
+    pub fn count_conflicts(&self, status: ConflictStatus) -> Result<i64> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        let count: i64 = conn.query_row("SELECT count(*) FROM conflicts WHERE status = ?", params![status], |r| r.get(0))?;
+        Ok(count)
+    }
+
     pub fn get_neighborhood_with_namespace(&self, entity_name: &str, namespace: &str) -> Result<Value> {
         let entity = self.get_entity_by_name_with_namespace(entity_name, namespace)?;
         if let Some((id, etype, desc)) = entity {
             let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
-            let mut stmt = conn.prepare("SELECT r.predicate, e.name FROM relationships r JOIN entities e ON r.target_id = e.id WHERE r.source_id = ?")?;
+            let mut stmt = conn.prepare("SELECT r.predicate, e.name FROM relationships r JOIN entities e ON r.target_id = e.id WHERE r.source_id = ? AND r.superseded_by IS NULL")?;
             let relations = stmt.query_map(params![id.to_string()], |row| {
                 Ok(json!({"predicate": row.get::<_, String>(0)?, "target": row.get::<_, String>(1)?}))
             })?.collect::<Result<Vec<_>, _>>()?;
@@ -450,6 +920,7 @@ impl SqliteDatabase {
              JOIN entities e2 ON r.target_id = e2.id 
              WHERE e1.is_latest = 1 AND e2.is_latest = 1 
              AND e1.decay_factor > 0.0 AND e2.decay_factor > 0.0 
+             AND r.superseded_by IS NULL
              LIMIT ?"
         )?;
         let rows = stmt.query_map(params![limit], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
@@ -464,6 +935,224 @@ impl SqliteDatabase {
         Ok(count)
     }
 
+    pub fn get_source(&self, path: &str, namespace: &str) -> Result<Option<SourceRecord>> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        let mut stmt = conn.prepare(
+            "SELECT path, namespace, content_hash, document_id, indexed_at FROM sources WHERE path = ? AND namespace = ?"
+        )?;
+        let mut rows = stmt.query_map(params![path, namespace], Self::source_from_row)?;
+        Ok(rows.next().transpose()?)
+    }
+
+    /// Tracked files in `namespace` whose path starts with `prefix`.
+    pub fn list_sources(&self, prefix: &str, namespace: &str) -> Result<Vec<SourceRecord>> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        let mut stmt = conn.prepare(
+            "SELECT path, namespace, content_hash, document_id, indexed_at FROM sources
+             WHERE namespace = ? AND substr(path, 1, length(?2)) = ?2 ORDER BY path"
+        )?;
+        let rows = stmt.query_map(params![namespace, prefix], Self::source_from_row)?;
+        let mut results = Vec::new();
+        for row in rows { results.push(row?); }
+        Ok(results)
+    }
+
+    pub fn upsert_source(&self, source: &SourceRecord) -> Result<()> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        conn.execute(
+            "INSERT INTO sources (path, namespace, content_hash, document_id, indexed_at) VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT(path, namespace) DO UPDATE SET content_hash = excluded.content_hash, document_id = excluded.document_id, indexed_at = excluded.indexed_at",
+            params![source.path, source.namespace, source.content_hash, source.document_id.to_string(), source.indexed_at],
+        )?;
+        Ok(())
+    }
+
+    /// Stop tracking a file and take its documents out of search; their
+    /// versions stay in the history.
+    pub fn remove_source(&self, path: &str, namespace: &str) -> Result<()> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        conn.execute(
+            "UPDATE documents SET is_latest = 0 WHERE namespace = ? AND json_extract(metadata, '$.source_file') = ?",
+            params![namespace, path],
+        )?;
+        conn.execute("DELETE FROM sources WHERE path = ? AND namespace = ?", params![path, namespace])?;
+        Ok(())
+    }
+
+    fn source_from_row(row: &rusqlite::Row) -> rusqlite::Result<SourceRecord> {
+        let document_id: String = row.get(3)?;
+        Ok(SourceRecord {
+            path: row.get(0)?,
+            namespace: row.get(1)?,
+            content_hash: row.get(2)?,
+            document_id: Uuid::parse_str(&document_id).unwrap_or_default(),
+            indexed_at: row.get(4)?,
+        })
+    }
+
+    /// Number of searchable (latest-version) documents, chunks included, from `source_file`.
+    pub fn count_latest_documents_by_source(&self, source_file: &str, namespace: &str) -> Result<i32> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        let count: i32 = conn.query_row(
+            "SELECT count(*) FROM documents WHERE namespace = ? AND is_latest = 1 AND json_extract(metadata, '$.source_file') = ?",
+            params![namespace, source_file],
+            |r| r.get(0),
+        )?;
+        Ok(count)
+    }
+
+    /// The stored rows of a document: the document itself, or its chunks in
+    /// insertion order.
+    pub fn list_document_chunks(&self, document_id: Uuid) -> Result<Vec<Uuid>> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        let mut stmt = conn.prepare("SELECT id FROM documents WHERE id = ?1 OR parent_id = ?1 ORDER BY rowid")?;
+        let rows = stmt.query_map(params![document_id.to_string()], |row| row.get::<_, String>(0))?;
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(Uuid::parse_str(&row?)?);
+        }
+        Ok(results)
+    }
+
+    pub fn insert_job(&self, job: &JobRecord) -> Result<()> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        conn.execute(
+            "INSERT INTO jobs (id, kind, namespace, document_id, items, status, completed, failed, error, progress_token, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                job.id.to_string(),
+                job.kind,
+                job.namespace,
+                job.document_id.to_string(),
+                serde_json::to_string(&job.items)?,
+                job.status,
+                job.completed,
+                job.failed,
+                job.error,
+                job.progress_token.as_ref().map(|t| t.to_string()),
+                job.created_at,
+                job.updated_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Save the status and progress of a job `owner` is running. False if
+    /// the job was requeued in the meantime, and so is no longer `owner`'s.
+    pub fn update_job(&self, job: &JobRecord, owner: Uuid) -> Result<bool> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        let updated = conn.execute(
+            "UPDATE jobs SET status = ?, completed = ?, failed = ?, error = ?, updated_at = ?
+             WHERE id = ? AND status = ? AND owner = ?",
+            params![job.status, job.completed, job.failed, job.error, job.updated_at, job.id.to_string(), JobStatus::Running, owner.to_string()],
+        )?;
+        Ok(updated == 1)
+    }
+
+    /// Atomically move a queued job to running under `owner`. False if
+    /// another worker (possibly in another process sharing the database)
+    /// claimed it first.
+    pub fn claim_job(&self, id: Uuid, owner: Uuid, now: u64) -> Result<bool> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        let claimed = conn.execute(
+            "UPDATE jobs SET status = ?, owner = ?, updated_at = ? WHERE id = ? AND status = ?",
+            params![JobStatus::Running, owner.to_string(), now, id.to_string(), JobStatus::Queued],
+        )?;
+        Ok(claimed == 1)
+    }
+
+    /// Record that `owner` is still working on a running job, so it is not
+    /// taken for stale. False if the job is no longer `owner`'s.
+    pub fn renew_job(&self, id: Uuid, owner: Uuid, now: u64) -> Result<bool> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        let renewed = conn.execute(
+            "UPDATE jobs SET updated_at = ? WHERE id = ? AND status = ? AND owner = ?",
+            params![now, id.to_string(), JobStatus::Running, owner.to_string()],
+        )?;
+        Ok(renewed == 1)
+    }
+
+    /// Put a job `owner` is running back in the queue.
+    pub fn requeue_job(&self, id: Uuid, owner: Uuid) -> Result<bool> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        let requeued = conn.execute(
+            "UPDATE jobs SET status = ?, owner = NULL WHERE id = ? AND status = ? AND owner = ?",
+            params![JobStatus::Queued, id.to_string(), JobStatus::Running, owner.to_string()],
+        )?;
+        Ok(requeued == 1)
+    }
+
+    /// Put running jobs not updated since `stale_before` back in the queue;
+    /// their owner stopped renewing them and is assumed to have died.
+    /// Returns how many were requeued.
+    pub fn requeue_stale_jobs(&self, stale_before: u64) -> Result<usize> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        Ok(conn.execute(
+            "UPDATE jobs SET status = ?, owner = NULL WHERE status = ? AND updated_at < ?",
+            params![JobStatus::Queued, JobStatus::Running, stale_before],
+        )?)
+    }
+
+    pub fn get_job(&self, id: Uuid) -> Result<Option<JobRecord>> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        let mut stmt = conn.prepare(
+            "SELECT id, kind, namespace, document_id, items, status, completed, failed, error, progress_token, created_at, updated_at
+             FROM jobs WHERE id = ?"
+        )?;
+        let mut rows = stmt.query_map(params![id.to_string()], Self::job_from_row)?;
+        Ok(rows.next().transpose()?)
+    }
+
+    /// List jobs, newest first.
+    pub fn list_jobs(&self, status: Option<JobStatus>, limit: usize) -> Result<Vec<JobRecord>> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        let mut stmt = conn.prepare(
+            "SELECT id, kind, namespace, document_id, items, status, completed, failed, error, progress_token, created_at, updated_at
+             FROM jobs WHERE (?1 IS NULL OR status = ?1) ORDER BY created_at DESC, rowid DESC LIMIT ?2"
+        )?;
+        let rows = stmt.query_map(params![status, limit], Self::job_from_row)?;
+        let mut results = Vec::new();
+        for row in rows { results.push(row?); }
+        Ok(results)
+    }
+
+    /// Jobs that are queued or were running when the process stopped, oldest first.
+    pub fn list_unfinished_jobs(&self) -> Result<Vec<JobRecord>> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        let mut stmt = conn.prepare(
+            "SELECT id, kind, namespace, document_id, items, status, completed, failed, error, progress_token, created_at, updated_at
+             FROM jobs WHERE status IN (?, ?) ORDER BY created_at, rowid"
+        )?;
+        let rows = stmt.query_map(params![JobStatus::Queued, JobStatus::Running], Self::job_from_row)?;
+        let mut results = Vec::new();
+        for row in rows { results.push(row?); }
+        Ok(results)
+    }
+
+    fn job_from_row(row: &rusqlite::Row) -> rusqlite::Result<JobRecord> {
+        let id: String = row.get(0)?;
+        let document_id: String = row.get(3)?;
+        let items: Vec<Uuid> = row.get::<_, Option<String>>(4)?
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        let token: Option<String> = row.get(9)?;
+        Ok(JobRecord {
+            id: Uuid::parse_str(&id).unwrap_or_default(),
+            kind: row.get(1)?,
+            namespace: row.get(2)?,
+            document_id: Uuid::parse_str(&document_id).unwrap_or_default(),
+            total: items.len(),
+            items,
+            status: row.get(5)?,
+            completed: row.get(6)?,
+            failed: row.get(7)?,
+            error: row.get(8)?,
+            progress_token: token.and_then(|t| serde_json::from_str(&t).ok()),
+            created_at: row.get(10)?,
+            updated_at: row.get(11)?,
+        })
+    }
+
     pub fn update_entity_community(&self, entity_id: Uuid, community_id: &str) -> Result<()> {
         let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
         conn.execute("UPDATE entities SET community_id = ? WHERE id = ?", params![community_id, entity_id.to_string()])?;
@@ -474,7 +1163,8 @@ impl SqliteDatabase {
         let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
         let mut stmt = conn.prepare(
             "SELECT source_id, target_id, predicate FROM relationships 
-             WHERE source_id IN (SELECT id FROM entities WHERE decay_factor > 0.0) 
+             WHERE superseded_by IS NULL
+             AND source_id IN (SELECT id FROM entities WHERE decay_factor > 0.0) 
              AND target_id IN (SELECT id FROM entities WHERE decay_factor > 0.0)"
         )?;
         let rows = stmt.query_map([], |row| {
@@ -526,6 +1216,21 @@ impl SqliteDatabase {
         Ok(results)
     }
 
+    /// Summaries of the communities whose members all belong to `namespace`.
+    pub fn list_community_summaries_with_namespace(&self, namespace: &str, limit: usize) -> Result<Vec<(String, String, String)>> {
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
+        let mut stmt = conn.prepare(
+            "SELECT id, title, summary FROM communities
+             WHERE id IN (SELECT community_id FROM entities WHERE namespace = ?1)
+             AND id NOT IN (SELECT community_id FROM entities WHERE namespace != ?1 AND community_id IS NOT NULL)
+             LIMIT ?2"
+        )?;
+        let rows = stmt.query_map(params![namespace, limit], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+        let mut results = Vec::new();
+        for row in rows { results.push(row?); }
+        Ok(results)
+    }
+
     #[allow(clippy::type_complexity)]
     pub fn list_entities_full(&self, limit: usize) -> Result<Vec<(String, String, Option<String>, String)>> {
         let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("Mutex error: {}", e))?;
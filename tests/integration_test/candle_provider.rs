@@ -7,12 +7,14 @@
 use anyhow::Result;
 use async_trait::async_trait;
 use candle_core::Device;
-use edgequake_llm::{EmbeddingProvider, LLMProvider, LlmError};
+use edgequake_llm::{ChatMessage, CompletionOptions, EmbeddingProvider, ImageData, LLMProvider, LlmError};
 use local_memory::model::candle::{
-    backend::{ModelBackend, ModelRole},
+    backend::{GenerateOptions, ModelBackend, ModelRole, Pooling},
     registry::{ArchConfig, EmbeddingConfig, ModelRegistry},
     CandleProvider,
 };
+use local_memory::model::RetrievalEmbedder;
+use std::sync::{Arc, Mutex};
 use tokenizers::Tokenizer;
 
 // ── Mock backends ─────────────────────────────────────────────────────────────
@@ -32,6 +34,7 @@ impl ModelBackend for MockEmbeddingBackend {
     async fn generate(
         &self,
         _prompt: &str,
+        _options: &GenerateOptions,
         _tokenizer: &Tokenizer,
         _device: &Device,
     ) -> Result<String, LlmError> {
@@ -47,8 +50,51 @@ impl ModelBackend for MockEmbeddingBackend {
     }
 }
 
+/// Records the token ids of every batch and embeds each sequence as `[len]`.
+type Batches = Arc<Mutex<Vec<Vec<Vec<u32>>>>>;
+
+struct RecordingEmbeddingBackend {
+    batches: Batches,
+    max_positions: Option<usize>,
+}
+
+#[async_trait]
+impl ModelBackend for RecordingEmbeddingBackend {
+    fn role(&self) -> ModelRole {
+        ModelRole::Embedding
+    }
+    fn dimension(&self) -> usize {
+        1
+    }
+    fn max_positions(&self) -> Option<usize> {
+        self.max_positions
+    }
+    async fn generate(
+        &self,
+        _prompt: &str,
+        _options: &GenerateOptions,
+        _tokenizer: &Tokenizer,
+        _device: &Device,
+    ) -> Result<String, LlmError> {
+        Err(LlmError::Unknown("embedding backend cannot generate".into()))
+    }
+    async fn embed_batch(
+        &self,
+        token_ids: &[Vec<u32>],
+        _type_ids: &[Vec<u32>],
+        _device: &Device,
+    ) -> Result<Vec<Vec<f32>>, LlmError> {
+        self.batches.lock().unwrap().push(token_ids.to_vec());
+        Ok(token_ids.iter().map(|ids| vec![ids.len() as f32]).collect())
+    }
+}
+
+/// Records every prompt and option set it receives.
+type Calls = Arc<Mutex<Vec<(String, GenerateOptions)>>>;
+
 struct MockGenerationBackend {
     response: String,
+    calls: Calls,
 }
 
 #[async_trait]
@@ -61,10 +107,12 @@ impl ModelBackend for MockGenerationBackend {
     }
     async fn generate(
         &self,
-        _prompt: &str,
+        prompt: &str,
+        options: &GenerateOptions,
         _tokenizer: &Tokenizer,
         _device: &Device,
     ) -> Result<String, LlmError> {
+        self.calls.lock().unwrap().push((prompt.to_string(), options.clone()));
         Ok(self.response.clone())
     }
     async fn embed_batch(
@@ -122,6 +170,7 @@ fn embedding_arch_config() -> ArchConfig {
     ArchConfig::Embedding(EmbeddingConfig {
         query_prefix: "search_query: ".to_string(),
         document_prefix: "search_document: ".to_string(),
+        pooling: Pooling::Mean,
     })
 }
 
@@ -141,14 +190,21 @@ fn make_embedding_provider() -> CandleProvider {
 }
 
 fn make_generation_provider() -> CandleProvider {
-    CandleProvider::from_parts(
+    make_recording_provider().0
+}
+
+fn make_recording_provider() -> (CandleProvider, Calls) {
+    let calls = Calls::default();
+    let provider = CandleProvider::from_parts(
         "mock-qwen2",
         make_test_tokenizer(),
         Box::new(MockGenerationBackend {
             response: r#"{"entities":[],"relationships":[]}"#.to_string(),
+            calls: calls.clone(),
         }),
         generation_arch_config(),
-    )
+    );
+    (provider, calls)
 }
 
 // ── EmbeddingProvider tests ───────────────────────────────────────────────────
@@ -191,6 +247,87 @@ async fn test_embed_single_text_succeeds() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_embed_query_and_embed_documents_use_their_prefixes() -> Result<()> {
+    let batches = Batches::default();
+    let p = CandleProvider::from_parts(
+        "mock-bert",
+        make_test_tokenizer(),
+        Box::new(RecordingEmbeddingBackend { batches: batches.clone(), max_positions: None }),
+        ArchConfig::Embedding(EmbeddingConfig {
+            query_prefix: "query ".to_string(),
+            document_prefix: "search ".to_string(),
+            pooling: Pooling::Mean,
+        }),
+    );
+    p.embed_query("hello").await?;
+    // A single document is still a document.
+    p.embed_documents(&["hello".to_string()]).await?;
+    // The plain provider methods embed the text as given.
+    p.embed_one("hello").await?;
+    assert_eq!(*batches.lock().unwrap(), vec![vec![vec![19, 5]], vec![vec![18, 5]], vec![vec![5]]]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_embed_groups_by_token_length_and_keeps_order() -> Result<()> {
+    let batches = Batches::default();
+    let p = CandleProvider::from_parts(
+        "mock-bert",
+        make_test_tokenizer(),
+        Box::new(RecordingEmbeddingBackend { batches: batches.clone(), max_positions: None }),
+        embedding_arch_config(),
+    );
+    let texts: Vec<String> = (0..40).map(|i| "hello ".repeat(1 + i % 4)).collect();
+    let result = p.embed_documents(&texts).await?;
+    // "search_document: " adds two tokens to each text.
+    let lengths: Vec<f32> = (0..40).map(|i| (3 + i % 4) as f32).collect();
+    assert_eq!(result.iter().map(|v| v[0]).collect::<Vec<_>>(), lengths);
+
+    let batches = batches.lock().unwrap();
+    assert_eq!(batches.iter().map(Vec::len).collect::<Vec<_>>(), vec![32, 8]);
+    // The first batch holds the shortest sequences.
+    assert_eq!(batches[0].iter().filter(|ids| ids.len() == 6).count(), 2);
+    assert!(batches[1].iter().all(|ids| ids.len() == 6));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_max_tokens_and_token_budget_follow_the_backend() -> Result<()> {
+    use local_memory::model::TokenCounter;
+    let batches = Batches::default();
+    let p = CandleProvider::from_parts(
+        "mock-bert",
+        make_test_tokenizer(),
+        Box::new(RecordingEmbeddingBackend { batches: batches.clone(), max_positions: Some(6) }),
+        embedding_arch_config(),
+    );
+    assert_eq!(p.max_tokens(), 6);
+    assert_eq!(make_embedding_provider().max_tokens(), 2048);
+    // A configured limit can lower the model's, never raise it.
+    let limited = |max_tokens| {
+        CandleProvider::from_parts(
+            "mock-bert",
+            make_test_tokenizer(),
+            Box::new(RecordingEmbeddingBackend { batches: Batches::default(), max_positions: Some(6) }),
+            embedding_arch_config(),
+        )
+        .with_max_tokens(Some(max_tokens))
+        .max_tokens()
+    };
+    assert_eq!((limited(4), limited(100)), (4, 6));
+
+    // "search_document: " takes two of the six positions.
+    let counter = p.token_counter();
+    assert_eq!(counter.token_budget(), 4);
+    assert_eq!(counter.token_spans("the lazy dog").len(), 3);
+
+    // Direct callers over the limit are truncated rather than failing.
+    p.embed(&["the fox jumped over the lazy dog".to_string()]).await?;
+    assert_eq!(batches.lock().unwrap()[0][0].len(), 6);
+    Ok(())
+}
+
 // ── Role guard: embed on generation model returns error ───────────────────────
 
 #[tokio::test]
@@ -275,6 +412,84 @@ async fn test_complete_with_template_tag_does_not_error() -> Result<()> {
     Ok(())
 }
 
+// ── CompletionOptions and chat ────────────────────────────────────────────────
+
+#[tokio::test]
+async fn test_complete_with_options_forwards_sampling_options() -> Result<()> {
+    let (p, calls) = make_recording_provider();
+    let options = CompletionOptions {
+        max_tokens: Some(32),
+        temperature: Some(0.7),
+        top_p: Some(0.9),
+        stop: Some(vec!["###".to_string()]),
+        ..Default::default()
+    };
+    p.complete_with_options("hello world", &options).await?;
+    let calls = calls.lock().unwrap();
+    let (_, sent) = &calls[0];
+    assert_eq!(sent.max_new_tokens, Some(32));
+    assert_eq!(sent.temperature, Some(0.7f32 as f64));
+    assert_eq!(sent.top_p, Some(0.9f32 as f64));
+    assert_eq!(sent.stop, vec!["###".to_string()]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_complete_with_system_prompt_uses_chat_template() -> Result<()> {
+    let (p, calls) = make_recording_provider();
+    let options = CompletionOptions {
+        system_prompt: Some("be brief".to_string()),
+        ..Default::default()
+    };
+    p.complete_with_options("hello world", &options).await?;
+    let prompt = calls.lock().unwrap()[0].0.clone();
+    assert_eq!(
+        prompt,
+        "<|im_start|>system\nbe brief<|im_end|>\n\
+         <|im_start|>user\nhello world<|im_end|>\n\
+         <|im_start|>assistant\n"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_chat_renders_full_message_list() -> Result<()> {
+    let (p, calls) = make_recording_provider();
+    let messages = vec![
+        ChatMessage::user("first question"),
+        ChatMessage::assistant("first answer"),
+        ChatMessage::user("second question"),
+    ];
+    let options = CompletionOptions {
+        max_tokens: Some(8),
+        ..Default::default()
+    };
+    p.chat(&messages, Some(&options)).await?;
+    let calls = calls.lock().unwrap();
+    let (prompt, sent) = &calls[0];
+    assert!(prompt.contains("first question"));
+    assert!(prompt.contains("<|im_start|>assistant\nfirst answer<|im_end|>"));
+    assert!(prompt.ends_with("second question<|im_end|>\n<|im_start|>assistant\n"));
+    assert_eq!(sent.max_new_tokens, Some(8));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_chat_with_images_returns_error() {
+    let (p, calls) = make_recording_provider();
+    let message = ChatMessage::user_with_images("describe", vec![ImageData::new("iVBORw0KGgo=", "image/png")]);
+    let result = p.chat(&[message], None).await;
+    assert!(result.unwrap_err().to_string().contains("cannot read images"));
+    assert!(calls.lock().unwrap().is_empty(), "nothing must be generated");
+}
+
+#[tokio::test]
+async fn test_chat_on_embedding_model_returns_error() {
+    let p = make_embedding_provider();
+    let result = p.chat(&[ChatMessage::user("hello")], None).await;
+    assert!(result.unwrap_err().to_string().contains("embedding model"));
+}
+
 // ── Token counts ──────────────────────────────────────────────────────────────
 
 #[tokio::test]
@@ -296,3 +511,49 @@ async fn test_prepare_is_noop() -> Result<()> {
     let p = make_generation_provider();
     p.prepare().await
 }
+
+// ── Quantized weights ─────────────────────────────────────────────────────────
+
+#[tokio::test]
+async fn test_gguf_weights_rejected_for_embedding_models() {
+    use local_memory::model::candle::{HubClient, Weights};
+    let registry = ModelRegistry::load().unwrap();
+    let base = tempfile::tempdir().unwrap();
+    let weights = Weights::Gguf { repo: "x/y".to_string(), file: "y.gguf".to_string() };
+    let err = CandleProvider::load_with_weights(
+        "nomic-ai/nomic-embed-text-v1.5",
+        base.path(),
+        false,
+        &registry,
+        &weights,
+        &HubClient::new("http://127.0.0.1:9").with_offline(true),
+    )
+    .await
+    .err()
+    .unwrap();
+    assert!(err.to_string().contains("embedding model"), "{err}");
+}
+
+#[test]
+fn test_quantized_backend_rejects_unsupported_kinds_and_bad_files() {
+    use local_memory::model::candle::backends::QuantizedBackend;
+    use local_memory::model::candle::registry::ArchKind;
+
+    let registry = ModelRegistry::load().unwrap();
+    let ArchConfig::Generation(gen_config) = registry.resolve("google/gemma-2-2b-it").unwrap().config else {
+        panic!("expected generation config")
+    };
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("model.gguf");
+    std::fs::write(&path, b"not a gguf file").unwrap();
+
+    let err = QuantizedBackend::load(&path, ArchKind::Gemma2, &Device::Cpu, gen_config.clone())
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("not supported for 'gemma2'"), "{err}");
+
+    let err = QuantizedBackend::load(&path, ArchKind::Qwen2, &Device::Cpu, gen_config)
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("model.gguf"), "{err}");
+}
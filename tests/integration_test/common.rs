@@ -0,0 +1,129 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use edgequake_llm::{ChatMessage, CompletionOptions, EmbeddingProvider, LLMProvider, LLMResponse, LlmError};
+use local_memory::config::Config;
+use local_memory::mcp::tools::McpContext;
+use local_memory::model::{RetrievalEmbedder, UnifiedModel};
+use local_memory::storage::SqliteDatabase;
+use serde_json::Value;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+type Reply = Box<dyn Fn(&str) -> std::result::Result<String, LlmError> + Send + Sync>;
+
+/// Answers every prompt with `reply` (after `delay`, if set) and embeds
+/// every text as a zero vector. Records the prompts it was sent and how
+/// many calls overlapped.
+pub struct MockModel {
+    reply: Reply,
+    delay: Option<Duration>,
+    dimension: usize,
+    pub prompts: Mutex<Vec<String>>,
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+}
+
+impl MockModel {
+    pub fn new(reply: impl Fn(&str) -> std::result::Result<String, LlmError> + Send + Sync + 'static) -> Self {
+        Self {
+            reply: Box::new(reply),
+            delay: None,
+            dimension: 768,
+            prompts: Mutex::default(),
+            in_flight: AtomicUsize::new(0),
+            max_in_flight: AtomicUsize::new(0),
+        }
+    }
+
+    /// A model that gives the same answer to every prompt.
+    pub fn replying(content: impl Into<String>) -> Self {
+        let content = content.into();
+        Self::new(move |_| Ok(content.clone()))
+    }
+
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    pub fn with_dimension(mut self, dimension: usize) -> Self {
+        self.dimension = dimension;
+        self
+    }
+
+    pub fn calls(&self) -> usize {
+        self.prompts.lock().unwrap().len()
+    }
+
+    pub fn max_in_flight(&self) -> usize {
+        self.max_in_flight.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl LLMProvider for MockModel {
+    fn name(&self) -> &str { "mock" }
+    fn model(&self) -> &str { "mock-model" }
+    fn max_context_length(&self) -> usize { 4096 }
+
+    async fn complete(&self, prompt: &str) -> std::result::Result<LLMResponse, LlmError> {
+        self.prompts.lock().unwrap().push(prompt.to_string());
+        let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_in_flight.fetch_max(now, Ordering::SeqCst);
+        if let Some(delay) = self.delay {
+            tokio::time::sleep(delay).await;
+        }
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+        Ok(LLMResponse {
+            content: (self.reply)(prompt)?,
+            model: "mock-model".to_string(),
+            prompt_tokens: 0, completion_tokens: 0, total_tokens: 0,
+            finish_reason: Some("stop".to_string()), tool_calls: vec![],
+            metadata: std::collections::HashMap::new(),
+            cache_hit_tokens: Some(0), thinking_tokens: Some(0), thinking_content: None,
+        })
+    }
+
+    async fn complete_with_options(&self, prompt: &str, _options: &CompletionOptions) -> std::result::Result<LLMResponse, LlmError> {
+        self.complete(prompt).await
+    }
+
+    async fn chat(&self, messages: &[ChatMessage], _options: Option<&CompletionOptions>) -> std::result::Result<LLMResponse, LlmError> {
+        self.complete(messages.last().map(|m| m.content.as_str()).unwrap_or("")).await
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for MockModel {
+    fn name(&self) -> &str { "mock-embed" }
+    fn model(&self) -> &str { "mock-model" }
+    fn dimension(&self) -> usize { self.dimension }
+    fn max_tokens(&self) -> usize { 2048 }
+    async fn embed(&self, texts: &[String]) -> std::result::Result<Vec<Vec<f32>>, LlmError> {
+        Ok(texts.iter().map(|_| vec![0.0; self.dimension]).collect())
+    }
+}
+
+impl RetrievalEmbedder for MockModel {}
+
+#[async_trait]
+impl UnifiedModel for MockModel {
+    async fn prepare(&self) -> Result<()> { Ok(()) }
+}
+
+pub fn context(db: Arc<SqliteDatabase>, model: Arc<MockModel>, config: Config) -> McpContext {
+    let (event_tx, _rx) = tokio::sync::broadcast::channel(100);
+    McpContext::new(db, model, config, event_tx)
+}
+
+/// The text of a tool call result.
+pub fn tool_text(result: &Value) -> String {
+    result["content"][0]["text"].as_str().unwrap().to_string()
+}
+
+/// The text of a tool call result, parsed as JSON.
+pub fn tool_json(result: &Value) -> Value {
+    serde_json::from_str(&tool_text(result)).unwrap()
+}
@@ -0,0 +1,137 @@
+use super::common::{self, tool_json, MockModel};
+use anyhow::Result;
+use local_memory::config::Config;
+use local_memory::mcp::tools::{call_tool, McpContext};
+use local_memory::storage::sqlite::{ConflictResolution, ConflictStatus};
+use local_memory::storage::SqliteDatabase;
+use serde_json::json;
+use std::sync::Arc;
+use tempfile::tempdir;
+
+/// Reports that Alice changed employer, contradicting what the store already knows.
+fn contradicting_model(dimension: usize) -> Arc<MockModel> {
+    let reply = json!({
+        "entities": [
+            {"name": "Alice", "type": "Person", "description": "Works at Globex"},
+            {"name": "Globex", "type": "Organization", "description": "A company"}
+        ],
+        "relationships": [
+            {"source": "Alice", "target": "Globex", "predicate": "WORKS_AT", "description": "Employment"}
+        ],
+        "conflicts": [
+            {"entity": "Alice", "existing": "Alice works at Acme", "claim": "Alice works at Globex", "predicate": "WORKS_AT"}
+        ]
+    });
+    Arc::new(MockModel::replying(reply.to_string()).with_dimension(dimension))
+}
+
+fn setup(db: Arc<SqliteDatabase>, dimension: usize) -> Result<(McpContext, uuid::Uuid, uuid::Uuid)> {
+    let alice = db.insert_entity_with_namespace("Alice", "Person", "Works at Acme", "default")?;
+    let acme = db.insert_entity_with_namespace("Acme", "Organization", "A company", "default")?;
+    let rel = db.insert_relationship(alice, acme, "WORKS_AT", "Employment")?;
+    let context = common::context(db, contradicting_model(dimension), Config::default());
+    Ok((context, alice, rel))
+}
+
+#[tokio::test]
+async fn test_conflict_is_recorded_with_contradicted_facts() -> Result<()> {
+    let dir = tempdir()?;
+    let db = Arc::new(SqliteDatabase::open(dir.path().join("conflicts.db"), 768)?);
+    let (context, alice, rel) = setup(db.clone(), 768)?;
+
+    let doc_id = context.get_pipeline().run("Alice now works at Globex.", json!({})).await?;
+
+    let conflicts = db.list_conflicts(Some(ConflictStatus::Open), Some("default"), 10)?;
+    assert_eq!(conflicts.len(), 1);
+    let conflict = &conflicts[0];
+    assert_eq!(conflict.entity_name, "Alice");
+    assert_eq!(conflict.claim, "Alice works at Globex");
+    assert_eq!(conflict.entity_id, Some(alice));
+    assert_eq!(conflict.relationship_id, Some(rel));
+    assert!(conflict.claim_entity_id.is_some_and(|id| id != alice));
+    assert!(conflict.claim_relationship_id.is_some());
+    assert_eq!(conflict.source_documents, vec![doc_id.to_string()]);
+    assert_eq!(db.count_conflicts(ConflictStatus::Open)?, 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_accepting_claim_supersedes_existing_relationship() -> Result<()> {
+    let dir = tempdir()?;
+    let db = Arc::new(SqliteDatabase::open(dir.path().join("conflicts.db"), 768)?);
+    let (context, _alice, _rel) = setup(db.clone(), 768)?;
+    context.get_pipeline().run("Alice now works at Globex.", json!({})).await?;
+
+    let listed = tool_json(&call_tool("memory_conflicts", json!({}), &context).await?);
+    let conflict_id = listed[0]["id"].as_str().unwrap().to_string();
+
+    let resolved = tool_json(&call_tool(
+        "memory_conflicts",
+        json!({"action": "resolve", "conflict_id": conflict_id, "resolution": "accept_claim"}),
+        &context,
+    ).await?);
+    assert_eq!(resolved["status"], "resolved");
+    assert_eq!(resolved["resolution"], "accept_claim");
+
+    // Settled conflicts stay listable by status, and omitting it lists every status.
+    for (status, expected) in [(json!("open"), 0), (json!("resolved"), 1), (json!("dismissed"), 0), (json!(null), 1)] {
+        let listed = tool_json(&call_tool("memory_conflicts", json!({"status": status}), &context).await?);
+        assert_eq!(listed.as_array().unwrap().len(), expected, "status {status}");
+    }
+
+    let targets: Vec<String> = db.list_relationships(10)?.into_iter().map(|(_, _, t)| t).collect();
+    assert_eq!(targets, vec!["Globex".to_string()]);
+    let (_, _, desc) = db.get_entity_by_name_with_namespace("Alice", "default")?.unwrap();
+    assert_eq!(desc, "Works at Globex");
+    assert_eq!(db.count_conflicts(ConflictStatus::Open)?, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_keeping_existing_restores_previous_facts() -> Result<()> {
+    let dir = tempdir()?;
+    let db = Arc::new(SqliteDatabase::open(dir.path().join("conflicts.db"), 768)?);
+    let (context, alice, _rel) = setup(db.clone(), 768)?;
+    context.get_pipeline().run("Alice now works at Globex.", json!({})).await?;
+
+    let conflict = db.list_conflicts(Some(ConflictStatus::Open), None, 10)?.remove(0);
+    db.resolve_conflict(conflict.id, ConflictResolution::KeepExisting)?;
+
+    let (id, _, desc) = db.get_entity_by_name_with_namespace("Alice", "default")?.unwrap();
+    assert_eq!(id, alice);
+    assert_eq!(desc, "Works at Acme");
+    let targets: Vec<String> = db.list_relationships(10)?.into_iter().map(|(_, _, t)| t).collect();
+    assert_eq!(targets, vec!["Acme".to_string()]);
+
+    // A conflict can only be resolved once.
+    assert!(db.resolve_conflict(conflict.id, ConflictResolution::Dismiss).is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_concurrent_resolvers_settle_a_conflict_once() -> Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("conflicts.db");
+    let db = Arc::new(SqliteDatabase::open(&path, 768)?);
+    let (context, _alice, _rel) = setup(db.clone(), 768)?;
+    context.get_pipeline().run("Alice now works at Globex.", json!({})).await?;
+    let conflict = db.list_conflicts(Some(ConflictStatus::Open), None, 10)?.remove(0);
+
+    // Two connections, as from the server and `lmcli` at the same time.
+    let other = Arc::new(SqliteDatabase::open(&path, 768)?);
+    let resolvers: Vec<_> = [(db.clone(), ConflictResolution::AcceptClaim), (other, ConflictResolution::KeepExisting)]
+        .into_iter()
+        .map(|(db, resolution)| std::thread::spawn(move || db.resolve_conflict(conflict.id, resolution)))
+        .collect();
+    let outcomes: Vec<_> = resolvers.into_iter().map(|r| r.join().unwrap()).collect();
+    assert_eq!(outcomes.iter().filter(|o| o.is_ok()).count(), 1);
+    let failure = outcomes.iter().find_map(|o| o.as_ref().err()).unwrap().to_string();
+    assert!(failure.contains("already resolved"), "{}", failure);
+
+    // Only the winner's facts were superseded.
+    let winner = outcomes.into_iter().find_map(|o| o.ok()).unwrap();
+    let targets: Vec<String> = db.list_relationships(10)?.into_iter().map(|(_, _, t)| t).collect();
+    let expected = if winner.resolution == Some(ConflictResolution::AcceptClaim) { "Globex" } else { "Acme" };
+    assert_eq!(targets, vec![expected.to_string()]);
+    Ok(())
+}
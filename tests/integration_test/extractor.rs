@@ -1,9 +1,11 @@
+use super::common::{self, MockModel};
 use anyhow::Result;
 use local_memory::config::Config;
 use local_memory::mcp::tools::McpContext;
 use local_memory::storage::SqliteDatabase;
-use local_memory::model::UnifiedModel;
+use local_memory::model::{RetrievalEmbedder, UnifiedModel};
 use serde_json::json;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use tempfile::tempdir;
 use async_trait::async_trait;
@@ -72,6 +74,8 @@ impl EmbeddingProvider for MockUnified {
     }
 }
 
+impl RetrievalEmbedder for MockUnified {}
+
 #[async_trait]
 impl UnifiedModel for MockUnified {
     async fn prepare(&self) -> Result<()> { Ok(()) }
@@ -86,12 +90,7 @@ async fn test_ingestion_with_llm_extractor() -> Result<()> {
     
     let model = Arc::new(MockUnified { extractor: MockExtractor, dimension });
     let (event_tx, _rx) = tokio::sync::broadcast::channel(100);
-    let context = McpContext {
-        db: db.clone(),
-        model: model.clone(),
-        config: Config::default(),
-        event_tx,
-    };
+    let context = McpContext::new(db.clone(), model.clone(), Config::default(), event_tx);
 
     // Run ingestion
     let pipeline = context.get_pipeline();
@@ -112,3 +111,55 @@ async fn test_ingestion_with_llm_extractor() -> Result<()> {
 
     Ok(())
 }
+
+/// Returns broken JSON for the first `failures` calls, then a valid graph.
+fn flaky_context(db: Arc<SqliteDatabase>, failures: usize) -> (McpContext, Arc<MockModel>) {
+    let calls = AtomicUsize::new(0);
+    let model = Arc::new(MockModel::new(move |_| {
+        if calls.fetch_add(1, Ordering::SeqCst) < failures {
+            Ok("I found Apple and Cupertino.".to_string())
+        } else {
+            Ok(json!({"entities": [{"name": "Apple", "type": "Company", "description": "Tech giant"}]}).to_string())
+        }
+    }));
+    (common::context(db, model.clone(), Config::default()), model)
+}
+
+#[tokio::test]
+async fn test_extraction_retries_with_parse_error_feedback() -> Result<()> {
+    let dir = tempdir()?;
+    let db = Arc::new(SqliteDatabase::open(dir.path().join("retry.db"), 768)?);
+    let (context, model) = flaky_context(db.clone(), 1);
+
+    let id = context.get_pipeline().run("Apple is based in Cupertino.", json!({})).await?;
+
+    let prompts = model.prompts.lock().unwrap().clone();
+    assert_eq!(prompts.len(), 2);
+    assert!(prompts[1].contains("could not be parsed as JSON"));
+    assert!(prompts[1].contains("I found Apple and Cupertino."));
+    assert_eq!(db.list_entities(10)?.len(), 1);
+
+    let (_, meta) = db.get_document_content(id)?.unwrap();
+    assert_eq!(meta["extraction"]["status"], "ok");
+    assert_eq!(meta["extraction"]["attempts"], 2);
+    assert_eq!(meta["extraction"]["failures"], 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_extraction_failure_is_recorded_on_document() -> Result<()> {
+    let dir = tempdir()?;
+    let db = Arc::new(SqliteDatabase::open(dir.path().join("retry.db"), 768)?);
+    let (context, model) = flaky_context(db.clone(), usize::MAX);
+
+    let id = context.get_pipeline().with_max_retries(1).run("Apple is based in Cupertino.", json!({})).await?;
+
+    assert_eq!(model.prompts.lock().unwrap().len(), 2);
+    let (_, meta) = db.get_document_content(id)?.unwrap();
+    assert_eq!(meta["extraction"]["status"], "failed");
+    assert_eq!(meta["extraction"]["failures"], 2);
+    assert!(meta["extraction"]["last_error"].is_string());
+    assert_eq!(db.count_failed_extractions()?, 1);
+    assert_eq!(db.count_entities()?, 0);
+    Ok(())
+}
@@ -2,7 +2,7 @@ use anyhow::Result;
 use local_memory::config::Config;
 use local_memory::mcp::tools::{call_tool, McpContext};
 use local_memory::storage::SqliteDatabase;
-use local_memory::model::UnifiedModel;
+use local_memory::model::{RetrievalEmbedder, UnifiedModel};
 use serde_json::json;
 use std::sync::Arc;
 use tempfile::tempdir;
@@ -83,6 +83,8 @@ impl EmbeddingProvider for MockUnified {
     }
 }
 
+impl RetrievalEmbedder for MockUnified {}
+
 #[async_trait]
 impl UnifiedModel for MockUnified {
     async fn prepare(&self) -> Result<()> { Ok(()) }
@@ -97,12 +99,7 @@ async fn test_mcp_context_flow() -> Result<()> {
     
     let model = Arc::new(MockUnified { llm: MockLLM, dimension });
     let (event_tx, _rx) = tokio::sync::broadcast::channel(100);
-    let context = McpContext {
-        db: db.clone(),
-        model: model.clone(),
-        config: Config::default(),
-        event_tx,
-    };
+    let context = McpContext::new(db.clone(), model.clone(), Config::default(), event_tx);
 
     // 1. Ingestion
     context.get_pipeline().run("Alice works at Acme Corp.", json!({})).await?;
@@ -0,0 +1,223 @@
+use super::common::{self, MockModel};
+use anyhow::Result;
+use local_memory::config::Config;
+use local_memory::mcp::http::{router, serve_http, Bind};
+use local_memory::mcp::tools::McpContext;
+use local_memory::storage::SqliteDatabase;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::tempdir;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::RwLock;
+use tokio::time::Instant;
+
+fn context(db_path: std::path::PathBuf) -> Result<Arc<McpContext>> {
+    context_with(db_path, Config::default())
+}
+
+fn context_with(db_path: std::path::PathBuf, config: Config) -> Result<Arc<McpContext>> {
+    let db = Arc::new(SqliteDatabase::open(db_path, 768)?);
+    // Extracts nothing, slowly enough for a client to watch progress.
+    let model = MockModel::replying(json!({"entities": [], "relationships": []}).to_string())
+        .with_delay(Duration::from_millis(200));
+    Ok(Arc::new(common::context(db, Arc::new(model), config)))
+}
+
+async fn serve_router(context: Arc<McpContext>) -> Result<String> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("http://{}/mcp", listener.local_addr()?);
+    let app = router(context, Arc::new(RwLock::new(Instant::now())));
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    Ok(url)
+}
+
+async fn open_session(client: &reqwest::Client, url: &str) -> Result<String> {
+    let response = client.post(url).body(initialize().to_string()).send().await?;
+    Ok(response.headers()["mcp-session-id"].to_str()?.to_string())
+}
+
+/// Read `events` until `done` returns true or `wait` passes.
+async fn read_events(events: &mut reqwest::Response, wait: Duration, done: impl Fn(&str) -> bool) -> Result<String> {
+    let mut stream = String::new();
+    let _ = tokio::time::timeout(wait, async {
+        while !done(&stream) {
+            let Some(chunk) = events.chunk().await? else { break };
+            stream.push_str(&String::from_utf8_lossy(&chunk));
+        }
+        anyhow::Ok(())
+    }).await;
+    Ok(stream)
+}
+
+fn initialize() -> Value {
+    json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {"protocolVersion": "2024-11-05", "capabilities": {}, "clientInfo": {"name": "test", "version": "0"}}})
+}
+
+#[tokio::test]
+async fn test_http_sessions_requests_and_progress_events() -> Result<()> {
+    let dir = tempdir()?;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("http://{}/mcp", listener.local_addr()?);
+    let app = router(context(dir.path().join("http.db"))?, Arc::new(RwLock::new(Instant::now())));
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    let client = reqwest::Client::new();
+
+    let response = client.post(&url).body(initialize().to_string()).send().await?;
+    assert_eq!(response.status(), 200);
+    let session = response.headers()["mcp-session-id"].to_str()?.to_string();
+    let reply: Value = serde_json::from_str(&response.text().await?)?;
+    assert_eq!(reply["result"]["serverInfo"]["name"], "local-memory");
+
+    let list = json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"}).to_string();
+    assert_eq!(client.post(&url).body(list.clone()).send().await?.status(), 400);
+    assert_eq!(client.post(&url).header("mcp-session-id", "nope").body(list.clone()).send().await?.status(), 404);
+    let foreign = client.post(&url).header("mcp-session-id", &session).header("origin", "https://evil.example").body(list.clone()).send().await?;
+    assert_eq!(foreign.status(), 403);
+
+    // Batches get an array; notifications alone are only accepted.
+    let batch = json!([
+        {"jsonrpc": "2.0", "id": 3, "method": "tools/list"},
+        {"jsonrpc": "2.0", "method": "notifications/initialized"}
+    ]).to_string();
+    let replies: Value = serde_json::from_str(&client.post(&url).header("mcp-session-id", &session).header("origin", "http://localhost:3000").body(batch).send().await?.text().await?)?;
+    assert_eq!(replies.as_array().unwrap().len(), 1);
+    assert_eq!(replies[0]["id"], 3);
+    let initialized = json!({"jsonrpc": "2.0", "method": "notifications/initialized"}).to_string();
+    assert_eq!(client.post(&url).header("mcp-session-id", &session).body(initialized).send().await?.status(), 202);
+
+    // Job progress for this session's token arrives on its event stream.
+    let mut events = client.get(&url).header("mcp-session-id", &session).header("accept", "text/event-stream").send().await?;
+    assert_eq!(events.status(), 200);
+    let memorize = json!({"jsonrpc": "2.0", "id": 4, "method": "tools/call", "params": {
+        "name": "memorize", "arguments": {"text": "Ferris is the Rust mascot."}, "_meta": {"progressToken": "tok-1"}
+    }}).to_string();
+    let reply: Value = serde_json::from_str(&client.post(&url).header("mcp-session-id", &session).body(memorize).send().await?.text().await?)?;
+    assert!(reply["result"]["content"][0]["text"].as_str().unwrap().contains("queued as job"));
+
+    let mut stream = String::new();
+    tokio::time::timeout(Duration::from_secs(10), async {
+        while !stream.contains("\"progress\":1") {
+            let Some(chunk) = events.chunk().await? else { break };
+            stream.push_str(&String::from_utf8_lossy(&chunk));
+        }
+        anyhow::Ok(())
+    }).await??;
+    assert!(stream.contains("notifications/progress"), "{}", stream);
+    assert!(stream.contains("tok-1"), "{}", stream);
+
+    assert_eq!(client.delete(&url).header("mcp-session-id", &session).send().await?.status(), 200);
+    let list = json!({"jsonrpc": "2.0", "id": 5, "method": "tools/list"}).to_string();
+    assert_eq!(client.post(&url).header("mcp-session-id", &session).body(list).send().await?.status(), 404);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_over_unix_socket() -> Result<()> {
+    let dir = tempdir()?;
+    let context = context(dir.path().join("unix.db"))?;
+
+    // A regular file at the path is never deleted.
+    let file = dir.path().join("notes.txt");
+    std::fs::write(&file, b"keep me")?;
+    let refused = serve_http(Bind::Unix(file.clone()), false, context.clone(), Arc::new(RwLock::new(Instant::now()))).await;
+    assert!(refused.unwrap_err().to_string().contains("not a socket"));
+    assert_eq!(std::fs::read(&file)?, b"keep me");
+
+    // A stale socket from an earlier run is replaced.
+    let socket = dir.path().join("memory.sock");
+    drop(std::os::unix::net::UnixListener::bind(&socket)?);
+    tokio::spawn(serve_http(Bind::Unix(socket.clone()), false, context, Arc::new(RwLock::new(Instant::now()))));
+
+    let mut stream = None;
+    for _ in 0..100 {
+        if let Ok(connected) = tokio::net::UnixStream::connect(&socket).await {
+            stream = Some(connected);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let mut stream = stream.expect("socket never came up");
+    let body = initialize().to_string();
+    let request = format!(
+        "POST /mcp HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(), body
+    );
+    stream.write_all(request.as_bytes()).await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(response.to_lowercase().contains("mcp-session-id:"), "{}", response);
+    assert!(response.contains("\"protocolVersion\""), "{}", response);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_job_notifications_reach_only_their_session() -> Result<()> {
+    let dir = tempdir()?;
+    let url = serve_router(context(dir.path().join("owners.db"))?).await?;
+    let client = reqwest::Client::new();
+    let owner = open_session(&client, &url).await?;
+    let other = open_session(&client, &url).await?;
+
+    // Log levels are per session: only the owner asks for job logs here.
+    let set_level = json!({"jsonrpc": "2.0", "id": 2, "method": "logging/setLevel", "params": {"level": "info"}}).to_string();
+    assert_eq!(client.post(&url).header("mcp-session-id", &owner).body(set_level).send().await?.status(), 200);
+
+    let mut owner_events = client.get(&url).header("mcp-session-id", &owner).send().await?;
+    let mut other_events = client.get(&url).header("mcp-session-id", &other).send().await?;
+    let memorize = json!({"jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": {
+        "name": "memorize", "arguments": {"text": "Ferris is the Rust mascot."}, "_meta": {"progressToken": "tok-owner"}
+    }}).to_string();
+    assert_eq!(client.post(&url).header("mcp-session-id", &owner).body(memorize).send().await?.status(), 200);
+
+    let stream = read_events(&mut owner_events, Duration::from_secs(10), |s| s.contains("notifications/message")).await?;
+    assert!(stream.contains("tok-owner"), "{}", stream);
+    assert!(stream.contains("\"level\":\"info\""), "{}", stream);
+    let stream = read_events(&mut other_events, Duration::from_millis(500), |_| false).await?;
+    assert!(!stream.contains("notifications/"), "{}", stream);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_idle_sessions_expire() -> Result<()> {
+    let dir = tempdir()?;
+    let config = Config { session_timeout_seconds: 1, ..Config::default() };
+    let url = serve_router(context_with(dir.path().join("expiry.db"), config)?).await?;
+    let client = reqwest::Client::new();
+    let idle = open_session(&client, &url).await?;
+    let listening = open_session(&client, &url).await?;
+    let _events = client.get(&url).header("mcp-session-id", &listening).send().await?;
+
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    let ping = json!({"jsonrpc": "2.0", "id": 2, "method": "ping"}).to_string();
+    assert_eq!(client.post(&url).header("mcp-session-id", &idle).body(ping.clone()).send().await?.status(), 404);
+    // An open event stream keeps its session alive.
+    assert_eq!(client.post(&url).header("mcp-session-id", &listening).body(ping).send().await?.status(), 200);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_never_exposes_server_files() -> Result<()> {
+    let dir = tempdir()?;
+    let context = context(dir.path().join("remote.db"))?;
+
+    let refused = serve_http(Bind::Tcp("0.0.0.0:0".parse()?), false, context.clone(), Arc::new(RwLock::new(Instant::now()))).await;
+    assert!(refused.unwrap_err().to_string().contains("--allow-remote"));
+
+    // A path sent to memorize is remembered as text, not read from disk.
+    let secret = dir.path().join("secret.txt");
+    std::fs::write(&secret, "hunter2")?;
+    let url = serve_router(context.clone()).await?;
+    let client = reqwest::Client::new();
+    let session = open_session(&client, &url).await?;
+    let memorize = json!({"jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": {
+        "name": "memorize", "arguments": {"text": secret.to_string_lossy()}
+    }}).to_string();
+    let reply: Value = serde_json::from_str(&client.post(&url).header("mcp-session-id", &session).body(memorize).send().await?.text().await?)?;
+    let text = reply["result"]["content"][0]["text"].as_str().unwrap();
+    let id = text.split("ID: ").nth(1).unwrap().split('.').next().unwrap().parse()?;
+    let (content, _) = context.db.get_document_content(id)?.unwrap();
+    assert_eq!(content, secret.to_string_lossy());
+    Ok(())
+}
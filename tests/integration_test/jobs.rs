@@ -0,0 +1,245 @@
+use super::common::{self, tool_json, tool_text, MockModel};
+use anyhow::Result;
+use edgequake_llm::LlmError;
+use local_memory::config::Config;
+use local_memory::engine::jobs::{progress_notification, JobQueue};
+use local_memory::mcp::tools::{call_tool, call_tool_with_progress, McpContext};
+use local_memory::storage::sqlite::{JobRecord, JobStatus};
+use local_memory::storage::SqliteDatabase;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::tempdir;
+
+/// Extracts one entity per call, slowly.
+fn slow_extractor() -> MockModel {
+    let calls = AtomicUsize::new(0);
+    MockModel::new(move |prompt| {
+        let n = calls.fetch_add(1, Ordering::SeqCst);
+        let name = if prompt.contains("Cupertino") { "Apple".to_string() } else { format!("Entity{}", n) };
+        Ok(json!({"entities": [{"name": name, "type": "Company", "description": "Extracted"}], "relationships": []}).to_string())
+    })
+    .with_delay(Duration::from_millis(50))
+}
+
+fn context(db: Arc<SqliteDatabase>, model: Arc<MockModel>, workers: usize) -> McpContext {
+    common::context(db, model, Config { extraction_workers: workers, ..Config::default() })
+}
+
+/// The job ID in a `memorize` reply.
+fn job_id(reply: &str) -> String {
+    reply.split("job ").nth(1).unwrap().split_whitespace().next().unwrap().to_string()
+}
+
+async fn wait_for_job(context: &McpContext, id: &str) -> Result<Value> {
+    for _ in 0..200 {
+        let status = tool_json(&call_tool("memory_job_status", json!({"job_id": id}), context).await?);
+        if status["status"] == "done" || status["status"] == "failed" {
+            return Ok(status);
+        }
+        tokio::time::sleep(Duration::from_millis(25)).await;
+    }
+    anyhow::bail!("job {} did not finish", id)
+}
+
+#[tokio::test]
+async fn test_memorize_returns_before_graph_extraction() -> Result<()> {
+    let dir = tempdir()?;
+    let db = Arc::new(SqliteDatabase::open(dir.path().join("jobs.db"), 768)?);
+    let context = context(db.clone(), Arc::new(slow_extractor()), 2);
+    let mut progress = context.jobs.subscribe();
+
+    let reply = tool_text(&call_tool_with_progress(
+        "memorize",
+        json!({"text": "Apple is based in Cupertino."}),
+        Some(json!("token-1")),
+        None,
+        &context,
+    ).await?);
+    assert!(reply.contains("(1 chunk(s))"), "{}", reply);
+    // Stored and searchable, but not yet extracted.
+    assert_eq!(db.list_entities(10)?.len(), 0);
+
+    let job = wait_for_job(&context, &job_id(&reply)).await?;
+    assert_eq!(job["status"], "done");
+    assert_eq!((job["completed"].as_u64(), job["total"].as_u64()), (Some(1), Some(1)));
+    assert_eq!(job["kind"], "graph_extraction");
+    assert_eq!(db.list_entities(10)?[0].0, "Apple");
+
+    // Progress went out under the caller's token, ending at 1/1.
+    let mut last = None;
+    while let Ok(update) = progress.try_recv() {
+        last = progress_notification(&update);
+    }
+    let last = last.unwrap();
+    assert_eq!(last["method"], "notifications/progress");
+    assert_eq!(last["params"]["progressToken"], "token-1");
+    assert_eq!((last["params"]["progress"].as_u64(), last["params"]["total"].as_u64()), (Some(1), Some(1)));
+
+    let listed = tool_json(&call_tool("memory_job_status", json!({"status": "done"}), &context).await?);
+    assert_eq!(listed.as_array().unwrap().len(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_jobs_respect_the_worker_limit() -> Result<()> {
+    let dir = tempdir()?;
+    let db = Arc::new(SqliteDatabase::open(dir.path().join("workers.db"), 768)?);
+    let model = Arc::new(slow_extractor());
+    let context = context(db.clone(), model.clone(), 1);
+
+    let mut jobs = Vec::new();
+    for i in 0..3 {
+        let reply = tool_text(&call_tool("memorize", json!({"text": format!("Note number {}", i)}), &context).await?);
+        jobs.push(job_id(&reply));
+    }
+    for job in &jobs {
+        assert_eq!(wait_for_job(&context, job).await?["status"], "done");
+    }
+    assert_eq!(model.calls(), 3);
+    assert_eq!(model.max_in_flight(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_unfinished_jobs_resume_after_restart() -> Result<()> {
+    let dir = tempdir()?;
+    let db = Arc::new(SqliteDatabase::open(dir.path().join("resume.db"), 768)?);
+
+    // A job that a previous process queued but never ran.
+    let first = context(db.clone(), Arc::new(slow_extractor()), 1);
+    let id = first.get_pipeline().with_graph_extraction(false).run("Apple is based in Cupertino.", json!({})).await?;
+    let job = JobRecord {
+        id: uuid::Uuid::new_v4(),
+        kind: "graph_extraction".to_string(),
+        namespace: "default".to_string(),
+        document_id: id,
+        items: db.list_document_chunks(id)?,
+        status: JobStatus::Running,
+        total: 1,
+        completed: 0,
+        failed: 0,
+        error: None,
+        progress_token: None,
+        created_at: 0,
+        updated_at: 0,
+    };
+    db.insert_job(&job)?;
+    assert_eq!(db.list_unfinished_jobs()?.len(), 1);
+
+    // Two servers sharing the database both resume it; only one runs it.
+    let (model_a, model_b) = (Arc::new(slow_extractor()), Arc::new(slow_extractor()));
+    let restarted = context(db.clone(), model_a.clone(), 1);
+    let other = context(db.clone(), model_b.clone(), 1);
+    assert_eq!(restarted.jobs.resume()?, 1);
+    other.jobs.resume()?;
+    let status = wait_for_job(&restarted, &job.id.to_string()).await?;
+    assert_eq!(status["status"], "done");
+    assert_eq!(db.list_entities(10)?[0].0, "Apple");
+    assert!(db.list_unfinished_jobs()?.is_empty());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(model_a.calls() + model_b.calls(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_running_jobs_are_left_to_their_process_until_released() -> Result<()> {
+    let dir = tempdir()?;
+    let db = Arc::new(SqliteDatabase::open(dir.path().join("release.db"), 768)?);
+    let owner = context(db.clone(), Arc::new(slow_extractor()), 1);
+
+    // Slow enough that the job is still running when a second server starts.
+    let text = (0..6).map(|i| format!("Fact {}.", i)).collect::<Vec<_>>().join(" ---CHUNK--- ");
+    let reply = tool_text(&call_tool("memorize", json!({"text": text}), &owner).await?);
+    let id = job_id(&reply);
+    tokio::time::sleep(Duration::from_millis(80)).await;
+    let second = context(db.clone(), Arc::new(slow_extractor()), 1);
+    assert_eq!(second.jobs.resume()?, 0, "a fresh running job belongs to its process");
+
+    // On shutdown the owner hands it back, and the next process finishes it.
+    assert_eq!(owner.jobs.release()?, 1);
+    let job = tool_json(&call_tool("memory_job_status", json!({"job_id": id}), &second).await?);
+    assert_eq!(job["status"], "queued");
+    drop(owner);
+    assert_eq!(second.jobs.resume()?, 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_job_fails_when_every_extraction_fails() -> Result<()> {
+    let dir = tempdir()?;
+    let db = Arc::new(SqliteDatabase::open(dir.path().join("failing.db"), 768)?);
+    let model = Arc::new(MockModel::new(|_| Err(LlmError::Unknown("extractor unavailable".to_string()))));
+    let context = context(db.clone(), model, 1);
+
+    let reply = tool_text(&call_tool("memorize", json!({"text": "Apple is based in Cupertino."}), &context).await?);
+    let job = wait_for_job(&context, &job_id(&reply)).await?;
+    assert_eq!(job["status"], "failed");
+    assert_eq!((job["failed"].as_u64(), job["total"].as_u64()), (Some(1), Some(1)));
+    assert!(job["error"].as_str().unwrap().contains("extractor unavailable"), "{}", job);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_long_chunks_keep_their_job_from_going_stale() -> Result<()> {
+    let dir = tempdir()?;
+    let db = Arc::new(SqliteDatabase::open(dir.path().join("heartbeat.db"), 768)?);
+    let model = Arc::new(slow_extractor().with_delay(Duration::from_millis(2500)));
+    let owner = context(db.clone(), model.clone(), 1);
+    let id = owner.get_pipeline().with_graph_extraction(false).run("Apple is based in Cupertino.", json!({})).await?;
+    let queue = Arc::new(JobQueue::new(db.clone(), Arc::new(owner.get_pipeline()), 1).with_stale_after(1));
+    let job = queue.enqueue(id, "default", None)?;
+
+    // The one chunk outlasts the stale period, but the owner keeps renewing it.
+    tokio::time::sleep(Duration::from_millis(2000)).await;
+    let other_model = Arc::new(slow_extractor());
+    let other = context(db.clone(), other_model.clone(), 1);
+    let other_queue = Arc::new(JobQueue::new(db.clone(), Arc::new(other.get_pipeline()), 1).with_stale_after(1));
+    assert_eq!(other_queue.resume()?, 0);
+
+    assert_eq!(wait_for_job(&owner, &job.id.to_string()).await?["status"], "done");
+    assert_eq!(model.calls(), 1);
+    assert_eq!(other_model.calls(), 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_only_the_claiming_worker_can_save_a_job() -> Result<()> {
+    let dir = tempdir()?;
+    let db = Arc::new(SqliteDatabase::open(dir.path().join("lease.db"), 768)?);
+    let first = context(db.clone(), Arc::new(slow_extractor()), 1);
+    let id = first.get_pipeline().with_graph_extraction(false).run("Apple is based in Cupertino.", json!({})).await?;
+    let mut job = JobRecord {
+        id: uuid::Uuid::new_v4(),
+        kind: "graph_extraction".to_string(),
+        namespace: "default".to_string(),
+        document_id: id,
+        items: db.list_document_chunks(id)?,
+        status: JobStatus::Queued,
+        total: 1,
+        completed: 0,
+        failed: 0,
+        error: None,
+        progress_token: None,
+        created_at: 0,
+        updated_at: 0,
+    };
+    db.insert_job(&job)?;
+
+    let (a, b) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+    assert!(db.claim_job(job.id, a, 10)?);
+    assert!(!db.claim_job(job.id, b, 10)?);
+    assert!(db.renew_job(job.id, a, 20)?);
+    assert!(!db.renew_job(job.id, b, 20)?);
+
+    // Once the job is taken for stale and reclaimed, its first worker is locked out.
+    assert_eq!(db.requeue_stale_jobs(30)?, 1);
+    assert!(db.claim_job(job.id, b, 40)?);
+    job.completed = 1;
+    assert!(!db.update_job(&job, a)?);
+    assert!(!db.renew_job(job.id, a, 50)?);
+    assert!(!db.requeue_job(job.id, a)?);
+    assert!(db.update_job(&job, b)?);
+    Ok(())
+}
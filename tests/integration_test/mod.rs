@@ -1,5 +1,12 @@
+pub mod common;
 pub mod graphrag;
 pub mod mcp;
 pub mod retrieval;
 pub mod extractor;
 pub mod candle_provider;
+pub mod conflicts;
+pub mod ontology;
+pub mod model_download;
+pub mod jobs;
+pub mod server;
+pub mod http;
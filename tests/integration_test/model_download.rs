@@ -0,0 +1,347 @@
+//! Model downloads against a local stand-in for the HuggingFace hub.
+//!
+//! The stand-in serves `/{repo}/resolve/main/{file}` with the headers the
+//! hub sends (`X-Linked-Etag` / `X-Linked-Size`), honours `Range`, and can
+//! cut a transfer short or demand a token.
+use anyhow::Result;
+use local_memory::model::candle::loader::weight_files;
+use local_memory::model::candle::{ensure_weights_from, get_model_dir, HubClient, Weights};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+#[derive(Default)]
+struct StandIn {
+    /// `repo/file` → content.
+    files: HashMap<String, Vec<u8>>,
+    /// Advertised checksums that differ from the content.
+    wrong_sha: HashMap<String, String>,
+    token: Option<String>,
+    /// Cut the first full transfer of this file after half its bytes.
+    cut: Option<String>,
+    /// Old repo name → new one; requests for the old name get a relative redirect.
+    renamed: HashMap<String, String>,
+}
+
+struct Server {
+    endpoint: String,
+    /// `METHOD path [range]` for every request received.
+    log: Arc<Mutex<Vec<String>>>,
+}
+
+async fn serve(stand_in: StandIn) -> Server {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let stand_in = Arc::new(stand_in);
+    let cut_done = Arc::new(AtomicBool::new(false));
+    let server_log = log.clone();
+    tokio::spawn(async move {
+        loop {
+            let Ok((mut socket, _)) = listener.accept().await else { return };
+            let (stand_in, log, cut_done) = (stand_in.clone(), server_log.clone(), cut_done.clone());
+            tokio::spawn(async move {
+                let mut buf = Vec::new();
+                let mut chunk = [0u8; 1024];
+                while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match socket.read(&mut chunk).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => buf.extend_from_slice(&chunk[..n]),
+                    }
+                }
+                let request = String::from_utf8_lossy(&buf).to_string();
+                let response = respond(&stand_in, &request, &log, &cut_done);
+                let _ = socket.write_all(&response).await;
+                let _ = socket.shutdown().await;
+            });
+        }
+    });
+    Server { endpoint, log }
+}
+
+fn respond(stand_in: &StandIn, request: &str, log: &Mutex<Vec<String>>, cut_done: &AtomicBool) -> Vec<u8> {
+    let mut lines = request.lines();
+    let mut parts = lines.next().unwrap_or_default().split_whitespace();
+    let (method, path) = (parts.next().unwrap_or_default(), parts.next().unwrap_or_default());
+    let headers: HashMap<String, String> = lines
+        .filter_map(|l| l.split_once(':'))
+        .map(|(k, v)| (k.trim().to_ascii_lowercase(), v.trim().to_string()))
+        .collect();
+    let range = headers.get("range").cloned();
+    log.lock().unwrap().push(format!("{} {} {}", method, path, range.clone().unwrap_or_default()).trim().to_string());
+
+    let status = |code: &str| format!("HTTP/1.1 {}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n", code).into_bytes();
+    if let Some(token) = &stand_in.token
+        && headers.get("authorization") != Some(&format!("Bearer {}", token))
+    {
+        return status("401 Unauthorized");
+    }
+    if let Some((old, new)) = stand_in.renamed.iter().find(|(old, _)| path.starts_with(&format!("/{}/", old))) {
+        let location = path.replacen(old.as_str(), new, 1);
+        return format!(
+            "HTTP/1.1 307 Temporary Redirect\r\nlocation: {}\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok",
+            location
+        )
+        .into_bytes();
+    }
+    let key = path.trim_start_matches('/').replacen("/resolve/main/", "/", 1);
+    let Some(body) = stand_in.files.get(&key) else { return status("404 Not Found") };
+    let sha = stand_in.wrong_sha.get(&key).cloned().unwrap_or_else(|| hex::encode(Sha256::digest(body)));
+
+    if method == "HEAD" {
+        return format!(
+            "HTTP/1.1 200 OK\r\nx-linked-etag: \"{}\"\r\nx-linked-size: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+            sha,
+            body.len(),
+            body.len()
+        )
+        .into_bytes();
+    }
+    let start = range
+        .as_deref()
+        .and_then(|r| r.strip_prefix("bytes="))
+        .and_then(|r| r.trim_end_matches('-').parse::<usize>().ok());
+    match start {
+        Some(start) if start >= body.len() => status("416 Range Not Satisfiable"),
+        Some(start) => {
+            let mut out = format!(
+                "HTTP/1.1 206 Partial Content\r\ncontent-range: bytes {}-{}/{}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                start,
+                body.len() - 1,
+                body.len(),
+                body.len() - start
+            )
+            .into_bytes();
+            out.extend_from_slice(&body[start..]);
+            out
+        }
+        None => {
+            let mut out = format!("HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n", body.len()).into_bytes();
+            if stand_in.cut.as_deref() == Some(key.as_str()) && !cut_done.swap(true, Ordering::SeqCst) {
+                out.extend_from_slice(&body[..body.len() / 2]);
+            } else {
+                out.extend_from_slice(body);
+            }
+            out
+        }
+    }
+}
+
+fn model_files(repo: &str, weights: &[u8]) -> HashMap<String, Vec<u8>> {
+    HashMap::from([
+        (format!("{}/config.json", repo), b"{\"hidden_size\": 8}".to_vec()),
+        (format!("{}/tokenizer.json", repo), b"{\"model\": {}}".to_vec()),
+        (format!("{}/model.safetensors", repo), weights.to_vec()),
+    ])
+}
+
+fn weights_blob() -> Vec<u8> {
+    (0..4096u32).flat_map(|i| i.to_le_bytes()).collect()
+}
+
+fn part_files(dir: &Path) -> Vec<String> {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.file_name().to_string_lossy().into_owned())
+                .filter(|name| name.ends_with(".part"))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[tokio::test]
+async fn test_download_verifies_and_renames_into_place() -> Result<()> {
+    let blob = weights_blob();
+    let server = serve(StandIn { files: model_files("org/model", &blob), ..Default::default() }).await;
+    let base = tempfile::tempdir()?;
+    let hub = HubClient::new(&server.endpoint);
+
+    let dir = ensure_weights_from(&hub, "org/model", base.path(), &Weights::default(), true).await?;
+    assert_eq!(std::fs::read(dir.join("model.safetensors"))?, blob);
+    assert!(dir.join("config.json").exists() && dir.join("tokenizer.json").exists());
+    assert!(part_files(&dir).is_empty());
+
+    // A complete model is not fetched again.
+    let requests = server.log.lock().unwrap().len();
+    ensure_weights_from(&hub, "org/model", base.path(), &Weights::default(), true).await?;
+    assert_eq!(server.log.lock().unwrap().len(), requests);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_partial_download_is_resumed_with_range() -> Result<()> {
+    let blob = weights_blob();
+    let server = serve(StandIn { files: model_files("org/model", &blob), ..Default::default() }).await;
+    let base = tempfile::tempdir()?;
+    let dir = get_model_dir(base.path(), "org/model");
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(dir.join("model.safetensors.part"), &blob[..1000])?;
+
+    ensure_weights_from(&HubClient::new(&server.endpoint), "org/model", base.path(), &Weights::default(), true)
+        .await?;
+    assert_eq!(std::fs::read(dir.join("model.safetensors"))?, blob);
+    let log = server.log.lock().unwrap();
+    assert!(log.contains(&"GET /org/model/resolve/main/model.safetensors bytes=1000-".to_string()), "{log:?}");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_interrupted_transfer_is_retried_from_where_it_stopped() -> Result<()> {
+    let blob = weights_blob();
+    let server = serve(StandIn {
+        files: model_files("org/model", &blob),
+        cut: Some("org/model/model.safetensors".to_string()),
+        ..Default::default()
+    })
+    .await;
+    let base = tempfile::tempdir()?;
+
+    let dir = ensure_weights_from(&HubClient::new(&server.endpoint), "org/model", base.path(), &Weights::default(), true)
+        .await?;
+    assert_eq!(std::fs::read(dir.join("model.safetensors"))?, blob);
+    let resumed = format!("GET /org/model/resolve/main/model.safetensors bytes={}-", blob.len() / 2);
+    assert!(server.log.lock().unwrap().contains(&resumed));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_checksum_mismatch_leaves_nothing_behind() -> Result<()> {
+    let server = serve(StandIn {
+        files: model_files("org/model", &weights_blob()),
+        wrong_sha: HashMap::from([("org/model/model.safetensors".to_string(), "ab".repeat(32))]),
+        ..Default::default()
+    })
+    .await;
+    let base = tempfile::tempdir()?;
+
+    let err = ensure_weights_from(&HubClient::new(&server.endpoint), "org/model", base.path(), &Weights::default(), true)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("Checksum mismatch"), "{err}");
+    let dir = get_model_dir(base.path(), "org/model");
+    assert!(!dir.join("model.safetensors").exists());
+    assert!(part_files(&dir).is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sharded_weights_are_resolved_through_the_index() -> Result<()> {
+    let mut files = model_files("org/big", b"");
+    files.remove("org/big/model.safetensors");
+    let index = serde_json::json!({
+        "weight_map": {
+            "a.weight": "model-00001-of-00002.safetensors",
+            "b.weight": "model-00002-of-00002.safetensors",
+            "c.weight": "model-00002-of-00002.safetensors"
+        }
+    });
+    files.insert("org/big/model.safetensors.index.json".to_string(), index.to_string().into_bytes());
+    files.insert("org/big/model-00001-of-00002.safetensors".to_string(), b"shard one".to_vec());
+    files.insert("org/big/model-00002-of-00002.safetensors".to_string(), b"shard two".to_vec());
+    let server = serve(StandIn { files, ..Default::default() }).await;
+    let base = tempfile::tempdir()?;
+
+    let dir = ensure_weights_from(&HubClient::new(&server.endpoint), "org/big", base.path(), &Weights::default(), true)
+        .await?;
+    let shards = weight_files(&dir)?;
+    assert_eq!(shards.len(), 2);
+    assert_eq!(std::fs::read(&shards[1])?, b"shard two");
+    assert!(!server.log.lock().unwrap().iter().any(|r| r.starts_with("GET /org/big/resolve/main/model.safetensors ")));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_gguf_fetches_tokenizer_and_named_file_only() -> Result<()> {
+    let mut files = model_files("org/model", &weights_blob());
+    files.insert("org/model-GGUF/model-q4_k_m.gguf".to_string(), b"GGUF....".to_vec());
+    let server = serve(StandIn { files, ..Default::default() }).await;
+    let base = tempfile::tempdir()?;
+    let weights = Weights::Gguf { repo: "org/model-GGUF".to_string(), file: "model-q4_k_m.gguf".to_string() };
+
+    let dir = ensure_weights_from(&HubClient::new(&server.endpoint), "org/model", base.path(), &weights, true).await?;
+    assert!(dir.join("tokenizer.json").exists() && dir.join("model-q4_k_m.gguf").exists());
+    assert!(!dir.join("model.safetensors").exists() && !dir.join("config.json").exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_token_is_sent_to_gated_repositories() -> Result<()> {
+    let server = serve(StandIn {
+        files: model_files("org/gated", &weights_blob()),
+        token: Some("secret".to_string()),
+        ..Default::default()
+    })
+    .await;
+    let base = tempfile::tempdir()?;
+
+    let err = ensure_weights_from(&HubClient::new(&server.endpoint), "org/gated", base.path(), &Weights::default(), true)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("HF_TOKEN"), "{err}");
+
+    let hub = HubClient::new(&server.endpoint).with_token(Some("secret".to_string()));
+    ensure_weights_from(&hub, "org/gated", base.path(), &Weights::default(), true).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_offline_mode_fails_fast_without_network() -> Result<()> {
+    let server = serve(StandIn { files: model_files("org/model", &weights_blob()), ..Default::default() }).await;
+    let base = tempfile::tempdir()?;
+    let offline = HubClient::new(&server.endpoint).with_offline(true);
+
+    let err = ensure_weights_from(&offline, "org/model", base.path(), &Weights::default(), true)
+        .await
+        .unwrap_err();
+    let message = err.to_string();
+    assert!(message.contains("Offline mode") && message.contains("model.safetensors"), "{message}");
+    assert!(server.log.lock().unwrap().is_empty());
+
+    // Once the files are there, offline mode loads them as usual.
+    ensure_weights_from(&HubClient::new(&server.endpoint), "org/model", base.path(), &Weights::default(), true)
+        .await?;
+    ensure_weights_from(&offline, "org/model", base.path(), &Weights::default(), true).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_metadata_reports_checksum_size_and_missing_files() -> Result<()> {
+    let blob = weights_blob();
+    let server = serve(StandIn { files: model_files("org/model", &blob), ..Default::default() }).await;
+    let hub = HubClient::new(format!("{}/", server.endpoint));
+    assert_eq!(hub.file_url("org/model", "config.json"), format!("{}/org/model/resolve/main/config.json", server.endpoint));
+
+    let metadata = hub.metadata("org/model", "model.safetensors").await?.unwrap();
+    assert_eq!(metadata.size, Some(blob.len() as u64));
+    assert_eq!(metadata.sha256, Some(hex::encode(Sha256::digest(&blob))));
+    assert!(hub.metadata("org/model", "nope.bin").await?.is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_renamed_repository_is_followed_for_metadata_and_download() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let blob = weights_blob();
+    let server = serve(StandIn {
+        files: model_files("org/new-name", &blob),
+        renamed: HashMap::from([("org/old-name".to_string(), "org/new-name".to_string())]),
+        ..Default::default()
+    })
+    .await;
+    let hub = HubClient::new(&server.endpoint);
+
+    // The redirect's own Content-Length is not the file size.
+    let metadata = hub.metadata("org/old-name", "model.safetensors").await?.unwrap();
+    assert_eq!(metadata.size, Some(blob.len() as u64));
+
+    let target = dir.path().join("model.safetensors");
+    hub.download("org/old-name", "model.safetensors", &target).await?;
+    assert_eq!(std::fs::read(&target)?, blob);
+    Ok(())
+}
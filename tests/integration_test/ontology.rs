@@ -0,0 +1,40 @@
+use super::common::{self, MockModel};
+use anyhow::Result;
+use local_memory::config::Config;
+use local_memory::storage::SqliteDatabase;
+use serde_json::json;
+use std::sync::Arc;
+use tempfile::tempdir;
+
+/// Extracts a person working at a planet, which the ontology does not know.
+fn planet_model() -> Arc<MockModel> {
+    let reply = json!({
+        "entities": [
+            {"name": "Alice", "type": "Person", "description": "An engineer"},
+            {"name": "Mars", "type": "Planet", "description": "The red planet"}
+        ],
+        "relationships": [
+            {"source": "Alice", "target": "Mars", "predicate": "WORKS_AT", "description": "Remote office"}
+        ]
+    });
+    Arc::new(MockModel::replying(reply.to_string()))
+}
+
+#[tokio::test]
+async fn test_rejected_entity_is_not_recreated_by_relationships() -> Result<()> {
+    let dir = tempdir()?;
+    let db = Arc::new(SqliteDatabase::open(dir.path().join("ontology.db"), 768)?);
+    let config: Config = serde_json::from_value(json!({
+        "ontology": {"*": {"entity_types": ["Person"], "predicates": ["WORKS_AT"], "on_unknown": "reject"}}
+    }))?;
+    let context = common::context(db.clone(), planet_model(), config);
+
+    context.get_pipeline().run("Alice works on Mars.", json!({})).await?;
+
+    let entities = db.list_entities(10)?;
+    assert_eq!(entities.len(), 1, "{:?}", entities);
+    assert_eq!(entities[0].0, "Alice");
+    assert!(db.get_entity_by_name_with_namespace("Mars", "default")?.is_none());
+    assert!(db.list_relationships(10)?.is_empty());
+    Ok(())
+}
@@ -0,0 +1,150 @@
+use super::common::{self, tool_json, tool_text, MockModel};
+use anyhow::Result;
+use local_memory::config::Config;
+use local_memory::mcp::server::{handle_request, paginate, serve, Client, PROTOCOL_VERSIONS};
+use local_memory::mcp::tools::{call_tool, McpContext};
+use local_memory::storage::SqliteDatabase;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::tempdir;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::RwLock;
+use tokio::time::Instant;
+
+/// Takes its time over global search answers.
+fn slow_synthesizer() -> Arc<MockModel> {
+    Arc::new(MockModel::replying("Everything is connected.").with_delay(Duration::from_millis(500)))
+}
+
+fn call(id: u64, name: &str, arguments: Value) -> String {
+    json!({"jsonrpc": "2.0", "id": id, "method": "tools/call", "params": {"name": name, "arguments": arguments}}).to_string() + "\n"
+}
+
+#[tokio::test]
+async fn test_requests_run_concurrently_and_can_be_cancelled() -> Result<()> {
+    let dir = tempdir()?;
+    let db = Arc::new(SqliteDatabase::open(dir.path().join("server.db"), 768)?);
+    db.upsert_community("c1", "Storage", "SQLite stores everything.")?;
+    let alice = db.insert_entity_with_namespace("Alice", "Person", "Engineer", "default")?;
+    db.update_entity_community(alice, "c1")?;
+    let context = Arc::new(common::context(db, slow_synthesizer(), Config::default()));
+
+    let (mut client, server_input) = tokio::io::duplex(64 * 1024);
+    let (server_output, client_output) = tokio::io::duplex(64 * 1024);
+    let server = tokio::spawn(serve(
+        BufReader::new(server_input),
+        server_output,
+        context,
+        Arc::new(RwLock::new(Instant::now())),
+    ));
+    let mut responses = BufReader::new(client_output).lines();
+
+    // A slow global search does not hold up a fast explore.
+    client.write_all(call(1, "memory_global_search", json!({"query": "What is stored?"})).as_bytes()).await?;
+    client.write_all(call(2, "explore", json!({"entity_name": "Alice"})).as_bytes()).await?;
+    let first: Value = serde_json::from_str(&responses.next_line().await?.unwrap())?;
+    let second: Value = serde_json::from_str(&responses.next_line().await?.unwrap())?;
+    assert_eq!((first["id"].as_u64(), second["id"].as_u64()), (Some(2), Some(1)));
+    assert!(second["result"]["content"][0]["text"].as_str().unwrap().contains("Everything is connected."));
+
+    // A cancelled call gets no response; the next one still does.
+    client.write_all(call(3, "memory_global_search", json!({"query": "Again?"})).as_bytes()).await?;
+    let cancel = json!({"jsonrpc": "2.0", "method": "notifications/cancelled", "params": {"requestId": 3, "reason": "user"}});
+    client.write_all(format!("{}\n", cancel).as_bytes()).await?;
+    client.write_all(b"not json\n").await?;
+    client.write_all(call(4, "explore", json!({"entity_name": "Alice"})).as_bytes()).await?;
+    tokio::time::sleep(Duration::from_millis(800)).await;
+    drop(client);
+
+    let mut rest = Vec::new();
+    while let Some(line) = responses.next_line().await? {
+        rest.push(serde_json::from_str::<Value>(&line)?);
+    }
+    server.await??;
+    assert_eq!(rest.len(), 2, "{:?}", rest);
+    assert_eq!(rest[0]["error"]["code"], -32700);
+    assert_eq!(rest[1]["id"], 4);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_global_search_only_reads_communities_of_its_namespace() -> Result<()> {
+    let dir = tempdir()?;
+    let db = Arc::new(SqliteDatabase::open(dir.path().join("global.db"), 768)?);
+    for (community, title, name, namespace) in [("c1", "Work", "Alice", "work"), ("c2", "Home", "Bob", "home")] {
+        db.upsert_community(community, title, &format!("{} lives here.", name))?;
+        let id = db.insert_entity_with_namespace(name, "Person", "Someone", namespace)?;
+        db.update_entity_community(id, community)?;
+    }
+    let model = Arc::new(MockModel::replying("Alice."));
+    let context = common::context(db, model.clone(), Config::default());
+
+    let result = call_tool("memory_global_search", json!({"query": "Who is here?", "namespace": "work"}), &context).await?;
+    assert_eq!(tool_json(&result)["source_communities_count"], 1);
+    let prompt = model.prompts.lock().unwrap()[0].clone();
+    assert!(prompt.contains("Alice lives here.") && !prompt.contains("Bob"), "{}", prompt);
+
+    let result = call_tool("memory_global_search", json!({"query": "Who is here?"}), &context).await?;
+    assert!(tool_text(&result).contains("No communities found"));
+    assert_eq!(model.calls(), 1);
+    Ok(())
+}
+
+async fn rpc(method: &str, params: Value, context: &McpContext) -> Value {
+    let request = json!({"jsonrpc": "2.0", "id": 7, "method": method, "params": params});
+    handle_request(&request, context, &Client::default()).await.expect("requests get a response")
+}
+
+#[tokio::test]
+async fn test_protocol_methods_and_error_codes() -> Result<()> {
+    let dir = tempdir()?;
+    let db = Arc::new(SqliteDatabase::open(dir.path().join("protocol.db"), 768)?);
+    let context = common::context(db, slow_synthesizer(), Config::default());
+
+    // Version negotiation: echo a revision we speak, otherwise offer our newest.
+    let init = rpc("initialize", json!({"protocolVersion": "2025-03-26", "capabilities": {}}), &context).await;
+    assert_eq!(init["result"]["protocolVersion"], "2025-03-26");
+    assert!(init["result"]["capabilities"]["logging"].is_object());
+    let init = rpc("initialize", json!({"protocolVersion": "1999-01-01"}), &context).await;
+    assert_eq!(init["result"]["protocolVersion"], PROTOCOL_VERSIONS[0]);
+
+    assert_eq!(rpc("ping", json!({}), &context).await["result"], json!({}));
+    assert_eq!(rpc("resources/unsubscribe", json!({}), &context).await["error"]["code"], -32601);
+    let unknown = json!({"jsonrpc": "2.0", "method": "notifications/whatever"});
+    assert!(handle_request(&unknown, &context, &Client::default()).await.is_none());
+
+    // Bad requests are protocol errors; failing tools are tool results.
+    let missing = rpc("tools/call", json!({"name": "recall", "arguments": {}}), &context).await;
+    assert_eq!(missing["error"]["code"], -32602);
+    assert_eq!(rpc("tools/call", json!({"name": "no_such_tool"}), &context).await["error"]["code"], -32602);
+    let typo = rpc("tools/call", json!({"name": "explore", "arguments": {"entity_name": "Alice", "namespce": "x"}}), &context).await;
+    assert_eq!(typo["error"]["code"], -32602);
+    assert!(typo["error"]["message"].as_str().unwrap().contains("unknown field `namespce`"));
+    let failed = rpc("tools/call", json!({"name": "memory_job_status", "arguments": {"job_id": uuid::Uuid::new_v4().to_string()}}), &context).await;
+    assert_eq!(failed["result"]["isError"], true);
+    assert!(failed["result"]["content"][0]["text"].as_str().unwrap().contains("Job not found"));
+    let resource = rpc("resources/read", json!({"uri": "local-memory://nope"}), &context).await;
+    assert_eq!(resource["error"]["code"], -32002);
+    assert_eq!(resource["error"]["data"]["uri"], "local-memory://nope");
+
+    assert_eq!(rpc("logging/setLevel", json!({"level": "loud"}), &context).await["error"]["code"], -32602);
+    assert_eq!(rpc("logging/setLevel", json!({"level": "warning"}), &context).await["result"], json!({}));
+
+    // Everything fits on one page; a cursor we never issued is rejected.
+    let tools = rpc("tools/list", json!({}), &context).await;
+    assert!(tools["result"].get("nextCursor").is_none());
+    assert!(tools["result"]["tools"].as_array().unwrap().len() > 3);
+    assert_eq!(rpc("tools/list", json!({"cursor": "bogus"}), &context).await["error"]["code"], -32602);
+    Ok(())
+}
+
+#[test]
+fn test_pagination_cursors() {
+    let items: Vec<Value> = (0..5).map(|i| json!(i)).collect();
+    let (page, next) = paginate(items.clone(), None, 2).unwrap();
+    assert_eq!((page, next.as_deref()), (vec![json!(0), json!(1)], Some("2")));
+    let (page, next) = paginate(items.clone(), Some(&json!("4")), 2).unwrap();
+    assert_eq!((page, next), (vec![json!(4)], None));
+    assert!(paginate(items, Some(&json!("9")), 2).is_err());
+}
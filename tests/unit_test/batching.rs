@@ -0,0 +1,148 @@
+use async_trait::async_trait;
+use edgequake_llm::{EmbeddingProvider, LlmError};
+use local_memory::engine::ingestion::IngestionPipeline;
+use local_memory::model::RetrievalEmbedder;
+use local_memory::model::batching::{group_by_length, EmbeddingBatcher};
+use local_memory::storage::SqliteDatabase;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tempfile::tempdir;
+
+/// Embeds each text as `[len, 0, ...]`, recording every batch, and fails any
+/// batch containing "poison"; `embed_one` (the query path) is refused, so
+/// documents must never go through it.
+struct RecordingEmbedder {
+    batches: Mutex<Vec<Vec<String>>>,
+    delay: Duration,
+}
+
+impl RecordingEmbedder {
+    fn new() -> Arc<Self> {
+        Arc::new(Self { batches: Mutex::new(Vec::new()), delay: Duration::ZERO })
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for RecordingEmbedder {
+    fn name(&self) -> &str { "recording" }
+    fn model(&self) -> &str { "recording" }
+    fn dimension(&self) -> usize { 24 }
+    fn max_tokens(&self) -> usize { 512 }
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, LlmError> {
+        self.batches.lock().unwrap().push(texts.to_vec());
+        tokio::time::sleep(self.delay).await;
+        if texts.iter().any(|t| t == "poison") {
+            return Err(LlmError::Unknown("cannot embed poison".into()));
+        }
+        Ok(texts.iter().map(|t| {
+            let mut v = vec![0.0; 24];
+            v[0] = t.len() as f32;
+            v
+        }).collect())
+    }
+    async fn embed_one(&self, _text: &str) -> Result<Vec<f32>, LlmError> {
+        Err(LlmError::Unknown("documents must not be embedded as queries".into()))
+    }
+}
+
+impl RetrievalEmbedder for RecordingEmbedder {}
+
+fn texts(items: &[&str]) -> Vec<String> {
+    items.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn test_group_by_length_groups_similar_lengths() {
+    let lengths = [10, 500, 12, 480, 11];
+    let groups = group_by_length(&lengths, 3, usize::MAX);
+    assert_eq!(groups, vec![vec![0, 4, 2], vec![3, 1]]);
+
+    // The padded budget splits long items apart; an oversized item stands alone.
+    assert_eq!(group_by_length(&lengths, 8, 900), vec![vec![0, 4, 2], vec![3], vec![1]]);
+    assert_eq!(group_by_length(&[5000, 1], 8, 1000), vec![vec![1], vec![0]]);
+    assert!(group_by_length(&[], 8, 1000).is_empty());
+}
+
+#[tokio::test]
+async fn test_batcher_returns_vectors_in_input_order() {
+    let embedder = RecordingEmbedder::new();
+    let batcher = EmbeddingBatcher::new(embedder.clone()).with_batch_size(2);
+
+    let vectors = batcher.embed_documents(texts(&["ccc", "a", "bb", "dddd"])).await.unwrap();
+    let lengths: Vec<f32> = vectors.iter().map(|v| v[0]).collect();
+    assert_eq!(lengths, vec![3.0, 1.0, 2.0, 4.0]);
+
+    let batches = embedder.batches.lock().unwrap();
+    assert_eq!(*batches, vec![texts(&["a", "bb"]), texts(&["ccc", "dddd"])]);
+}
+
+#[tokio::test]
+async fn test_batcher_merges_concurrent_requests() {
+    let embedder = Arc::new(RecordingEmbedder { batches: Mutex::new(Vec::new()), delay: Duration::from_millis(50) });
+    let batcher = Arc::new(EmbeddingBatcher::new(embedder.clone()));
+
+    // The first request occupies the worker while the others queue up.
+    let first = tokio::spawn({
+        let batcher = batcher.clone();
+        async move { batcher.embed_documents(texts(&["first"])).await }
+    });
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    let (a, b) = tokio::join!(
+        batcher.embed_documents(texts(&["one", "three"])),
+        batcher.embed_documents(texts(&["fourth"])),
+    );
+    let first_len = |vectors: Vec<Vec<f32>>| vectors.iter().map(|v| v[0]).collect::<Vec<_>>();
+    assert_eq!(first_len(first.await.unwrap().unwrap()), vec![5.0]);
+    assert_eq!(first_len(a.unwrap()), vec![3.0, 5.0]);
+    assert_eq!(first_len(b.unwrap()), vec![6.0]);
+    assert_eq!(embedder.batches.lock().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_failed_input_only_fails_its_own_request() {
+    let embedder = Arc::new(RecordingEmbedder { batches: Mutex::new(Vec::new()), delay: Duration::from_millis(50) });
+    let batcher = Arc::new(EmbeddingBatcher::new(embedder.clone()));
+
+    let first = tokio::spawn({
+        let batcher = batcher.clone();
+        async move { batcher.embed_documents(texts(&["first"])).await }
+    });
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    // Merged into one batch, which fails, then retried per request.
+    let (good, bad) = tokio::join!(
+        batcher.embed_documents(texts(&["fine", "ok"])),
+        batcher.embed_documents(texts(&["poison"])),
+    );
+    assert!(first.await.unwrap().is_ok());
+    assert_eq!(good.unwrap().iter().map(|v| v[0]).collect::<Vec<_>>(), vec![4.0, 2.0]);
+    assert!(bad.unwrap_err().to_string().contains("poison"));
+}
+
+#[tokio::test]
+async fn test_batcher_bounds_padded_batch_size() {
+    let embedder = RecordingEmbedder::new();
+    let batcher = EmbeddingBatcher::new(embedder.clone()).with_batch_tokens(4);
+
+    // 8 characters ≈ 2 tokens: two short texts fit, a long one goes alone.
+    let long = "x".repeat(40);
+    batcher.embed_documents(vec!["aaaaaaaa".to_string(), "bbbbbbbb".to_string(), long.clone()]).await.unwrap();
+    let batches = embedder.batches.lock().unwrap();
+    assert_eq!(*batches, vec![texts(&["aaaaaaaa", "bbbbbbbb"]), vec![long]]);
+}
+
+#[tokio::test]
+async fn test_pipeline_embeds_document_chunks_as_one_batch() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let db = Arc::new(SqliteDatabase::open(dir.path().join("batching.db"), 24)?);
+    let embedder = RecordingEmbedder::new();
+    let pipeline = IngestionPipeline::new(embedder.clone(), db, None, false, None);
+
+    pipeline.run("alpha ---CHUNK--- beta ---CHUNK--- gamma", json!({})).await?;
+    // A single-chunk document is still embedded as a document.
+    pipeline.run("solo", json!({})).await?;
+
+    let batches = embedder.batches.lock().unwrap();
+    assert_eq!(*batches, vec![texts(&["beta", "alpha", "gamma"]), texts(&["solo"])]);
+    Ok(())
+}
@@ -1,4 +1,10 @@
+use local_memory::config::{ChunkingConfig, ChunkingStrategy};
+use local_memory::engine::chunking::{
+    Chunk, Chunker, CodeChunker, MarkdownChunker, MarkerChunker, SimilarityChunker, TokenChunker,
+};
 use local_memory::engine::ingestion::IngestionPipeline;
+use local_memory::model::tokens::ApproxTokenCounter;
+use local_memory::model::EmbeddingBatcher;
 use local_memory::storage::SqliteDatabase;
 use tempfile::tempdir;
 use serde_json::json;
@@ -23,6 +29,8 @@ async fn test_explicit_chunking() -> anyhow::Result<()> {
         }
     }
 
+    impl local_memory::model::RetrievalEmbedder for MockEmbedder {}
+
     let pipeline = IngestionPipeline::new(Arc::new(MockEmbedder), db.clone(), None, false, None);
     
     let text = "Chunk 1 ---CHUNK--- Chunk 2";
@@ -34,3 +42,155 @@ async fn test_explicit_chunking() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+fn counter() -> Arc<ApproxTokenCounter> {
+    Arc::new(ApproxTokenCounter::new(10_000))
+}
+
+/// Every chunk with a range must be exactly that slice of the source.
+fn assert_slices(source: &str, chunks: &[Chunk]) {
+    for chunk in chunks {
+        let range = chunk.range.clone().expect("range");
+        assert_eq!(&source[range], chunk.text);
+    }
+}
+
+#[tokio::test]
+async fn test_marker_chunks_record_offsets() -> anyhow::Result<()> {
+    let text = "one ---CHUNK---  two \n---CHUNK---";
+    let chunks = MarkerChunker.chunk(text).await?;
+    assert_eq!(chunks.iter().map(|c| c.text.as_str()).collect::<Vec<_>>(), vec!["one", "two"]);
+    assert_eq!(chunks[1].range, Some(17..20));
+    assert_slices(text, &chunks);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_token_chunks_overlap() -> anyhow::Result<()> {
+    // Two-letter words are one token each.
+    let text = (0..10).map(|i| format!("w{}", i)).collect::<Vec<_>>().join(" ");
+    let chunks = TokenChunker::new(counter(), 4, 1).chunk(&text).await?;
+    let texts: Vec<&str> = chunks.iter().map(|c| c.text.as_str()).collect();
+    assert_eq!(texts, vec!["w0 w1 w2 w3", "w3 w4 w5 w6", "w6 w7 w8 w9"]);
+    assert_slices(&text, &chunks);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_markdown_chunks_follow_headings() -> anyhow::Result<()> {
+    let text = "Intro line.\n\n# Guide\n## Install\nRun it.\n```sh\n# not a heading\n```\n## Use\nCall it.\n# Faq\nAsk.\n";
+    let chunks = MarkdownChunker::new(counter(), 100, 0).chunk(text).await?;
+    let sections: Vec<Option<&str>> = chunks.iter().map(|c| c.section.as_deref()).collect();
+    assert_eq!(sections, vec![None, Some("Guide > Install"), Some("Guide > Use"), Some("Faq")]);
+    // The heading-only "# Guide" section joins "## Install", fence and all.
+    assert!(chunks[1].text.starts_with("# Guide\n## Install"));
+    assert!(chunks[1].text.contains("# not a heading"));
+    assert_slices(text, &chunks);
+
+    // Oversized sections are windowed and keep their heading path.
+    let long = format!("# Big\n{}", "ab ".repeat(30));
+    let chunks = MarkdownChunker::new(counter(), 8, 0).chunk(&long).await?;
+    assert!(chunks.len() > 1);
+    assert!(chunks.iter().all(|c| c.section.as_deref() == Some("Big")));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_code_chunks_split_at_definitions_with_doc_comments() -> anyhow::Result<()> {
+    let body = "    let x = 1;\n".repeat(6);
+    let text = format!(
+        "use std::fmt;\n\n/// Adds.\n#[inline]\npub fn add() {{\n{body}}}\n\nimpl Foo {{\n    fn bar() {{}}\n{body}}}\n"
+    );
+    let chunks = CodeChunker::new(counter(), 40, 0).chunk(&text).await?;
+    let sections: Vec<Option<&str>> = chunks.iter().map(|c| c.section.as_deref()).collect();
+    // The short `use` header merges into the first definition.
+    assert_eq!(sections, vec![Some("pub fn add()"), Some("impl Foo")]);
+    assert!(chunks[0].text.contains("/// Adds.\n#[inline]\npub fn add()"));
+    assert!(chunks[1].text.starts_with("impl Foo"));
+    // Indented functions are not top-level boundaries.
+    assert!(chunks[1].text.contains("fn bar()"));
+    assert_slices(&text, &chunks);
+
+    // With room to spare, neighbouring definitions are merged.
+    let chunks = CodeChunker::new(counter(), 1000, 0).chunk(&text).await?;
+    assert_eq!(chunks.len(), 1);
+    Ok(())
+}
+
+/// Embeds sentences mentioning cats and stocks on orthogonal axes.
+struct TopicEmbedder;
+
+#[async_trait::async_trait]
+impl edgequake_llm::EmbeddingProvider for TopicEmbedder {
+    fn name(&self) -> &str { "topic" }
+    fn model(&self) -> &str { "topic" }
+    fn dimension(&self) -> usize { 2 }
+    fn max_tokens(&self) -> usize { 512 }
+    async fn embed(&self, texts: &[String]) -> std::result::Result<Vec<Vec<f32>>, edgequake_llm::LlmError> {
+        Ok(texts.iter().map(|t| if t.contains("cat") { vec![1.0, 0.1] } else { vec![0.1, 1.0] }).collect())
+    }
+}
+
+impl local_memory::model::RetrievalEmbedder for TopicEmbedder {}
+
+#[tokio::test]
+async fn test_similarity_chunks_break_at_topic_changes() -> anyhow::Result<()> {
+    let text = "My cat sleeps. The cat purrs. A cat naps! Stocks fell today. Markets closed lower. Bonds rallied.";
+    let batcher = Arc::new(EmbeddingBatcher::new(Arc::new(TopicEmbedder)));
+    let chunks = SimilarityChunker::new(batcher, counter(), 100, 80.0).chunk(text).await?;
+    let texts: Vec<&str> = chunks.iter().map(|c| c.text.as_str()).collect();
+    assert_eq!(texts, vec!["My cat sleeps. The cat purrs. A cat naps!", "Stocks fell today. Markets closed lower. Bonds rallied."]);
+    assert_slices(text, &chunks);
+    Ok(())
+}
+
+#[test]
+fn test_strategy_follows_extension_then_config() {
+    let mut config = ChunkingConfig::default();
+    assert_eq!(config.strategy_for(Some("md")), ChunkingStrategy::Markdown);
+    assert_eq!(config.strategy_for(Some("RS")), ChunkingStrategy::Code);
+    assert_eq!(config.strategy_for(Some("txt")), ChunkingStrategy::None);
+    assert_eq!(config.strategy_for(None), ChunkingStrategy::None);
+
+    config.strategy = ChunkingStrategy::Tokens;
+    config.by_extension.insert("md".to_string(), ChunkingStrategy::Similarity);
+    assert_eq!(config.strategy_for(Some("md")), ChunkingStrategy::Similarity);
+    assert_eq!(config.strategy_for(Some("txt")), ChunkingStrategy::Tokens);
+    assert!("paragraphs".parse::<ChunkingStrategy>().is_err());
+}
+
+#[tokio::test]
+async fn test_pipeline_records_chunk_offsets_per_file_type() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let db = Arc::new(SqliteDatabase::open(dir.path().join("md.db"), 768)?);
+    let path = dir.path().join("notes.md");
+    let text = "# Alpha\nFirst part.\n# Beta\nSecond part.\n";
+    std::fs::write(&path, text)?;
+
+    struct ZeroEmbedder;
+    #[async_trait::async_trait]
+    impl edgequake_llm::EmbeddingProvider for ZeroEmbedder {
+        fn name(&self) -> &str { "zero" }
+        fn model(&self) -> &str { "zero" }
+        fn dimension(&self) -> usize { 768 }
+        fn max_tokens(&self) -> usize { 512 }
+        async fn embed(&self, texts: &[String]) -> std::result::Result<Vec<Vec<f32>>, edgequake_llm::LlmError> {
+            Ok(texts.iter().map(|_| vec![0.0; 768]).collect())
+        }
+    }
+    impl local_memory::model::RetrievalEmbedder for ZeroEmbedder {}
+
+    let pipeline = || IngestionPipeline::new(Arc::new(ZeroEmbedder), db.clone(), None, false, None);
+
+    let parent = pipeline().run_file(&path, json!({}), "default").await?;
+    assert_eq!(db.count_documents_by_parent(&parent.to_string())?, 2);
+
+    // The same text memorized directly is one chunk unless a strategy is chosen.
+    let single = pipeline().run(text, json!({})).await?;
+    let (_, metadata) = db.get_document_content(single)?.unwrap();
+    assert!(metadata.get("chunk").is_none());
+
+    let parent = pipeline().with_strategy(Some(ChunkingStrategy::Markdown)).run(text, json!({})).await?;
+    assert_eq!(db.count_documents_by_parent(&parent.to_string())?, 2);
+    Ok(())
+}
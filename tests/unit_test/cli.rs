@@ -0,0 +1,41 @@
+use clap::Parser;
+use local_memory::cli::{Cli, Commands, ConflictCommands};
+use local_memory::storage::sqlite::{ConflictResolution, ConflictStatus};
+
+#[test]
+fn test_conflict_status_is_validated() {
+    let cli = Cli::try_parse_from(["lmcli", "conflicts", "list", "--status", "resolved"]).unwrap();
+    match cli.command {
+        Commands::Conflicts { action: ConflictCommands::List { status, .. } } => assert!(matches!(status, ConflictStatus::Resolved)),
+        _ => panic!("expected conflicts list"),
+    }
+    // `-s` is the global --storage flag.
+    let cli = Cli::try_parse_from(["lmcli", "conflicts", "list", "-S", "dismissed", "-s", "/tmp/store"]).unwrap();
+    match cli.command {
+        Commands::Conflicts { action: ConflictCommands::List { status, .. } } => assert!(matches!(status, ConflictStatus::Dismissed)),
+        _ => panic!("expected conflicts list"),
+    }
+    assert!(cli.storage.is_some());
+
+    let typo = Cli::try_parse_from(["lmcli", "conflicts", "list", "--status", "opne"]).err().unwrap().to_string();
+    assert!(typo.contains("possible values: open, resolved, dismissed"), "{}", typo);
+}
+
+#[test]
+fn test_conflict_resolution_is_validated() {
+    for (arg, expected) in [("accept_claim", ConflictResolution::AcceptClaim), ("keep", ConflictResolution::KeepExisting), ("dismiss", ConflictResolution::Dismiss)] {
+        let cli = Cli::try_parse_from(["lmcli", "conflicts", "resolve", "00000000-0000-0000-0000-000000000000", arg]).unwrap();
+        match cli.command {
+            Commands::Conflicts { action: ConflictCommands::Resolve { resolution, .. } } => assert_eq!(resolution, expected),
+            _ => panic!("expected conflicts resolve"),
+        }
+    }
+    let typo = Cli::try_parse_from(["lmcli", "conflicts", "resolve", "x", "acept"]).err().unwrap().to_string();
+    assert!(typo.contains("possible values: accept_claim, keep_existing, dismiss"), "{}", typo);
+}
+
+#[test]
+fn test_cli_definition_is_consistent() {
+    use clap::CommandFactory;
+    Cli::command().debug_assert();
+}
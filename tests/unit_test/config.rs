@@ -53,3 +53,34 @@ fn test_gemini_config() {
     let config: Config = serde_json::from_str(json).unwrap();
     assert_eq!(config.embedding.api_key, Some("mock-key".to_string()));
 }
+
+#[test]
+fn test_extractor_dtype_and_gguf() {
+    use local_memory::config::{GgufConfig, ModelDType};
+
+    let json = r#"{
+        "llm_extractor": {
+            "provider": "huggingface",
+            "name": "Qwen/Qwen2.5-1.5B-Instruct",
+            "dtype": "f16",
+            "gguf": { "repo": "Qwen/Qwen2.5-1.5B-Instruct-GGUF", "file": "qwen2.5-1.5b-instruct-q4_k_m.gguf" }
+        }
+    }"#;
+    let ext = serde_json::from_str::<Config>(json).unwrap().llm_extractor.unwrap();
+    assert_eq!(ext.dtype, ModelDType::F16);
+    assert_eq!(
+        ext.gguf,
+        Some(GgufConfig {
+            file: "qwen2.5-1.5b-instruct-q4_k_m.gguf".to_string(),
+            repo: Some("Qwen/Qwen2.5-1.5B-Instruct-GGUF".to_string()),
+        })
+    );
+
+    // Both default to full-precision safetensors.
+    let ext = Config::default().llm_extractor.unwrap();
+    assert_eq!(ext.dtype, ModelDType::F32);
+    assert!(ext.gguf.is_none());
+
+    let bad = r#"{"llm_extractor": {"provider": "huggingface", "name": "x", "dtype": "int3"}}"#;
+    assert!(serde_json::from_str::<Config>(bad).is_err());
+}
@@ -0,0 +1,138 @@
+use local_memory::model::candle::constrained::{ConstrainedDecoder, JsonState, Schema};
+use local_memory::model::candle::ModelRegistry;
+use local_memory::model::candle::registry::ArchConfig;
+
+const PROMPT: &str = "<|im_start|>user\n# Template:\n{\"entities\":[{\"name\":\"verbatim-string\",\"type\":[\"Person\",\"Place\"]}],\"count\":\"integer\"}\n# Context:\nAlice lives in Paris\n<|im_end|>";
+
+fn schema() -> Schema {
+    Schema::from_prompt(PROMPT).expect("template should parse")
+}
+
+#[test]
+fn test_schema_from_prompt_keeps_key_order_and_enums() {
+    let expected = Schema::Object(vec![
+        ("entities".to_string(), Schema::Array(Box::new(Schema::Object(vec![
+            ("name".to_string(), Schema::String),
+            ("type".to_string(), Schema::Enum(vec!["Person".to_string(), "Place".to_string()])),
+        ])))),
+        ("count".to_string(), Schema::Number),
+    ]);
+    assert_eq!(schema(), expected);
+    assert!(Schema::from_prompt("Extract entities. Text: hi").is_none());
+}
+
+#[test]
+fn test_schema_from_registry_templates() {
+    let registry = ModelRegistry::load().unwrap();
+    for model in [
+        "numind/NuExtract-1.5",
+        "numind/NuExtract-2.0-2B",
+        "meta-llama/Llama-3.2-1B-Instruct",
+        "mistralai/Mistral-7B-Instruct-v0.3",
+        "google/gemma-2-2b-it",
+    ] {
+        let resolved = registry.resolve(model).unwrap();
+        let ArchConfig::Generation(cfg) = &resolved.config else { panic!("expected generation config") };
+        assert!(cfg.constrained_decoding, "{model}");
+        let prompt = resolved.config.format_prompt("Text: Alice lives in Paris").unwrap();
+        let Some(Schema::Object(fields)) = Schema::from_prompt(&prompt) else { panic!("{model}: expected an object schema") };
+        // Conflicts must be expressible under constrained decoding.
+        let keys: Vec<&str> = fields.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, ["entities", "relationships", "conflicts"], "{model}");
+    }
+}
+
+#[test]
+fn test_state_accepts_conforming_output() {
+    let mut state = JsonState::new(&schema());
+    let output = "{\n  \"entities\": [\n    {\"name\": \"Alice\", \"type\": \"Person\"},\n    {\"name\": \"Paris\", \"type\": null}\n  ],\n  \"count\": 2\n}";
+    assert!(state.feed(output));
+    assert!(state.is_complete());
+}
+
+#[test]
+fn test_state_rejects_nonconforming_output() {
+    for bad in [
+        "Here is the JSON",
+        "{\"count\": 1}",
+        "{\"entities\": [{\"name\": \"Alice\", \"type\": \"City\"}]",
+        "{\"entities\": [], \"count\": \"two\"}",
+        "{\"entities\": [], \"count\": 1, \"extra\": 1}",
+    ] {
+        let mut state = JsonState::new(&schema());
+        assert!(!state.feed(bad), "{bad}");
+    }
+}
+
+#[test]
+fn test_completion_closes_truncated_output() {
+    for prefix in [
+        "",
+        "{\"entities\": [{\"name\": \"Ali",
+        "{\"entities\": [{\"name\": \"Alice\", \"type\": \"Per",
+        "{\"entities\": [{\"name\": \"Alice\", \"type\": \"Person\"}], \"cou",
+        "{\"entities\": [], \"count\": 4",
+    ] {
+        let mut state = JsonState::new(&schema());
+        assert!(state.feed(prefix), "{prefix}");
+        let full = format!("{}{}", prefix, state.completion());
+        let value: serde_json::Value = serde_json::from_str(&full)
+            .unwrap_or_else(|e| panic!("{full}: {e}"));
+        assert!(value.get("entities").is_some(), "{full}");
+        assert!(state.feed(&state.completion()) && state.is_complete(), "{full}");
+    }
+}
+
+#[test]
+fn test_decoder_masks_tokens_outside_schema() {
+    let schema = Schema::Object(vec![("title".to_string(), Schema::String)]);
+    let vocab: Vec<String> = ["Sure", "{", "\"", "title", ":", " ", "}", ""]
+        .iter().map(|s| s.to_string()).collect();
+    let mut decoder = ConstrainedDecoder::new(schema, &vocab);
+
+    // A model that always prefers prose; greedy decoding under the mask.
+    let preference = [9.0, 1.0, 1.0, 1.0, 1.0, 0.5, 1.0, 8.0];
+    let mut output = String::new();
+    for step in 0..12 {
+        let mut logits = preference.to_vec();
+        assert!(decoder.mask_logits(&mut logits));
+        assert_eq!(logits[7], f32::NEG_INFINITY, "empty/special tokens must be masked");
+        if step == 0 {
+            assert_eq!(logits[0], f32::NEG_INFINITY, "prose must be masked outside strings");
+        }
+        let best = (0..logits.len()).max_by(|&a, &b| logits[a].total_cmp(&logits[b])).unwrap();
+        output.push_str(&vocab[best]);
+        decoder.advance(&output);
+    }
+    assert!(output.starts_with("{\"title\":\"Sure"), "{output}");
+
+    let value: serde_json::Value = serde_json::from_str(&decoder.finish(output)).unwrap();
+    assert!(value["title"].as_str().unwrap().starts_with("Sure"));
+}
+
+#[test]
+fn test_decoder_finds_allowed_tokens_ranked_below_many_disallowed_ones() {
+    let schema = Schema::Object(vec![("title".to_string(), Schema::String)]);
+    // 1000 prose tokens all outscore the only token that can open the object.
+    let mut vocab: Vec<String> = (0..1000).map(|i| format!("word{i}")).collect();
+    vocab.push("{".to_string());
+    let decoder = ConstrainedDecoder::new(schema, &vocab);
+
+    let mut logits: Vec<f32> = (0..1000).map(|i| 10.0 + i as f32 * 0.001).collect();
+    logits.push(0.0);
+    assert!(decoder.mask_logits(&mut logits));
+    assert_eq!(logits[1000], 0.0);
+    assert!(logits[..1000].iter().all(|l| *l == f32::NEG_INFINITY));
+}
+
+#[test]
+fn test_decoder_finish_closes_output_when_budget_runs_out() {
+    let schema = Schema::Object(vec![("title".to_string(), Schema::String), ("summary".to_string(), Schema::String)]);
+    let vocab: Vec<String> = vec![];
+    let mut decoder = ConstrainedDecoder::new(schema, &vocab);
+    decoder.advance("{\"title\": \"Unfinished");
+    let output = decoder.finish("{\"title\": \"Unfinished".to_string());
+    let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(value["title"], "Unfinished");
+    assert!(value["summary"].is_null());
+}
@@ -0,0 +1,110 @@
+use async_trait::async_trait;
+use edgequake_llm::{EmbeddingProvider, LlmError};
+use local_memory::model::{CachedEmbedder, RetrievalEmbedder};
+use local_memory::storage::EmbeddingCache;
+use std::sync::{Arc, Mutex};
+use tempfile::tempdir;
+
+/// Embeds each document as `[len, batch_size]` and each query as `[len, -1]`,
+/// recording every call.
+struct CountingEmbedder {
+    calls: Mutex<Vec<Vec<String>>>,
+}
+
+#[async_trait]
+impl EmbeddingProvider for CountingEmbedder {
+    fn name(&self) -> &str { "counting" }
+    fn model(&self) -> &str { "counter-v1" }
+    fn dimension(&self) -> usize { 2 }
+    fn max_tokens(&self) -> usize { 512 }
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, LlmError> {
+        self.calls.lock().unwrap().push(texts.to_vec());
+        Ok(texts.iter().map(|t| vec![t.len() as f32, texts.len() as f32]).collect())
+    }
+    async fn embed_one(&self, text: &str) -> Result<Vec<f32>, LlmError> {
+        self.calls.lock().unwrap().push(vec![text.to_string()]);
+        Ok(vec![text.len() as f32, -1.0])
+    }
+}
+
+impl RetrievalEmbedder for CountingEmbedder {}
+
+fn cached(cache: &Arc<EmbeddingCache>) -> (CachedEmbedder, Arc<CountingEmbedder>) {
+    let inner = Arc::new(CountingEmbedder { calls: Mutex::new(Vec::new()) });
+    let embedder = CachedEmbedder::new(inner.clone(), cache.clone()).with_prefixes("query: ", "passage: ");
+    (embedder, inner)
+}
+
+fn texts(items: &[&str]) -> Vec<String> {
+    items.iter().map(|s| s.to_string()).collect()
+}
+
+#[tokio::test]
+async fn test_repeated_text_is_served_from_cache() {
+    let dir = tempdir().unwrap();
+    let cache = Arc::new(EmbeddingCache::open_in(dir.path()).unwrap());
+    let (embedder, inner) = cached(&cache);
+
+    let first = embedder.embed_query("hello").await.unwrap();
+    let second = embedder.embed_query("hello").await.unwrap();
+    assert_eq!(first, second);
+    assert_eq!(inner.calls.lock().unwrap().len(), 1);
+
+    let stats = cache.stats().unwrap();
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].model, "counting:counter-v1@2");
+    assert_eq!((stats[0].entries, stats[0].hits, stats[0].misses), (1, 1, 1));
+    assert!((stats[0].hit_rate() - 0.5).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn test_query_and_document_vectors_are_cached_separately() {
+    let dir = tempdir().unwrap();
+    let cache = Arc::new(EmbeddingCache::open_in(dir.path()).unwrap());
+    let (embedder, inner) = cached(&cache);
+
+    // `embed_query` and `embed_documents` are keyed by their own prefixes.
+    let query = embedder.embed_query("alpha").await.unwrap();
+    let batch = embedder.embed_documents(&texts(&["alpha", "beta"])).await.unwrap();
+    assert_eq!(query, vec![5.0, -1.0]);
+    assert_eq!(batch[0], vec![5.0, 2.0]);
+    assert_eq!(inner.calls.lock().unwrap().len(), 2);
+
+    // Only the misses of a batch are embedded.
+    let batch = embedder.embed_documents(&texts(&["alpha", "gamma", "delta"])).await.unwrap();
+    assert_eq!(inner.calls.lock().unwrap().last().unwrap(), &texts(&["gamma", "delta"]));
+    assert_eq!(batch[0], vec![5.0, 2.0]);
+}
+
+#[tokio::test]
+async fn test_single_document_keeps_document_semantics() {
+    let dir = tempdir().unwrap();
+    let cache = Arc::new(EmbeddingCache::open_in(dir.path()).unwrap());
+    let (embedder, inner) = cached(&cache);
+
+    embedder.embed_documents(&texts(&["a", "bb"])).await.unwrap();
+    // A lone miss, or a one-chunk document, is still embedded as a document.
+    let batch = embedder.embed_documents(&texts(&["a", "ccc"])).await.unwrap();
+    assert_eq!(inner.calls.lock().unwrap().last().unwrap(), &texts(&["ccc"]));
+    assert_eq!(batch[1], vec![3.0, 1.0]);
+    assert_eq!(cache.get("counting:counter-v1@2", "passage: ", "ccc").unwrap(), Some(vec![3.0, 1.0]));
+    assert_eq!(cache.get("counting:counter-v1@2", "query: ", "ccc").unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_cache_persists_across_reopen_and_is_keyed_by_model() {
+    let dir = tempdir().unwrap();
+    {
+        let cache = Arc::new(EmbeddingCache::open_in(dir.path()).unwrap());
+        cached(&cache).0.embed_query("persisted").await.unwrap();
+    }
+    let cache = Arc::new(EmbeddingCache::open_in(dir.path()).unwrap());
+    let (embedder, inner) = cached(&cache);
+    embedder.embed_query("persisted").await.unwrap();
+    assert!(inner.calls.lock().unwrap().is_empty());
+
+    assert_eq!(cache.get("counting:counter-v1@2", "query: ", "persisted").unwrap(), Some(vec![9.0, -1.0]));
+    assert_eq!(cache.get("other-model", "query: ", "persisted").unwrap(), None);
+    assert_eq!(cache.get("counting:counter-v1@2", "passage: ", "persisted").unwrap(), None);
+    assert_ne!(EmbeddingCache::key("m", "ab", "c"), EmbeddingCache::key("m", "a", "bc"));
+}
@@ -0,0 +1,77 @@
+use async_trait::async_trait;
+use edgequake_llm::{EmbeddingProvider, LLMProvider, LlmError};
+use local_memory::config::{ModelConfig, ModelProvider};
+use local_memory::model::candle::ModelRegistry;
+use local_memory::model::{embedding_prefixes, GenericUnifiedModel, PrefixedEmbedder, RetrievalEmbedder};
+use std::sync::{Arc, Mutex};
+
+/// Records the raw texts it is sent; `embed_one` is left to the default.
+struct RawEmbedder {
+    seen: Mutex<Vec<String>>,
+}
+
+#[async_trait]
+impl EmbeddingProvider for RawEmbedder {
+    fn name(&self) -> &str { "raw" }
+    fn model(&self) -> &str { "raw" }
+    fn dimension(&self) -> usize { 1 }
+    fn max_tokens(&self) -> usize { 512 }
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, LlmError> {
+        self.seen.lock().unwrap().extend(texts.iter().cloned());
+        Ok(texts.iter().map(|_| vec![1.0]).collect())
+    }
+}
+
+fn model_config(provider: ModelProvider, name: &str) -> ModelConfig {
+    ModelConfig { name: name.to_string(), provider, ..ModelConfig::default() }
+}
+
+#[test]
+fn test_prefixes_follow_registry_and_config() {
+    let reg = ModelRegistry::load().unwrap();
+    let prefixes = |m: &ModelConfig| embedding_prefixes(m, &reg);
+
+    let ollama = model_config(ModelProvider::Ollama, "nomic-embed-text");
+    assert_eq!(prefixes(&ollama), ("search_query: ".to_string(), "search_document: ".to_string()));
+
+    let openai = model_config(ModelProvider::OpenAI, "text-embedding-3-small");
+    assert_eq!(prefixes(&openai), (String::new(), String::new()));
+
+    // Generation models have no embedding prefixes.
+    let llama = model_config(ModelProvider::Ollama, "llama3.2");
+    assert_eq!(prefixes(&llama), (String::new(), String::new()));
+
+    let custom = ModelConfig {
+        query_prefix: Some("query: ".to_string()),
+        ..model_config(ModelProvider::OpenAI, "nomic-ai/nomic-embed-text-v1.5")
+    };
+    assert_eq!(prefixes(&custom), ("query: ".to_string(), "search_document: ".to_string()));
+}
+
+#[tokio::test]
+async fn test_unified_model_applies_query_and_document_prefixes() {
+    let raw = Arc::new(RawEmbedder { seen: Mutex::new(Vec::new()) });
+    let embedder = Arc::new(PrefixedEmbedder::new(raw.clone(), "search_query: ", "search_document: "));
+    let model = GenericUnifiedModel {
+        llm: Arc::new(edgequake_llm::MockProvider::new()) as Arc<dyn LLMProvider>,
+        embedder,
+        prepare_list: vec![],
+        override_dimension: None,
+        token_counter: None,
+    };
+
+    model.embed_query("who wrote it?").await.unwrap();
+    // A single document is embedded as a document, not as a query.
+    model.embed_documents(&["one chunk".to_string()]).await.unwrap();
+    model.embed_documents(&["a".to_string(), "b".to_string()]).await.unwrap();
+
+    assert_eq!(
+        *raw.seen.lock().unwrap(),
+        vec![
+            "search_query: who wrote it?",
+            "search_document: one chunk",
+            "search_document: a",
+            "search_document: b",
+        ]
+    );
+}
@@ -0,0 +1,181 @@
+use async_trait::async_trait;
+use base64::Engine as _;
+use edgequake_llm::{ChatMessage, CompletionOptions, LLMProvider, LLMResponse, LlmError};
+use local_memory::config::ImageConfig;
+use local_memory::engine::ingestion::IngestionPipeline;
+use local_memory::engine::loaders::exif_datetime;
+use local_memory::storage::SqliteDatabase;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use tempfile::tempdir;
+
+const DIM: usize = 24;
+
+struct ZeroEmbedder;
+
+#[async_trait]
+impl edgequake_llm::EmbeddingProvider for ZeroEmbedder {
+    fn name(&self) -> &str { "zero" }
+    fn model(&self) -> &str { "zero" }
+    fn dimension(&self) -> usize { DIM }
+    fn max_tokens(&self) -> usize { 512 }
+    async fn embed(&self, texts: &[String]) -> std::result::Result<Vec<Vec<f32>>, edgequake_llm::LlmError> {
+        Ok(texts.iter().map(|_| vec![0.1; DIM]).collect())
+    }
+}
+
+impl local_memory::model::RetrievalEmbedder for ZeroEmbedder {}
+
+/// Captions every image and records the (mime, width, height) it was sent.
+#[derive(Default)]
+struct MockVision {
+    seen: Mutex<Vec<(String, u32, u32)>>,
+}
+
+fn response(content: &str) -> LLMResponse {
+    LLMResponse {
+        content: content.to_string(),
+        model: "mock-vision".to_string(),
+        prompt_tokens: 0, completion_tokens: 0, total_tokens: 0,
+        finish_reason: Some("stop".to_string()), tool_calls: vec![],
+        metadata: std::collections::HashMap::new(),
+        cache_hit_tokens: Some(0), thinking_tokens: Some(0), thinking_content: None,
+    }
+}
+
+#[async_trait]
+impl LLMProvider for MockVision {
+    fn name(&self) -> &str { "mock" }
+    fn model(&self) -> &str { "mock-vision" }
+    fn max_context_length(&self) -> usize { 4096 }
+
+    async fn complete(&self, _prompt: &str) -> std::result::Result<LLMResponse, LlmError> {
+        Ok(response(r#"{"entities": [], "relationships": []}"#))
+    }
+
+    async fn complete_with_options(&self, prompt: &str, _options: &CompletionOptions) -> std::result::Result<LLMResponse, LlmError> {
+        self.complete(prompt).await
+    }
+
+    async fn chat(&self, messages: &[ChatMessage], _options: Option<&CompletionOptions>) -> std::result::Result<LLMResponse, LlmError> {
+        let Some(images) = messages.last().and_then(|m| m.images.clone()) else {
+            return self.complete("").await;
+        };
+        for image in images {
+            let bytes = base64::engine::general_purpose::STANDARD.decode(&image.data).unwrap();
+            let (width, height) = image::ImageReader::new(std::io::Cursor::new(bytes))
+                .with_guessed_format()
+                .unwrap()
+                .into_dimensions()
+                .unwrap();
+            self.seen.lock().unwrap().push((image.mime_type, width, height));
+        }
+        Ok(response("A sequence diagram of the login flow between the browser and the auth service."))
+    }
+}
+
+#[tokio::test]
+async fn test_images_are_captioned_by_the_llm() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let db = Arc::new(SqliteDatabase::open(dir.path().join("images.db"), DIM)?);
+    let vision = Arc::new(MockVision::default());
+    let pipeline = IngestionPipeline::new(Arc::new(ZeroEmbedder), db.clone(), Some(vision.clone()), false, None)
+        .with_images(ImageConfig { vision: true, max_side: 500, ..ImageConfig::default() });
+
+    let path = dir.path().join("login-flow.png");
+    image::RgbImage::new(2000, 100).save(&path)?;
+    let id = pipeline.run_image(&path, json!({"tags": ["auth"]}), "default").await?;
+
+    let (content, metadata) = db.get_document_content(id)?.unwrap();
+    assert!(content.starts_with("A sequence diagram of the login flow"));
+    assert_eq!(metadata["format"], "image");
+    assert_eq!(metadata["title"], "login-flow.png");
+    assert_eq!(metadata["tags"], json!(["auth"]));
+    let image = &metadata["image"];
+    assert_eq!((image["width"].as_u64(), image["height"].as_u64()), (Some(2000), Some(100)));
+    assert_eq!(image["format"], "png");
+    assert_eq!(image["sha256"].as_str().map(str::len), Some(64));
+    assert_eq!(image["caption_model"], "mock/mock-vision");
+    assert_eq!(image["ocr"], false);
+    assert!(image.get("taken_at").is_none());
+
+    // Large images are sent downscaled.
+    assert_eq!(vision.seen.lock().unwrap().as_slice(), &[("image/png".to_string(), 500, 25)]);
+
+    // Only images go through run_image.
+    let notes = dir.path().join("notes.txt");
+    std::fs::write(&notes, "not an image")?;
+    assert!(pipeline.run_image(&notes, json!({}), "default").await.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_images_are_read_by_the_ocr_command() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let db = Arc::new(SqliteDatabase::open(dir.path().join("ocr.db"), DIM)?);
+    let path = dir.path().join("error.jpg");
+    image::RgbImage::new(64, 48).save(&path)?;
+
+    // Without an LLM or an OCR command, images are not ingestible.
+    let plain = IngestionPipeline::new(Arc::new(ZeroEmbedder), db.clone(), None, false, None)
+        .with_images(ImageConfig::default());
+    assert!(plain.loaders().loader_for(&path, &std::fs::read(&path)?).is_none());
+    // Nor with an LLM that is not declared to accept images.
+    let vision = Arc::new(MockVision::default());
+    let text_only = IngestionPipeline::new(Arc::new(ZeroEmbedder), db.clone(), Some(vision.clone()), false, None)
+        .with_images(ImageConfig::default());
+    assert!(text_only.loaders().loader_for(&path, &std::fs::read(&path)?).is_none());
+
+    let ocr = ImageConfig {
+        ocr_command: vec!["echo".to_string(), "502 Bad Gateway in {path}".to_string()],
+        ..ImageConfig::default()
+    };
+    let pipeline = IngestionPipeline::new(Arc::new(ZeroEmbedder), db.clone(), None, false, None).with_images(ocr);
+    let id = pipeline.run_file(&path, json!({}), "default").await?;
+    let (content, metadata) = db.get_document_content(id)?.unwrap();
+    assert_eq!(content, format!("Text in the image:\n502 Bad Gateway in {}", path.display()));
+    assert_eq!(metadata["image"]["ocr"], true);
+    assert_eq!(metadata["image"]["format"], "jpg");
+    assert!(metadata["image"].get("caption_model").is_none());
+    Ok(())
+}
+
+/// A TIFF (EXIF) block: IFD0 and an Exif IFD with the given entries
+/// (tag, type, count, value), followed by `tail`.
+fn tiff(little: bool, ifd0: &[(u16, u16, u32, u32)], exif: &[(u16, u16, u32, u32)], tail: &[u8]) -> Vec<u8> {
+    let u16b = |v: u16| if little { v.to_le_bytes() } else { v.to_be_bytes() };
+    let u32b = |v: u32| if little { v.to_le_bytes() } else { v.to_be_bytes() };
+    let mut out = if little { b"II*\0".to_vec() } else { b"MM\0*".to_vec() };
+    out.extend(u32b(8));
+    for ifd in [ifd0, exif] {
+        out.extend(u16b(ifd.len() as u16));
+        for (tag, kind, count, value) in ifd {
+            out.extend(u16b(*tag));
+            out.extend(u16b(*kind));
+            out.extend(u32b(*count));
+            out.extend(u32b(*value));
+        }
+        out.extend(u32b(0));
+    }
+    out.extend(tail);
+    out
+}
+
+#[test]
+fn test_exif_capture_time() {
+    // IFD0 at 8 (one entry: 18 bytes), Exif IFD at 26 (one entry), dates after it.
+    let dates = b"2021:01:02 03:04:05\x002024:05:06 07:08:09\x00";
+    let data = 44;
+    let block = tiff(true, &[(0x8769, 4, 1, 26)], &[(0x9003, 2, 20, data + 20)], dates);
+    assert_eq!(exif_datetime(&block).as_deref(), Some("2024-05-06T07:08:09"));
+
+    let mut jpeg_app1 = b"Exif\0\0".to_vec();
+    jpeg_app1.extend(&block);
+    assert_eq!(exif_datetime(&jpeg_app1).as_deref(), Some("2024-05-06T07:08:09"));
+
+    // Without DateTimeOriginal, the modification time; big-endian too.
+    let block = tiff(false, &[(0x0132, 2, 20, 32)], &[], dates);
+    assert_eq!(exif_datetime(&block).as_deref(), Some("2021-01-02T03:04:05"));
+
+    assert_eq!(exif_datetime(b"not exif"), None);
+}
@@ -10,6 +10,7 @@ fn test_get_llm_provider_ollama_config() {
         api_key: None,
         base_url: Some("http://localhost:11434".to_string()),
         auto_download: true,
+        ..ExtractorConfig::default()
     });
     
     let provider = get_llm_provider(&config);
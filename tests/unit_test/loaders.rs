@@ -0,0 +1,170 @@
+use local_memory::engine::ingestion::IngestionPipeline;
+use local_memory::engine::loaders::{
+    parse_delimited, split_front_matter, Loaded, Loader, LoaderRegistry, UnsupportedFormat, CSV_ROWS_PER_CHUNK,
+};
+use local_memory::storage::SqliteDatabase;
+use serde_json::json;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::tempdir;
+
+const DIM: usize = 24;
+
+struct ZeroEmbedder;
+
+#[async_trait::async_trait]
+impl edgequake_llm::EmbeddingProvider for ZeroEmbedder {
+    fn name(&self) -> &str { "zero" }
+    fn model(&self) -> &str { "zero" }
+    fn dimension(&self) -> usize { DIM }
+    fn max_tokens(&self) -> usize { 512 }
+    async fn embed(&self, texts: &[String]) -> std::result::Result<Vec<Vec<f32>>, edgequake_llm::LlmError> {
+        Ok(texts.iter().map(|_| vec![0.1; DIM]).collect())
+    }
+}
+
+impl local_memory::model::RetrievalEmbedder for ZeroEmbedder {}
+
+async fn load(name: &str, content: &[u8]) -> anyhow::Result<(Loaded, &'static str)> {
+    let dir = tempdir()?;
+    let path = dir.path().join(name);
+    std::fs::write(&path, content)?;
+    LoaderRegistry::default().load(&path).await
+}
+
+#[tokio::test]
+async fn test_html_is_converted_to_text() -> anyhow::Result<()> {
+    let html = br#"<!DOCTYPE html><html lang="en"><head><title> Install   Guide </title>
+        <meta name="description" content="How to install"><style>body { color: red }</style></head>
+        <body><nav><a href="/">Home</a></nav><h1>Install</h1><p>Run <code>cargo   build</code> &amp; wait.</p>
+        <script>alert("x")</script><ul><li>Fast</li><li>Local</li></ul>
+        <pre>fn main() {
+    println!("hi");
+}</pre></body></html>"#;
+    let (loaded, format) = load("guide.html", html).await?;
+    assert_eq!(format, "html");
+    assert_eq!(loaded.metadata["title"], "Install Guide");
+    assert_eq!(loaded.metadata["description"], "How to install");
+    assert_eq!(loaded.metadata["lang"], "en");
+    assert!(loaded.text.contains("# Install\n\nRun cargo build & wait."), "{}", loaded.text);
+    assert!(loaded.text.contains("- Fast\n- Local"), "{}", loaded.text);
+    assert!(loaded.text.contains("```\nfn main() {\n    println!(\"hi\");\n}\n```"), "{}", loaded.text);
+    assert!(!loaded.text.contains("alert") && !loaded.text.contains("color") && !loaded.text.contains('<'));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_markdown_front_matter_becomes_metadata() -> anyhow::Result<()> {
+    let (loaded, format) = load("adr.md", b"---\ntitle: Use SQLite\nstatus: accepted\ntags: [storage, adr]\n---\n\n# Context\nWe need storage.\n").await?;
+    assert_eq!(format, "markdown");
+    assert_eq!(loaded.metadata["title"], "Use SQLite");
+    assert_eq!(loaded.metadata["tags"], json!(["storage", "adr"]));
+    assert_eq!(loaded.text, "# Context\nWe need storage.\n");
+
+    assert_eq!(split_front_matter("---\na: 1\n...\nbody"), Some(("a: 1\n", "body")));
+    assert_eq!(split_front_matter("--- not front matter\n"), None);
+    assert_eq!(split_front_matter("---\nunterminated: true\n"), None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_notebook_cells_are_extracted() -> anyhow::Result<()> {
+    let notebook = json!({
+        "metadata": {"kernelspec": {"language": "python"}},
+        "cells": [
+            {"cell_type": "markdown", "source": ["# Analysis\n", "Load the data."]},
+            {"cell_type": "code", "source": "df = load()\nprint(len(df))", "outputs": [{"output_type": "stream", "text": ["42\n"]}]},
+            {"cell_type": "code", "source": [], "outputs": []},
+            {"cell_type": "code", "source": "df", "outputs": [{"output_type": "execute_result", "data": {"text/plain": ["   a  b"]}}]}
+        ]
+    });
+    let (loaded, format) = load("analysis.ipynb", notebook.to_string().as_bytes()).await?;
+    assert_eq!(format, "notebook");
+    assert_eq!(
+        loaded.text,
+        "# Analysis\nLoad the data.\n\n```python\ndf = load()\nprint(len(df))\n```\n\nOutput:\n```\n42\n```\n\n```python\ndf\n```\n\nOutput:\n```\n   a  b\n```"
+    );
+    assert_eq!(loaded.metadata["notebook"], json!({"cells": 4, "code_cells": 2, "language": "python"}));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_csv_rows_are_chunked() -> anyhow::Result<()> {
+    assert_eq!(
+        parse_delimited("a,b\r\n\"x, \"\"y\"\"\",\"multi\nline\"\n1,\n", ','),
+        vec![vec!["a", "b"], vec!["x, \"y\"", "multi\nline"], vec!["1", ""]]
+    );
+
+    let mut csv = String::from("name,team\n");
+    for i in 0..CSV_ROWS_PER_CHUNK + 5 {
+        csv.push_str(&format!("user{},core\n", i));
+    }
+    let (loaded, format) = load("people.csv", csv.as_bytes()).await?;
+    assert_eq!(format, "table");
+    assert!(loaded.text.starts_with("name: user0 | team: core\nname: user1 | team: core"));
+    assert_eq!(loaded.text.matches("---CHUNK---").count(), 1);
+    assert_eq!(loaded.metadata["table"], json!({"columns": ["name", "team"], "rows": CSV_ROWS_PER_CHUNK + 5}));
+
+    let (loaded, _) = load("scores.tsv", b"id\tscore\n1\t0.5\n").await?;
+    assert_eq!(loaded.text, "id: 1 | score: 0.5");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_json_is_flattened() -> anyhow::Result<()> {
+    let (loaded, format) = load("config.json", br#"{"server": {"port": 8080, "hosts": ["a", "b"]}, "debug": false, "name": null}"#).await?;
+    assert_eq!(format, "json");
+    // Keys come out sorted.
+    assert_eq!(loaded.text, "debug: false\nname: null\nserver.hosts[0]: a\nserver.hosts[1]: b\nserver.port: 8080");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_unsupported_binaries_are_skipped_with_a_message() -> anyhow::Result<()> {
+    let err = load("logo.png", b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").await.unwrap_err();
+    let unsupported = err.downcast_ref::<UnsupportedFormat>().expect("UnsupportedFormat");
+    assert_eq!(unsupported.mime, "image/png");
+    assert!(err.to_string().contains("unsupported binary format (image/png)"));
+
+    // Unknown extensions are still read when the content is text.
+    let (loaded, format) = load("notes.unknownext", b"plain words").await?;
+    assert_eq!((loaded.text.as_str(), format), ("plain words", "text"));
+    Ok(())
+}
+
+struct ShoutLoader;
+
+#[async_trait::async_trait]
+impl Loader for ShoutLoader {
+    fn name(&self) -> &'static str { "shout" }
+    async fn load(&self, _path: &Path, bytes: &[u8]) -> anyhow::Result<Loaded> {
+        Ok(Loaded::text(String::from_utf8_lossy(bytes).to_uppercase()))
+    }
+}
+
+#[tokio::test]
+async fn test_pipeline_uses_registered_loaders() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let db = Arc::new(SqliteDatabase::open(dir.path().join("loaders.db"), DIM)?);
+    let mut registry = LoaderRegistry::default();
+    registry.register_extension("shout", "text/x-shout");
+    registry.register("text/x-shout", Arc::new(ShoutLoader));
+    let pipeline = IngestionPipeline::new(Arc::new(ZeroEmbedder), db.clone(), None, false, None)
+        .with_loaders(Arc::new(registry));
+
+    let path = dir.path().join("memo.shout");
+    std::fs::write(&path, "quiet please")?;
+    let id = pipeline.run_file(&path, json!({}), "default").await?;
+    let (content, metadata) = db.get_document_content(id)?.unwrap();
+    assert_eq!(content, "QUIET PLEASE");
+    assert_eq!(metadata["format"], "shout");
+
+    // Front matter fills in metadata, but the caller's keys win.
+    let path = dir.path().join("adr.md");
+    std::fs::write(&path, "---\ntitle: From front matter\nstatus: draft\n---\nBody.")?;
+    let id = pipeline.run_file(&path, json!({"status": "final"}), "default").await?;
+    let (content, metadata) = db.get_document_content(id)?.unwrap();
+    assert_eq!(content, "Body.");
+    assert_eq!((metadata["title"].as_str(), metadata["status"].as_str()), (Some("From front matter"), Some("final")));
+    Ok(())
+}
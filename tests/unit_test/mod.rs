@@ -1,5 +1,7 @@
+#[allow(clippy::bool_assert_comparison)]
 pub mod config;
 pub mod downloader;
+#[allow(clippy::field_reassign_with_default)]
 pub mod llm;
 pub mod tier;
 pub mod temporal;
@@ -9,3 +11,17 @@ pub mod decay_entities;
 pub mod chunking;
 pub mod model_registry;
 pub mod model_loader;
+pub mod ontology;
+pub mod structured;
+pub mod constrained;
+pub mod embedding_cache;
+pub mod batching;
+pub mod embedding_prefixes;
+pub mod tokens;
+pub mod sources;
+pub mod watch;
+pub mod loaders;
+pub mod pdf;
+pub mod images;
+pub mod tool_args;
+pub mod cli;
@@ -88,3 +88,79 @@ fn test_missing_files_returns_empty_when_complete() {
     let missing = pub_test_missing_files(dir.path());
     assert!(missing.is_empty());
 }
+
+// ── Weights / ensure_weights ──────────────────────────────────────────────────
+
+#[test]
+fn test_weights_for_extractor() {
+    use candle_core::DType;
+    use local_memory::config::{ExtractorConfig, GgufConfig, ModelDType};
+    use local_memory::model::candle::Weights;
+
+    let mut config = ExtractorConfig {
+        name: "Qwen/Qwen2.5-1.5B-Instruct".to_string(),
+        ..ExtractorConfig::default()
+    };
+    assert_eq!(Weights::for_extractor(&config), Weights::Safetensors(DType::F32));
+
+    config.dtype = ModelDType::Bf16;
+    assert_eq!(Weights::for_extractor(&config), Weights::Safetensors(DType::BF16));
+
+    // `gguf` takes precedence over `dtype`; the repo defaults to the model's own.
+    config.gguf = Some(GgufConfig { file: "q4.gguf".to_string(), repo: None });
+    assert_eq!(
+        Weights::for_extractor(&config),
+        Weights::Gguf { repo: "Qwen/Qwen2.5-1.5B-Instruct".to_string(), file: "q4.gguf".to_string() }
+    );
+    config.gguf = Some(GgufConfig {
+        file: "q4.gguf".to_string(),
+        repo: Some("Qwen/Qwen2.5-1.5B-Instruct-GGUF".to_string()),
+    });
+    assert!(matches!(
+        Weights::for_extractor(&config),
+        Weights::Gguf { repo, .. } if repo == "Qwen/Qwen2.5-1.5B-Instruct-GGUF"
+    ));
+}
+
+#[tokio::test]
+async fn test_ensure_weights_gguf_needs_only_tokenizer_and_file() {
+    use local_memory::model::candle::{ensure_weights, Weights};
+
+    let base = tempdir().unwrap();
+    let weights = Weights::Gguf { repo: "org/model-GGUF".to_string(), file: "model-q4_k_m.gguf".to_string() };
+    let model_dir = get_model_dir(base.path(), "org/model");
+    fs::create_dir_all(&model_dir).unwrap();
+    fs::write(model_dir.join("tokenizer.json"), b"{}").unwrap();
+
+    // The GGUF file is missing and downloads are disabled.
+    assert!(ensure_weights("org/model", base.path(), &weights, false).await.is_err());
+
+    // No config.json or model.safetensors is needed once the file is there.
+    fs::write(model_dir.join("model-q4_k_m.gguf"), b"GGUF").unwrap();
+    let dir = ensure_weights("org/model", base.path(), &weights, false).await.unwrap();
+    assert_eq!(dir, model_dir);
+    assert!(ensure_weights("org/model", base.path(), &Weights::default(), false).await.is_err());
+}
+
+#[test]
+fn test_missing_files_follow_the_shard_index() {
+    use local_memory::model::candle::{missing_files, Weights};
+
+    let dir = tempdir().unwrap();
+    assert_eq!(
+        missing_files(dir.path(), &Weights::default()),
+        vec!["config.json", "tokenizer.json", "model.safetensors"]
+    );
+    for name in ["config.json", "tokenizer.json"] {
+        fs::write(dir.path().join(name), b"{}").unwrap();
+    }
+    fs::write(
+        dir.path().join("model.safetensors.index.json"),
+        r#"{"weight_map": {"a": "model-00001-of-00002.safetensors", "b": "model-00002-of-00002.safetensors"}}"#,
+    )
+    .unwrap();
+    fs::write(dir.path().join("model-00001-of-00002.safetensors"), b"x").unwrap();
+    // Leftover partial downloads do not count as present.
+    fs::write(dir.path().join("model-00002-of-00002.safetensors.part"), b"x").unwrap();
+    assert_eq!(missing_files(dir.path(), &Weights::default()), vec!["model-00002-of-00002.safetensors"]);
+}
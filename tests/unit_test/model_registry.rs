@@ -2,7 +2,12 @@
 ///
 /// These tests operate entirely on the embedded `models.yaml` — no model
 /// files, no network, no GPU required.
-use local_memory::model::candle::registry::{ArchConfig, ArchKind, ModelRegistry};
+use candle_transformers::generation::Sampling;
+use edgequake_llm::{ChatMessage, CompletionOptions};
+use local_memory::model::candle::backend::{GenerateOptions, Pooling};
+use local_memory::model::candle::registry::{
+    ArchConfig, ArchKind, ChatTemplate, EmbeddingConfig, MatchSource, ModelRegistry,
+};
 
 // ── Registry::load ────────────────────────────────────────────────────────────
 
@@ -156,19 +161,34 @@ fn test_phi3_generation_config_eos_tokens() {
 
 #[test]
 fn test_generation_config_contains_text_placeholder() {
-    // The prompt template must contain {text} so format_prompt can substitute.
+    // Every prompt template must contain {text} so format_prompt can substitute.
     let reg = ModelRegistry::load().unwrap();
     for name in ["numind/NuExtract-1.5", "numind/NuExtract-2.0-2B"] {
         let resolved = reg.resolve(name).unwrap();
         if let ArchConfig::Generation(cfg) = resolved.config {
-            assert!(
-                cfg.prompt_template.contains("{text}"),
-                "template for {name} is missing {{text}} placeholder"
-            );
+            for (template_name, template) in &cfg.prompt_templates {
+                assert!(
+                    template.contains("{text}"),
+                    "template {template_name} for {name} is missing {{text}} placeholder"
+                );
+            }
         }
     }
 }
 
+#[test]
+fn test_generation_config_carries_all_named_templates() {
+    let reg = ModelRegistry::load().unwrap();
+    for name in ["numind/NuExtract-1.5", "numind/NuExtract-2.0-2B"] {
+        let ArchConfig::Generation(cfg) = reg.resolve(name).unwrap().config else {
+            panic!("expected GenerationConfig");
+        };
+        assert_eq!(cfg.default_template, "extraction");
+        assert!(cfg.template("extraction").is_ok());
+        assert!(cfg.template("summary").is_ok());
+    }
+}
+
 // ── ArchConfig::format_prompt ─────────────────────────────────────────────────
 
 fn qwen2_gen_config() -> ArchConfig {
@@ -194,14 +214,14 @@ fn bert_embed_config() -> ArchConfig {
 fn test_format_prompt_passthrough_qwen2_markers() {
     let cfg = qwen2_gen_config();
     let prompt = "<|im_start|>user\nhello<|im_end|>\n<|im_start|>assistant\n";
-    assert_eq!(cfg.format_prompt(prompt), prompt);
+    assert_eq!(cfg.format_prompt(prompt).unwrap(), prompt);
 }
 
 #[test]
 fn test_format_prompt_passthrough_phi3_markers() {
     let cfg = phi3_gen_config();
     let prompt = "<|input|>\n### Template:\n{}\n### Text:\nhello\n<|output|>\n";
-    assert_eq!(cfg.format_prompt(prompt), prompt);
+    assert_eq!(cfg.format_prompt(prompt).unwrap(), prompt);
 }
 
 // Rule 2 — TEMPLATE:<name> prefix uses the named template.
@@ -211,7 +231,7 @@ fn test_format_prompt_template_tag_substitutes_text() {
     let cfg = qwen2_gen_config();
     // communities.rs sends this format for summarisation.
     let prompt = "TEMPLATE:summary\nentity A: desc\nentity B: desc";
-    let result = cfg.format_prompt(prompt);
+    let result = cfg.format_prompt(prompt).unwrap();
     // The summary template has {text} replaced with the body.
     assert!(result.contains("entity A: desc"));
     assert!(result.contains("entity B: desc"));
@@ -219,12 +239,31 @@ fn test_format_prompt_template_tag_substitutes_text() {
     assert!(!result.contains("TEMPLATE:"));
 }
 
+#[test]
+fn test_format_prompt_template_tag_selects_named_template() {
+    for cfg in [qwen2_gen_config(), phi3_gen_config()] {
+        let summary = cfg.format_prompt("TEMPLATE:summary\nentity A").unwrap();
+        assert!(summary.contains(r#"{"title":"string","summary":"string"}"#));
+        assert!(!summary.contains("relationships"));
+        let extraction = cfg.format_prompt("TEMPLATE:extraction\nentity A").unwrap();
+        assert!(extraction.contains("relationships"));
+    }
+}
+
+#[test]
+fn test_format_prompt_unknown_template_is_an_error() {
+    let cfg = qwen2_gen_config();
+    let err = cfg.format_prompt("TEMPLATE:nonexistent\nbody").unwrap_err().to_string();
+    assert!(err.contains("nonexistent"), "{err}");
+    assert!(err.contains("summary"), "error should list available templates: {err}");
+}
+
 #[test]
 fn test_format_prompt_template_tag_trims_body() {
     let cfg = qwen2_gen_config();
     // Extra whitespace around body should be trimmed.
     let prompt = "TEMPLATE:summary\n  trimmed content  ";
-    let result = cfg.format_prompt(prompt);
+    let result = cfg.format_prompt(prompt).unwrap();
     assert!(result.contains("trimmed content"));
     assert!(!result.contains("  trimmed content  "));
 }
@@ -236,7 +275,7 @@ fn test_format_prompt_strips_text_prefix_for_qwen2() {
     let cfg = qwen2_gen_config();
     // ingestion.rs appends the text after "Text: ".
     let prompt = "Extract entities ...\nText: The quick brown fox";
-    let result = cfg.format_prompt(prompt);
+    let result = cfg.format_prompt(prompt).unwrap();
     assert!(result.contains("The quick brown fox"));
     // The legacy instruction text should NOT appear in the final prompt.
     assert!(!result.contains("Extract entities"));
@@ -246,16 +285,35 @@ fn test_format_prompt_strips_text_prefix_for_qwen2() {
 fn test_format_prompt_plain_text_inserted_when_no_text_prefix() {
     let cfg = qwen2_gen_config();
     let prompt = "just some raw text";
-    let result = cfg.format_prompt(prompt);
+    let result = cfg.format_prompt(prompt).unwrap();
     assert!(result.contains("just some raw text"));
 }
 
+#[test]
+fn test_format_prompt_renders_ontology_as_enum() {
+    let cfg = qwen2_gen_config();
+    let prompt = "Extract ...\nALLOWED ENTITY TYPES: Person, Organization\nALLOWED PREDICATES: WORKS_AT\nText: Alice works at Acme";
+    let result = cfg.format_prompt(prompt).unwrap();
+    assert!(result.contains(r#""type":["Person","Organization"]"#));
+    assert!(result.contains(r#""predicate":["WORKS_AT"]"#));
+    assert!(!result.contains("ALLOWED"));
+}
+
+#[test]
+fn test_format_prompt_without_ontology_uses_string_type() {
+    let cfg = phi3_gen_config();
+    let result = cfg.format_prompt("Text: Alice works at Acme").unwrap();
+    assert!(result.contains(r#""type":"string""#));
+    assert!(!result.contains("{entity_types}"));
+    assert!(!result.contains("{predicates}"));
+}
+
 #[test]
 fn test_format_prompt_embedding_passthrough() {
     // Embedding configs must return the prompt unchanged.
     let cfg = bert_embed_config();
     let prompt = "any text whatsoever";
-    assert_eq!(cfg.format_prompt(prompt), prompt);
+    assert_eq!(cfg.format_prompt(prompt).unwrap(), prompt);
 }
 
 // Edge cases.
@@ -264,7 +322,7 @@ fn test_format_prompt_embedding_passthrough() {
 fn test_format_prompt_empty_string() {
     let cfg = qwen2_gen_config();
     // Should not panic; result contains the template structure with empty body.
-    let result = cfg.format_prompt("");
+    let result = cfg.format_prompt("").unwrap();
     assert!(!result.contains("{text}"));
 }
 
@@ -274,6 +332,405 @@ fn test_format_prompt_template_tag_no_newline_is_treated_as_plain() {
     let cfg = qwen2_gen_config();
     let prompt = "TEMPLATE:summary-no-newline";
     // Must not panic; result should include the prompt content.
-    let result = cfg.format_prompt(prompt);
+    let result = cfg.format_prompt(prompt).unwrap();
     assert!(!result.is_empty());
 }
+
+// ── ChatTemplate::render ──────────────────────────────────────────────────────
+
+fn chat_template(cfg: &ArchConfig) -> ChatTemplate {
+    match cfg {
+        ArchConfig::Generation(g) => g.chat_template.clone().expect("chat_template"),
+        _ => panic!("expected GenerationConfig"),
+    }
+}
+
+#[test]
+fn test_qwen2_chat_template_renders_every_message_in_order() {
+    let template = chat_template(&qwen2_gen_config());
+    let messages = vec![
+        ChatMessage::system("be brief"),
+        ChatMessage::user("first"),
+        ChatMessage::assistant("reply"),
+        ChatMessage::user("second"),
+    ];
+    assert_eq!(
+        template.render(None, &messages),
+        "<|im_start|>system\nbe brief<|im_end|>\n\
+         <|im_start|>user\nfirst<|im_end|>\n\
+         <|im_start|>assistant\nreply<|im_end|>\n\
+         <|im_start|>user\nsecond<|im_end|>\n\
+         <|im_start|>assistant\n"
+    );
+}
+
+#[test]
+fn test_chat_template_system_prompt_option_is_prepended() {
+    let template = chat_template(&phi3_gen_config());
+    let rendered = template.render(Some("be brief"), &[ChatMessage::user("hi")]);
+    assert_eq!(
+        rendered,
+        "<|system|>\nbe brief<|end|>\n<|user|>\nhi<|end|>\n<|assistant|>\n"
+    );
+}
+
+#[test]
+fn test_chat_template_system_message_wins_over_option() {
+    let template = chat_template(&qwen2_gen_config());
+    let rendered = template.render(
+        Some("option"),
+        &[ChatMessage::system("message"), ChatMessage::user("hi")],
+    );
+    assert!(rendered.contains("message"));
+    assert!(!rendered.contains("option"));
+}
+
+#[test]
+fn test_chat_template_without_system_turn_folds_into_user() {
+    let template = ChatTemplate {
+        system: None,
+        user: "<u>{content}</u>".to_string(),
+        assistant: "<a>{content}</a>".to_string(),
+        tool: None,
+        generation_prompt: "<a>".to_string(),
+    };
+    let rendered = template.render(None, &[ChatMessage::system("rules"), ChatMessage::user("hi")]);
+    assert_eq!(rendered, "<u>rules\n\nhi</u><a>");
+}
+
+// ── GenerateOptions ───────────────────────────────────────────────────────────
+
+#[test]
+fn test_generate_options_from_completion_options() {
+    let options = CompletionOptions {
+        max_tokens: Some(16),
+        temperature: Some(0.5),
+        top_p: Some(0.9),
+        stop: Some(vec!["END".to_string()]),
+        ..Default::default()
+    };
+    let generate = GenerateOptions::from_completion(&options);
+    assert_eq!(generate.max_new_tokens, Some(16));
+    assert_eq!(generate.stop, vec!["END".to_string()]);
+    assert!(matches!(generate.sampling(), Sampling::TopP { .. }));
+}
+
+#[test]
+fn test_generate_options_default_is_greedy() {
+    assert_eq!(GenerateOptions::default().sampling(), Sampling::ArgMax);
+    let zero = GenerateOptions { temperature: Some(0.0), ..Default::default() };
+    assert_eq!(zero.sampling(), Sampling::ArgMax);
+    let warm = GenerateOptions { temperature: Some(0.8), ..Default::default() };
+    assert_eq!(warm.sampling(), Sampling::All { temperature: 0.8 });
+}
+
+#[test]
+fn test_generate_options_truncate_at_earliest_stop() {
+    let options = GenerateOptions {
+        stop: vec!["\n\n".to_string(), "###".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(options.truncate_at_stop("answer###\n\nmore".to_string()), "answer");
+    assert_eq!(options.truncate_at_stop("no stop".to_string()), "no stop");
+}
+
+#[test]
+fn test_resolve_options_fills_arch_defaults() {
+    let ArchConfig::Generation(cfg) = qwen2_gen_config() else {
+        panic!("expected GenerationConfig");
+    };
+    let resolved = cfg.resolve_options(&GenerateOptions::default());
+    assert_eq!(resolved.max_new_tokens, Some(cfg.max_new_tokens));
+    let resolved = cfg.resolve_options(&GenerateOptions { max_new_tokens: Some(8), ..Default::default() });
+    assert_eq!(resolved.max_new_tokens, Some(8));
+}
+
+// ── User registry overlay ─────────────────────────────────────────────────────
+
+fn with_user(yaml: &str) -> anyhow::Result<ModelRegistry> {
+    ModelRegistry::load().unwrap().with_user_yaml(yaml)
+}
+
+fn error_chain(result: anyhow::Result<ModelRegistry>) -> String {
+    format!("{:#}", result.err().expect("expected an error"))
+}
+
+#[test]
+fn test_user_registry_adds_exact_model() {
+    let reg = with_user(
+        r#"
+models:
+  "acme/extractor-7b": { arch: qwen2, prompt_template: summary }
+"#,
+    )
+    .unwrap();
+    let resolved = reg.resolve("acme/extractor-7b").unwrap();
+    assert_eq!(resolved.arch, ArchKind::Qwen2);
+    assert_eq!(resolved.matched_by, MatchSource::Exact);
+    let ArchConfig::Generation(cfg) = resolved.config else { panic!("expected GenerationConfig") };
+    assert_eq!(cfg.default_template, "summary");
+    // Embedded entries are still there.
+    assert!(reg.resolve("numind/NuExtract-1.5").is_ok());
+}
+
+#[test]
+fn test_user_patterns_take_precedence() {
+    let reg = with_user(
+        r#"
+patterns:
+  - { contains: "phi-embed", arch: bert }
+"#,
+    )
+    .unwrap();
+    let resolved = reg.resolve("acme/Phi-Embed-small").unwrap();
+    assert_eq!(resolved.arch, ArchKind::Bert);
+    assert_eq!(resolved.matched_by, MatchSource::Pattern("phi-embed".to_string()));
+    assert_eq!(reg.patterns()[0].key, "phi-embed");
+}
+
+#[test]
+fn test_user_registry_merges_architecture_fields() {
+    let reg = with_user(
+        r#"
+architectures:
+  bert:
+    query_prefix: "query: "
+  qwen2:
+    max_new_tokens: 64
+    prompt_templates:
+      answer: "<|im_start|>user\n{text}<|im_end|>\n"
+"#,
+    )
+    .unwrap();
+    let ArchConfig::Embedding(embed) = reg.resolve("nomic-ai/nomic-embed-text-v1.5").unwrap().config else {
+        panic!("expected EmbeddingConfig")
+    };
+    assert_eq!(embed.query_prefix, "query: ");
+    assert_eq!(embed.document_prefix, "search_document: ");
+
+    let ArchConfig::Generation(cfg) = reg.resolve("numind/NuExtract-2.0-2B").unwrap().config else {
+        panic!("expected GenerationConfig")
+    };
+    assert_eq!(cfg.max_new_tokens, 64);
+    assert!(cfg.template("answer").is_ok());
+    assert!(cfg.template("extraction").is_ok(), "built-in templates must survive the merge");
+    assert!(cfg.eos_tokens.contains(&"<|im_end|>".to_string()));
+    // Other architectures are unaffected.
+    let ArchConfig::Generation(phi) = reg.resolve("numind/NuExtract-1.5").unwrap().config else {
+        panic!("expected GenerationConfig")
+    };
+    assert!(phi.template("answer").is_err());
+}
+
+#[test]
+fn test_user_architecture_reuses_backend_via_kind() {
+    let reg = with_user(
+        r#"
+architectures:
+  qwen2-chat:
+    kind: qwen2
+    role: generation
+    eos_tokens: ["<|im_end|>"]
+    prompt_templates:
+      extraction: "<|im_start|>user\n{text}<|im_end|>\n<|im_start|>assistant\n"
+models:
+  "Qwen/Qwen2.5-1.5B-Instruct": { arch: qwen2-chat }
+"#,
+    )
+    .unwrap();
+    let resolved = reg.resolve("Qwen/Qwen2.5-1.5B-Instruct").unwrap();
+    assert_eq!(resolved.arch, ArchKind::Qwen2);
+    assert_eq!(resolved.arch_name, "qwen2-chat");
+}
+
+#[test]
+fn test_user_registry_errors_name_the_offending_key() {
+    let err = error_chain(with_user("models:\n  \"acme/x\": { arch: qwen2, promt_template: summary }\n"));
+    assert!(err.contains(r#"models."acme/x""#), "{err}");
+    assert!(err.contains("promt_template"), "{err}");
+
+    let err = error_chain(with_user("models:\n  \"acme/x\": { arch: llama9 }\n"));
+    assert!(err.contains(r#"models."acme/x".arch"#) && err.contains("llama9"), "{err}");
+
+    let err = error_chain(with_user("patterns:\n  - { contains: acme, arch: phi3, prompt_template: nope }\n"));
+    assert!(err.contains("patterns[0].prompt_template"), "{err}");
+
+    let err = error_chain(with_user("architectures:\n  custom: { role: generation, prompt_templates: { x: \"{text}\" } }\n"));
+    assert!(err.contains("architectures.custom.kind"), "{err}");
+
+    let err = error_chain(with_user("architectures:\n  qwen2:\n    prompt_templates: { broken: \"no placeholder\" }\n"));
+    assert!(err.contains("architectures.qwen2.prompt_templates.broken"), "{err}");
+
+    let err = error_chain(with_user("modles: {}\n"));
+    assert!(err.contains("modles"), "{err}");
+}
+
+#[test]
+fn test_user_registry_file_errors_include_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("models.yaml");
+    std::fs::write(&path, "models:\n  \"acme/x\": { arch: nope }\n").unwrap();
+    let err = error_chain(ModelRegistry::load().unwrap().with_user_file(&path));
+    assert!(err.contains(&path.display().to_string()), "{err}");
+    // `lmcli` prints the whole chain, so the failing key follows the path.
+    assert!(err.contains("models.\"acme/x\".arch: unknown architecture 'nope'"), "{err}");
+
+    std::fs::write(&path, "models:\n  \"acme/x\": { arch: bert }\n").unwrap();
+    let reg = ModelRegistry::load().unwrap().with_user_file(&path).unwrap();
+    assert!(reg.models().iter().any(|m| m.key == "acme/x"));
+}
+
+#[test]
+fn test_for_config_requires_explicit_models_file_to_exist() {
+    let config = local_memory::config::Config {
+        models_file: Some(std::path::PathBuf::from("/nonexistent/models.yaml")),
+        ..Default::default()
+    };
+    assert!(ModelRegistry::for_config(&config).is_err());
+}
+
+// ── Additional architectures ──────────────────────────────────────────────────
+
+fn embedding(reg: &ModelRegistry, name: &str) -> (ArchKind, EmbeddingConfig) {
+    let resolved = reg.resolve(name).unwrap();
+    match resolved.config {
+        ArchConfig::Embedding(e) => (resolved.arch, e),
+        _ => panic!("expected EmbeddingConfig for {name}"),
+    }
+}
+
+#[test]
+fn test_embedders_resolve_with_family_pooling_and_prefixes() {
+    let reg = ModelRegistry::load().unwrap();
+    let cases = [
+        ("BAAI/bge-small-en-v1.5", ArchKind::Bert, Pooling::Cls, "Represent this sentence for searching relevant passages: ", ""),
+        ("intfloat/e5-base-v2", ArchKind::Bert, Pooling::Mean, "query: ", "passage: "),
+        ("thenlper/gte-base", ArchKind::Bert, Pooling::Mean, "", ""),
+        ("jinaai/jina-embeddings-v2-base-en", ArchKind::JinaBert, Pooling::Mean, "", ""),
+        ("nomic-ai/modernbert-embed-base", ArchKind::ModernBert, Pooling::Mean, "search_query: ", "search_document: "),
+        ("Alibaba-NLP/gte-modernbert-base", ArchKind::ModernBert, Pooling::Cls, "", ""),
+        ("nomic-ai/nomic-embed-text-v1.5", ArchKind::Bert, Pooling::Mean, "search_query: ", "search_document: "),
+    ];
+    for (name, kind, pooling, query, document) in cases {
+        let (arch, cfg) = embedding(&reg, name);
+        assert_eq!(arch, kind, "{name}");
+        assert_eq!(cfg.pooling, pooling, "{name}");
+        assert_eq!(cfg.query_prefix, query, "{name}");
+        assert_eq!(cfg.document_prefix, document, "{name}");
+    }
+}
+
+#[test]
+fn test_specific_patterns_win_over_generic_ones() {
+    let reg = ModelRegistry::load().unwrap();
+    // Not in `models:`, so these go through patterns.
+    assert_eq!(reg.resolve("someone/gte-modernbert-large").unwrap().arch_name, "gte-modernbert");
+    assert_eq!(reg.resolve("nomic-ai/modernbert-embed-large").unwrap().arch_name, "modernbert");
+    assert_eq!(reg.resolve("BAAI/bge-m3-bert").unwrap().arch_name, "bge");
+    assert_eq!(reg.resolve("intfloat/e5-small").unwrap().arch_name, "e5");
+}
+
+#[test]
+fn test_generation_families_resolve_with_chat_templates() {
+    let reg = ModelRegistry::load().unwrap();
+    let cases = [
+        ("meta-llama/Llama-3.2-3B-Instruct", ArchKind::Llama, "<|eot_id|>"),
+        ("HuggingFaceTB/SmolLM2-360M-llama", ArchKind::Llama, "<|eot_id|>"),
+        ("mistralai/Mistral-7B-Instruct-v0.3", ArchKind::Mistral, "</s>"),
+        ("google/gemma-2-9b-it", ArchKind::Gemma2, "<end_of_turn>"),
+    ];
+    for (name, kind, eos) in cases {
+        let resolved = reg.resolve(name).unwrap();
+        assert_eq!(resolved.arch, kind, "{name}");
+        let ArchConfig::Generation(cfg) = resolved.config else { panic!("expected GenerationConfig") };
+        assert!(cfg.eos_tokens.contains(&eos.to_string()), "{name}");
+        assert!(cfg.chat_template.is_some(), "{name}");
+        assert!(cfg.template("summary").is_ok(), "{name}");
+    }
+}
+
+#[test]
+fn test_llama_chat_template_renders_headers() {
+    let reg = ModelRegistry::load().unwrap();
+    let template = chat_template(&reg.resolve("meta-llama/Llama-3.2-1B-Instruct").unwrap().config);
+    let rendered = template.render(Some("be brief"), &[ChatMessage::user("hi")]);
+    assert_eq!(
+        rendered,
+        "<|start_header_id|>system<|end_header_id|>\n\nbe brief<|eot_id|>\
+         <|start_header_id|>user<|end_header_id|>\n\nhi<|eot_id|>\
+         <|start_header_id|>assistant<|end_header_id|>\n\n"
+    );
+}
+
+#[test]
+fn test_mistral_and_gemma_fold_system_into_user_turn() {
+    let reg = ModelRegistry::load().unwrap();
+    let mistral = chat_template(&reg.resolve("mistralai/Mistral-7B-Instruct-v0.3").unwrap().config);
+    assert_eq!(
+        mistral.render(Some("be brief"), &[ChatMessage::user("hi"), ChatMessage::assistant("hello"), ChatMessage::user("bye")]),
+        "[INST] be brief\n\nhi [/INST]hello</s>[INST] bye [/INST]"
+    );
+    let gemma = chat_template(&reg.resolve("google/gemma-2-2b-it").unwrap().config);
+    assert_eq!(
+        gemma.render(Some("be brief"), &[ChatMessage::user("hi")]),
+        "<start_of_turn>user\nbe brief\n\nhi<end_of_turn>\n<start_of_turn>model\n"
+    );
+}
+
+#[test]
+fn test_format_prompt_passes_through_chat_rendered_prompts() {
+    let reg = ModelRegistry::load().unwrap();
+    let cfg = reg.resolve("google/gemma-2-2b-it").unwrap().config;
+    let rendered = "<start_of_turn>user\nhello<end_of_turn>\n<start_of_turn>model\n";
+    assert_eq!(cfg.format_prompt(rendered).unwrap(), rendered);
+    // Plain text is still wrapped in the extraction template.
+    assert!(cfg.format_prompt("Alice works at Acme").unwrap().contains("# Template:"));
+}
+
+#[test]
+fn test_role_must_match_backend_kind() {
+    let err = format!(
+        "{:#}",
+        ModelRegistry::load()
+            .unwrap()
+            .with_user_yaml("architectures:\n  bad:\n    kind: llama\n    role: embedding\n")
+            .err()
+            .unwrap()
+    );
+    assert!(err.contains("architectures.bad.role"), "{err}");
+}
+
+// ── Pooling ───────────────────────────────────────────────────────────────────
+
+#[test]
+fn test_pooling_mean_and_cls_are_normalized() {
+    use candle_core::{Device, Tensor};
+    // One sequence, two tokens, hidden size 2.
+    let hidden = Tensor::new(&[[[3.0f32, 0.0], [0.0, 4.0]]], &Device::Cpu).unwrap();
+
+    let cls = Pooling::Cls.apply(&hidden).unwrap();
+    assert_eq!(cls, vec![1.0, 0.0]);
+
+    let mean = Pooling::Mean.apply(&hidden).unwrap();
+    // mean = [1.5, 2.0], norm 2.5
+    assert!((mean[0] - 0.6).abs() < 1e-6 && (mean[1] - 0.8).abs() < 1e-6, "{mean:?}");
+}
+
+#[test]
+fn test_masked_pooling_ignores_padding() {
+    use candle_core::{Device, Tensor};
+    // Two sequences padded to three tokens; the second has one real token.
+    let hidden = Tensor::new(
+        &[[[3.0f32, 0.0], [0.0, 4.0], [0.0, 0.0]], [[0.0, 2.0], [9.0, 9.0], [9.0, 9.0]]],
+        &Device::Cpu,
+    )
+    .unwrap();
+    let mask = Tensor::new(&[[1u32, 1, 0], [1, 0, 0]], &Device::Cpu).unwrap();
+
+    let mean = Pooling::Mean.apply_masked(&hidden, &mask).unwrap();
+    assert!((mean[0][0] - 0.6).abs() < 1e-6 && (mean[0][1] - 0.8).abs() < 1e-6, "{mean:?}");
+    assert_eq!(mean[1], vec![0.0, 1.0]);
+
+    let cls = Pooling::Cls.apply_masked(&hidden, &mask).unwrap();
+    assert_eq!(cls, vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+}
@@ -0,0 +1,100 @@
+use local_memory::config::{Config, OntologyConfig, UnknownTermPolicy};
+use local_memory::engine::ontology::{Ontology, TermMatch};
+use std::collections::HashMap;
+
+fn people_ontology(on_unknown: UnknownTermPolicy) -> OntologyConfig {
+    serde_json::from_value(serde_json::json!({
+        "entity_types": [
+            "Organization",
+            {"name": "Person", "description": "A human being", "aliases": ["human", "individual"]}
+        ],
+        "predicates": ["WORKS_AT"],
+        "on_unknown": on_unknown,
+    }))
+    .unwrap()
+}
+
+#[test]
+fn test_ontology_config_accepts_bare_strings_and_objects() {
+    let cfg = people_ontology(UnknownTermPolicy::Map);
+    assert_eq!(cfg.entity_types[0].name, "Organization");
+    assert_eq!(cfg.entity_types[1].aliases, vec!["human", "individual"]);
+    assert_eq!(cfg.fallback_entity_type, "Concept");
+    assert_eq!(cfg.fallback_predicate, "RELATED_TO");
+}
+
+#[test]
+fn test_entity_type_is_canonicalised_case_insensitively() {
+    let ontology = Ontology::new(people_ontology(UnknownTermPolicy::Map));
+    for raw in ["person", "PERSON", "Person", "human", "Individual"] {
+        assert_eq!(ontology.entity_type(raw), TermMatch::Accepted("Person".to_string()), "{raw}");
+    }
+}
+
+#[test]
+fn test_predicate_ignores_separators() {
+    let ontology = Ontology::new(people_ontology(UnknownTermPolicy::Map));
+    assert_eq!(ontology.predicate("works at"), TermMatch::Accepted("WORKS_AT".to_string()));
+    assert_eq!(ontology.predicate("worksAt"), TermMatch::Accepted("WORKS_AT".to_string()));
+}
+
+#[test]
+fn test_unknown_values_are_mapped_to_fallback() {
+    let ontology = Ontology::new(people_ontology(UnknownTermPolicy::Map));
+    assert_eq!(ontology.entity_type("Planet"), TermMatch::Mapped("Concept".to_string()));
+    assert_eq!(ontology.predicate("ORBITS"), TermMatch::Mapped("RELATED_TO".to_string()));
+    // The fallback itself is always allowed.
+    assert_eq!(ontology.entity_type("concept"), TermMatch::Accepted("Concept".to_string()));
+}
+
+#[test]
+fn test_unknown_values_are_rejected_by_policy() {
+    let ontology = Ontology::new(people_ontology(UnknownTermPolicy::Reject));
+    assert_eq!(ontology.entity_type("Planet"), TermMatch::Rejected);
+    assert_eq!(ontology.predicate("ORBITS"), TermMatch::Rejected);
+}
+
+#[test]
+fn test_empty_lists_leave_values_unconstrained() {
+    let ontology = Ontology::new(OntologyConfig::default());
+    assert_eq!(ontology.entity_type("anything"), TermMatch::Accepted("anything".to_string()));
+}
+
+#[test]
+fn test_for_namespace_falls_back_to_wildcard() {
+    let mut map = HashMap::new();
+    map.insert("*".to_string(), OntologyConfig::default());
+    map.insert("work".to_string(), people_ontology(UnknownTermPolicy::Reject));
+    assert_eq!(Ontology::for_namespace(&map, "work").unwrap().entity_type("Planet"), TermMatch::Rejected);
+    assert!(Ontology::for_namespace(&map, "personal").is_some());
+    assert!(Ontology::for_namespace(&HashMap::new(), "personal").is_none());
+}
+
+#[test]
+fn test_prompt_section_lists_allowed_values() {
+    let section = Ontology::new(people_ontology(UnknownTermPolicy::Map)).prompt_section();
+    assert!(section.contains("ALLOWED ENTITY TYPES: Organization, Person"));
+    assert!(section.contains("- Person: A human being"));
+    assert!(section.contains("ALLOWED PREDICATES: WORKS_AT"));
+}
+
+#[test]
+fn test_validate_rejects_ambiguous_aliases() {
+    let cfg: OntologyConfig = serde_json::from_value(serde_json::json!({
+        "entity_types": [
+            {"name": "Person", "aliases": ["member"]},
+            {"name": "Team", "aliases": ["Member"]}
+        ]
+    }))
+    .unwrap();
+    assert!(cfg.validate().is_err());
+    assert!(people_ontology(UnknownTermPolicy::Map).validate().is_ok());
+}
+
+#[test]
+fn test_config_ontology_is_keyed_by_namespace() {
+    let config: Config = serde_json::from_str(r#"{
+        "ontology": { "work": { "entity_types": ["Person"], "on_unknown": "reject" } }
+    }"#).unwrap();
+    assert_eq!(config.ontology["work"].on_unknown, UnknownTermPolicy::Reject);
+}
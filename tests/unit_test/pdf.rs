@@ -0,0 +1,163 @@
+use local_memory::config::{ChunkingStrategy, PdfConfig, PdfExtraction};
+use local_memory::engine::ingestion::IngestionPipeline;
+use local_memory::engine::loaders::{pdf_text_pages, Loader, PdfLoader};
+use local_memory::storage::SqliteDatabase;
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Document, Object, Stream};
+use serde_json::json;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::tempdir;
+
+const DIM: usize = 24;
+
+struct ZeroEmbedder;
+
+#[async_trait::async_trait]
+impl edgequake_llm::EmbeddingProvider for ZeroEmbedder {
+    fn name(&self) -> &str { "zero" }
+    fn model(&self) -> &str { "zero" }
+    fn dimension(&self) -> usize { DIM }
+    fn max_tokens(&self) -> usize { 512 }
+    async fn embed(&self, texts: &[String]) -> std::result::Result<Vec<Vec<f32>>, edgequake_llm::LlmError> {
+        Ok(texts.iter().map(|_| vec![0.1; DIM]).collect())
+    }
+}
+
+impl local_memory::model::RetrievalEmbedder for ZeroEmbedder {}
+
+/// A PDF with one page per entry, each holding the given lines of text
+/// (no lines: a page without a text layer, like a scan).
+fn write_pdf(path: &Path, pages: &[&[&str]]) {
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let font_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+    });
+    let resources_id = doc.add_object(dictionary! {
+        "Font" => dictionary! { "F1" => font_id },
+    });
+
+    let mut kids = Vec::new();
+    for lines in pages {
+        let mut operations = vec![
+            Operation::new("BT", vec![]),
+            Operation::new("Tf", vec!["F1".into(), 12.into()]),
+            Operation::new("TL", vec![14.into()]),
+            Operation::new("Td", vec![72.into(), 720.into()]),
+        ];
+        for line in *lines {
+            operations.push(Operation::new("Tj", vec![Object::string_literal(*line)]));
+            operations.push(Operation::new("T*", vec![]));
+        }
+        operations.push(Operation::new("ET", vec![]));
+        let content = Content { operations };
+        let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+        });
+        kids.push(page_id.into());
+    }
+
+    let count = kids.len() as i64;
+    doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+        "Type" => "Pages",
+        "Kids" => kids,
+        "Count" => count,
+        "Resources" => resources_id,
+        "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+    }));
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    doc.trailer.set("Root", catalog_id);
+    doc.save(path).unwrap();
+}
+
+#[tokio::test]
+async fn test_pdf_text_layer_is_read_per_page() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("report.pdf");
+    write_pdf(&path, &[&["Quarterly report for the storage team."], &[], &["Latency improved by forty percent."]]);
+
+    let pages = pdf_text_pages(&std::fs::read(&path)?)?;
+    assert_eq!(pages.len(), 3);
+    assert!(pages[0].contains("Quarterly report"), "{:?}", pages);
+    assert!(pages[1].is_empty());
+
+    // Without an LLM, the page without a text layer is left out.
+    let loader = PdfLoader::new(None, PdfConfig::default());
+    let loaded = loader.load(&path, &std::fs::read(&path)?).await?;
+    assert_eq!(loaded.metadata["page_count"], 3);
+    assert_eq!(loaded.metadata["pdf_extraction"], json!({"text_pages": 2, "llm_pages": 0, "missing_pages": [2]}));
+    assert_eq!(loaded.pages.len(), 3);
+    assert!(loaded.text[loaded.pages[2]..].starts_with("Latency improved"));
+
+    // Asking for the LLM converter without an LLM is an error, not a silent fallback.
+    let loader = PdfLoader::new(None, PdfConfig { extraction: PdfExtraction::Llm, ..PdfConfig::default() });
+    let err = loader.load(&path, &std::fs::read(&path)?).await.unwrap_err();
+    assert!(err.to_string().contains("LLM required"));
+
+    // A scan-only PDF has nothing to offer without one.
+    let scan = dir.path().join("scan.pdf");
+    write_pdf(&scan, &[&[]]);
+    let loader = PdfLoader::new(None, PdfConfig::default());
+    assert!(loader.load(&scan, &std::fs::read(&scan)?).await.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_pdf_chunks_record_their_pages() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let db = Arc::new(SqliteDatabase::open(dir.path().join("pdf.db"), DIM)?);
+    let path = dir.path().join("manual.pdf");
+    let first: Vec<String> = (0..6).map(|i| format!("Setup step {} explains the install.", i)).collect();
+    let second: Vec<String> = (0..6).map(|i| format!("Usage note {} explains the commands.", i)).collect();
+    let first: Vec<&str> = first.iter().map(String::as_str).collect();
+    let second: Vec<&str> = second.iter().map(String::as_str).collect();
+    write_pdf(&path, &[&first, &second]);
+
+    // PDFs load without any LLM, and with the `text` mode as well.
+    let pipeline = IngestionPipeline::new(Arc::new(ZeroEmbedder), db.clone(), None, false, None)
+        .with_pdf(PdfConfig { extraction: PdfExtraction::Text, ..PdfConfig::default() })
+        .with_chunking(local_memory::config::ChunkingConfig { chunk_tokens: 40, overlap_tokens: 0, ..Default::default() })
+        .with_strategy(Some(ChunkingStrategy::Tokens));
+    let parent = pipeline.run_file(&path, json!({}), "default").await?;
+    assert!(db.count_documents_by_parent(&parent.to_string())? >= 2);
+
+    let pages: Vec<(i64, i64, String)> = chunks_of(&dir.path().join("pdf.db"), &parent.to_string())?
+        .into_iter()
+        .map(|(content, metadata)| {
+            let span = &metadata["chunk"]["pages"];
+            (span[0].as_i64().unwrap(), span[1].as_i64().unwrap(), content)
+        })
+        .collect();
+    assert!(pages.iter().all(|(first, last, _)| (1..=2).contains(first) && first <= last && *last <= 2));
+    for (first, last, content) in &pages {
+        if content.contains("Setup") && !content.contains("Usage") {
+            assert_eq!((*first, *last), (1, 1));
+        }
+        if content.contains("Usage") && !content.contains("Setup") {
+            assert_eq!((*first, *last), (2, 2));
+        }
+    }
+    assert!(pages.iter().any(|(first, _, _)| *first == 2));
+    Ok(())
+}
+
+fn chunks_of(db_path: &Path, parent_id: &str) -> anyhow::Result<Vec<(String, serde_json::Value)>> {
+    let conn = rusqlite::Connection::open(db_path)?;
+    let mut stmt = conn.prepare("SELECT content, metadata FROM documents WHERE parent_id = ?1")?;
+    let rows = stmt.query_map([parent_id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+    let mut chunks = Vec::new();
+    for row in rows {
+        let (content, metadata) = row?;
+        chunks.push((content, serde_json::from_str(&metadata)?));
+    }
+    Ok(chunks)
+}
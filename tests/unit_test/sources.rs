@@ -0,0 +1,139 @@
+use local_memory::config::ChunkingStrategy;
+use local_memory::engine::ingestion::IngestionPipeline;
+use local_memory::engine::sources::{DirectoryIngest, FileStatus};
+use local_memory::storage::SqliteDatabase;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::tempdir;
+
+const DIM: usize = 24;
+
+struct ZeroEmbedder;
+
+#[async_trait::async_trait]
+impl edgequake_llm::EmbeddingProvider for ZeroEmbedder {
+    fn name(&self) -> &str { "zero" }
+    fn model(&self) -> &str { "zero" }
+    fn dimension(&self) -> usize { DIM }
+    fn max_tokens(&self) -> usize { 512 }
+    async fn embed(&self, texts: &[String]) -> std::result::Result<Vec<Vec<f32>>, edgequake_llm::LlmError> {
+        Ok(texts.iter().map(|_| vec![0.1; DIM]).collect())
+    }
+}
+
+impl local_memory::model::RetrievalEmbedder for ZeroEmbedder {}
+
+fn pipeline(db: &Arc<SqliteDatabase>) -> IngestionPipeline {
+    IngestionPipeline::new(Arc::new(ZeroEmbedder), db.clone(), None, false, None)
+}
+
+fn write(root: &Path, relative: &str, content: &[u8]) {
+    let path = root.join(relative);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, content).unwrap();
+}
+
+fn source_key(root: &Path, relative: &str) -> String {
+    std::fs::canonicalize(root.join(relative)).unwrap().to_string_lossy().to_string()
+}
+
+#[tokio::test]
+async fn test_directory_ingest_is_incremental() -> anyhow::Result<()> {
+    let storage = tempdir()?;
+    let tree = tempdir()?;
+    let root = tree.path();
+    let db = Arc::new(SqliteDatabase::open(storage.path().join("sources.db"), DIM)?);
+    write(root, "a.txt", b"Alpha notes.");
+    write(root, "docs/b.txt", b"Beta notes.");
+    write(root, "docs/c.txt", b"Gamma notes.");
+
+    let ingest = DirectoryIngest::new(root);
+    let report = ingest.run(&pipeline(&db), &db, "default").await?;
+    assert_eq!(report.added, vec!["a.txt", "docs/b.txt", "docs/c.txt"]);
+
+    // Nothing changed: nothing is re-ingested.
+    let report = ingest.run(&pipeline(&db), &db, "default").await?;
+    assert!(report.added.is_empty() && report.updated.is_empty());
+    assert_eq!(report.unchanged, 3);
+
+    // An edit becomes a new version that replaces the old one in search.
+    let before = db.get_source(&source_key(root, "docs/b.txt"), "default")?.unwrap();
+    write(root, "docs/b.txt", b"Beta notes, revised.");
+    std::fs::remove_file(root.join("docs/c.txt"))?;
+    let c_key = format!("{}/docs/c.txt", std::fs::canonicalize(root)?.to_string_lossy());
+
+    let report = ingest.run(&pipeline(&db), &db, "default").await?;
+    assert_eq!(report.updated, vec!["docs/b.txt"]);
+    assert_eq!(report.deleted, vec!["docs/c.txt"]);
+    assert_eq!(report.unchanged, 1);
+
+    let after = db.get_source(&source_key(root, "docs/b.txt"), "default")?.unwrap();
+    assert_ne!(before.content_hash, after.content_hash);
+    assert_eq!(db.count_latest_documents_by_source(&after.path, "default")?, 1);
+    let (content, metadata) = db.get_document_content(after.document_id)?.unwrap();
+    assert_eq!(content, "Beta notes, revised.");
+    assert_eq!(metadata["title"], "docs/b.txt");
+
+    // Deleted files leave search and are no longer tracked.
+    assert_eq!(db.count_latest_documents_by_source(&c_key, "default")?, 0);
+    assert!(db.get_source(&c_key, "default")?.is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_directory_ingest_filters() -> anyhow::Result<()> {
+    let storage = tempdir()?;
+    let tree = tempdir()?;
+    let root = tree.path();
+    let db = Arc::new(SqliteDatabase::open(storage.path().join("filters.db"), DIM)?);
+    write(root, ".gitignore", b"target/\n*.log\n");
+    write(root, "src/lib.rs", b"pub fn f() {}\n");
+    write(root, "src/gen/out.rs", b"pub fn g() {}\n");
+    write(root, "README.md", b"# Readme\n");
+    write(root, "run.log", b"noise");
+    write(root, "target/debug.txt", b"build output");
+    write(root, "logo.png", b"\x89PNG\r\n\x1a\n\0\0\0");
+
+    let relative = |ingest: &DirectoryIngest| -> anyhow::Result<Vec<String>> {
+        let root = ingest.root()?;
+        Ok(ingest.files()?.iter().map(|p| p.strip_prefix(&root).unwrap().to_string_lossy().replace('\\', "/")).collect())
+    };
+
+    let all = DirectoryIngest::new(root);
+    assert_eq!(relative(&all)?, vec!["README.md", "logo.png", "src/gen/out.rs", "src/lib.rs"]);
+
+    let filtered = DirectoryIngest::new(root)
+        .with_include(vec!["*.rs".to_string(), "*.md".to_string()])
+        .with_exclude(vec!["src/gen/**".to_string()]);
+    assert_eq!(relative(&filtered)?, vec!["README.md", "src/lib.rs"]);
+
+    let unignored = DirectoryIngest::new(root).with_gitignore(false).with_include(vec!["*.log".to_string(), "target/**".to_string()]);
+    assert_eq!(relative(&unignored)?, vec!["run.log", "target/debug.txt"]);
+
+    // Binary files are skipped without failing the run.
+    let status = all.sync_file(&pipeline(&db), &db, &root.join("logo.png"), "default").await?;
+    assert_eq!(status, FileStatus::Skipped);
+    assert!(DirectoryIngest::new(root).with_include(vec!["[".to_string()]).files().is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_new_version_replaces_every_chunk() -> anyhow::Result<()> {
+    let storage = tempdir()?;
+    let tree = tempdir()?;
+    let root = tree.path();
+    let db = Arc::new(SqliteDatabase::open(storage.path().join("chunks.db"), DIM)?);
+    write(root, "guide.md", b"# One\nFirst.\n# Two\nSecond.\n# Three\nThird.\n");
+
+    let ingest = DirectoryIngest::new(root);
+    let pipeline = pipeline(&db).with_strategy(Some(ChunkingStrategy::Markdown));
+    ingest.run(&pipeline, &db, "default").await?;
+    let key = source_key(root, "guide.md");
+    // Chunks of one version do not supersede each other.
+    assert_eq!(db.count_latest_documents_by_source(&key, "default")?, 3);
+
+    write(root, "guide.md", b"# One\nFirst.\n# Two\nSecond, revised.\n");
+    ingest.run(&pipeline, &db, "default").await?;
+    assert_eq!(db.count_latest_documents_by_source(&key, "default")?, 2);
+    Ok(())
+}
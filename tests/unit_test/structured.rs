@@ -0,0 +1,58 @@
+use local_memory::engine::structured::{parse_structured, repair_json, CommunitySummary, ExtractedGraph};
+use serde_json::Value;
+
+#[test]
+fn test_repair_strips_code_fence_and_prose() {
+    let raw = "Sure! Here is the JSON:\n```json\n{\"title\": \"A\", \"summary\": \"B\"}\n```\nHope this helps.";
+    let summary: CommunitySummary = parse_structured(raw).unwrap();
+    assert_eq!(summary.title, "A");
+    assert_eq!(summary.summary, "B");
+}
+
+#[test]
+fn test_repair_removes_trailing_commas() {
+    let repaired = repair_json(r#"{"entities": [{"name": "A",}, ], "relationships": [],}"#);
+    let value: Value = serde_json::from_str(&repaired).unwrap();
+    assert_eq!(value["entities"][0]["name"], "A");
+}
+
+#[test]
+fn test_repair_closes_truncated_output() {
+    let raw = r#"{"entities": [{"name": "Alice", "type": "Person"}, {"name": "Bo"#;
+    let graph: ExtractedGraph = parse_structured(raw).unwrap();
+    assert_eq!(graph.entities.len(), 2);
+    assert_eq!(graph.entities[0].name, "Alice");
+    assert_eq!(graph.entities[1].name, "Bo");
+}
+
+#[test]
+fn test_repair_drops_dangling_key() {
+    let raw = r#"{"entities": [{"name": "Alice"}], "relationships": [{"source": "Alice", "target""#;
+    let repaired = repair_json(raw);
+    let value: Value = serde_json::from_str(&repaired).unwrap();
+    assert_eq!(value["entities"][0]["name"], "Alice");
+}
+
+#[test]
+fn test_repair_ignores_braces_inside_strings() {
+    let raw = r#"{"title": "Set {a, b}", "summary": "x"} trailing {junk}"#;
+    let summary: CommunitySummary = parse_structured(raw).unwrap();
+    assert_eq!(summary.title, "Set {a, b}");
+}
+
+#[test]
+fn test_graph_schema_is_lenient_about_types() {
+    let raw = r#"{"entities": [{"name": "Apollo", "type": null, "description": 11}], "relationships": null}"#;
+    let graph: ExtractedGraph = parse_structured(raw).unwrap();
+    assert_eq!(graph.entities[0].entity_type, "");
+    assert_eq!(graph.entities[0].description, "11");
+    assert!(graph.relationships.is_empty());
+    assert!(graph.conflicts.is_empty());
+}
+
+#[test]
+fn test_unparseable_output_is_an_error() {
+    assert!(parse_structured::<CommunitySummary>("I cannot answer that.").is_err());
+    // Valid JSON, wrong shape.
+    assert!(parse_structured::<CommunitySummary>(r#"{"name": "x"}"#).is_err());
+}
@@ -0,0 +1,97 @@
+use async_trait::async_trait;
+use edgequake_llm::{EmbeddingProvider, LlmError};
+use local_memory::engine::ingestion::IngestionPipeline;
+use local_memory::engine::vectors::weighted_mean;
+use local_memory::model::RetrievalEmbedder;
+use local_memory::model::tokens::{windows, ApproxTokenCounter, TokenCounter};
+use local_memory::storage::SqliteDatabase;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use tempfile::tempdir;
+
+#[test]
+fn test_approx_counter_splits_words_into_short_pieces() {
+    let counter = ApproxTokenCounter::new(10);
+    let text = "hello  wörld a";
+    let pieces: Vec<&str> = counter.token_spans(text).into_iter().map(|r| &text[r]).collect();
+    assert_eq!(pieces, vec!["hel", "lo", "wör", "ld", "a"]);
+    assert_eq!(counter.token_budget(), 10);
+}
+
+#[test]
+fn test_short_text_is_a_single_window() {
+    let counter = ApproxTokenCounter::new(8);
+    let check = windows("a b c", &counter);
+    assert_eq!(check.tokens, 3);
+    assert_eq!(check.windows, vec!["a b c"]);
+    assert!(!check.is_split());
+}
+
+#[test]
+fn test_long_text_is_cut_into_overlapping_windows() {
+    let counter = ApproxTokenCounter::new(8);
+    let words: Vec<String> = (0..20).map(|i| format!("w{}", i % 10)).collect();
+    let text = words.join(" ");
+    let check = windows(&text, &counter);
+
+    assert_eq!(check.tokens, 20);
+    // Stride is the budget minus an eighth: windows start at tokens 0, 7 and 14.
+    assert_eq!(check.window_tokens, vec![8, 8, 6]);
+    assert_eq!(check.windows[0], words[..8].join(" "));
+    assert_eq!(check.windows[1], words[7..15].join(" "));
+    assert_eq!(check.windows[2], words[14..].join(" "));
+}
+
+#[test]
+fn test_weighted_mean_is_normalized() {
+    let mean = weighted_mean(&[vec![1.0, 0.0], vec![0.0, 1.0]], &[3, 4]);
+    assert!((mean[0] - 0.6).abs() < 1e-6 && (mean[1] - 0.8).abs() < 1e-6, "{mean:?}");
+}
+
+/// Records every text and embeds it as a one-hot vector by input order.
+struct RecordingEmbedder {
+    seen: Mutex<Vec<String>>,
+}
+
+#[async_trait]
+impl EmbeddingProvider for RecordingEmbedder {
+    fn name(&self) -> &str { "recording" }
+    fn model(&self) -> &str { "recording" }
+    fn dimension(&self) -> usize { 24 }
+    fn max_tokens(&self) -> usize { 512 }
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, LlmError> {
+        let mut seen = self.seen.lock().unwrap();
+        Ok(texts.iter().map(|t| {
+            let mut v = vec![0.0; 24];
+            v[seen.len() % 24] = 1.0;
+            seen.push(t.clone());
+            v
+        }).collect())
+    }
+}
+
+impl RetrievalEmbedder for RecordingEmbedder {}
+
+#[tokio::test]
+async fn test_pipeline_windows_long_chunks_and_records_it() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let db = Arc::new(SqliteDatabase::open(dir.path().join("tokens.db"), 24)?);
+    let embedder = Arc::new(RecordingEmbedder { seen: Mutex::new(Vec::new()) });
+    let pipeline = IngestionPipeline::new(embedder.clone(), db.clone(), None, false, None)
+        .with_token_counter(Arc::new(ApproxTokenCounter::new(8)));
+
+    let long: Vec<String> = (0..20).map(|i| format!("w{}", i % 10)).collect();
+    let long_id = pipeline.run(&long.join(" "), json!({})).await?;
+    let short_id = pipeline.run("short text", json!({})).await?;
+
+    // Three windows for the long chunk, one input for the short one.
+    assert_eq!(embedder.seen.lock().unwrap().len(), 4);
+
+    let (text, metadata) = db.get_document_content(long_id)?.unwrap();
+    assert_eq!(text, long.join(" "), "the stored text is not truncated");
+    assert_eq!(metadata["embedding"], json!({"tokens": 20, "token_limit": 8, "windows": 3, "truncated": false}));
+
+    let (_, metadata) = db.get_document_content(short_id)?.unwrap();
+    assert!(metadata.get("embedding").is_none());
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use local_memory::mcp::args::{input_schema, parse_args, ConflictsArgs, MemorizeArgs, RecallArgs};
+use local_memory::mcp::tools::list_tools;
+use local_memory::storage::sqlite::ConflictResolution;
+use serde_json::{json, Value};
+
+#[test]
+fn test_arguments_decode_with_defaults() {
+    let args: RecallArgs = parse_args("recall", json!({"query": "rust"})).unwrap();
+    assert_eq!((args.query.as_str(), args.top_k, args.namespace.as_str()), ("rust", 5, "default"));
+
+    let args: MemorizeArgs = parse_args("memorize", json!({"text": "hi", "chunking": "markdown", "metadata": {"k": 1}})).unwrap();
+    assert_eq!(args.chunking.map(|c| c.as_str()), Some("markdown"));
+    assert_eq!(args.metadata.unwrap()["k"], 1);
+
+    // Missing arguments are an empty object; resolution aliases still work.
+    let args: ConflictsArgs = parse_args("memory_conflicts", Value::Null).unwrap();
+    assert!(args.conflict_id.is_none());
+    let args: ConflictsArgs = parse_args("memory_conflicts", json!({"action": "resolve", "resolution": "keep"})).unwrap();
+    assert_eq!(args.resolution, Some(ConflictResolution::KeepExisting));
+}
+
+#[test]
+fn test_unknown_and_mistyped_arguments_are_rejected() {
+    let typo = parse_args::<RecallArgs>("recall", json!({"query": "rust", "namespce": "work"})).unwrap_err();
+    assert_eq!(typo.code, -32602);
+    assert!(typo.message.contains("'recall'") && typo.message.contains("unknown field `namespce`"), "{}", typo.message);
+
+    let mistyped = parse_args::<RecallArgs>("recall", json!({"query": "rust", "top_k": "5"})).unwrap_err();
+    assert!(mistyped.message.contains("invalid type: string \"5\""), "{}", mistyped.message);
+
+    let missing = parse_args::<RecallArgs>("recall", json!({})).unwrap_err();
+    assert!(missing.message.contains("missing field `query`"), "{}", missing.message);
+
+    let bad_enum = parse_args::<MemorizeArgs>("memorize", json!({"text": "hi", "chunking": "paragraphs"})).unwrap_err();
+    assert!(bad_enum.message.contains("unknown variant `paragraphs`"), "{}", bad_enum.message);
+}
+
+#[test]
+fn test_input_schemas_come_from_the_argument_types() {
+    let schema = input_schema::<RecallArgs>();
+    assert_eq!(schema["type"], "object");
+    assert_eq!(schema["additionalProperties"], false);
+    assert_eq!(schema["required"], json!(["query"]));
+    assert_eq!(schema["properties"]["top_k"]["default"], 5);
+    assert!(schema.get("$schema").is_none() && schema.get("$defs").is_none());
+
+    let tools = list_tools();
+    let recall = tools.as_array().unwrap().iter().find(|t| t["name"] == "recall").unwrap();
+    assert_eq!(recall["inputSchema"], schema);
+    for tool in tools.as_array().unwrap() {
+        assert_eq!(tool["inputSchema"]["type"], "object", "{}", tool["name"]);
+        assert_eq!(tool["inputSchema"]["additionalProperties"], false, "{}", tool["name"]);
+    }
+}
@@ -0,0 +1,103 @@
+use local_memory::config::WatchConfig;
+use local_memory::engine::ingestion::IngestionPipeline;
+use local_memory::engine::watch::watch_paths;
+use local_memory::storage::SqliteDatabase;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::tempdir;
+
+const DIM: usize = 24;
+
+struct ZeroEmbedder;
+
+#[async_trait::async_trait]
+impl edgequake_llm::EmbeddingProvider for ZeroEmbedder {
+    fn name(&self) -> &str { "zero" }
+    fn model(&self) -> &str { "zero" }
+    fn dimension(&self) -> usize { DIM }
+    fn max_tokens(&self) -> usize { 512 }
+    async fn embed(&self, texts: &[String]) -> std::result::Result<Vec<Vec<f32>>, edgequake_llm::LlmError> {
+        Ok(texts.iter().map(|_| vec![0.1; DIM]).collect())
+    }
+}
+
+impl local_memory::model::RetrievalEmbedder for ZeroEmbedder {}
+
+/// Poll `check` until it holds, for up to ten seconds.
+async fn eventually(mut check: impl FnMut() -> bool) -> bool {
+    for _ in 0..200 {
+        if check() {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    false
+}
+
+async fn watch_and_edit(poll: bool) -> anyhow::Result<()> {
+    let storage = tempdir()?;
+    let tree = tempdir()?;
+    let root = std::fs::canonicalize(tree.path())?;
+    std::fs::write(root.join("before.md"), "# Written before the watch started")?;
+
+    let db = Arc::new(SqliteDatabase::open(storage.path().join("watch.db"), DIM)?);
+    let pipeline = Arc::new(IngestionPipeline::new(Arc::new(ZeroEmbedder), db.clone(), None, false, None));
+    let config = WatchConfig {
+        paths: vec![root.clone()],
+        namespace: "notes".to_string(),
+        exclude: vec!["*.tmp".to_string()],
+        debounce_ms: 50,
+        poll,
+        poll_interval_seconds: 1,
+        ..WatchConfig::default()
+    };
+    let task = watch_paths(config, pipeline, db.clone())?;
+
+    let source = |name: &str| db.get_source(&root.join(name).to_string_lossy(), "notes").unwrap();
+    assert!(eventually(|| source("before.md").is_some()).await, "initial sync");
+
+    std::fs::create_dir(root.join("adr"))?;
+    let adr = root.join("adr/0001-use-sqlite.md");
+    std::fs::write(&adr, "# Use SQLite\nAccepted.")?;
+    std::fs::write(root.join("scratch.tmp"), "ignored")?;
+    assert!(eventually(|| source("adr/0001-use-sqlite.md").is_some()).await, "new file ingested");
+    let first = source("adr/0001-use-sqlite.md").unwrap();
+
+    std::fs::write(&adr, "# Use SQLite\nSuperseded by ADR 2, which replaces it.")?;
+    assert!(
+        eventually(|| source("adr/0001-use-sqlite.md").is_some_and(|s| s.content_hash != first.content_hash)).await,
+        "edit re-ingested"
+    );
+    let latest = source("adr/0001-use-sqlite.md").unwrap();
+    let (content, _) = db.get_document_content(latest.document_id)?.unwrap();
+    assert!(content.contains("Superseded"));
+    assert_eq!(db.count_latest_documents_by_source(&latest.path, "notes")?, 1);
+
+    std::fs::remove_file(&adr)?;
+    assert!(eventually(|| source("adr/0001-use-sqlite.md").is_none()).await, "deletion retired");
+    assert_eq!(db.count_latest_documents_by_source(&latest.path, "notes")?, 0);
+    assert!(source("scratch.tmp").is_none());
+
+    task.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_watcher_reingests_changed_files() -> anyhow::Result<()> {
+    watch_and_edit(false).await
+}
+
+#[tokio::test]
+async fn test_polling_watcher_reingests_changed_files() -> anyhow::Result<()> {
+    watch_and_edit(true).await
+}
+
+#[test]
+fn test_watch_config_defaults() {
+    let config: WatchConfig = serde_json::from_str(r#"{"paths": ["docs"]}"#).unwrap();
+    assert_eq!(config.paths, vec![Path::new("docs").to_path_buf()]);
+    assert_eq!(config.namespace, "docs");
+    assert_eq!(config.debounce_ms, 1000);
+    assert!(config.gitignore && !config.poll);
+}